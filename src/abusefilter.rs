@@ -0,0 +1,137 @@
+/*!
+Filter definitions of the [AbuseFilter](https://www.mediawiki.org/wiki/Extension:AbuseFilter)
+extension, from `list=abusefilters`: descriptions, status flags, actions and hit counts.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+/// The properties loaded for each filter
+const FILTER_PROPS: &str =
+    "id|description|pattern|actions|hits|comments|lasteditor|lastedittime|status|private";
+
+/// An abuse filter, from a `formatversion=2` `list=abusefilters` result. The pattern and comments
+/// of private filters are only returned to users with the `abusefilter-view-private` right.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AbuseFilter {
+    /// ID of the filter
+    pub id: u64,
+    /// Public description
+    #[serde(default)]
+    pub description: String,
+    /// The filter rules
+    pub pattern: Option<String>,
+    /// Actions taken when the filter matches, separated by commas (e.g. "warn,tag")
+    #[serde(default)]
+    pub actions: String,
+    /// Number of times the filter matched
+    pub hits: Option<u64>,
+    /// Notes of the filter editors
+    pub comments: Option<String>,
+    /// User who last changed the filter
+    pub lasteditor: Option<String>,
+    /// Time of the last change of the filter
+    pub lastedittime: Option<Timestamp>,
+    /// Whether the filter is enabled
+    #[serde(default)]
+    pub enabled: bool,
+    /// Whether the filter is deleted
+    #[serde(default)]
+    pub deleted: bool,
+    /// Whether the filter is private
+    #[serde(default)]
+    pub private: bool,
+}
+
+impl AbuseFilter {
+    /// Returns the actions taken when the filter matches, e.g. "warn" and "tag"
+    pub fn action_list(&self) -> Vec<&str> {
+        self.actions
+            .split(',')
+            .filter(|action| !action.is_empty())
+            .collect()
+    }
+}
+
+impl Api {
+    /// Loads the abuse filters, up to `max` filters. `show` restricts them by status, e.g.
+    /// `["enabled", "!private"]`; the values are "enabled", "deleted" and "private", each possibly
+    /// negated with "!".
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the AbuseFilter extension is not
+    /// installed), or any error from the request.
+    pub fn abuse_filters(
+        &self,
+        show: &[&str],
+        max: Option<usize>,
+    ) -> Result<Vec<AbuseFilter>, Box<dyn Error + Send + Sync>> {
+        let mut params = self.params_into(&[
+            ("action", "query"),
+            ("list", "abusefilters"),
+            ("abfprop", FILTER_PROPS),
+            ("abflimit", "max"),
+            ("formatversion", "2"),
+        ]);
+        if !show.is_empty() {
+            params.insert("abfshow".to_string(), show.join("|"));
+        }
+        let result = self.get_query_api_json_limit(&params, max)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("list=abusefilters failed: {}", error)));
+        }
+        Ok(abuse_filters_from_api_result(&result)?)
+    }
+}
+
+/// Parses the filters of a `list=abusefilters` result
+fn abuse_filters_from_api_result(result: &Value) -> Result<Vec<AbuseFilter>, serde_json::Error> {
+    match result["query"].get("abusefilters") {
+        Some(filters) => Vec::<AbuseFilter>::deserialize(filters),
+        None => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_from_api_result() {
+        let result = json!({"query": {"abusefilters": [
+            {"id": 1, "description": "Page blanking", "pattern": "new_size < 50",
+            "actions": "warn,tag",
+             "hits": 1234, "lasteditor": "Example", "lastedittime": "2020-04-14T10:22:31Z",
+             "enabled": true, "deleted": false, "private": false},
+            {"id": 2, "description": "LTA", "actions": "disallow", "hits": 5, "enabled": true,
+            "private": true}
+        ]}});
+        let filters = abuse_filters_from_api_result(&result).unwrap();
+        assert_eq!(filters.len(), 2);
+        assert_eq!(filters[0].action_list(), vec!["warn", "tag"]);
+        assert_eq!(filters[0].hits, Some(1234));
+        assert!(filters[0].lastedittime.is_some());
+        assert!(filters[1].private && !filters[1].deleted);
+        assert_eq!(filters[1].pattern, None);
+        assert!(
+            abuse_filters_from_api_result(&json!({"batchcomplete": true}))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,155 @@
+/*!
+The `Action` enum classifies API requests by what they change. It decides which requests are
+simulated in dry-run mode, reported to write hooks, sent with `maxlag`, and which delay of a
+`DelayPolicy` applies to them.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// API actions that require a token, but don't change anything
+const TOKEN_READ_ACTIONS: &[&str] = &[
+    "checktoken",
+    "stashedit",
+    "oathvalidate",
+    "validatepassword",
+    "query",
+];
+
+/// The kind of an API request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Action {
+    /// A request that does not change anything
+    Read,
+    /// A page edit (`action=edit`)
+    Edit,
+    /// A page move
+    Move,
+    /// A deletion
+    Delete,
+    /// An undeletion
+    Undelete,
+    /// A change of page protection
+    Protect,
+    /// A file upload
+    Upload,
+    /// A rollback
+    Rollback,
+    /// A block or unblock
+    Block,
+    /// Marking a change as patrolled
+    Patrol,
+    /// A change of the watchlist
+    Watch,
+    /// An edit of a Wikibase entity (`action=wb...`)
+    WikibaseEdit,
+    /// Any other action that changes something, e.g. `import` or `options`
+    OtherWrite,
+}
+
+impl Action {
+    /// Returns the kind of a request with the API parameters `params`, sent with `method` (GET or
+    /// POST). Writes have to be POSTed. Actions that are known to write are writes, with or without
+    /// a token; other actions are writes if they have a token, except some actions that need a
+    /// token to read (e.g. `checktoken`).
+    pub fn new_from_params(params: &HashMap<String, String>, method: &str) -> Action {
+        if method != "POST" {
+            return Action::Read;
+        }
+        let action = params.get("action").map(|s| s.as_str()).unwrap_or_default();
+        Action::new_from_action(action, params.contains_key("token"))
+    }
+
+    /// Returns the kind of a successful write with API action `action`, e.g. from a `WriteRecord`
+    pub fn new_from_write_action(action: &str) -> Action {
+        match Action::new_from_action(action, true) {
+            Action::Read => Action::OtherWrite,
+            kind => kind,
+        }
+    }
+
+    /// Returns the kind of a POST request with API action `action`
+    fn new_from_action(action: &str, has_token: bool) -> Action {
+        match action {
+            "edit" => Action::Edit,
+            "move" => Action::Move,
+            "delete" => Action::Delete,
+            "undelete" => Action::Undelete,
+            "protect" => Action::Protect,
+            "upload" => Action::Upload,
+            "rollback" => Action::Rollback,
+            "block" | "unblock" => Action::Block,
+            "patrol" | "pagetriageaction" => Action::Patrol,
+            "watch" | "setnotificationtimestamp" => Action::Watch,
+            // Wikibase reads such as `wbgetentities` may be POSTed, but have no token
+            _ if action.starts_with("wb") && has_token => Action::WikibaseEdit,
+            _ if TOKEN_READ_ACTIONS.contains(&action) => Action::Read,
+            _ if has_token => Action::OtherWrite,
+            _ => Action::Read,
+        }
+    }
+
+    /// Checks if requests of this kind change something
+    pub fn is_write(&self) -> bool {
+        *self != Action::Read
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn classify() {
+        let edit = params(&[("action", "edit"), ("title", "Foo"), ("token", "x")]);
+        assert_eq!(Action::new_from_params(&edit, "POST"), Action::Edit);
+        assert_eq!(Action::new_from_params(&edit, "GET"), Action::Read);
+        let check = params(&[("action", "checktoken"), ("type", "csrf"), ("token", "x")]);
+        assert_eq!(Action::new_from_params(&check, "POST"), Action::Read);
+        let stash = params(&[("action", "stashedit"), ("title", "Foo"), ("token", "x")]);
+        assert!(!Action::new_from_params(&stash, "POST").is_write());
+        let check = params(&[("action", "query"), ("list", "checkuser"), ("token", "x")]);
+        assert_eq!(Action::new_from_params(&check, "POST"), Action::Read);
+        let get_entities = params(&[("action", "wbgetentities"), ("ids", "Q1|Q2")]);
+        assert_eq!(Action::new_from_params(&get_entities, "POST"), Action::Read);
+        let set_claim = params(&[("action", "wbsetclaim"), ("claim", "{}"), ("token", "x")]);
+        assert_eq!(
+            Action::new_from_params(&set_claim, "POST"),
+            Action::WikibaseEdit
+        );
+        let options = params(&[("action", "options"), ("change", "x=y"), ("token", "x")]);
+        assert_eq!(
+            Action::new_from_params(&options, "POST"),
+            Action::OtherWrite
+        );
+
+        assert_eq!(Action::new_from_write_action("move"), Action::Move);
+        assert_eq!(Action::new_from_write_action("import"), Action::OtherWrite);
+        assert_eq!(
+            Action::new_from_write_action("setnotificationtimestamp"),
+            Action::Watch
+        );
+        assert_eq!(json!(Action::WikibaseEdit), json!("wikibaseedit"));
+    }
+}
@@ -0,0 +1,141 @@
+/*!
+Enumerations of the link tables of a whole wiki: `list=alllinks`, `list=allredirects` and
+`list=allfileusages`. They are the only efficient way to build link or redirect tables for an entire
+wiki through the API. With `unique`, each target is listed once; otherwise, once per page that links
+to it.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::params_map;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+/// A link target from an `all*` list module (`formatversion=2`)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LinkTarget {
+    /// ID of the page that links, redirects to, or uses the target; `None` with `unique`
+    pub fromid: Option<u64>,
+    /// Namespace of the target
+    pub ns: NamespaceID,
+    /// Full title of the target
+    pub title: String,
+    /// Section of a redirect target, if any
+    pub fragment: Option<String>,
+    /// Interwiki prefix of a redirect target, if it is on another wiki
+    pub interwiki: Option<String>,
+}
+
+impl Api {
+    /// Iterates over the links to pages in `namespace`, ordered by target
+    pub fn all_links(
+        &self,
+        namespace: NamespaceID,
+        unique: bool,
+    ) -> impl Iterator<Item = Result<LinkTarget, Box<dyn Error + Send + Sync>>> + '_ {
+        self.all_link_targets("alllinks", "al", Some(namespace), unique)
+    }
+
+    /// Iterates over the redirects to pages in `namespace`, ordered by target.
+    /// Without `unique`, the fragment and interwiki prefix of each redirect are included.
+    pub fn all_redirects(
+        &self,
+        namespace: NamespaceID,
+        unique: bool,
+    ) -> impl Iterator<Item = Result<LinkTarget, Box<dyn Error + Send + Sync>>> + '_ {
+        self.all_link_targets("allredirects", "ar", Some(namespace), unique)
+    }
+
+    /// Iterates over the file usages, ordered by file, including usages of files that don't exist
+    pub fn all_file_usages(
+        &self,
+        unique: bool,
+    ) -> impl Iterator<Item = Result<LinkTarget, Box<dyn Error + Send + Sync>>> + '_ {
+        self.all_link_targets("allfileusages", "af", None, unique)
+    }
+
+    /// Iterates over the results of the list module `list`, with parameter prefix `prefix`
+    fn all_link_targets(
+        &self,
+        list: &'static str,
+        prefix: &str,
+        namespace: Option<NamespaceID>,
+        unique: bool,
+    ) -> impl Iterator<Item = Result<LinkTarget, Box<dyn Error + Send + Sync>>> + '_ {
+        let prop = match (unique, list) {
+            (true, _) => "title",
+            (false, "allredirects") => "ids|title|fragment|interwiki",
+            (false, _) => "ids|title",
+        };
+        let params = params_map![
+            "action" => "query",
+            "list" => list,
+            format!("{}prop", prefix) => prop,
+            format!("{}namespace", prefix) => namespace,
+            format!("{}unique", prefix) => unique,
+            format!("{}limit", prefix) => "max",
+            "formatversion" => "2",
+        ];
+        self.get_query_api_json_limit_iter(&params, None)
+            .flat_map(move |result| match result {
+                Ok(result) => link_targets_from_api_result(&result, list),
+                Err(e) => vec![Err(e)],
+            })
+    }
+}
+
+/// Parses the targets of a result of the list module `list`, or returns the API error
+fn link_targets_from_api_result(
+    result: &Value,
+    list: &str,
+) -> Vec<Result<LinkTarget, Box<dyn Error + Send + Sync>>> {
+    if let Some(error) = result["error"]["info"].as_str() {
+        return vec![Err(From::from(format!("list={} failed: {}", list, error)))];
+    }
+    result["query"][list]
+        .as_array()
+        .map(|targets| {
+            targets
+                .iter()
+                .map(|target| LinkTarget::deserialize(target).map_err(From::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_from_api_result() {
+        let result = json!({"query": {"allredirects": [
+            {"fromid": 12, "ns": 0, "title": "Foo", "fragment": "History"},
+            {"fromid": 13, "ns": 0, "title": "Bar", "interwiki": "wikt"}
+        ]}});
+        let targets = link_targets_from_api_result(&result, "allredirects");
+        let targets: Vec<LinkTarget> = targets.into_iter().map(|t| t.unwrap()).collect();
+        assert_eq!(targets[0].fromid, Some(12));
+        assert_eq!(targets[0].fragment.as_deref(), Some("History"));
+        assert_eq!(targets[1].interwiki.as_deref(), Some("wikt"));
+
+        let result = json!({"query": {"allfileusages": [{"ns": 6, "title": "File:Example.jpg"}]}});
+        let targets = link_targets_from_api_result(&result, "allfileusages");
+        assert_eq!(targets[0].as_ref().unwrap().fromid, None);
+
+        let result = json!({"error": {"code": "badvalue", "info": "Unrecognized value"}});
+        assert!(link_targets_from_api_result(&result, "alllinks")[0].is_err());
+    }
+}
@@ -20,17 +20,32 @@ extern crate hmac;
 extern crate reqwest;
 extern crate sha1;
 
+use crate::action::Action;
 use crate::api::hmac::Mac;
+use crate::audit::{WriteHook, WriteRecord};
+use crate::connection::ConnectionSettings;
+use crate::delay::DelayPolicy;
+use crate::maxlag::{MaxlagHandler, MaxlagStrategy, WaitForLag};
+use crate::paraminfo::ModuleInfo;
+use crate::site_info::{NamespaceInfo, SiteInfo, SiteUrl};
+use crate::sparql::{self, SparqlError};
+use crate::timestamp::unix_from_timestamp;
 use crate::title::Title;
+use crate::traits::{Countable, Mergeable};
 use crate::user::User;
 use cookie::{Cookie, CookieJar};
 use reqwest::header::{HeaderMap, HeaderValue};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
-use std::fmt::Write;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{thread, time};
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use url::Url;
 use urlencoding;
 use uuid::Uuid;
@@ -41,6 +56,28 @@ pub type NamespaceID = i64;
 const DEFAULT_USER_AGENT: &str = "Rust mediawiki API";
 const DEFAULT_MAXLAG: Option<u64> = Some(5);
 const DEFAULT_MAX_RETRY_ATTEMPTS: u64 = 5;
+const DEFAULT_SPARQL_RETRY_BUDGET: Duration = Duration::from_secs(60);
+
+/// API error codes of edits that were not saved, and may succeed if tried again
+pub(crate) const TRANSIENT_ERROR_CODES: &[&str] = &[
+    "maxlag",
+    "readonly",
+    "ratelimited",
+    "editconflict",
+    "internal_api_error_DBQueryError",
+];
+
+/// Maximum number of warnings kept by an `Api`; older ones are dropped
+const MAX_STORED_WARNINGS: usize = 1000;
+
+/// Keys in query results that are not lists of results
+const QUERY_METADATA_KEYS: &[&str] = &[
+    "normalized",
+    "redirects",
+    "converted",
+    "interwiki",
+    "badrevids",
+];
 
 type HmacSha1 = hmac::Hmac<sha1::Sha1>;
 
@@ -55,8 +92,283 @@ macro_rules! hashmap {
     }}
 }
 
+#[macro_export]
+/// Creates a `HashMap<String, String>` of API parameters, converting each value with `ParamValue`:
+/// strings are used as they are, numbers are formatted, `true` becomes "1" and `false` leaves out
+/// the parameter, lists (slices, arrays or `Vec`s) are joined with "|", or with U+001F if a value
+/// contains "|", and `None` leaves out the parameter. Example: `params_map!["action" => "query",
+/// "titles" => &["Foo", "Bar"], "redirects" => true, "rvlimit" => Some(5)]`
+macro_rules! params_map {
+    ($( $key: expr => $val: expr ),* $(,)?) => {{
+         let mut map: ::std::collections::HashMap<String, String> = ::std::collections::HashMap::new();
+         $(
+             if let Some(value) = $crate::api::ParamValue::into_param_value($val) {
+                 map.insert(($key).to_string(), value);
+             }
+         )*
+         map
+    }}
+}
+
+/// A value that can be used as an API parameter, see `params_map!`
+pub trait ParamValue {
+    /// Returns the parameter value, or `None` if the parameter should be left out
+    fn into_param_value(self) -> Option<String>;
+}
+
+impl ParamValue for &str {
+    fn into_param_value(self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl ParamValue for String {
+    fn into_param_value(self) -> Option<String> {
+        Some(self)
+    }
+}
+
+impl ParamValue for &String {
+    fn into_param_value(self) -> Option<String> {
+        Some(self.clone())
+    }
+}
+
+impl ParamValue for bool {
+    fn into_param_value(self) -> Option<String> {
+        if self {
+            Some("1".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+macro_rules! impl_param_value_for_numbers {
+    ($( $t: ty ),*) => {
+        $(
+            impl ParamValue for $t {
+                fn into_param_value(self) -> Option<String> {
+                    Some(self.to_string())
+                }
+            }
+        )*
+    }
+}
+
+impl_param_value_for_numbers!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl<T: ParamValue> ParamValue for Option<T> {
+    fn into_param_value(self) -> Option<String> {
+        self.and_then(ParamValue::into_param_value)
+    }
+}
+
+impl<T: AsRef<str>> ParamValue for &[T] {
+    fn into_param_value(self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        let values: Vec<&str> = self.iter().map(|value| value.as_ref()).collect();
+        if values.iter().any(|value| value.contains('|')) {
+            Some(format!("\u{1f}{}", values.join("\u{1f}")))
+        } else {
+            Some(values.join("|"))
+        }
+    }
+}
+
+impl<T: AsRef<str>, const N: usize> ParamValue for &[T; N] {
+    fn into_param_value(self) -> Option<String> {
+        self.as_slice().into_param_value()
+    }
+}
+
+impl<T: AsRef<str>> ParamValue for &Vec<T> {
+    fn into_param_value(self) -> Option<String> {
+        self.as_slice().into_param_value()
+    }
+}
+
+impl<T: AsRef<str>> ParamValue for Vec<T> {
+    fn into_param_value(self) -> Option<String> {
+        self.as_slice().into_param_value()
+    }
+}
+
+/// A warning of the API, e.g. about a deprecated parameter or a truncated result
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ApiWarning {
+    /// The module that issued the warning, e.g. "main" or "query+revisions"
+    pub module: String,
+    /// The warning code, if the query used an `errorformat` other than "bc"
+    pub code: Option<String>,
+    /// The warning message
+    pub text: String,
+}
+
+impl ApiWarning {
+    /// Returns the warnings in an API result, in any `errorformat` and `formatversion`
+    pub fn new_from_api_result(result: &Value) -> Vec<ApiWarning> {
+        match &result["warnings"] {
+            Value::Array(warnings) => warnings
+                .iter()
+                .map(|warning| ApiWarning {
+                    module: warning["module"].as_str().unwrap_or("main").to_string(),
+                    code: warning["code"].as_str().map(|s| s.to_string()),
+                    text: ["text", "html", "*", "key"]
+                        .iter()
+                        .find_map(|key| warning[key].as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                })
+                .collect(),
+            Value::Object(modules) => modules
+                .iter()
+                .flat_map(|(module, warnings)| {
+                    let text = warnings["warnings"]
+                        .as_str()
+                        .or_else(|| warnings["*"].as_str());
+                    text.unwrap_or_default()
+                        .lines()
+                        .map(move |line| ApiWarning {
+                            module: module.to_string(),
+                            code: None,
+                            text: line.to_string(),
+                        })
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Checks if this warning is about a deprecated feature (e.g. a parameter or result format
+    /// that will be removed), or about a parameter that is not known, e.g. because it was removed
+    pub fn is_deprecation(&self) -> bool {
+        if let Some(code) = &self.code {
+            return code.starts_with("deprecat")
+                || code.ends_with("-deprecated")
+                || code == "unrecognizedparams";
+        }
+        let text = self.text.to_lowercase();
+        [
+            "deprecated",
+            "will be removed",
+            "has been removed",
+            "unrecognized parameter",
+            "mediawiki-api-announce",
+        ]
+        .iter()
+        .any(|phrase| text.contains(phrase))
+    }
+}
+
+/// An error returned by the API, e.g. for a missing permission or an invalid parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    /// The error code, e.g. "permissiondenied"
+    pub code: String,
+    /// The error message
+    pub info: String,
+}
+
+impl ApiError {
+    /// Returns the (first) error in an API result, if any, in any `errorformat`
+    pub fn new_from_api_result(result: &Value) -> Option<ApiError> {
+        let error = match result.get("error") {
+            Some(error) => error,
+            None => result.get("errors")?.get(0)?,
+        };
+        Some(ApiError {
+            code: error["code"].as_str().unwrap_or_default().to_string(),
+            info: ["info", "text", "html", "*", "key"]
+                .iter()
+                .find_map(|key| error[key].as_str())
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API error {}: {}", self.code, self.info)
+    }
+}
+
+impl Error for ApiError {}
+
+/// A function that is called with every deprecation warning
+#[derive(Clone)]
+struct DeprecationHook(Arc<dyn Fn(&ApiWarning) + Send + Sync>);
+
+impl fmt::Debug for DeprecationHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DeprecationHook")
+    }
+}
+
+/// The validity of a token, from `action=checktoken`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenStatus {
+    /// The token is valid
+    Valid,
+    /// The token is invalid, e.g. because the session ended
+    Invalid,
+    /// The token was valid, but has expired
+    Expired,
+}
+
+impl TokenStatus {
+    /// Parses the result of `action=checktoken`
+    pub fn new_from_api_result(result: &Value) -> Option<TokenStatus> {
+        match result["checktoken"]["result"].as_str()? {
+            "valid" => Some(TokenStatus::Valid),
+            "invalid" => Some(TokenStatus::Invalid),
+            "expired" => Some(TokenStatus::Expired),
+            _ => None,
+        }
+    }
+
+    /// Checks if the token can be used
+    pub fn is_valid(&self) -> bool {
+        *self == TokenStatus::Valid
+    }
+}
+
+/// The language of a request: `uselang` for interface messages (e.g. errors and parsed system
+/// messages), and `variant` for the LanguageConverter variant of parsed content (e.g. "zh-hant" or
+/// "sr-el")
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestLanguage {
+    /// The `uselang` parameter; besides language codes, "user" and "content" are allowed
+    pub uselang: Option<String>,
+    /// The `variant` parameter
+    pub variant: Option<String>,
+}
+
+impl RequestLanguage {
+    /// Returns a new `RequestLanguage`
+    pub fn new(uselang: Option<&str>, variant: Option<&str>) -> RequestLanguage {
+        RequestLanguage {
+            uselang: uselang.map(|s| s.to_string()),
+            variant: variant.map(|s| s.to_string()),
+        }
+    }
+
+    /// Sets the parameters of a single request, replacing any `uselang` and `variant` values in it
+    pub fn apply(&self, params: &mut HashMap<String, String>) {
+        for (key, value) in [("uselang", &self.uselang), ("variant", &self.variant)] {
+            if let Some(value) = value {
+                params.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+}
+
 /// `OAuthParams` contains parameters for OAuth requests
 #[derive(Debug, Clone)]
+#[allow(dead_code)] // Not all fields of the QuickStatements JSON are used for signing
 pub struct OAuthParams {
     g_consumer_key: Option<String>,
     g_consumer_secret: Option<String>,
@@ -96,45 +408,185 @@ impl OAuthParams {
 #[derive(Debug, Clone)]
 pub struct Api {
     api_url: String,
-    site_info: Value,
+    site_info: SiteInfo,
     client: reqwest::blocking::Client,
-    cookie_jar: CookieJar,
-    user: User,
+    plain_client: reqwest::blocking::Client,
+    connection_settings: ConnectionSettings,
+    default_params: HashMap<String, String>,
+    cookie_jar: Arc<Mutex<CookieJar>>,
+    user: Arc<Mutex<User>>,
     user_agent: String,
     maxlag_seconds: Option<u64>,
+    maxlag_strategy: Option<MaxlagHandler>,
+    maxlag_reads: bool,
     edit_delay_ms: Option<u64>,
+    delay_policy: DelayPolicy,
     max_retry_attempts: u64,
     oauth: Option<OAuthParams>,
+    clock_offset: Arc<AtomicI64>,
+    auto_relogin: bool,
+    credentials: Arc<Mutex<Option<(String, String)>>>,
+    language_codes: OnceLock<HashSet<String>>,
+    sparql_endpoint: Option<String>,
+    sparql_retry_budget: Duration,
+    simulate: bool,
+    simulated_writes: Arc<Mutex<Vec<HashMap<String, String>>>>,
+    write_hook: Option<WriteHook>,
+    deprecation_hook: Option<DeprecationHook>,
+    param_validation: bool,
+    param_info: Arc<Mutex<HashMap<String, Option<ModuleInfo>>>>,
+    warnings: Arc<Mutex<VecDeque<ApiWarning>>>,
 }
 
 impl Api {
     /// Returns a new `Api` element, and loads the MediaWiki site info from the `api_url` site.
     /// This is done both to get basic information about the site, and to test the API.
-    pub fn new(api_url: &str) -> Result<Api, Box<dyn Error>> {
+    pub fn new(api_url: &str) -> Result<Api, Box<dyn Error + Send + Sync>> {
         Api::new_from_builder(api_url, reqwest::blocking::Client::builder())
     }
 
+    /// Returns a new `Api` element, and loads the MediaWiki site info from the `api_url` site.
+    /// This is done both to get basic information about the site, and to test the API.
+    /// Uses the HTTP `settings` for all requests.
+    pub fn new_with_settings(
+        api_url: &str,
+        settings: ConnectionSettings,
+    ) -> Result<Api, Box<dyn Error + Send + Sync>> {
+        let mut ret = Api::new_from_builder(
+            api_url,
+            settings.apply(reqwest::blocking::Client::builder()),
+        )?;
+        ret.plain_client = settings
+            .apply(reqwest::blocking::Client::builder())
+            .build()?;
+        ret.connection_settings = settings;
+        Ok(ret)
+    }
+
     /// Returns a new `Api` element, and loads the MediaWiki site info from the `api_url` site.
     /// This is done both to get basic information about the site, and to test the API.
     /// Uses a bespoke reqwest::ClientBuilder.
     pub fn new_from_builder(
         api_url: &str,
         builder: reqwest::blocking::ClientBuilder,
-    ) -> Result<Api, Box<dyn Error>> {
-        let mut ret = Api {
+    ) -> Result<Api, Box<dyn Error + Send + Sync>> {
+        let mut ret = Api::new_without_site_info(api_url, builder)?;
+        ret.load_site_info()?;
+        Ok(ret)
+    }
+
+    /// Returns a new `Api` element, like `new()`, but caches the site info as a file in
+    /// `cache_dir`. The cached site info is reused across process runs until it is older than
+    /// `ttl`; then it is loaded from the API again, and the cache is updated.
+    pub fn new_cached<P: AsRef<Path>>(
+        api_url: &str,
+        cache_dir: P,
+        ttl: Duration,
+    ) -> Result<Api, Box<dyn Error + Send + Sync>> {
+        let mut ret = Api::new_without_site_info(api_url, reqwest::blocking::Client::builder())?;
+        let cache_dir = cache_dir.as_ref();
+        let cache_file = cache_dir.join(SiteInfo::cache_file_name(api_url));
+        match SiteInfo::read_cache(&cache_file, ttl) {
+            Some(site_info) => ret.site_info = site_info,
+            None => {
+                ret.load_site_info()?;
+                fs::create_dir_all(cache_dir)?;
+                ret.site_info.write_cache(&cache_file)?;
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Returns a new `Api` element that uses already loaded site info, without contacting the API
+    pub fn new_with_site_info(
+        api_url: &str,
+        site_info: SiteInfo,
+    ) -> Result<Api, Box<dyn Error + Send + Sync>> {
+        let mut ret = Api::new_without_site_info(api_url, reqwest::blocking::Client::builder())?;
+        ret.site_info = site_info;
+        Ok(ret)
+    }
+
+    /// Returns a new `Api` element with empty site info.
+    /// Used by the constructors, which then load the site info
+    fn new_without_site_info(
+        api_url: &str,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> Result<Api, Box<dyn Error + Send + Sync>> {
+        Ok(Api {
             api_url: api_url.to_string(),
-            site_info: serde_json::from_str(r"{}")?,
+            site_info: SiteInfo::default(),
             client: builder.build()?,
-            cookie_jar: CookieJar::new(),
-            user: User::new(),
+            plain_client: reqwest::blocking::Client::builder().build()?,
+            connection_settings: ConnectionSettings::new(),
+            default_params: HashMap::new(),
+            cookie_jar: Arc::new(Mutex::new(CookieJar::new())),
+            user: Arc::new(Mutex::new(User::new())),
             user_agent: DEFAULT_USER_AGENT.to_string(),
             maxlag_seconds: DEFAULT_MAXLAG,
+            maxlag_strategy: None,
+            maxlag_reads: false,
             max_retry_attempts: DEFAULT_MAX_RETRY_ATTEMPTS,
             edit_delay_ms: None,
+            delay_policy: DelayPolicy::new(),
             oauth: None,
-        };
-        ret.load_site_info()?;
-        Ok(ret)
+            clock_offset: Arc::new(AtomicI64::new(0)),
+            auto_relogin: false,
+            credentials: Arc::new(Mutex::new(None)),
+            language_codes: OnceLock::new(),
+            sparql_endpoint: None,
+            sparql_retry_budget: DEFAULT_SPARQL_RETRY_BUDGET,
+            simulate: false,
+            simulated_writes: Arc::new(Mutex::new(vec![])),
+            write_hook: None,
+            deprecation_hook: None,
+            param_validation: false,
+            param_info: Arc::new(Mutex::new(HashMap::new())),
+            warnings: Arc::new(Mutex::new(VecDeque::new())),
+        })
+    }
+
+    /// Returns a copy of this `Api` for another wiki.
+    ///
+    /// Kept: the HTTP clients, connection settings, user agent, maxlag, edit delay and retry
+    /// settings, SPARQL retry budget, simulate mode, write and deprecation hooks, and parameter
+    /// validation, so a dry run stays a dry run and writes stay audited.
+    ///
+    /// Reset: site info, default parameters (which may be specific to this wiki), login, OAuth,
+    /// stored credentials and automatic re-login, clock offset and SPARQL endpoint. Caches,
+    /// simulated writes and warnings are not shared.
+    pub(crate) fn for_other_wiki(&self, api_url: &str) -> Api {
+        Api {
+            api_url: api_url.to_string(),
+            site_info: SiteInfo::default(),
+            client: self.client.clone(),
+            plain_client: self.plain_client.clone(),
+            connection_settings: self.connection_settings.clone(),
+            default_params: HashMap::new(),
+            cookie_jar: Arc::new(Mutex::new(CookieJar::new())),
+            user: Arc::new(Mutex::new(User::new())),
+            user_agent: self.user_agent.clone(),
+            maxlag_seconds: self.maxlag_seconds,
+            maxlag_strategy: self.maxlag_strategy.clone(),
+            maxlag_reads: self.maxlag_reads,
+            edit_delay_ms: self.edit_delay_ms,
+            delay_policy: self.delay_policy.clone(),
+            max_retry_attempts: self.max_retry_attempts,
+            oauth: None,
+            clock_offset: Arc::new(AtomicI64::new(0)),
+            auto_relogin: false,
+            credentials: Arc::new(Mutex::new(None)),
+            language_codes: OnceLock::new(),
+            sparql_endpoint: None,
+            sparql_retry_budget: self.sparql_retry_budget,
+            simulate: self.simulate,
+            simulated_writes: Arc::new(Mutex::new(vec![])),
+            write_hook: self.write_hook.clone(),
+            deprecation_hook: self.deprecation_hook.clone(),
+            param_validation: self.param_validation,
+            param_info: Arc::new(Mutex::new(HashMap::new())),
+            warnings: Arc::new(Mutex::new(VecDeque::new())),
+        }
     }
 
     /// Returns the API url
@@ -157,32 +609,104 @@ impl Api {
         &self.client
     }
 
+    /// Returns the difference between server and local time in seconds, as used for OAuth
+    /// timestamps
+    pub fn clock_offset(&self) -> i64 {
+        self.clock_offset.load(Ordering::Relaxed)
+    }
+
+    /// Measures the difference between server and local time with `curtimestamp`, and uses it for
+    /// OAuth timestamps, so OAuth requests work on machines with a skewed clock. This is done
+    /// automatically when the server rejects an OAuth timestamp. The request is not signed, as it
+    /// must work while the clock is off. Returns the offset in seconds.
+    pub fn sync_clock(&self) -> Result<i64, Box<dyn Error + Send + Sync>> {
+        let params = params_map!["action" => "query", "curtimestamp" => "1", "format" => "json"];
+        let result: Value = self
+            .client
+            .get(&self.api_url)
+            .header(reqwest::header::USER_AGENT, self.user_agent_full())
+            .query(&params)
+            .send()?
+            .json()?;
+        let server_time = result["curtimestamp"]
+            .as_str()
+            .and_then(unix_from_timestamp)
+            .ok_or_else(|| format!("No curtimestamp in API result: {}", result))?;
+        let offset = server_time - self.local_time()?;
+        self.clock_offset.store(offset, Ordering::Relaxed);
+        Ok(offset)
+    }
+
+    /// Sets the clock offset after an OAuth timestamp error; the error usually contains the server
+    /// time
+    fn sync_clock_from_error(&self, error: &Value) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match server_time_from_oauth_error(error) {
+            Some(server_time) => self
+                .clock_offset
+                .store(server_time - self.local_time()?, Ordering::Relaxed),
+            None => {
+                self.sync_clock()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the local time in seconds since the Unix epoch
+    fn local_time(&self) -> Result<i64, Box<dyn Error + Send + Sync>> {
+        Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64)
+    }
+
+    /// Returns the HTTP settings set with `new_with_settings` or `set_connection_settings`
+    pub fn connection_settings(&self) -> &ConnectionSettings {
+        &self.connection_settings
+    }
+
+    /// Replaces the HTTP client by one with `settings`, which then apply to all requests (to the
+    /// action API, SPARQL and REST endpoints). Replaces a client passed to `new_from_builder`.
+    ///
+    /// # Errors
+    /// Returns an error if the client can't be built.
+    pub fn set_connection_settings(
+        &mut self,
+        settings: ConnectionSettings,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.client = settings
+            .apply(reqwest::blocking::Client::builder())
+            .build()?;
+        self.plain_client = settings
+            .apply(reqwest::blocking::Client::builder())
+            .build()?;
+        self.connection_settings = settings;
+        Ok(())
+    }
+
     /// Returns a mutable reference to the reqwest client
     pub fn client_mut(&mut self) -> &mut reqwest::blocking::Client {
         &mut self.client
     }
 
-    /// Returns a reference to the current user object
-    pub fn user(&self) -> &User {
-        &self.user
+    /// Returns the current user object. It is locked until the returned guard is dropped,
+    /// so the guard should not be held while sending queries.
+    pub fn user(&self) -> MutexGuard<'_, User> {
+        lock(&self.user)
     }
 
-    /// Returns a mutable reference to the current user object
-    pub fn user_mut(&mut self) -> &mut User {
-        &mut self.user
+    /// Returns the current user object, for changes; like `user`
+    pub fn user_mut(&self) -> MutexGuard<'_, User> {
+        lock(&self.user)
     }
 
     /// Loads the current user info; returns Ok(()) is successful
-    pub fn load_user_info(&mut self) -> Result<(), Box<dyn Error>> {
-        let mut user = std::mem::take(&mut self.user);
-        user.load_user_info(&self)?;
-        self.user = user;
+    pub fn load_user_info(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut user = self.user().clone();
+        user.load_user_info(self)?;
+        *self.user_mut() = user;
         Ok(())
     }
 
     /// Returns the maximum number of retry attempts
     pub fn max_retry_attempts(&self) -> u64 {
-        return self.max_retry_attempts;
+        self.max_retry_attempts
     }
 
     /// Sets the maximum number of retry attempts
@@ -190,98 +714,111 @@ impl Api {
         self.max_retry_attempts = max_retry_attempts;
     }
 
-    /// Returns a reference to the serde_json Value containing the site info
-    pub fn get_site_info(&self) -> &Value {
-        return &self.site_info;
-    }
-
-    /// Returns a serde_json Value in site info, within the `["query"]` object.
-    pub fn get_site_info_value<'a>(&'a self, k1: &str, k2: &str) -> &'a Value {
-        &self.get_site_info()["query"][k1][k2]
+    /// Returns a reference to the site info
+    pub fn get_site_info(&self) -> &SiteInfo {
+        &self.site_info
+    }
+
+    /// Returns the codes of all languages known to the wiki, from `meta=languageinfo`.
+    /// They are loaded on first use, and cached for the lifetime of the `Api`.
+    pub fn language_codes(&self) -> Result<&HashSet<String>, Box<dyn Error + Send + Sync>> {
+        if self.language_codes.get().is_none() {
+            let params = self.params_into(&[
+                ("action", "query"),
+                ("meta", "languageinfo"),
+                ("liprop", "code"),
+                ("formatversion", "2"),
+            ]);
+            let result = self.get_query_api_json_all(&params)?;
+            let codes = Api::language_codes_from_api_result(&result)
+                .ok_or_else(|| format!("No languageinfo in API result: {}", result))?;
+            // Another thread may have loaded them in the meantime; both results are the same
+            let _ = self.language_codes.set(codes);
+        }
+        Ok(self.language_codes.get_or_init(HashSet::new))
     }
 
-    /// Returns a String from the site info, matching `["query"][k1][k2]`
-    pub fn get_site_info_string<'a>(&'a self, k1: &str, k2: &str) -> Result<&'a str, String> {
-        match self.get_site_info_value(k1, k2).as_str() {
-            Some(s) => Ok(s),
-            None => Err(format!("No 'query.{}.{}' value in site info", k1, k2)),
-        }
+    /// Returns the language codes of a `meta=languageinfo` result
+    fn language_codes_from_api_result(result: &Value) -> Option<HashSet<String>> {
+        Some(
+            result["query"]["languageinfo"]
+                .as_object()?
+                .keys()
+                .map(|code| code.to_string())
+                .collect(),
+        )
     }
 
-    /// Returns the raw data for the namespace, matching `["query"]["namespaces"][namespace_id]`
-    pub fn get_namespace_value(&self, namespace_id: NamespaceID) -> Option<&Value> {
-        let v = self.get_site_info_value("namespaces", format!("{}", namespace_id).as_str());
-        if v.is_object() {
-            Some(v)
-        } else {
-            None
-        }
+    /// Returns the site info for a namespace, if defined
+    pub fn get_namespace_info(&self, namespace_id: NamespaceID) -> Option<&NamespaceInfo> {
+        self.site_info.namespaces.get(&namespace_id)
     }
 
     /// Returns the canonical namespace name for a namespace ID, if defined
-    pub fn get_canonical_namespace_name<'a>(
-        &'a self,
-        namespace_id: NamespaceID,
-    ) -> Option<&'a str> {
-        let v = self.get_namespace_value(namespace_id)?;
-        match v["canonical"].as_str() {
-            Some(name) => Some(name),
-            None => match v["*"].as_str() {
-                Some(name) => Some(name),
-                None => None,
-            },
-        }
+    pub fn get_canonical_namespace_name(&self, namespace_id: NamespaceID) -> Option<&str> {
+        let ns = self.get_namespace_info(namespace_id)?;
+        Some(ns.canonical.as_deref().unwrap_or(&ns.name))
     }
 
     /// Returns the local namespace name for a namespace ID, if defined
-    pub fn get_local_namespace_name<'a>(&'a self, namespace_id: NamespaceID) -> Option<&'a str> {
-        let v = self.get_namespace_value(namespace_id)?;
-        match v["*"].as_str() {
-            Some(name) => Some(name),
-            None => match v["canonical"].as_str() {
-                Some(name) => Some(name),
-                None => None,
-            },
-        }
+    pub fn get_local_namespace_name(&self, namespace_id: NamespaceID) -> Option<&str> {
+        let ns = self.get_namespace_info(namespace_id)?;
+        Some(&ns.name)
+    }
+
+    /// Returns the ID of the namespace with the given local or canonical name, or alias.
+    /// Names are matched case-insensitively, with underscores and spaces treated as equal.
+    pub fn get_namespace_id(&self, name: &str) -> Option<NamespaceID> {
+        let normalize = |s: &str| Title::underscores_to_spaces(s).to_lowercase();
+        let name = normalize(name);
+        self.site_info
+            .namespaces
+            .values()
+            .find(|ns| {
+                normalize(&ns.name) == name
+                    || ns
+                        .canonical
+                        .as_deref()
+                        .is_some_and(|c| normalize(c) == name)
+            })
+            .map(|ns| ns.id)
+            .or_else(|| {
+                self.site_info
+                    .namespacealiases
+                    .iter()
+                    .find(|alias| normalize(&alias.alias) == name)
+                    .map(|alias| alias.id)
+            })
     }
 
     /// Loads the site info.
     /// Should only ever be called from `new()`
-    fn load_site_info(&mut self) -> Result<&Value, Box<dyn Error>> {
-        let params = hashmap!["action".to_string()=>"query".to_string(),"meta".to_string()=>"siteinfo".to_string(),"siprop".to_string()=>"general|namespaces|namespacealiases|libraries|extensions|statistics".to_string()];
-        self.site_info = self.get_query_api_json(&params)?;
+    fn load_site_info(&mut self) -> Result<&SiteInfo, Box<dyn Error + Send + Sync>> {
+        self.site_info = SiteInfo::load_default(self)?;
         Ok(&self.site_info)
     }
 
-    /// Merges two JSON objects that are MediaWiki API results.
-    /// If an array already exists in the `a` object, it will be expanded with the array from the `b` object
-    /// This allows for combining multiple API results via the `continue` parameter
-    fn json_merge(&self, a: &mut Value, b: Value) {
-        match (a, b) {
-            (a @ &mut Value::Object(_), Value::Object(b)) => match a.as_object_mut() {
-                Some(a) => {
-                    for (k, v) in b {
-                        self.json_merge(a.entry(k).or_insert(Value::Null), v);
-                    }
-                }
-                None => {}
-            },
-            (a @ &mut Value::Array(_), Value::Array(b)) => match a.as_array_mut() {
-                Some(a) => {
-                    for v in b {
-                        a.push(v);
-                    }
-                }
-                None => {}
-            },
-            (a, b) => *a = b,
-        }
+    /// Loads site info sections that are not in `DEFAULT_SIPROP` (e.g. "usergroups|interwikimap"),
+    /// and adds them to the site info of this `Api`
+    pub fn load_site_info_sections(
+        &mut self,
+        siprop: &str,
+    ) -> Result<&SiteInfo, Box<dyn Error + Send + Sync>> {
+        let params = self.params_into(&[
+            ("action", "query"),
+            ("meta", "siteinfo"),
+            ("siprop", siprop),
+            ("formatversion", "2"),
+        ]);
+        let result = self.get_query_api_json(&params)?;
+        self.site_info.add_sections_from_api_result(&result)?;
+        Ok(&self.site_info)
     }
 
     /// Turns a Vec of str tuples into a Hashmap of String, to be used in API calls
     pub fn params_into(&self, params: &[(&str, &str)]) -> HashMap<String, String> {
         params
-            .into_iter()
+            .iter()
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect()
     }
@@ -291,15 +828,61 @@ impl Api {
         HashMap::new()
     }
 
-    /// Returns a token of a `token_type`, such as `login` or `csrf` (for editing)
-    pub fn get_token(&mut self, token_type: &str) -> Result<String, Box<dyn Error>> {
-        let mut params = hashmap!["action".to_string()=>"query".to_string(),"meta".to_string()=>"tokens".to_string()];
-        if token_type.len() != 0 {
-            params.insert("type".to_string(), token_type.to_string());
+    /// Returns the parameters that are added to every query to the action API
+    pub fn default_params(&self) -> &HashMap<String, String> {
+        &self.default_params
+    }
+
+    /// Sets a parameter that is added to every query to the action API, e.g. `formatversion=2` or
+    /// `uselang=en`, unless the query has a value for it. Note that `formatversion=2` changes the
+    /// shape of many results.
+    pub fn set_default_param(&mut self, key: &str, value: &str) {
+        self.default_params
+            .insert(key.to_string(), value.to_string());
+    }
+
+    /// Removes a parameter set with `set_default_param`
+    pub fn remove_default_param(&mut self, key: &str) {
+        self.default_params.remove(key);
+    }
+
+    /// Returns the default language of requests, from the default parameters
+    pub fn language(&self) -> RequestLanguage {
+        RequestLanguage::new(
+            self.default_params.get("uselang").map(|s| s.as_str()),
+            self.default_params.get("variant").map(|s| s.as_str()),
+        )
+    }
+
+    /// Sets the default language of requests, as default parameters; single requests can set
+    /// another one with `RequestLanguage::apply`. `None` values remove the default.
+    pub fn set_language(&mut self, language: RequestLanguage) {
+        for (key, value) in [("uselang", language.uselang), ("variant", language.variant)] {
+            match value {
+                Some(value) => self.set_default_param(key, &value),
+                None => self.remove_default_param(key),
+            }
         }
+    }
+
+    /// Returns `params`, with the default parameters that it has no value for
+    fn with_default_params(&self, params: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut ret = params.clone();
+        for (key, value) in &self.default_params {
+            ret.entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+        ret
+    }
+
+    /// Returns a token of a `token_type`, such as `login` or `csrf` (for editing)
+    pub fn get_token(&self, token_type: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let token_type_param = Some(token_type).filter(|t| !t.is_empty());
+        let params =
+            params_map!["action" => "query", "meta" => "tokens", "type" => token_type_param];
         let mut key = token_type.to_string();
-        key += &"token";
-        if token_type.len() == 0 {
+        key += "token";
+        if token_type.is_empty() {
             key = "csrftoken".into()
         }
         let x = self.query_api_json_mut(&params, "GET")?;
@@ -310,62 +893,86 @@ impl Api {
     }
 
     /// Calls `get_token()` to return an edit token
-    pub fn get_edit_token(&mut self) -> Result<String, Box<dyn Error>> {
+    pub fn get_edit_token(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
         self.get_token("csrf")
     }
 
-    /// Same as `get_query_api_json` but automatically loads all results via the `continue` parameter
+    /// Checks if a token of type `token_type` (e.g. "csrf") is still valid, e.g. before a large
+    /// batch of writes. This is a cheap request, and does not change anything.
+    pub fn check_token(
+        &self,
+        token_type: &str,
+        token: &str,
+    ) -> Result<TokenStatus, Box<dyn Error + Send + Sync>> {
+        let params = self.params_into(&[
+            ("action", "checktoken"),
+            ("type", token_type),
+            ("token", token),
+        ]);
+        let result = self.post_query_api_json(&params)?;
+        TokenStatus::new_from_api_result(&result)
+            .ok_or_else(|| From::from(format!("Could not check token: {:?}", result)))
+    }
+
+    /// Same as `get_query_api_json` but automatically loads all results via the `continue`
+    /// parameter
     pub fn get_query_api_json_all(
         &self,
         params: &HashMap<String, String>,
-    ) -> Result<Value, Box<dyn Error>> {
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
         self.get_query_api_json_limit(params, None)
     }
 
-    /// Tries to return the len() of an API query result. Returns 0 if unknown
-    fn query_result_count(&self, result: &Value) -> usize {
+    /// Tries to return the len() of an API query result. Returns 0 if unknown. If `key` is given,
+    /// the list with that key is counted (see `Countable`); otherwise the first list that is not
+    /// metadata about the query (such as `normalized` or `redirects`).
+    fn query_result_count(&self, result: &Value, key: Option<&str>) -> usize {
+        if let Some(key) = key {
+            return result.count_list(key);
+        }
         match result["query"].as_object() {
             Some(query) => query
                 .iter()
-                .filter_map(|(_key, part)| match part.as_array() {
-                    Some(a) => Some(a.len()),
-                    None => None,
-                })
+                .filter(|(key, _part)| !QUERY_METADATA_KEYS.contains(&key.as_str()))
+                .filter_map(|(_key, part)| part.as_array().map(|a| a.len()))
                 .next()
                 .unwrap_or(0),
             None => 0, // Don't know size
         }
     }
 
-    /// Same as `get_query_api_json` but automatically loads more results via the `continue` parameter
+    /// Same as `get_query_api_json` but automatically loads more results via the `continue`
+    /// parameter. The results are combined with `Mergeable::merge`.
     pub fn get_query_api_json_limit(
         &self,
         params: &HashMap<String, String>,
         max: Option<usize>,
-    ) -> Result<Value, Box<dyn Error>> {
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
         self.get_query_api_json_limit_iter(params, max)
             .try_fold(Value::Null, |mut acc, result| {
-                self.json_merge(&mut acc, result?);
+                acc.merge(result?);
                 Ok(acc)
             })
     }
 
-    /// Same as `get_query_api_json` but automatically loads more results via the `continue` parameter.
-    /// Returns an iterator; each item is a "page" of results.
+    /// Same as `get_query_api_json` but automatically loads more results via the `continue`
+    /// parameter. Returns an iterator; each item is a "page" of results. `max` is compared to the
+    /// number of results in the list of `list=` or, with a generator, the pages.
     pub fn get_query_api_json_limit_iter<'a>(
         &'a self,
         params: &HashMap<String, String>,
         max: Option<usize>,
-    ) -> impl Iterator<Item = Result<Value, Box<dyn Error>>> + 'a {
+    ) -> impl Iterator<Item = Result<Value, Box<dyn Error + Send + Sync>>> + 'a {
         struct ApiQuery<'a> {
             api: &'a Api,
             params: HashMap<String, String>,
             values_remaining: Option<usize>,
             continue_params: Value,
+            count_key: Option<String>,
         }
 
         impl<'a> Iterator for ApiQuery<'a> {
-            type Item = Result<Value, Box<dyn Error>>;
+            type Item = Result<Value, Box<dyn Error + Send + Sync>>;
             fn next(&mut self) -> Option<Self::Item> {
                 if let Some(0) = self.values_remaining {
                     return None;
@@ -373,12 +980,15 @@ impl Api {
 
                 let mut current_params = self.params.clone();
                 if let Value::Object(obj) = &self.continue_params {
-                    current_params.extend(obj.iter()
-                        .filter(|x| x.0 != "continue")
-
-                        // The default to_string() method for Value puts double-quotes around strings
-                        .map(|(k, v)| (k.to_string(),
-                            v.as_str().map_or(v.to_string(), Into::into))));
+                    current_params.extend(
+                        obj.iter()
+                            .filter(|x| x.0 != "continue")
+                            // The default to_string() method for Value puts double-quotes around
+                            // strings
+                            .map(|(k, v)| {
+                                (k.to_string(), v.as_str().map_or(v.to_string(), Into::into))
+                            }),
+                    );
                 }
 
                 Some(match self.api.get_query_api_json(&current_params) {
@@ -387,15 +997,18 @@ impl Api {
                         if self.continue_params.is_null() {
                             self.values_remaining = Some(0);
                         } else if let Some(num) = self.values_remaining {
-                            self.values_remaining = Some(num.saturating_sub(self.api.query_result_count(&result)));
+                            let count = self
+                                .api
+                                .query_result_count(&result, self.count_key.as_deref());
+                            self.values_remaining = Some(num.saturating_sub(count));
                         }
                         result.as_object_mut().map(|r| r.remove("continue"));
                         Ok(result)
-                    },
+                    }
                     e @ Err(_) => {
                         self.values_remaining = Some(0);
                         e
-                    },
+                    }
                 })
             }
         }
@@ -405,84 +1018,191 @@ impl Api {
             params: params.clone(),
             values_remaining: max,
             continue_params: Value::Null,
+            count_key: result_list_key(params),
         }
     }
 
+    /// Same as `get_query_api_json` but automatically loads more results via the `continue`
+    /// parameter, and yields the values at `selector` one by one, deserialized, instead of merging
+    /// all results. `selector` is a path of keys separated by dots; "[]" at the end yields each
+    /// element of the array (or each value of the object) there, e.g. "query.search[]" or
+    /// "query.pages[]". Without "[]", the value itself is yielded once per batch of results. At
+    /// most `max` values are yielded. If the API returns an error, it is yielded as an `ApiError`.
+    pub fn get_query_api_json_items<'a, T: DeserializeOwned + 'a>(
+        &'a self,
+        params: &HashMap<String, String>,
+        selector: &str,
+        max: Option<usize>,
+    ) -> impl Iterator<Item = Result<T, Box<dyn Error + Send + Sync>>> + 'a {
+        let (path, each) = match selector.strip_suffix("[]") {
+            Some(path) => (path, true),
+            None => (selector, false),
+        };
+        let pointer: String = path
+            .split('.')
+            .filter(|key| !key.is_empty())
+            .map(|key| format!("/{}", key))
+            .collect();
+        self.get_query_api_json_limit_iter(params, None)
+            .flat_map(move |result| match result {
+                Ok(result) => items_from_api_result(result, &pointer, each),
+                Err(e) => vec![Err(e)],
+            })
+            .take(max.unwrap_or(usize::MAX))
+    }
+
     /// Runs a query against the MediaWiki API, using `method` GET or POST.
     /// Parameters are a hashmap; `format=json` is enforced.
     pub fn query_api_json(
         &self,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<Value, Box<dyn Error>> {
-        let mut params = params.clone();
-        let mut attempts_left = self.max_retry_attempts;
-        params.insert("format".to_string(), "json".to_string());
-        let mut cumulative: u64 = 0;
-        loop {
-            self.set_cumulative_maxlag_params(&mut params, method, cumulative);
-            let t = self.query_api_raw(&params, method)?;
-            let v: Value = serde_json::from_str(&t)?;
-            match self.check_maxlag(&v) {
-                Some(lag_seconds) => {
-                    if attempts_left == 0 {
-                        return Err(From::from(format!(
-                            "Max attempts reached [MAXLAG] after {} attempts, cumulative maxlag {}",
-                            &self.max_retry_attempts, cumulative
-                        )));
-                    }
-                    attempts_left -= 1;
-                    cumulative += lag_seconds;
-                    thread::sleep(time::Duration::from_millis(1000 * lag_seconds));
-                }
-                None => return Ok(v),
-            }
-        }
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        self.send_api_json(params, method, false)
     }
 
-    /// Runs a query against the MediaWiki API, using `method` GET or POST.
-    /// Parameters are a hashmap; `format=json` is enforced.
+    /// Runs a query against the MediaWiki API, using `method` GET or POST, and stores session
+    /// cookies. Parameters are a hashmap; `format=json` is enforced.
     fn query_api_json_mut(
-        &mut self,
+        &self,
+        params: &HashMap<String, String>,
+        method: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        self.send_api_json(params, method, true)
+    }
+
+    /// Runs a query against the MediaWiki API, retrying after maxlag errors, OAuth timestamp
+    /// errors, and (if enabled) an expired session
+    fn send_api_json(
+        &self,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<Value, Box<dyn Error>> {
-        let mut params = params.clone();
-        let mut attempts_left = self.max_retry_attempts;
+        store_cookies: bool,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut params = self.with_default_params(params);
+        if self.param_validation {
+            self.validate_params(&params)?;
+        }
+        if self.is_simulated_write(&params, method) {
+            return Ok(self.simulate_write(&params));
+        }
         params.insert("format".to_string(), "json".to_string());
+        self.set_assert_params(&mut params);
         let mut cumulative: u64 = 0;
+        let mut attempt: u64 = 0;
+        let mut waited = Duration::ZERO;
+        let mut clock_synced = false;
+        let mut relogged = false;
         loop {
             self.set_cumulative_maxlag_params(&mut params, method, cumulative);
-            let t = self.query_api_raw_mut(&params, method)?;
+            let t = match store_cookies {
+                true => self.query_api_raw_mut(&params, method)?,
+                false => self.query_api_raw(&params, method)?,
+            };
             let v: Value = serde_json::from_str(&t)?;
+            if !clock_synced && self.oauth.is_some() && is_oauth_timestamp_error(&v) {
+                clock_synced = true;
+                self.sync_clock_from_error(&v)?;
+                continue;
+            }
+            if !relogged && self.auto_relogin && is_session_error(&v) {
+                relogged = true;
+                self.relogin(&mut params)?;
+                continue;
+            }
             match self.check_maxlag(&v) {
                 Some(lag_seconds) => {
-                    if attempts_left == 0 {
-                        return Err(From::from(format!(
+                    attempt += 1;
+                    let wait = self
+                        .maxlag_wait(lag_seconds, attempt, waited)
+                        .ok_or_else(|| {
+                            format!(
                             "Max attempts reached [MAXLAG] after {} attempts, cumulative maxlag {}",
-                            &self.max_retry_attempts, cumulative
-                        )));
-                    }
-                    attempts_left -= 1;
+                            attempt, cumulative
+                        )
+                        })?;
                     cumulative += lag_seconds;
-                    thread::sleep(time::Duration::from_millis(1000 * lag_seconds));
+                    waited += wait;
+                    thread::sleep(wait);
+                }
+                None => {
+                    self.store_warnings(&v);
+                    self.audit_write(&params, method, &v);
+                    return Ok(v);
                 }
-                None => return Ok(v),
             }
         }
     }
 
+    /// Returns whether an expired session is renewed automatically
+    pub fn auto_relogin(&self) -> bool {
+        self.auto_relogin
+    }
+
+    /// Turns automatic re-login on or off. While it is on, `login` keeps the credentials in memory,
+    /// queries of a logged-in user are sent with `assert=user`, and a query that fails because the
+    /// session expired (`assertuserfailed`, `assertbotfailed` or `notloggedin`) is retried once
+    /// after logging in again, with a new token if it had one. OAuth requests do not use sessions,
+    /// so they don't need this. Must be turned on before `login`.
+    pub fn set_auto_relogin(&mut self, auto_relogin: bool) {
+        self.auto_relogin = auto_relogin;
+        if !auto_relogin {
+            *lock(&self.credentials) = None;
+        }
+    }
+
+    /// Adds `assert=user` to a query of a logged-in user, so that an expired session is detected
+    fn set_assert_params(&self, params: &mut HashMap<String, String>) {
+        if self.auto_relogin
+            && self.oauth.is_none()
+            && !params.contains_key("assert")
+            && self.user().logged_in()
+        {
+            params.insert("assert".to_string(), "user".to_string());
+        }
+    }
+
+    /// Logs in again with the stored credentials, and replaces the token of a query that failed
+    fn relogin(
+        &self,
+        params: &mut HashMap<String, String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (lgname, lgpassword) = lock(&self.credentials)
+            .clone()
+            .ok_or("The session expired, and there are no credentials to log in again")?;
+        *self.user_mut() = User::new();
+        self.login(lgname, lgpassword)?;
+        if params.contains_key("token") {
+            let action = params.get("action").map(|s| s.as_str()).unwrap_or_default();
+            let token = self.get_token(token_type_for_action(action))?;
+            params.insert("token".to_string(), token);
+        }
+        Ok(())
+    }
+
     /// Returns the delay time after edits, in milliseconds, if set
     pub fn edit_delay(&self) -> &Option<u64> {
         &self.edit_delay_ms
     }
 
     /// Sets the delay time after edits in milliseconds (or `None`).
-    /// This is independent of, and additional to, MAXLAG
+    /// This is independent of, and additional to, MAXLAG.
+    /// It applies to all write actions that have no delay in the delay policy.
     pub fn set_edit_delay(&mut self, edit_delay_ms: Option<u64>) {
         self.edit_delay_ms = edit_delay_ms;
     }
 
+    /// Returns the delay policy
+    pub fn delay_policy(&self) -> &DelayPolicy {
+        &self.delay_policy
+    }
+
+    /// Sets the delays after reads and different kinds of writes, e.g.
+    /// `DelayPolicy::new().with_delay(Action::Move, Duration::from_secs(10))`
+    pub fn set_delay_policy(&mut self, delay_policy: DelayPolicy) {
+        self.delay_policy = delay_policy;
+    }
+
     /// Returns the maxlag, in seconds, if set
     pub fn maxlag(&self) -> &Option<u64> {
         &self.maxlag_seconds
@@ -493,30 +1213,229 @@ impl Api {
         self.maxlag_seconds = maxlag_seconds;
     }
 
-    /// Checks if a query is an edit, based on parameters and method (GET/POST)
-    fn is_edit_query(&self, params: &HashMap<String, String>, method: &str) -> bool {
-        // Editing only through POST (?)
-        if method != "POST" {
-            return false;
+    /// Sets how queries that failed because of `maxlag` are retried. By default, the query waits as
+    /// long as the reported lag, up to `max_retry_attempts` times; see the `maxlag` module for
+    /// other strategies.
+    pub fn set_maxlag_strategy<S: MaxlagStrategy + 'static>(&mut self, strategy: S) {
+        self.maxlag_strategy = Some(MaxlagHandler(Arc::new(strategy)));
+    }
+
+    /// Restores the default maxlag strategy
+    pub fn clear_maxlag_strategy(&mut self) {
+        self.maxlag_strategy = None;
+    }
+
+    /// Returns whether `maxlag` is also sent with heavy read queries
+    pub fn maxlag_reads(&self) -> bool {
+        self.maxlag_reads
+    }
+
+    /// Sets whether `maxlag` is also sent with heavy read queries (see `is_heavy_read_query`), as
+    /// recommended for non-interactive tools. By default, it is only sent with write actions.
+    pub fn set_maxlag_reads(&mut self, maxlag_reads: bool) {
+        self.maxlag_reads = maxlag_reads;
+    }
+
+    /// Returns how long to wait after the failed `attempt`, or `None` to give up
+    fn maxlag_wait(&self, lag: u64, attempt: u64, waited: Duration) -> Option<Duration> {
+        match &self.maxlag_strategy {
+            Some(strategy) => strategy.0.wait(lag, attempt, waited),
+            None => WaitForLag {
+                max_attempts: self.max_retry_attempts,
+            }
+            .wait(lag, attempt, waited),
+        }
+    }
+
+    /// Returns whether query parameters are validated before queries are sent
+    pub fn param_validation(&self) -> bool {
+        self.param_validation
+    }
+
+    /// Turns validation of query parameters on or off. While it is on, the parameters of every
+    /// query are checked with `validate_params` before it is sent, and a `ParamError` is returned
+    /// for unknown parameters or invalid values. The module metadata is loaded with `paraminfo`
+    /// when first needed.
+    pub fn set_param_validation(&mut self, param_validation: bool) {
+        self.param_validation = param_validation;
+    }
+
+    /// Returns the cached module metadata for parameter validation, by module path;
+    /// `None` for modules that do not exist. Clones of this `Api` share the cache.
+    pub(crate) fn param_info_cache(&self) -> MutexGuard<'_, HashMap<String, Option<ModuleInfo>>> {
+        lock(&self.param_info)
+    }
+
+    /// Returns whether write actions are simulated
+    pub fn simulate(&self) -> bool {
+        self.simulate
+    }
+
+    /// Turns simulation of write actions on or off. While it is on, write actions (any POST request
+    /// with a token, e.g. edits, moves, deletions, uploads and Wikibase edits) are not sent; they
+    /// are logged (see `simulated_writes`), and a synthesized success result is returned. Read
+    /// requests are sent as usual.
+    pub fn set_simulate(&mut self, simulate: bool) {
+        self.simulate = simulate;
+    }
+
+    /// Returns the parameters of the write actions that were simulated, oldest first, without
+    /// tokens. Clones of this `Api` share the log.
+    pub fn simulated_writes(&self) -> Vec<HashMap<String, String>> {
+        lock(&self.simulated_writes).clone()
+    }
+
+    /// Sets a function that is called after every successful write (edits, moves, deletions,
+    /// uploads, Wikibase edits etc.), with a description of the write. Clones of this `Api` share
+    /// the function. No requests are made for the hook, so the change in bytes is only known if the
+    /// API returned it.
+    pub fn set_write_hook<F: Fn(&WriteRecord) + Send + Sync + 'static>(&mut self, hook: F) {
+        self.write_hook = Some(WriteHook {
+            hook: Arc::new(hook),
+            load_sizes: false,
+        });
+    }
+
+    /// Like `set_write_hook`, but if the old and the new revision of a write are known, their sizes
+    /// are loaded to compute the change in bytes. This is one more request after every such write.
+    pub fn set_write_hook_with_sizes<F: Fn(&WriteRecord) + Send + Sync + 'static>(
+        &mut self,
+        hook: F,
+    ) {
+        self.write_hook = Some(WriteHook {
+            hook: Arc::new(hook),
+            load_sizes: true,
+        });
+    }
+
+    /// Removes the function set with `set_write_hook`
+    pub fn clear_write_hook(&mut self) {
+        self.write_hook = None;
+    }
+
+    /// Returns the warnings of the API results received since the last call, oldest first, and
+    /// removes them. At most the last 1000 warnings are kept. Clones of this `Api` share the
+    /// warnings.
+    pub fn take_warnings(&self) -> Vec<ApiWarning> {
+        let mut warnings = lock(&self.warnings);
+        warnings.drain(..).collect()
+    }
+
+    /// Sets a function that is called with every deprecation warning (see
+    /// `ApiWarning::is_deprecation`) in API results, e.g. to log them prominently, so that changes
+    /// of MediaWiki are noticed before they break a tool
+    pub fn set_deprecation_hook<F: Fn(&ApiWarning) + Send + Sync + 'static>(&mut self, hook: F) {
+        self.deprecation_hook = Some(DeprecationHook(Arc::new(hook)));
+    }
+
+    /// Removes the function set with `set_deprecation_hook`
+    pub fn clear_deprecation_hook(&mut self) {
+        self.deprecation_hook = None;
+    }
+
+    /// Stores the warnings of an API result, and reports deprecations
+    fn store_warnings(&self, result: &Value) {
+        let new_warnings = ApiWarning::new_from_api_result(result);
+        if new_warnings.is_empty() {
+            return;
+        }
+        if let Some(hook) = &self.deprecation_hook {
+            new_warnings
+                .iter()
+                .filter(|warning| warning.is_deprecation())
+                .for_each(|warning| (hook.0)(warning));
         }
-        // Editing requires a token
-        if !params.contains_key("token") {
-            return false;
+        let mut warnings = lock(&self.warnings);
+        warnings.extend(new_warnings);
+        while warnings.len() > MAX_STORED_WARNINGS {
+            warnings.pop_front();
         }
-        true
     }
 
-    /// Sets the maglag parameter for a query, if necessary
-    fn _set_maxlag_params(&self, params: &mut HashMap<String, String>, method: &str) {
-        if !self.is_edit_query(params, method) {
+    /// Calls the write hook, if the query was a successful write
+    fn audit_write(&self, params: &HashMap<String, String>, method: &str, result: &Value) {
+        if self.write_hook.is_none() || !self.is_write_query(params, method) {
             return;
         }
-        match self.maxlag_seconds {
-            Some(maxlag_seconds) => {
-                params.insert("maxlag".to_string(), maxlag_seconds.to_string());
+        if let Some(record) = WriteRecord::new_from_api_result(params, result) {
+            self.audit(record);
+        }
+    }
+
+    /// Calls the write hook with a successful write, after adding the change in bytes if the hook
+    /// wants it
+    pub(crate) fn audit(&self, mut record: WriteRecord) {
+        let hook = match &self.write_hook {
+            Some(hook) => hook.clone(),
+            None => return,
+        };
+        if hook.load_sizes && record.bytes_changed.is_none() {
+            if let (Some(old_revid), Some(new_revid)) = (record.old_revid, record.new_revid) {
+                record.bytes_changed = self.size_change(old_revid, new_revid).ok().flatten();
             }
-            None => {}
         }
+        (hook.hook)(&record);
+    }
+
+    /// Returns the size of a revision minus the size of an older one
+    fn size_change(
+        &self,
+        old_revid: u64,
+        new_revid: u64,
+    ) -> Result<Option<i64>, Box<dyn Error + Send + Sync>> {
+        let params = self.params_into(&[
+            ("action", "query"),
+            ("prop", "revisions"),
+            ("revids", &format!("{}|{}", old_revid, new_revid)),
+            ("rvprop", "ids|size"),
+            ("formatversion", "2"),
+        ]);
+        let result = self.get_query_api_json(&params)?;
+        let sizes: HashMap<u64, i64> = result["query"]["pages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|page| page["revisions"].as_array())
+            .flatten()
+            .filter_map(|rev| Some((rev["revid"].as_u64()?, rev["size"].as_i64()?)))
+            .collect();
+        Ok(sizes
+            .get(&old_revid)
+            .zip(sizes.get(&new_revid))
+            .map(|(old_size, new_size)| new_size - old_size))
+    }
+
+    /// Checks if a query would be simulated instead of sent
+    fn is_simulated_write(&self, params: &HashMap<String, String>, method: &str) -> bool {
+        self.simulate && self.is_write_query(params, method)
+    }
+
+    /// Checks if a query is a write action
+    fn is_write_query(&self, params: &HashMap<String, String>, method: &str) -> bool {
+        Action::new_from_params(params, method).is_write()
+    }
+
+    /// Logs a simulated write action, and returns a synthesized success result for it
+    pub(crate) fn simulate_write(&self, params: &HashMap<String, String>) -> Value {
+        let mut logged = params.clone();
+        logged.remove("token");
+        lock(&self.simulated_writes).push(logged);
+        simulated_result(params)
+    }
+
+    /// Checks if a query reads a lot, i.e. uses a generator or list, or a limit of `max`
+    pub fn is_heavy_read_query(params: &HashMap<String, String>) -> bool {
+        params.contains_key("generator")
+            || params.contains_key("list")
+            || params
+                .iter()
+                .any(|(key, value)| key.ends_with("limit") && value == "max")
+    }
+
+    /// Checks if the maxlag parameter should be sent with a query
+    fn uses_maxlag(&self, params: &HashMap<String, String>, method: &str) -> bool {
+        self.is_write_query(params, method)
+            || (self.maxlag_reads && Api::is_heavy_read_query(params))
     }
 
     /// Sets the maglag parameter for a query, if necessary
@@ -526,26 +1445,21 @@ impl Api {
         method: &str,
         cumulative: u64,
     ) {
-        if !self.is_edit_query(params, method) {
+        if !self.uses_maxlag(params, method) {
             return;
         }
-        match self.maxlag_seconds {
-            Some(maxlag_seconds) => {
-                let added = cumulative + maxlag_seconds;
-                params.insert("maxlag".to_string(), added.to_string());
-            }
-            None => {}
+        if let Some(maxlag_seconds) = self.maxlag_seconds {
+            let added = cumulative + maxlag_seconds;
+            params.insert("maxlag".to_string(), added.to_string());
         }
     }
 
     /// Checks for a MAGLAG error, and returns the lag if so
     fn check_maxlag(&self, v: &Value) -> Option<u64> {
         match v["error"]["code"].as_str() {
-            Some(code) => match code {
-                "maxlag" => v["error"]["lag"].as_u64().or(self.maxlag_seconds), // Current lag, if given, or fallback
-                _ => None,
-            },
-            None => None,
+            // Current lag, if given, or fallback
+            Some("maxlag") => v["error"]["lag"].as_u64().or(self.maxlag_seconds),
+            _ => None,
         }
     }
 
@@ -553,7 +1467,7 @@ impl Api {
     pub fn get_query_api_json(
         &self,
         params: &HashMap<String, String>,
-    ) -> Result<Value, Box<dyn Error>> {
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
         self.query_api_json(params, "GET")
     }
 
@@ -561,43 +1475,86 @@ impl Api {
     pub fn post_query_api_json(
         &self,
         params: &HashMap<String, String>,
-    ) -> Result<Value, Box<dyn Error>> {
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
         self.query_api_json(params, "POST")
     }
 
-    /// POST wrapper for `query_api_json`.
-    /// Requires `&mut self`, for sassion cookie storage
+    /// POST wrapper for `query_api_json`, storing session cookies
     pub fn post_query_api_json_mut(
-        &mut self,
+        &self,
         params: &HashMap<String, String>,
-    ) -> Result<Value, Box<dyn Error>> {
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
         self.query_api_json_mut(params, "POST")
     }
 
-    /// Adds or replaces cookies in the cookie jar from a http `Response`
-    pub fn set_cookies_from_response(&mut self, resp: &reqwest::blocking::Response) {
+    /// Runs a multipart POST query against the MediaWiki API, as needed for file uploads. `params`
+    /// are sent as text fields, and `part` as the field `part_name`; `format=json` is enforced.
+    /// Unlike `post_query_api_json`, maxlag errors are not retried, as the body can only be sent
+    /// once.
+    pub fn post_multipart_api_json(
+        &self,
+        params: &HashMap<String, String>,
+        part_name: &str,
+        part: reqwest::blocking::multipart::Part,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut params = self.with_default_params(params);
+        if self.param_validation {
+            self.validate_params(&params)?;
+        }
+        if self.is_simulated_write(&params, "POST") {
+            return Ok(self.simulate_write(&params));
+        }
+        params.insert("format".to_string(), "json".to_string());
+        self.set_cumulative_maxlag_params(&mut params, "POST", 0);
+        // Fields of a multipart body are not part of the OAuth signature
+        let headers = match self.oauth {
+            Some(_) => self.oauth_headers("POST", &self.api_url, &HashMap::new())?,
+            None => {
+                let mut headers = HeaderMap::new();
+                headers.insert(reqwest::header::COOKIE, self.cookies_to_string().parse()?);
+                headers.insert(reqwest::header::USER_AGENT, self.user_agent_full().parse()?);
+                headers
+            }
+        };
+        let form = params
+            .iter()
+            .fold(reqwest::blocking::multipart::Form::new(), |form, (k, v)| {
+                form.text(k.clone(), v.clone())
+            })
+            .part(part_name.to_string(), part);
+        let resp = self
+            .client
+            .post(&self.api_url)
+            .headers(headers)
+            .multipart(form)
+            .send()?;
+        self.enact_delay(&params, "POST");
+        let v = serde_json::from_str(&resp.text()?)?;
+        self.store_warnings(&v);
+        self.audit_write(&params, "POST", &v);
+        Ok(v)
+    }
+
+    /// Adds or replaces cookies in the cookie jar from a http `Response`.
+    /// Clones of this `Api` share the cookie jar, so they share the session.
+    pub fn set_cookies_from_response(&self, resp: &reqwest::blocking::Response) {
         let cookie_strings = resp
             .headers()
             .get_all(reqwest::header::SET_COOKIE)
             .iter()
-            .filter_map(|v| match v.to_str() {
-                Ok(x) => Some(x.to_string()),
-                Err(_) => None,
-            })
+            .filter_map(|v| v.to_str().ok().map(|x| x.to_string()))
             .collect::<Vec<String>>();
+        let mut cookie_jar = lock(&self.cookie_jar);
         for cs in cookie_strings {
-            match Cookie::parse(cs.clone()) {
-                Ok(cookie) => {
-                    self.cookie_jar.add(cookie);
-                }
-                Err(_) => {}
+            if let Ok(cookie) = Cookie::parse(cs) {
+                cookie_jar.add(cookie);
             }
         }
     }
 
     /// Generates a single string to pass as COOKIE parameter in a http `Request`
     pub fn cookies_to_string(&self) -> String {
-        self.cookie_jar
+        lock(&self.cookie_jar)
             .iter()
             .map(|c| c.to_string())
             .collect::<Vec<String>>()
@@ -610,18 +1567,18 @@ impl Api {
         &self,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<String, Box<dyn Error>> {
-        self.query_raw(&self.api_url, params, method)
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        self.query_raw(&self.api_url, &self.with_default_params(params), method)
     }
 
     /// Runs a query against the MediaWiki API, and returns a text.
     /// Uses `query_raw_mut`
     fn query_api_raw_mut(
-        &mut self,
+        &self,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<String, Box<dyn Error>> {
-        self.query_raw_mut(&self.api_url.clone(), params, method)
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        self.query_raw_mut(&self.api_url, &self.with_default_params(params), method)
     }
 
     /// Generates a `RequestBuilder` for the API URL
@@ -629,8 +1586,8 @@ impl Api {
         &self,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error>> {
-        self.request_builder(&self.api_url, params, method)
+    ) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error + Send + Sync>> {
+        self.request_builder(&self.api_url, &self.with_default_params(params), method)
     }
 
     /// Returns the user agent name
@@ -665,39 +1622,38 @@ impl Api {
         api_url: &str,
         to_sign: &HashMap<String, String>,
         oauth: &OAuthParams,
-    ) -> Result<String, Box<dyn Error>> {
-        let mut keys: Vec<String> = to_sign.iter().map(|(k, _)| self.rawurlencode(k)).collect();
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut keys: Vec<String> = to_sign.keys().map(|k| self.rawurlencode(k)).collect();
         keys.sort();
 
         let ret: Vec<String> = keys
             .iter()
             .filter_map(|k| match to_sign.get(k) {
                 Some(k2) => {
-                    let v = self.rawurlencode(&k2);
-                    Some(k.clone() + &"=" + &v)
+                    let v = self.rawurlencode(k2);
+                    Some(k.clone() + "=" + &v)
                 }
                 None => None,
             })
             .collect();
 
         let url = Url::parse(api_url)?;
-        let mut url_string = url.scheme().to_owned() + &"://";
+        let mut url_string = url.scheme().to_owned() + "://";
         url_string += url.host_str().ok_or("url.host_str is None")?;
-        match url.port() {
-            Some(port) => write!(url_string, ":{}", port).unwrap(),
-            None => {}
+        if let Some(port) = url.port() {
+            write!(url_string, ":{}", port).unwrap()
         }
         url_string += url.path();
 
-        let ret = self.rawurlencode(&method)
-            + &"&"
+        let ret = self.rawurlencode(method)
+            + "&"
             + &self.rawurlencode(&url_string)
-            + &"&"
+            + "&"
             + &self.rawurlencode(&ret.join("&"));
 
         let key: String = match (&oauth.g_consumer_secret, &oauth.g_token_secret) {
             (Some(g_consumer_secret), Some(g_token_secret)) => {
-                self.rawurlencode(g_consumer_secret) + &"&" + &self.rawurlencode(g_token_secret)
+                self.rawurlencode(g_consumer_secret) + "&" + &self.rawurlencode(g_token_secret)
             }
             _ => {
                 return Err(From::from("g_consumer_secret or g_token_secret not set"));
@@ -718,7 +1674,22 @@ impl Api {
         method: &str,
         api_url: &str,
         params: &HashMap<String, String>,
-    ) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error>> {
+    ) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error + Send + Sync>> {
+        let headers = self.oauth_headers(method, api_url, params)?;
+        match method {
+            "GET" => Ok(self.client.get(api_url).headers(headers).query(&params)),
+            "POST" => Ok(self.client.post(api_url).headers(headers).form(&params)),
+            other => panic!("Unsupported method '{}'", other),
+        }
+    }
+
+    /// Returns the headers for a signed OAuth request, including cookies and user agent
+    pub(crate) fn oauth_headers(
+        &self,
+        method: &str,
+        api_url: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<HeaderMap, Box<dyn Error + Send + Sync>> {
         let oauth = match &self.oauth {
             Some(oauth) => oauth,
             None => {
@@ -728,10 +1699,7 @@ impl Api {
             }
         };
 
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)?
-            .as_secs()
-            .to_string();
+        let timestamp = (self.local_time()? + self.clock_offset()).to_string();
 
         let nonce = Uuid::new_v4().to_simple().to_string();
 
@@ -758,7 +1726,7 @@ impl Api {
 
         headers.insert(
             "oauth_signature",
-            self.sign_oauth_request(method, api_url, &to_sign, &oauth)?
+            self.sign_oauth_request(method, api_url, &to_sign, oauth)?
                 .parse()?,
         );
 
@@ -770,8 +1738,8 @@ impl Api {
                 let key = key.to_string();
                 let value = value.to_str().unwrap();
                 let key = self.rawurlencode(&key);
-                let value = self.rawurlencode(&value);
-                key.to_string() + &"=\"" + &value + &"\""
+                let value = self.rawurlencode(value);
+                key + "=\"" + &value + "\""
             })
             .collect();
         header += &parts.join(", ");
@@ -783,21 +1751,16 @@ impl Api {
         );
         headers.insert(reqwest::header::COOKIE, self.cookies_to_string().parse()?);
         headers.insert(reqwest::header::USER_AGENT, self.user_agent_full().parse()?);
-
-        match method {
-            "GET" => Ok(self.client.get(api_url).headers(headers).query(&params)),
-            "POST" => Ok(self.client.post(api_url).headers(headers).form(&params)),
-            other => panic!("Unsupported method '{}'", other),
-        }
+        Ok(headers)
     }
 
     /// Returns a `RequestBuilder` for a generic URL
-    fn request_builder(
+    pub(crate) fn request_builder(
         &self,
         api_url: &str,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error>> {
+    ) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error + Send + Sync>> {
         // Use OAuth if set
         if self.oauth.is_some() {
             return self.oauth_request_builder(method, api_url, params);
@@ -820,38 +1783,62 @@ impl Api {
         })
     }
 
+    /// Returns a `RequestBuilder` for a URL that is not part of the wiki's API, e.g. a file on a
+    /// media server: only the user agent is sent, without the cookies or OAuth header of the
+    /// session. The client is built from the connection settings alone, so a client passed to
+    /// `new_from_builder` is not used.
+    pub(crate) fn plain_request_builder(
+        &self,
+        url: &str,
+        params: &HashMap<String, String>,
+        method: &str,
+    ) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error + Send + Sync>> {
+        let builder = match method {
+            "GET" => self.plain_client.get(url).query(&params),
+            "POST" => self.plain_client.post(url).form(&params),
+            other => return Err(From::from(format!("Unsupported method '{}'", other))),
+        };
+        Ok(builder.header(reqwest::header::USER_AGENT, self.user_agent_full()))
+    }
+
     /// Performs a query, pauses if required, and returns the raw response
-    fn query_raw_response(
+    pub(crate) fn query_raw_response(
         &self,
         api_url: &str,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<reqwest::blocking::Response, Box<dyn Error>> {
+    ) -> Result<reqwest::blocking::Response, Box<dyn Error + Send + Sync>> {
         let req = self.request_builder(api_url, params, method)?;
         let resp = req.send()?;
-        self.enact_edit_delay(params, method);
-        return Ok(resp);
+        self.enact_delay(params, method);
+        Ok(resp)
     }
 
-    /// Delays the current thread, if the query performs an edit, and a delay time is set
-    fn enact_edit_delay(&self, params: &HashMap<String, String>, method: &str) {
-        if !self.is_edit_query(params, method) {
-            return;
-        }
-        match self.edit_delay_ms {
-            Some(ms) => thread::sleep(time::Duration::from_millis(ms)),
-            None => {}
+    /// Delays the current thread, if a delay time is set for the query in the delay policy,
+    /// or the query performs a write and an edit delay is set
+    fn enact_delay(&self, params: &HashMap<String, String>, method: &str) {
+        if let Some(delay) = self.delay_after(params, method) {
+            thread::sleep(delay)
         }
     }
 
+    /// Returns the delay after a query, if any
+    fn delay_after(&self, params: &HashMap<String, String>, method: &str) -> Option<Duration> {
+        let kind = Action::new_from_params(params, method);
+        let action = params.get("action").map(|s| s.as_str()).unwrap_or_default();
+        self.delay_policy.delay(action, kind).or_else(|| {
+            Some(Duration::from_millis(self.edit_delay_ms?)).filter(|_| kind.is_write())
+        })
+    }
+
     /// Runs a query against a generic URL, stores cookies, and returns a text
     /// Used for non-stateless queries, such as logins
     fn query_raw_mut(
-        &mut self,
+        &self,
         api_url: &str,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<String, Box<dyn Error>> {
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
         let resp = self.query_raw_response(api_url, params, method)?;
         self.set_cookies_from_response(&resp);
         Ok(resp.text()?)
@@ -865,7 +1852,7 @@ impl Api {
         api_url: &str,
         params: &HashMap<String, String>,
         method: &str,
-    ) -> Result<String, Box<dyn Error>> {
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
         let resp = self.query_raw_response(api_url, params, method)?;
         Ok(resp.text()?)
     }
@@ -873,24 +1860,53 @@ impl Api {
     /// Performs a login against the MediaWiki API.
     /// If successful, user information is stored in `User`, and in the cookie jar
     pub fn login<S: Into<String>>(
-        &mut self,
+        &self,
         lgname: S,
         lgpassword: S,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
         let lgname: &str = &lgname.into();
         let lgpassword: &str = &lgpassword.into();
-        let lgtoken = self.get_token("login")?;
-        let params = hashmap!("action".to_string()=>"login".to_string(),"lgname".to_string()=>lgname.into(),"lgpassword".to_string()=>lgpassword.into(),"lgtoken".to_string()=>lgtoken.into());
-        let res = self.query_api_json_mut(&params, "POST")?;
+        let params = params_map!["action" => "query", "meta" => "tokens", "type" => "login"];
+        let result = self.query_api_json_mut(&params, "GET")?;
+        let lgtoken = Api::login_token_from_api_result(&result)?.unwrap_or_default();
+        let mut params = hashmap!("action".to_string()=>"login".to_string(),"lgname".to_string()=>lgname.into(),"lgpassword".to_string()=>lgpassword.into(),"lgtoken".to_string()=>lgtoken);
+        let mut res = self.query_api_json_mut(&params, "POST")?;
+        if let Some(lgtoken) = Api::login_token_needed(&res) {
+            params.insert("lgtoken".to_string(), lgtoken);
+            res = self.query_api_json_mut(&params, "POST")?;
+        }
         if res["login"]["result"] == "Success" {
-            self.user.set_from_login(&res["login"])?;
+            self.user_mut().set_from_login(&res["login"])?;
+            if self.auto_relogin {
+                *lock(&self.credentials) = Some((lgname.to_string(), lgpassword.to_string()));
+            }
             self.load_user_info()
         } else {
             Err(From::from("Login failed"))
         }
     }
 
-    /// From an API result that has a list of entries with "title" and "ns" (e.g. search), returns a vector of `Title` objects.
+    /// Returns the login token from the result of `meta=tokens`. MediaWiki < 1.27 has no login
+    /// token there (`None`); it answers `NeedToken` with the token instead.
+    fn login_token_from_api_result(result: &Value) -> Result<Option<String>, ApiError> {
+        match ApiError::new_from_api_result(result) {
+            Some(error) => Err(error),
+            None => Ok(result["query"]["tokens"]["logintoken"]
+                .as_str()
+                .map(|s| s.to_string())),
+        }
+    }
+
+    /// Returns the login token from a legacy `NeedToken` answer of `action=login`
+    fn login_token_needed(result: &Value) -> Option<String> {
+        match result["login"]["result"].as_str() {
+            Some("NeedToken") => result["login"]["token"].as_str().map(|s| s.to_string()),
+            _ => None,
+        }
+    }
+
+    /// From an API result that has a list of entries with "title" and "ns" (e.g. search), returns a
+    /// vector of `Title` objects.
     pub fn result_array_to_titles(data: &Value) -> Vec<Title> {
         // See if it's the "root" of the result, then try each sub-object separately
         if data.is_object() {
@@ -898,33 +1914,133 @@ impl Api {
                 .as_object()
                 .unwrap() // OK
                 .iter()
-                .flat_map(|(_k, v)| Api::result_array_to_titles(&v))
+                .flat_map(|(_k, v)| Api::result_array_to_titles(v))
                 .collect();
         }
         data.as_array()
             .unwrap_or(&vec![])
             .iter()
-            .map(|v| Title::new_from_api_result(&v))
+            .map(Title::new_from_api_result)
             .collect()
     }
 
-    /// Performs a SPARQL query against a wikibase installation.
-    /// Tries to get the SPARQL endpoint URL from the site info
-    pub fn sparql_query(&self, query: &str) -> Result<Value, Box<dyn Error>> {
-        let query_api_url = self.get_site_info_string("general", "wikibase-sparql")?;
-        let params = hashmap!["query".to_string()=>query.to_string(),"format".to_string()=>"json".to_string()];
-        let response = self.query_raw_response(&query_api_url, &params, "POST")?;
+    /// Returns the SPARQL endpoint URL: the one set with `set_sparql_endpoint`,
+    /// or else the `wikibase-sparql` value of the site info
+    pub fn sparql_endpoint(&self) -> Option<&str> {
+        self.sparql_endpoint.as_deref().or_else(|| {
+            self.site_info
+                .general
+                .wikibase_sparql
+                .as_ref()
+                .map(SiteUrl::as_str)
+        })
+    }
+
+    /// Sets the SPARQL endpoint URL, e.g. for Wikibase installations that do not announce one
+    /// in the site info, or to use a query service replica. `None` restores the site info value.
+    pub fn set_sparql_endpoint(&mut self, endpoint: Option<String>) {
+        self.sparql_endpoint = endpoint;
+    }
+
+    /// Performs a SPARQL query against a wikibase installation, using POST.
+    /// Uses the endpoint from `sparql_endpoint`
+    pub fn sparql_query(&self, query: &str) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        self.sparql_query_json(query, "POST")
+    }
+
+    /// Performs a SPARQL query against a wikibase installation, using GET. Unlike POST requests,
+    /// GET requests can be cached by the query service, so repeated queries are cheaper. Very long
+    /// queries may exceed the maximum URL length; use `sparql_query` for those.
+    pub fn sparql_query_get(&self, query: &str) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        self.sparql_query_json(query, "GET")
+    }
+
+    /// Performs a SPARQL query with `method`, and parses the JSON result
+    fn sparql_query_json(
+        &self,
+        query: &str,
+        method: &str,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let response = self.sparql_request(query, method, "application/sparql-results+json")?;
         match response.json() {
             Ok(json) => Ok(json),
             Err(e) => Err(From::from(format!("{}", e))),
         }
     }
 
-    /// Given a `uri` (usually, an URL) that points to a Wikibase entity on this MediaWiki installation, returns the item ID
-    pub fn extract_entity_from_uri(&self, uri: &str) -> Result<String, Box<dyn Error>> {
-        let concept_base_uri = self.get_site_info_string("general", "wikibase-conceptbaseuri")?;
-        if uri.starts_with(concept_base_uri) {
-            Ok(uri[concept_base_uri.len()..].to_string())
+    /// Returns how long SPARQL queries are retried if the query service is overloaded
+    pub fn sparql_retry_budget(&self) -> Duration {
+        self.sparql_retry_budget
+    }
+
+    /// Sets how long SPARQL queries are retried if the query service answers with HTTP 429 or 503,
+    /// waiting as long as the `Retry-After` header says. `Duration::ZERO` disables retries.
+    pub fn set_sparql_retry_budget(&mut self, budget: Duration) {
+        self.sparql_retry_budget = budget;
+    }
+
+    /// Sends a SPARQL query with `method`, requesting the MIME type `accept`, and returns the raw
+    /// response. Retries within the retry budget if the query service is overloaded. Unsuccessful
+    /// responses are returned as a `SparqlError`.
+    pub(crate) fn sparql_request(
+        &self,
+        query: &str,
+        method: &str,
+        accept: &str,
+    ) -> Result<reqwest::blocking::Response, Box<dyn Error + Send + Sync>> {
+        let start = Instant::now();
+        loop {
+            let response = self.sparql_request_builder(query, method, accept)?.send()?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+            if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+            {
+                let retry_after = sparql::retry_after(response.headers());
+                let wait = retry_after.unwrap_or(sparql::DEFAULT_RETRY_AFTER);
+                if start.elapsed() + wait > self.sparql_retry_budget {
+                    return Err(Box::new(SparqlError::TooManyRequests(retry_after)));
+                }
+                thread::sleep(wait);
+                continue;
+            }
+            let body = response.text().unwrap_or_default();
+            return Err(Box::new(SparqlError::from_response(status.as_u16(), body)));
+        }
+    }
+
+    /// Returns a `RequestBuilder` for a SPARQL query. The endpoint may be on another host,
+    /// so the cookies and OAuth header of the session are not sent.
+    fn sparql_request_builder(
+        &self,
+        query: &str,
+        method: &str,
+        accept: &str,
+    ) -> Result<reqwest::blocking::RequestBuilder, Box<dyn Error + Send + Sync>> {
+        let query_api_url = self.sparql_endpoint().ok_or(SparqlError::NoEndpoint)?;
+        let params = hashmap!["query".to_string()=>query.to_string()];
+        Ok(self
+            .plain_request_builder(query_api_url, &params, method)?
+            .header(reqwest::header::ACCEPT, accept))
+    }
+
+    /// Given a `uri` (usually, an URL) that points to a Wikibase entity on this MediaWiki
+    /// installation, returns the item ID
+    pub fn extract_entity_from_uri(
+        &self,
+        uri: &str,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let concept_base_uri = self
+            .site_info
+            .general
+            .wikibase_conceptbaseuri
+            .as_ref()
+            .map(SiteUrl::as_str)
+            .ok_or("No 'wikibase-conceptbaseuri' value in site info")?;
+        if let Some(entity) = uri.strip_prefix(concept_base_uri) {
+            Ok(entity.to_string())
         } else {
             Err(From::from(format!(
                 "{} does not start with {}",
@@ -933,41 +2049,697 @@ impl Api {
         }
     }
 
-    /// Returns a vector of entity IDs (as String) from a SPARQL result, given a variable name
+    /// Returns a vector of entity IDs (as String) from a SPARQL result, given a variable name.
+    /// Values that are not entity URIs are skipped; use `sparql_entities` to get typed IDs, and
+    /// errors for those.
     pub fn entities_from_sparql_result(
         &self,
         sparql_result: &Value,
         variable_name: &str,
     ) -> Vec<String> {
         let mut entities = vec![];
-        match sparql_result["results"]["bindings"].as_array() {
-            Some(bindings) => {
-                for b in bindings {
-                    match b[variable_name]["value"].as_str() {
-                        Some(entity_url) => {
-                            entities.push(self.extract_entity_from_uri(entity_url).unwrap());
-                        }
-                        None => {}
+        if let Some(bindings) = sparql_result["results"]["bindings"].as_array() {
+            for b in bindings {
+                if let Some(entity_url) = b[variable_name]["value"].as_str() {
+                    if let Ok(entity) = self.extract_entity_from_uri(entity_url) {
+                        entities.push(entity);
                     }
                 }
             }
-            None => {}
         }
         entities
     }
 }
 
+/// Checks if an API result is an OAuth error about the request timestamp
+fn is_oauth_timestamp_error(result: &Value) -> bool {
+    result["error"]["code"]
+        .as_str()
+        .is_some_and(|code| code.starts_with("mwoauth"))
+        && result["error"]["info"]
+            .as_str()
+            .is_some_and(|info| info.to_lowercase().contains("timestamp"))
+}
+
+/// Returns the server time from an OAuth timestamp error, e.g. "Expired timestamp, yours
+/// 1586859751, ours 1586863351"
+fn server_time_from_oauth_error(result: &Value) -> Option<i64> {
+    let info = result["error"]["info"].as_str()?;
+    let (_, ours) = info.split_once(", ours ")?;
+    ours.split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Checks if an API result is an error because the session expired
+fn is_session_error(result: &Value) -> bool {
+    matches!(
+        result["error"]["code"].as_str(),
+        Some("assertuserfailed" | "assertbotfailed" | "notloggedin")
+    )
+}
+
+/// Returns the type of token (for `meta=tokens`) that an action needs
+fn token_type_for_action(action: &str) -> &'static str {
+    match action {
+        "rollback" => "rollback",
+        "watch" => "watch",
+        "patrol" => "patrol",
+        "userrights" => "userrights",
+        "createaccount" | "linkaccount" => "createaccount",
+        _ => "csrf",
+    }
+}
+
+/// Locks a mutex, ignoring poisoning: the state behind it stays usable if another thread panicked
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Returns the key of the list of results of a query: the `list` module (if there is only one), or
+/// "pages" for generators
+fn result_list_key(params: &HashMap<String, String>) -> Option<String> {
+    if params.contains_key("generator") {
+        return Some("pages".to_string());
+    }
+    match params.get("list") {
+        Some(list) if !list.is_empty() && !list.contains('|') => Some(list.to_string()),
+        _ => None,
+    }
+}
+
+/// Deserializes the items of an API result at `pointer` (see `select_items`), or returns the API
+/// error
+fn items_from_api_result<T: DeserializeOwned>(
+    mut result: Value,
+    pointer: &str,
+    each: bool,
+) -> Vec<Result<T, Box<dyn Error + Send + Sync>>> {
+    if let Some(error) = ApiError::new_from_api_result(&result) {
+        return vec![Err(Box::new(error))];
+    }
+    select_items(&mut result, pointer, each)
+        .into_iter()
+        .map(|item| serde_json::from_value(item).map_err(From::from))
+        .collect()
+}
+
+/// Takes the values at a JSON pointer out of a result: each element (or value) if `each` is set, or
+/// the value itself
+fn select_items(result: &mut Value, pointer: &str, each: bool) -> Vec<Value> {
+    let value = match result.pointer_mut(pointer) {
+        Some(value) => value.take(),
+        None => return vec![],
+    };
+    match value {
+        Value::Array(items) if each => items,
+        Value::Object(items) if each => items.into_iter().map(|(_, item)| item).collect(),
+        _ if each => vec![],
+        value => vec![value],
+    }
+}
+
+/// Returns a success result for a simulated write action, shaped like the result of the action
+fn simulated_result(params: &HashMap<String, String>) -> Value {
+    let param = |key: &str| params.get(key).map(|s| s.as_str()).unwrap_or_default();
+    let action = param("action");
+    if action.starts_with("wb") {
+        let id = match param("new") {
+            "" => param("id"),
+            "property" => "P0",
+            "lexeme" => "L0",
+            _ => "Q0",
+        };
+        let entity_type = match id.chars().next() {
+            Some('P') => "property",
+            Some('L') => "lexeme",
+            Some('M') => "mediainfo",
+            _ => "item",
+        };
+        let mut entity = json!({"id": id, "type": entity_type, "lastrevid": 0});
+        let term = json!({"language": param("language"), "value": param("value")});
+        match action {
+            "wbsetlabel" => entity["labels"] = json!({ param("language"): term }),
+            "wbsetdescription" => entity["descriptions"] = json!({ param("language"): term }),
+            _ => {}
+        }
+        let mut result = json!({"success": 1, "entity": entity, "pageinfo": {"lastrevid": 0}});
+        if let Ok(claim) = serde_json::from_str::<Value>(param("claim")) {
+            result["claim"] = claim;
+        }
+        return result;
+    }
+    match action {
+        "edit" => json!({"edit": {"result": "Success", "title": param("title"), "nochange": true}}),
+        "move" => {
+            json!({"move": {"from": param("from"), "to": param("to"), "reason": param("reason")}})
+        }
+        "delete" => {
+            json!({"delete": {"title": param("title"), "reason": param("reason"), "logid": 0}})
+        }
+        "upload" => {
+            let result = if params.contains_key("stash") {
+                "Continue"
+            } else {
+                "Success"
+            };
+            json!({"upload": {
+                "result": result,
+                "filename": param("filename"),
+                "filekey": "simulated",
+                "imageinfo": {}
+            }})
+        }
+        "import" => json!({"import": []}),
+        _ => json!({ action: {"result": "Success"}, "success": 1 }),
+    }
+}
+
+/// Returns an `Api` for the German Wikipedia with the site info of `test_data`, for tests that
+/// don't send requests
+#[cfg(test)]
+pub(crate) fn test_api() -> Api {
+    let result = serde_json::from_str(include_str!("../test_data/site_info/dewiki.json")).unwrap();
+    let site_info = SiteInfo::new_from_api_result(&result).unwrap();
+    Api::new_with_site_info("https://de.wikipedia.org/w/api.php", site_info).unwrap()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Api, Title};
+    use super::{
+        is_oauth_timestamp_error, is_session_error, items_from_api_result, lock, result_list_key,
+        select_items, server_time_from_oauth_error, test_api, token_type_for_action, Api, ApiError,
+        ApiWarning, Cookie, OAuthParams, RequestLanguage, SparqlError, Title, TokenStatus,
+    };
+    use crate::action::Action;
+    use crate::delay::DelayPolicy;
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     #[test]
-    fn site_info() {
-        let api = Api::new("https://www.wikidata.org/w/api.php").unwrap();
+    fn simulate() {
+        let mut api = test_api();
+        api.set_simulate(true);
+        let params = api.params_into(&[
+            ("action", "edit"),
+            ("title", "Foo"),
+            ("text", "Bar"),
+            ("token", "x"),
+        ]);
+        let result = api.post_query_api_json(&params).unwrap();
+        assert_eq!(result["edit"]["result"], "Success");
+        let params = api.params_into(&[
+            ("action", "wbsetlabel"),
+            ("id", "P5"),
+            ("language", "en"),
+            ("value", "x"),
+            ("token", "x"),
+        ]);
+        let result = api.post_query_api_json(&params).unwrap();
+        assert_eq!(result["entity"]["type"], "property");
+        assert_eq!(result["entity"]["labels"]["en"]["value"], "x");
+        let writes = api.clone().simulated_writes();
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0]["text"], "Bar");
+        assert!(!writes[0].contains_key("token"));
+        let params = api.params_into(&[("action", "checktoken"), ("type", "csrf"), ("token", "x")]);
+        assert!(!api.is_simulated_write(&params, "POST"));
+    }
+
+    #[test]
+    fn errors_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<Api>();
+        assert_send_sync::<crate::dump::DumpError>();
+        assert_send_sync::<crate::file::FileError>();
+        assert_send_sync::<crate::import::ImportError>();
+        assert_send_sync::<crate::journal::JournalError>();
+        assert_send_sync::<crate::mediainfo::MediaInfoError>();
+        assert_send_sync::<crate::page::PageError>();
+        assert_send_sync::<crate::paraminfo::ParamError>();
+        assert_send_sync::<crate::rest::RestError>();
+        assert_send_sync::<SparqlError>();
+        assert_send_sync::<crate::title::TitleError>();
+        assert_send_sync::<crate::upload::UploadError>();
+        assert_send_sync::<crate::wikibase::WikibaseError>();
+    }
+
+    #[test]
+    fn items() {
+        let mut result = json!({"batchcomplete": true, "query": {
+            "search": [{"title": "Foo"}, {"title": "Bar"}],
+            "pages": {"1": {"title": "Foo"}},
+            "searchinfo": {"totalhits": 2}
+        }});
+        assert_eq!(
+            select_items(&mut result, "/query/search", true),
+            vec![json!({"title": "Foo"}), json!({"title": "Bar"})]
+        );
+        assert_eq!(
+            select_items(&mut result, "/query/pages", true),
+            vec![json!({"title": "Foo"})]
+        );
+        assert_eq!(
+            select_items(&mut result, "/query/searchinfo", false),
+            vec![json!({"totalhits": 2})]
+        );
+        assert!(select_items(&mut result, "/query/allpages", true).is_empty());
+        assert!(select_items(&mut result, "/batchcomplete", true).is_empty());
+    }
+
+    #[test]
+    fn items_api_error() {
+        let result = json!({"error": {"code": "badtitle", "info": "Bad title \"<\"."}});
+        let items: Vec<Result<Value, _>> = items_from_api_result(result, "/query/pages", true);
+        assert_eq!(items.len(), 1);
+        let error = items[0]
+            .as_ref()
+            .unwrap_err()
+            .downcast_ref::<ApiError>()
+            .unwrap();
+        assert_eq!(error.code, "badtitle");
+        let result = json!({"errors": [{"code": "permissiondenied", "text": "Permission denied.",
+            "module": "main"}]});
+        assert_eq!(
+            ApiError::new_from_api_result(&result),
+            Some(ApiError {
+                code: "permissiondenied".to_string(),
+                info: "Permission denied.".to_string()
+            })
+        );
+        let result = json!({"query": {"pages": [{"title": "Foo"}]}});
+        let items: Vec<Result<Value, _>> = items_from_api_result(result, "/query/pages", true);
+        assert_eq!(items[0].as_ref().unwrap(), &json!({"title": "Foo"}));
+    }
+
+    #[test]
+    fn result_count() {
+        let api = test_api();
+        let result = json!({"query": {
+            "normalized": [{"from": "a", "to": "A"}],
+            "search": [{"title": "A"}, {"title": "B"}]
+        }});
+        assert_eq!(api.query_result_count(&result, Some("search")), 2);
+        assert_eq!(api.query_result_count(&result, None), 2);
+        let params = api.params_into(&[("action", "query"), ("list", "search")]);
+        assert_eq!(result_list_key(&params).as_deref(), Some("search"));
+        let params = api.params_into(&[
+            ("action", "query"),
+            ("generator", "allpages"),
+            ("prop", "info"),
+        ]);
+        assert_eq!(result_list_key(&params).as_deref(), Some("pages"));
+        let params = api.params_into(&[("action", "query"), ("list", "search|allpages")]);
+        assert_eq!(result_list_key(&params), None);
+    }
+
+    #[test]
+    fn params_map() {
+        let titles = vec!["Foo".to_string(), "Bar".to_string()];
+        let empty: &[&str] = &[];
+        let params = params_map![
+            "action" => "query",
+            "titles" => &titles,
+            "redirects" => true,
+            "export" => false,
+            "rvlimit" => 5,
+            "rvprop" => &["ids", "content"],
+            "aliases" => vec!["a|b", "c"],
+            "empty" => empty,
+            "rvstart" => None::<&str>,
+            "rvend" => Some("2020-01-01T00:00:00Z"),
+            "rvdir" => Some(false),
+        ];
+        assert_eq!(params.len(), 7);
+        assert_eq!(params["rvend"], "2020-01-01T00:00:00Z");
+        assert_eq!(params["titles"], "Foo|Bar");
+        assert_eq!(params["redirects"], "1");
+        assert_eq!(params["rvlimit"], "5");
+        assert_eq!(params["rvprop"], "ids|content");
+        assert_eq!(params["aliases"], "\u{1f}a|b\u{1f}c");
+    }
+
+    #[test]
+    fn warnings() {
+        let api = test_api();
+        api.store_warnings(&json!({"warnings": {"main": {
+            "warnings": "Unrecognized parameter: titels.\nSecond."}}}));
+        api.store_warnings(&json!({"warnings": {"revisions": {
+            "*": "Because \"rvslots\" was not specified, ..."}}}));
+        api.store_warnings(&json!({"warnings": [
+            {"code": "deprecation", "module": "query+revisions",
+            "text": "The parameter is deprecated."}
+        ]}));
+        let warnings = api.clone().take_warnings();
+        assert_eq!(warnings.len(), 4);
+        assert_eq!(warnings[1].text, "Second.");
+        assert_eq!(warnings[2].module, "revisions");
+        assert_eq!(
+            warnings[3],
+            ApiWarning {
+                module: "query+revisions".to_string(),
+                code: Some("deprecation".to_string()),
+                text: "The parameter is deprecated.".to_string()
+            }
+        );
+        assert!(api.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn token_status() {
+        let result =
+            json!({"checktoken": {"result": "expired", "generated": "2020-01-01T00:00:00Z"}});
+        assert_eq!(
+            TokenStatus::new_from_api_result(&result),
+            Some(TokenStatus::Expired)
+        );
+        let result = json!({"checktoken": {"result": "valid"}});
+        assert!(TokenStatus::new_from_api_result(&result)
+            .unwrap()
+            .is_valid());
+        assert_eq!(
+            TokenStatus::new_from_api_result(&json!({"error": {}})),
+            None
+        );
+    }
+
+    #[test]
+    fn maxlag_reads() {
+        let mut api = test_api();
+        let read = api.params_into(&[
+            ("action", "query"),
+            ("list", "allpages"),
+            ("aplimit", "max"),
+        ]);
+        let light = api.params_into(&[("action", "query"), ("prop", "info"), ("titles", "Foo")]);
+        let edit = api.params_into(&[("action", "edit"), ("title", "Foo"), ("token", "x")]);
+        assert!(!api.uses_maxlag(&read, "GET"));
+        assert!(api.uses_maxlag(&edit, "POST"));
+        api.set_maxlag_reads(true);
+        assert!(api.uses_maxlag(&read, "GET"));
+        assert!(!api.uses_maxlag(&light, "GET"));
+
+        assert_eq!(
+            api.maxlag_wait(2, 1, Duration::ZERO),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            api.maxlag_wait(2, api.max_retry_attempts() + 1, Duration::ZERO),
+            None
+        );
+        api.set_maxlag_strategy(|_: u64, attempt: u64, _: Duration| {
+            Some(Duration::from_millis(attempt))
+        });
+        assert_eq!(
+            api.maxlag_wait(2, 100, Duration::ZERO),
+            Some(Duration::from_millis(100))
+        );
+    }
+
+    #[test]
+    fn delays() {
+        let mut api = test_api();
+        let edit = api.params_into(&[("action", "edit"), ("title", "Foo"), ("token", "x")]);
+        let delete = api.params_into(&[("action", "delete"), ("title", "Foo"), ("token", "x")]);
+        let read = api.params_into(&[("action", "query"), ("list", "allpages")]);
+        api.set_edit_delay(Some(500));
+        api.set_delay_policy(
+            DelayPolicy::new()
+                .with_delay(Action::Read, Duration::from_millis(100))
+                .with_delay(Action::Delete, Duration::from_secs(2)),
+        );
+        assert_eq!(
+            api.delay_after(&edit, "POST"),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            api.delay_after(&delete, "POST"),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            api.delay_after(&read, "GET"),
+            Some(Duration::from_millis(100))
+        );
+        api.set_delay_policy(DelayPolicy::new());
+        assert_eq!(api.delay_after(&read, "GET"), None);
+    }
+
+    #[test]
+    fn shared_session() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Api>();
+
+        let api = Arc::new(test_api());
+        let clone = (*api).clone();
+        let shared = api.clone();
+        std::thread::spawn(move || {
+            shared
+                .user_mut()
+                .set_from_login(&json!({"result": "Success", "lgusername": "Foo", "lguserid": 1}))
+                .unwrap()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(api.user().user_name(), "Foo");
+        assert!(clone.user().logged_in());
+    }
+
+    #[test]
+    fn default_params() {
+        let mut api = test_api();
+        api.set_default_param("formatversion", "2");
+        api.set_default_param("uselang", "en");
+        let params = api.params_into(&[("action", "query"), ("uselang", "de")]);
+        let params = api.with_default_params(&params);
+        assert_eq!(params["formatversion"], "2");
+        assert_eq!(params["uselang"], "de");
+        api.remove_default_param("formatversion");
+        assert_eq!(api.default_params().len(), 1);
+    }
+
+    #[test]
+    fn request_language() {
+        let mut api = test_api();
+        api.set_language(RequestLanguage::new(Some("en"), Some("zh-hant")));
+        assert_eq!(api.language().variant.as_deref(), Some("zh-hant"));
+        let mut params = api.params_into(&[("action", "parse"), ("page", "Foo")]);
+        RequestLanguage::new(None, Some("zh-hans")).apply(&mut params);
+        let params = api.with_default_params(&params);
+        assert_eq!(
+            (params["uselang"].as_str(), params["variant"].as_str()),
+            ("en", "zh-hans")
+        );
+        api.set_language(RequestLanguage::default());
+        assert!(api.default_params().is_empty());
+    }
+
+    #[test]
+    fn oauth_timestamp_errors() {
+        let error = json!({"error": {"code": "mwoauth-invalid-authorization",
+            "info": "The authorization headers in your request are not valid: \
+                Expired timestamp, yours 1586859751, ours 1586863351"}});
+        assert!(is_oauth_timestamp_error(&error));
+        assert_eq!(server_time_from_oauth_error(&error), Some(1586863351));
+        let error = json!({"error": {"code": "mwoauth-invalid-authorization",
+            "info": "Invalid signature"}});
+        assert!(!is_oauth_timestamp_error(&error));
+        assert_eq!(server_time_from_oauth_error(&error), None);
+
+        let api = test_api();
+        let local = api.local_time().unwrap();
+        let error = json!({"error": {"code": "mwoauth-invalid-authorization",
+            "info": format!("Expired timestamp, yours {}, ours {}", local, local + 600)}});
+        api.sync_clock_from_error(&error).unwrap();
+        assert!((599..=601).contains(&api.clock_offset()));
+    }
+
+    #[test]
+    fn relogin() {
+        assert!(is_session_error(
+            &json!({"error": {"code": "assertuserfailed"}})
+        ));
+        assert!(!is_session_error(&json!({"error": {"code": "badtoken"}})));
+        assert_eq!(token_type_for_action("rollback"), "rollback");
+        assert_eq!(token_type_for_action("edit"), "csrf");
+
+        let mut api = test_api();
+        api.set_auto_relogin(true);
+        let mut params = api.params_into(&[("action", "query")]);
+        api.set_assert_params(&mut params);
+        assert!(!params.contains_key("assert"));
+        api.user_mut()
+            .set_from_login(&json!({"result": "Success", "lgusername": "Foo", "lguserid": 1}))
+            .unwrap();
+        api.set_assert_params(&mut params);
+        assert_eq!(params["assert"], "user");
+        // Without stored credentials, there is no way to log in again
+        assert!(api.relogin(&mut params).is_err());
+    }
+
+    #[test]
+    fn deprecation_warnings() {
+        let mut api = test_api();
+        let deprecations = Arc::new(Mutex::new(vec![]));
+        let log = deprecations.clone();
+        api.set_deprecation_hook(move |warning| log.lock().unwrap().push(warning.text.clone()));
+        api.store_warnings(&json!({"warnings": [
+            {"code": "deprecation",
+            "text": "The parameter \"rvcontentformat\" has been deprecated.",
+            "module": "query+revisions"},
+            {"code": "truncatedresult", "text": "This result was truncated.", "module": "main"}
+        ]}));
+        api.store_warnings(&json!({"warnings": {"main": {"*": "Unrecognized parameter: foo."}}}));
+        assert_eq!(
+            *deprecations.lock().unwrap(),
+            vec![
+                "The parameter \"rvcontentformat\" has been deprecated.",
+                "Unrecognized parameter: foo."
+            ]
+        );
+        assert_eq!(api.take_warnings().len(), 3);
+    }
+
+    #[test]
+    fn legacy_login_token() {
+        let result =
+            json!({"login": {"result": "NeedToken", "token": "abc+\\", "cookieprefix": "enwiki"}});
+        assert_eq!(Api::login_token_needed(&result).as_deref(), Some("abc+\\"));
+        assert_eq!(
+            Api::login_token_needed(&json!({"login": {"result": "Success"}})),
+            None
+        );
+    }
+
+    #[test]
+    fn login_token() {
+        let result = json!({"query": {"tokens": {"logintoken": "abc+\\"}}});
+        assert_eq!(
+            Api::login_token_from_api_result(&result)
+                .unwrap()
+                .as_deref(),
+            Some("abc+\\")
+        );
+        let result = json!({"warnings": {"tokens": {
+            "*": "Unrecognized value for parameter \"type\": login."}}, "query": {"tokens": {}}});
+        assert_eq!(Api::login_token_from_api_result(&result).unwrap(), None);
+        let result =
+            json!({"error": {"code": "readapidenied", "info": "You need read permission."}});
+        assert!(Api::login_token_from_api_result(&result).is_err());
+    }
+
+    #[test]
+    fn write_hook() {
+        let mut api = test_api();
+        let records = Arc::new(Mutex::new(vec![]));
+        let log = records.clone();
+        api.set_write_hook(move |record| log.lock().unwrap().push(record.clone()));
+        let params = api.params_into(&[
+            ("action", "move"),
+            ("from", "Foo"),
+            ("to", "Bar"),
+            ("token", "x"),
+        ]);
+        let result = json!({"move": {"from": "Foo", "to": "Bar"}});
+        api.audit_write(&params, "POST", &result);
+        api.audit_write(&params, "GET", &result);
+        api.audit_write(
+            &params,
+            "POST",
+            &json!({"error": {"code": "articleexists"}}),
+        );
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].title.as_deref(), Some("Foo"));
+        assert_eq!(records[0].bytes_changed, None);
+    }
+
+    #[test]
+    fn for_other_wiki() {
+        let mut api = test_api();
+        api.set_simulate(true);
+        api.set_maxlag(Some(7));
+        api.set_default_param("assert", "user");
+        api.param_info_cache().insert("query+foo".to_string(), None);
+        api.store_warnings(&json!({"warnings": {"main": {"*": "Unrecognized parameter: foo."}}}));
+        let params = api.params_into(&[
+            ("action", "edit"),
+            ("title", "Foo"),
+            ("text", "x"),
+            ("token", "x"),
+        ]);
+        api.post_query_api_json(&params).unwrap();
+
+        let other = api.for_other_wiki("https://de.wikipedia.org/w/api.php");
+        assert_eq!(other.api_url(), "https://de.wikipedia.org/w/api.php");
+        assert!(other.simulate());
+        assert_eq!(other.maxlag(), &Some(7));
+        assert!(other.default_params().is_empty());
+        assert!(other.param_info_cache().is_empty());
+        assert!(other.simulated_writes().is_empty());
+        assert!(other.take_warnings().is_empty());
+        assert_eq!(api.simulated_writes().len(), 1);
+        assert_eq!(api.take_warnings().len(), 1);
+    }
+
+    #[test]
+    fn sparql_endpoint() {
+        let mut api = test_api();
+        assert_eq!(api.sparql_endpoint(), None);
+        let error = api.sparql_query("SELECT ?x {}").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(SparqlError::NoEndpoint)
+        ));
+        api.set_sparql_endpoint(Some("https://query.example.org/sparql".to_string()));
         assert_eq!(
-            api.get_site_info_string("general", "sitename").unwrap(),
-            "Wikidata"
+            api.sparql_endpoint(),
+            Some("https://query.example.org/sparql")
         );
+        api.set_sparql_endpoint(None);
+        assert_eq!(api.sparql_endpoint(), None);
+    }
+
+    #[test]
+    fn sparql_request_without_session() {
+        let mut api = test_api();
+        lock(&api.cookie_jar).add(Cookie::new("examplewikiSession", "secret"));
+        api.set_oauth(Some(OAuthParams::new_from_json(&json!({
+            "gConsumerKey": "ck", "gConsumerSecret": "cs", "gTokenKey": "tk", "gTokenSecret": "ts"
+        }))));
+        api.set_sparql_endpoint(Some("https://query.example.net/sparql".to_string()));
+        for method in &["GET", "POST"] {
+            let request = api
+                .sparql_request_builder("SELECT ?x {}", method, "application/sparql-results+json")
+                .unwrap()
+                .build()
+                .unwrap();
+            let headers = request.headers();
+            assert!(headers.get(reqwest::header::COOKIE).is_none());
+            assert!(headers.get(reqwest::header::AUTHORIZATION).is_none());
+            assert!(headers.get(reqwest::header::USER_AGENT).is_some());
+            assert_eq!(request.url().host_str(), Some("query.example.net"));
+        }
+    }
+
+    #[test]
+    fn language_codes_from_api_result() {
+        let result = json!({"batchcomplete": true, "query": {"languageinfo": {
+            "en": {"code": "en"}, "de": {"code": "de"}, "zh-hant": {"code": "zh-hant"}}}});
+        let codes = Api::language_codes_from_api_result(&result).unwrap();
+        assert_eq!(codes.len(), 3);
+        assert!(codes.contains("zh-hant"));
+        assert!(Api::language_codes_from_api_result(&json!({"batchcomplete": true})).is_none());
+    }
+
+    #[test]
+    fn site_info() {
+        let api = Api::new("https://www.wikidata.org/w/api.php").unwrap();
+        assert_eq!(api.get_site_info().general.sitename, "Wikidata");
     }
 
     #[test]
@@ -1017,18 +2789,18 @@ mod tests {
     fn extract_entity_from_uri() {
         let api = Api::new("https://www.wikidata.org/w/api.php").unwrap();
         assert_eq!(
-            api.extract_entity_from_uri(&"http://www.wikidata.org/entity/Q123")
+            api.extract_entity_from_uri("http://www.wikidata.org/entity/Q123")
                 .unwrap(),
             "Q123"
         );
         assert_eq!(
-            api.extract_entity_from_uri(&"http://www.wikidata.org/entity/P456")
+            api.extract_entity_from_uri("http://www.wikidata.org/entity/P456")
                 .unwrap(),
             "P456"
         );
         // Expect error ('/' missing):
         assert!(api
-            .extract_entity_from_uri(&"http:/www.wikidata.org/entity/Q123")
+            .extract_entity_from_uri("http:/www.wikidata.org/entity/Q123")
             .is_err());
     }
 
@@ -0,0 +1,185 @@
+/*!
+The `WriteRecord` class describes a successful write operation (an edit, move, deletion, upload,
+Wikibase edit etc.). A hook set with `Api::set_write_hook` gets a `WriteRecord` after every
+successful write, e.g. to keep a local audit log or to collect statistics, without wrapping every
+call site. With `Api::set_write_hook_with_sizes`, the change in bytes is loaded, too, at the cost of
+one more request per write.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::action::Action;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A successful write operation
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WriteRecord {
+    /// The API action, e.g. "edit", "move" or "wbsetclaim"
+    pub action: String,
+    /// The page that was changed (for moves, the old title), if known
+    pub title: Option<String>,
+    /// The Wikibase entity that was changed, if any
+    pub entity: Option<String>,
+    /// The edit summary or reason, if any
+    pub summary: Option<String>,
+    /// The revision ID before the write, if known
+    pub old_revid: Option<u64>,
+    /// The revision ID after the write; `None` if no revision was created (e.g. a null edit)
+    pub new_revid: Option<u64>,
+    /// Change of the page size in bytes, if the old and the new revision are known, and the hook
+    /// was set with `Api::set_write_hook_with_sizes`
+    pub bytes_changed: Option<i64>,
+}
+
+impl WriteRecord {
+    /// Describes a write operation from its API parameters and result.
+    /// Returns `None` if the result is not a success.
+    pub fn new_from_api_result(
+        params: &HashMap<String, String>,
+        result: &Value,
+    ) -> Option<WriteRecord> {
+        let action = params.get("action")?.as_str();
+        if !is_success(action, result) {
+            return None;
+        }
+        let param = |key: &str| params.get(key).filter(|s| !s.is_empty()).cloned();
+        let mut record = WriteRecord {
+            action: action.to_string(),
+            title: param("title"),
+            entity: None,
+            summary: param("summary")
+                .or_else(|| param("reason"))
+                .or_else(|| param("comment")),
+            old_revid: None,
+            new_revid: None,
+            bytes_changed: None,
+        };
+        let output = &result[action];
+        match action {
+            "edit" => {
+                record.title = output["title"]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .or(record.title);
+                record.old_revid = output["oldrevid"].as_u64().filter(|id| *id > 0);
+                record.new_revid = output["newrevid"].as_u64();
+            }
+            "move" => {
+                record.title = output["from"]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .or_else(|| param("from"))
+            }
+            "upload" => {
+                record.title = output["filename"]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .or_else(|| param("filename"))
+                    .map(|filename| format!("File:{}", filename));
+            }
+            _ if action.starts_with("wb") => {
+                record.entity = result["entity"]["id"]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .or_else(|| param("id"))
+                    .or_else(|| param("entity"));
+                record.old_revid = param("baserevid").and_then(|id| id.parse().ok());
+                record.new_revid = result["pageinfo"]["lastrevid"]
+                    .as_u64()
+                    .or_else(|| result["entity"]["lastrevid"].as_u64())
+                    .filter(|id| *id > 0);
+            }
+            _ => {}
+        }
+        Some(record)
+    }
+
+    /// Returns the kind of the write operation
+    pub fn kind(&self) -> Action {
+        Action::new_from_write_action(&self.action)
+    }
+}
+
+/// Checks if the result of a write action is a success
+fn is_success(action: &str, result: &Value) -> bool {
+    if !result["error"].is_null() {
+        return false;
+    }
+    match result[action]["result"].as_str() {
+        Some(status) => status == "Success",
+        None => !result[action].is_null() || result["success"].as_u64() == Some(1),
+    }
+}
+
+/// A function that is called after every successful write
+#[derive(Clone)]
+pub(crate) struct WriteHook {
+    pub(crate) hook: Arc<dyn Fn(&WriteRecord) + Send + Sync>,
+    /// Whether the revision sizes are loaded to compute `WriteRecord::bytes_changed`
+    pub(crate) load_sizes: bool,
+}
+
+impl fmt::Debug for WriteHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WriteHook")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn records() {
+        let result = json!({"edit": {"result": "Success", "pageid": 1, "title": "Foo bar",
+            "contentmodel": "wikitext", "oldrevid": 10, "newrevid": 11}});
+        let record = WriteRecord::new_from_api_result(
+            &params(&[("action", "edit"), ("title", "Foo_bar"), ("summary", "x")]),
+            &result,
+        )
+        .unwrap();
+        assert_eq!(record.title.as_deref(), Some("Foo bar"));
+        assert_eq!(record.kind(), Action::Edit);
+        assert_eq!(record.summary.as_deref(), Some("x"));
+        assert_eq!((record.old_revid, record.new_revid), (Some(10), Some(11)));
+
+        let result = json!({"success": 1, "pageinfo": {"lastrevid": 7}, "claim": {}});
+        let params = params(&[
+            ("action", "wbsetclaim"),
+            ("baserevid", "6"),
+            ("claim", "{}"),
+        ]);
+        let record = WriteRecord::new_from_api_result(&params, &result).unwrap();
+        assert_eq!((record.old_revid, record.new_revid), (Some(6), Some(7)));
+
+        let result = json!({"error": {"code": "protectedpage"}});
+        assert!(WriteRecord::new_from_api_result(&params, &result).is_none());
+        let params = self::params(&[("action", "upload"), ("filename", "Foo.jpg")]);
+        let result = json!({"upload": {"result": "Warning", "warnings": {}}});
+        assert!(WriteRecord::new_from_api_result(&params, &result).is_none());
+        let result = json!({"upload": {"result": "Success", "filename": "Foo.jpg"}});
+        let record = WriteRecord::new_from_api_result(&params, &result).unwrap();
+        assert_eq!(record.title.as_deref(), Some("File:Foo.jpg"));
+    }
+}
@@ -0,0 +1,343 @@
+/*!
+The `BatchEditor` class runs a queue of Wikibase entity edits, like QuickStatements does:
+with a shared edit summary that groups the edits for [EditGroups](https://editgroups.toolforge.org),
+retries of failed edits, and progress that can be saved to resume an interrupted batch.
+With an `EditJournal`, each edit is sent at most once, even if the progress was not saved.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, TRANSIENT_ERROR_CODES};
+use crate::journal::{EditJournal, JournalError, JournalState};
+use crate::wikibase::{Entity, EntityEdit, EntityTarget, WikibaseError};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// The default EditGroups tool code, for custom bots
+pub const DEFAULT_EDITGROUPS_TOOL: &str = "CB";
+
+/// An edit in a batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchItem {
+    /// The entity to edit, or create
+    pub target: EntityTarget,
+    /// The changes
+    pub edit: EntityEdit,
+    /// A summary for this edit, added to the summary of the batch
+    pub summary: Option<String>,
+}
+
+/// The progress of a batch, which can be saved (e.g. as JSON) to resume the batch later
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BatchState {
+    /// The ID of the batch, used in the EditGroups summary token
+    pub batch_id: String,
+    /// The number of items that were processed
+    pub done: usize,
+}
+
+/// Runs a queue of entity edits with `wbeditentity`
+#[derive(Debug, Clone)]
+pub struct BatchEditor {
+    items: Vec<BatchItem>,
+    summary: String,
+    tool: String,
+    state: BatchState,
+    max_retries: u32,
+    retry_delay: Duration,
+    journal: Option<PathBuf>,
+}
+
+impl BatchEditor {
+    /// Creates an empty batch with a new random ID; `summary` is used for all edits
+    pub fn new(summary: &str) -> BatchEditor {
+        let batch_id = Uuid::new_v4().to_simple().to_string()[..12].to_string();
+        BatchEditor {
+            items: vec![],
+            summary: summary.to_string(),
+            tool: DEFAULT_EDITGROUPS_TOOL.to_string(),
+            state: BatchState { batch_id, done: 0 },
+            max_retries: 3,
+            retry_delay: Duration::from_secs(5),
+            journal: None,
+        }
+    }
+
+    /// Adds an edit to the queue, with an optional summary of its own
+    pub fn push(&mut self, target: EntityTarget, edit: EntityEdit, summary: Option<&str>) {
+        self.items.push(BatchItem {
+            target,
+            edit,
+            summary: summary.map(|s| s.to_string()),
+        });
+    }
+
+    /// Returns the edits in the queue, including those already done
+    pub fn items(&self) -> &[BatchItem] {
+        &self.items
+    }
+
+    /// Returns the progress of the batch
+    pub fn state(&self) -> &BatchState {
+        &self.state
+    }
+
+    /// Sets the progress of the batch, e.g. one saved from an interrupted run, with the same queue
+    pub fn set_state(&mut self, state: BatchState) {
+        self.state = state;
+    }
+
+    /// Sets the EditGroups tool code (default `DEFAULT_EDITGROUPS_TOOL`)
+    pub fn set_tool(&mut self, tool: &str) {
+        self.tool = tool.to_string();
+    }
+
+    /// Sets how often a failed edit is retried, if the error may be transient (default 3)
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// Sets the delay before the first retry, which doubles for each further retry (default 5
+    /// seconds)
+    pub fn set_retry_delay(&mut self, retry_delay: Duration) {
+        self.retry_delay = retry_delay;
+    }
+
+    /// Sets a file for an `EditJournal` of the edits. An edit that is in the journal as done is
+    /// skipped; new entities are recorded by their position in the batch, so the batch ID must stay
+    /// the same.
+    pub fn set_journal(&mut self, journal: Option<PathBuf>) {
+        self.journal = journal;
+    }
+
+    /// Returns the URL of the batch on EditGroups
+    pub fn editgroups_url(&self) -> String {
+        format!(
+            "https://editgroups.toolforge.org/b/{}/{}/",
+            self.tool, self.state.batch_id
+        )
+    }
+
+    /// Returns the summary for an item: the batch summary, the item summary, and the EditGroups
+    /// token
+    pub fn summary_for(&self, item: &BatchItem) -> String {
+        let mut summary = self.summary.clone();
+        if let Some(item_summary) = &item.summary {
+            if !summary.is_empty() {
+                summary += ": ";
+            }
+            summary += item_summary;
+        }
+        format!(
+            "{} ([[:toollabs:editgroups/b/{}/{}|details]])",
+            summary, self.tool, self.state.batch_id
+        )
+    }
+
+    /// Runs the edits that are not done yet, in order. After each edit, `on_result` is called with
+    /// the new progress and the result; failed edits are reported there, and the batch continues.
+    /// With a journal, an edit is marked as pending before it is sent, and as done after it
+    /// succeeded. An edit whose request failed stays pending, since it may have been saved.
+    ///
+    /// # Errors
+    /// Returns an error if the journal can't be read or written, or `JournalError::InDoubt` if an
+    /// earlier attempt of the next edit was interrupted; it has to be resolved in the journal
+    /// first.
+    pub fn run<F>(&mut self, api: &Api, mut on_result: F) -> Result<(), JournalError>
+    where
+        F: FnMut(&BatchState, &Result<Entity, WikibaseError>),
+    {
+        let mut journal = match &self.journal {
+            Some(path) => Some(EditJournal::open(path)?),
+            None => None,
+        };
+        while self.state.done < self.items.len() {
+            let index = self.state.done;
+            let item = &self.items[index];
+            let result = match journal.as_mut() {
+                Some(journal) => {
+                    let key = self.journal_key(index, item);
+                    let content = item.edit.to_data().to_string();
+                    match journal.state(&key, &content) {
+                        Some(JournalState::Done) => {
+                            self.state.done += 1;
+                            continue;
+                        }
+                        Some(JournalState::Pending) => {
+                            return Err(JournalError::InDoubt {
+                                page: key,
+                                hash: EditJournal::hash(&content),
+                            })
+                        }
+                        Some(JournalState::Discarded) | None => {}
+                    }
+                    journal.mark_pending(&key, &content)?;
+                    let result = self.run_item(api, item);
+                    match &result {
+                        Ok(entity) => journal.mark_done(&key, &content, entity.lastrevid)?,
+                        Err(WikibaseError::RequestError(_)) => {}
+                        Err(_) => journal.discard(&key, &content)?,
+                    }
+                    result
+                }
+                None => self.run_item(api, item),
+            };
+            self.state.done += 1;
+            on_result(&self.state, &result);
+        }
+        Ok(())
+    }
+
+    /// Returns the key of an edit in the journal: the entity ID, or the position in the batch for
+    /// new entities
+    fn journal_key(&self, index: usize, item: &BatchItem) -> String {
+        match &item.target {
+            EntityTarget::Id(id) => id.to_string(),
+            EntityTarget::NewItem | EntityTarget::NewProperty(_) => {
+                format!("batch {} #{}", self.state.batch_id, index)
+            }
+        }
+    }
+
+    /// Runs an edit, retrying it if the error may be transient. An edit whose request failed is not
+    /// retried, since it may have been saved, and a retry could apply it twice (e.g. add a
+    /// statement or alias again).
+    fn run_item(&self, api: &Api, item: &BatchItem) -> Result<Entity, WikibaseError> {
+        let summary = self.summary_for(item);
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match api.wbeditentity(&item.target, &item.edit, &summary, None) {
+                Err(e) if attempt < self.max_retries && is_transient(&e) => {
+                    attempt += 1;
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Checks if an edit that failed with an error was not saved, and may succeed if retried
+fn is_transient(error: &WikibaseError) -> bool {
+    match error {
+        WikibaseError::EditConflict(_) => true,
+        WikibaseError::ApiError { code, .. } => TRANSIENT_ERROR_CODES.contains(&code.as_str()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    #[test]
+    fn summaries() {
+        let mut batch = BatchEditor::new("Import from example.org");
+        assert_eq!(batch.state().batch_id.len(), 12);
+        batch.set_state(BatchState {
+            batch_id: "abc123".to_string(),
+            done: 0,
+        });
+        let mut edit = EntityEdit::new();
+        edit.set_label("en", "Foo");
+        batch.push(EntityTarget::NewItem, edit.clone(), None);
+        batch.push(EntityTarget::Id("Q1".to_string()), edit, Some("label"));
+        assert_eq!(
+            batch.summary_for(&batch.items()[0]),
+            "Import from example.org ([[:toollabs:editgroups/b/CB/abc123|details]])"
+        );
+        batch.set_tool("MYBOT");
+        assert_eq!(
+            batch.summary_for(&batch.items()[1]),
+            "Import from example.org: label ([[:toollabs:editgroups/b/MYBOT/abc123|details]])"
+        );
+        assert_eq!(
+            batch.editgroups_url(),
+            "https://editgroups.toolforge.org/b/MYBOT/abc123/"
+        );
+    }
+
+    #[test]
+    fn resume() {
+        let mut batch = BatchEditor::new("");
+        batch.push(EntityTarget::NewItem, EntityEdit::new(), None);
+        let state: BatchState =
+            serde_json::from_value(json!({"batch_id": "abc123", "done": 1})).unwrap();
+        batch.set_state(state);
+        let api = test_api();
+        let mut calls = 0;
+        batch.run(&api, |_, _| calls += 1).unwrap();
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn journal() {
+        let path =
+            std::env::temp_dir().join(format!("mediawiki-batch-journal-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut batch = BatchEditor::new("");
+        batch.set_state(BatchState {
+            batch_id: "abc123".to_string(),
+            done: 0,
+        });
+        let mut edit = EntityEdit::new();
+        edit.set_label("en", "Foo");
+        batch.push(EntityTarget::NewItem, edit.clone(), None);
+        batch.push(EntityTarget::Id("Q1".to_string()), edit.clone(), None);
+        assert_eq!(batch.journal_key(0, &batch.items()[0]), "batch abc123 #0");
+        assert_eq!(batch.journal_key(1, &batch.items()[1]), "Q1");
+
+        let content = edit.to_data().to_string();
+        let mut journal = EditJournal::open(&path).unwrap();
+        journal
+            .mark_done("batch abc123 #0", &content, Some(5))
+            .unwrap();
+        journal.mark_pending("Q1", &content).unwrap();
+        drop(journal);
+        batch.set_journal(Some(path.clone()));
+        let api = test_api();
+        let mut calls = 0;
+        // The creation is skipped, and the interrupted edit is not sent again
+        let result = batch.run(&api, |_, _| calls += 1);
+        assert!(matches!(result, Err(JournalError::InDoubt { .. })));
+        assert_eq!((calls, batch.state().done), (0, 1));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn transient_errors() {
+        assert!(is_transient(&WikibaseError::ApiError {
+            code: "maxlag".to_string(),
+            info: String::new()
+        }));
+        assert!(!is_transient(&WikibaseError::ApiError {
+            code: "permissiondenied".to_string(),
+            info: String::new()
+        }));
+        assert!(!is_transient(&WikibaseError::Validation {
+            code: "modification-failed".to_string(),
+            info: String::new(),
+            messages: vec![]
+        }));
+        // The edit may have been saved before the connection was lost
+        assert!(!is_transient(&WikibaseError::RequestError(From::from(
+            "timed out"
+        ))));
+    }
+}
@@ -144,7 +144,9 @@ fn main() {
     _wikidata_item_tester();
 }*/
 
-fn _edit_sandbox_item(api: &mut mediawiki::api::Api) -> Result<Value, Box<dyn Error>> {
+fn _edit_sandbox_item(
+    api: &mut mediawiki::api::Api,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
     let q = "Q13406268"; // Second sandbox item
     let token = api.get_edit_token().unwrap();
     let params: HashMap<String, String> = vec![
@@ -182,7 +184,7 @@ fn _oauth_edit(api: &mut mediawiki::api::Api) {
     api.set_oauth(Some(oauth_params));
     //let _x = api.oauth().clone();
 
-    let mut params: HashMap<String, String> = vec![
+    let mut params: HashMap<String, String> = [
         ("action", "wbeditentity"),
         ("id", sandbox_item),
         (
@@ -242,7 +244,7 @@ fn main() {
     */
 
     let api = mediawiki::api::Api::new("https://www.wikidata.org/w/api.php").unwrap();
-    let x = api.get_namespace_value(0);
+    let x = api.get_namespace_info(0);
     println!("{:?}", x);
     let x = api.get_local_namespace_name(0);
     println!("{:?}", x);
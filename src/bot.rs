@@ -0,0 +1,433 @@
+/*!
+The `BotTask` class runs a bot over the pages of a `PageGenerator`: it loads each page, passes the
+text to an edit function, and saves the result. Edits are throttled, transient errors (e.g. maxlag
+or edit conflicts) are retried, and failures are collected per page instead of stopping the run. If
+the `Api` simulates writes (see `Api::set_simulate`), nothing is saved. Processed pages can be
+recorded in a checkpoint file, so an interrupted run can be resumed, and edits can be recorded in an
+`EditJournal`, so that none is saved twice.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, TRANSIENT_ERROR_CODES};
+use crate::journal::{EditJournal, JournalError, JournalState};
+use crate::page::{Page, PageError};
+use crate::pagegenerator::PageGenerator;
+use crate::title::Title;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The change an edit function wants to make to a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BotEdit {
+    /// The new text of the page
+    pub text: String,
+    /// The edit summary
+    pub summary: String,
+    /// Whether the edit is minor
+    pub minor: bool,
+}
+
+impl BotEdit {
+    /// Creates an edit that is not minor
+    pub fn new(text: impl Into<String>, summary: impl Into<String>) -> BotEdit {
+        BotEdit {
+            text: text.into(),
+            summary: summary.into(),
+            minor: false,
+        }
+    }
+}
+
+/// A page that could not be processed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageFailure {
+    /// The page, unless the error came from the page generator
+    pub title: Option<Title>,
+    /// The error message
+    pub message: String,
+}
+
+/// The outcome of a bot run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BotReport {
+    /// Number of pages that were processed, including skipped and failed pages
+    pub processed: usize,
+    /// Number of pages that were edited (or would have been, in simulation mode)
+    pub edited: usize,
+    /// Number of pages that the edit function left unchanged, or that were in the checkpoint
+    pub skipped: usize,
+    /// Pages that could not be processed
+    pub failures: Vec<PageFailure>,
+}
+
+/// Runs an edit function over pages
+#[derive(Debug, Clone)]
+pub struct BotTask {
+    throttle: Duration,
+    max_retries: u32,
+    retry_delay: Duration,
+    checkpoint: Option<PathBuf>,
+    journal: Option<PathBuf>,
+    last_edit: Option<Instant>,
+}
+
+impl Default for BotTask {
+    fn default() -> Self {
+        BotTask::new()
+    }
+}
+
+impl BotTask {
+    /// Creates a task with a throttle of 10 seconds between edits, and 3 retries
+    pub fn new() -> BotTask {
+        BotTask {
+            throttle: Duration::from_secs(10),
+            max_retries: 3,
+            retry_delay: Duration::from_secs(5),
+            checkpoint: None,
+            journal: None,
+            last_edit: None,
+        }
+    }
+
+    /// Sets the minimum time between two edits
+    pub fn set_throttle(&mut self, throttle: Duration) {
+        self.throttle = throttle;
+    }
+
+    /// Sets how often a page is processed again after a transient error (default 3)
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// Sets the delay before the first retry, which doubles for each further retry (default 5
+    /// seconds)
+    pub fn set_retry_delay(&mut self, retry_delay: Duration) {
+        self.retry_delay = retry_delay;
+    }
+
+    /// Sets a file that records the processed pages, one title per line. Pages in the file are
+    /// skipped, so running the task again resumes after the last processed page, and retries the
+    /// failed ones.
+    pub fn set_checkpoint(&mut self, checkpoint: Option<PathBuf>) {
+        self.checkpoint = checkpoint;
+    }
+
+    /// Sets a file for an `EditJournal` of the edits. An edit that is in the journal as done is not
+    /// saved again. An edit that was interrupted is saved again only if the page still differs from
+    /// the edited text.
+    pub fn set_journal(&mut self, journal: Option<PathBuf>) {
+        self.journal = journal;
+    }
+
+    /// Runs `edit` over the pages that `pages` generates. `edit` gets each page with its current
+    /// text (empty for missing pages), and returns the edit to make, if any. To test an edit
+    /// function without saving anything, turn on `Api::set_simulate`; simulated edits are not
+    /// recorded in the journal.
+    ///
+    /// # Errors
+    /// Returns an error if the edit token can't be loaded, or the checkpoint or journal file can't
+    /// be read or written. Errors of single pages are collected in the report.
+    pub fn run<G, F>(
+        &mut self,
+        api: &Api,
+        pages: G,
+        mut edit: F,
+    ) -> Result<BotReport, Box<dyn Error + Send + Sync>>
+    where
+        G: for<'b> FnOnce(&'b Api) -> PageGenerator<'b>,
+        F: FnMut(&Page, &str) -> Result<Option<BotEdit>, Box<dyn Error + Send + Sync>>,
+    {
+        let token = if api.simulate() {
+            String::new()
+        } else {
+            api.get_edit_token()?
+        };
+        let api: &Api = api;
+        let mut done = self.read_checkpoint()?;
+        let mut checkpoint = match &self.checkpoint {
+            Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+            None => None,
+        };
+        let mut journal = match &self.journal {
+            Some(path) => Some(EditJournal::open(path)?),
+            None => None,
+        };
+        let mut report = BotReport::default();
+        for page in pages(api) {
+            report.processed += 1;
+            let page = match page {
+                Ok(page) => page,
+                Err(e) => {
+                    report.failures.push(PageFailure {
+                        title: None,
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let title = page
+                .title()
+                .full_pretty(api)
+                .unwrap_or_else(|| page.title().pretty().to_string());
+            if done.contains(&title) {
+                report.skipped += 1;
+                continue;
+            }
+            match self.process_page(api, &token, &page, &title, journal.as_mut(), &mut edit) {
+                Ok(edited) => {
+                    if edited {
+                        report.edited += 1;
+                    } else {
+                        report.skipped += 1;
+                    }
+                    // Failed pages are not recorded, so they are tried again when resuming
+                    if let Some(file) = &mut checkpoint {
+                        writeln!(file, "{}", title)?;
+                    }
+                    done.insert(title);
+                }
+                Err(e) => report.failures.push(PageFailure {
+                    title: Some(page.title().clone()),
+                    message: e.to_string(),
+                }),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Processes a page, retrying transient errors; returns whether the page was edited
+    fn process_page<F>(
+        &mut self,
+        api: &Api,
+        token: &str,
+        page: &Page,
+        title: &str,
+        mut journal: Option<&mut EditJournal>,
+        edit: &mut F,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>>
+    where
+        F: FnMut(&Page, &str) -> Result<Option<BotEdit>, Box<dyn Error + Send + Sync>>,
+    {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match self.try_page(api, token, page, title, journal.as_deref_mut(), edit) {
+                Err(e) if attempt < self.max_retries && is_transient(e.as_ref()) => {
+                    attempt += 1;
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Loads a page, runs the edit function, and saves the edit
+    fn try_page<F>(
+        &mut self,
+        api: &Api,
+        token: &str,
+        page: &Page,
+        title: &str,
+        journal: Option<&mut EditJournal>,
+        edit: &mut F,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>>
+    where
+        F: FnMut(&Page, &str) -> Result<Option<BotEdit>, Box<dyn Error + Send + Sync>>,
+    {
+        let params = api.params_into(&[
+            ("action", "query"),
+            ("prop", "revisions"),
+            ("titles", title),
+            ("rvprop", "content|timestamp"),
+            ("rvslots", "main"),
+            ("formatversion", "2"),
+        ]);
+        let result = api.get_query_api_json(&params)?;
+        let current = &result["query"]["pages"][0];
+        let revision = &current["revisions"][0];
+        let text = revision["slots"]["main"]["content"]
+            .as_str()
+            .unwrap_or_default();
+        let bot_edit = match edit(page, text)? {
+            Some(bot_edit) if bot_edit.text != text => bot_edit,
+            _ => return Ok(false),
+        };
+        let journal = match journal {
+            Some(journal) if !api.simulate() => journal,
+            _ => {
+                return self
+                    .save(api, token, title, current, &bot_edit)
+                    .map(|_| true)
+            }
+        };
+        if journal.state(title, &bot_edit.text) == Some(JournalState::Pending) {
+            // The page differs from the edited text, so the interrupted edit was not saved
+            journal.discard(title, &bot_edit.text)?;
+        }
+        Ok(journal.perform(title, &bot_edit.text, || {
+            self.save(api, token, title, current, &bot_edit)
+        })?)
+    }
+
+    /// Saves an edit, and returns the new revision ID
+    fn save(
+        &mut self,
+        api: &Api,
+        token: &str,
+        title: &str,
+        current: &Value,
+        bot_edit: &BotEdit,
+    ) -> Result<Option<u64>, Box<dyn Error + Send + Sync>> {
+        if !api.simulate() {
+            self.wait_for_throttle();
+        }
+        let mut params = api.params_into(&[
+            ("action", "edit"),
+            ("title", title),
+            ("text", &bot_edit.text),
+            ("summary", &bot_edit.summary),
+            ("formatversion", "2"),
+            ("token", token),
+        ]);
+        edit_conditions(&mut params, api, current);
+        if bot_edit.minor {
+            params.insert("minor".to_string(), "1".to_string());
+        }
+        let result = api.post_query_api_json(&params)?;
+        self.last_edit = Some(Instant::now());
+        match result["edit"]["result"].as_str() {
+            Some("Success") => Ok(result["edit"]["newrevid"].as_u64()),
+            _ => Err(Box::new(PageError::EditError(result))),
+        }
+    }
+
+    /// Sleeps until the throttle time has passed since the last edit
+    fn wait_for_throttle(&self) {
+        if let Some(last_edit) = self.last_edit {
+            let elapsed = last_edit.elapsed();
+            if elapsed < self.throttle {
+                thread::sleep(self.throttle - elapsed);
+            }
+        }
+    }
+
+    /// Reads the titles in the checkpoint file, if any
+    fn read_checkpoint(&self) -> Result<HashSet<String>, Box<dyn Error + Send + Sync>> {
+        let path = match &self.checkpoint {
+            Some(path) => path,
+            None => return Ok(HashSet::new()),
+        };
+        match File::open(path) {
+            Ok(file) => Ok(BufReader::new(file)
+                .lines()
+                .collect::<Result<HashSet<_>, _>>()?
+                .into_iter()
+                .filter(|line| !line.is_empty())
+                .collect()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(HashSet::new()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
+
+/// Adds the parameters that make an edit fail if the page changed since it was loaded, and marks
+/// bot edits
+fn edit_conditions(params: &mut HashMap<String, String>, api: &Api, page: &Value) {
+    match page["revisions"][0]["timestamp"].as_str() {
+        Some(timestamp) => {
+            params.insert("basetimestamp".to_string(), timestamp.to_string());
+            params.insert("nocreate".to_string(), "1".to_string());
+        }
+        None => {
+            params.insert("createonly".to_string(), "1".to_string());
+        }
+    }
+    if api.user().is_bot() {
+        params.insert("bot".to_string(), "1".to_string());
+    }
+    if !api.user().user_name().is_empty() {
+        params.insert("assert".to_string(), "user".to_string());
+    }
+}
+
+/// Checks if an error may go away if the page is processed again
+fn is_transient(error: &(dyn Error + 'static)) -> bool {
+    if let Some(JournalError::Write(error)) = error.downcast_ref::<JournalError>() {
+        return is_transient(error.as_ref());
+    }
+    match error.downcast_ref::<PageError>() {
+        Some(PageError::EditError(result)) => result["error"]["code"]
+            .as_str()
+            .is_some_and(|code| TRANSIENT_ERROR_CODES.contains(&code)),
+        Some(_) => false,
+        None => error.downcast_ref::<reqwest::Error>().is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+    use std::iter;
+
+    #[test]
+    fn checkpoint() {
+        let path =
+            std::env::temp_dir().join(format!("mediawiki-bot-checkpoint-{}", std::process::id()));
+        std::fs::write(&path, "Foo\nBar\n").unwrap();
+        let mut task = BotTask::new();
+        task.set_checkpoint(Some(path.clone()));
+        let mut api = test_api();
+        api.set_simulate(true);
+        let report = task
+            .run(
+                &api,
+                |_| {
+                    PageGenerator::new(iter::once(Err(From::from("generator failed"))).chain(vec![
+                        Ok(Page::new(Title::new("Foo", 0))),
+                        Ok(Page::new(Title::new("Bar", 0))),
+                    ]))
+                },
+                |_, _| panic!("checkpointed pages are skipped"),
+            )
+            .unwrap();
+        assert_eq!((report.processed, report.edited, report.skipped), (3, 0, 2));
+        assert_eq!(report.failures[0].title, None);
+        assert_eq!(report.failures[0].message, "generator failed");
+        std::fs::remove_file(&path).unwrap();
+        assert!(task.read_checkpoint().unwrap().is_empty());
+    }
+
+    #[test]
+    fn transient_errors() {
+        let error = PageError::EditError(json!({"error": {"code": "maxlag"}}));
+        assert!(is_transient(&error));
+        let error = PageError::EditError(json!({"error": {"code": "protectedpage"}}));
+        assert!(!is_transient(&error));
+        let error = JournalError::Write(Box::new(PageError::EditError(
+            json!({"error": {"code": "editconflict"}}),
+        )));
+        assert!(is_transient(&error));
+        let error: Box<dyn Error + Send + Sync> = From::from("other");
+        assert!(!is_transient(error.as_ref()));
+    }
+}
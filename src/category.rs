@@ -0,0 +1,345 @@
+/*!
+The `Category` class deals with categories: their members and sizes, and adding or removing pages
+by editing the category links in their wikitext.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::namespace::Namespace;
+use crate::page::Page;
+use crate::params_map;
+use crate::title::Title;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// The number of members of a category, from `prop=categoryinfo`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CategoryInfo {
+    /// Number of all members
+    #[serde(default)]
+    pub size: u64,
+    /// Number of members that are neither files nor subcategories
+    #[serde(default)]
+    pub pages: u64,
+    /// Number of files
+    #[serde(default)]
+    pub files: u64,
+    /// Number of subcategories
+    #[serde(default)]
+    pub subcats: u64,
+    /// Whether the category is hidden (with `__HIDDENCAT__`)
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// Represents a category.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    title: Title,
+}
+
+impl Category {
+    /// Creates a new `Category` from a `Title`.
+    ///
+    /// # Errors
+    /// Returns `CategoryError::NotACategory` if the title is not in the category namespace.
+    pub fn new(title: Title) -> Result<Self, CategoryError> {
+        if title.namespace() != Namespace::CATEGORY {
+            return Err(CategoryError::NotACategory(title));
+        }
+        Ok(Category { title })
+    }
+
+    /// Accesses the `Title` of this `Category`.
+    pub fn title(&self) -> &Title {
+        &self.title
+    }
+
+    /// Returns all members of this category: pages, files and subcategories
+    ///
+    /// # Errors
+    /// Returns `CategoryError::BadTitle` if the title has a namespace unknown to the Api,
+    /// or any error from the requests.
+    pub fn members(&self, api: &Api) -> Result<Vec<Title>, CategoryError> {
+        self.load_members(api, None)
+    }
+
+    /// Returns the subcategories of this category
+    ///
+    /// # Errors
+    /// Like `members`.
+    pub fn subcategories(&self, api: &Api) -> Result<Vec<Category>, CategoryError> {
+        self.load_members(api, Some("subcat"))?
+            .into_iter()
+            .map(Category::new)
+            .collect()
+    }
+
+    /// Returns the members of this category, optionally only of one type ("page", "subcat" or
+    /// "file")
+    fn load_members(
+        &self,
+        api: &Api,
+        member_type: Option<&str>,
+    ) -> Result<Vec<Title>, CategoryError> {
+        let params = params_map![
+            "action" => "query",
+            "list" => "categorymembers",
+            "cmtitle" => self.full_title(api)?,
+            "cmprop" => "ids|title",
+            "cmtype" => member_type,
+            "cmlimit" => "max",
+            "formatversion" => "2",
+        ];
+        api.get_query_api_json_items::<Value>(&params, "query.categorymembers[]", None)
+            .map(|member| Ok(Title::new_from_api_result(&member?)))
+            .collect::<Result<_, _>>()
+            .map_err(CategoryError::RequestError)
+    }
+
+    /// Returns the number of members of this category. A category without a page and without
+    /// members has none.
+    ///
+    /// # Errors
+    /// Returns `CategoryError::BadResponse` if the result can't be parsed, or any error from the
+    /// request.
+    pub fn size_info(&self, api: &Api) -> Result<CategoryInfo, CategoryError> {
+        let params = params_map![
+            "action" => "query",
+            "prop" => "categoryinfo",
+            "titles" => self.full_title(api)?,
+            "formatversion" => "2",
+        ];
+        let result = api
+            .get_query_api_json(&params)
+            .map_err(CategoryError::RequestError)?;
+        match &result["query"]["pages"][0] {
+            Value::Object(page) => match page.get("categoryinfo") {
+                Some(info) => CategoryInfo::deserialize(info)
+                    .map_err(|_| CategoryError::BadResponse(result.clone())),
+                None => Ok(CategoryInfo::default()),
+            },
+            _ => Err(CategoryError::BadResponse(result)),
+        }
+    }
+
+    /// Adds a page to this category, by appending a category link to its wikitext.
+    /// Returns `false` if the page already has a link to this category, and was not changed.
+    ///
+    /// # Errors
+    /// Returns any error from loading or editing the page.
+    pub fn add_page(&self, api: &Api, page: &Page, summary: &str) -> Result<bool, CategoryError> {
+        let text = page
+            .text(api)
+            .map_err(|e| CategoryError::RequestError(Box::new(e)))?;
+        if !self.links_in(api, &text).is_empty() {
+            return Ok(false);
+        }
+        let name = api
+            .get_local_namespace_name(Namespace::CATEGORY.id())
+            .ok_or_else(|| CategoryError::BadTitle(self.title.clone()))?;
+        let new_text = self.with_link(api, &text, &format!("[[{}:{}]]", name, self.title.pretty()));
+        page.edit_text(api, new_text, summary)
+            .map_err(CategoryError::RequestError)?;
+        Ok(true)
+    }
+
+    /// Removes a page from this category, by removing all links to this category from its wikitext.
+    /// Returns `false` if the page has no such link (it may still be in the category through a
+    /// template).
+    ///
+    /// # Errors
+    /// Returns any error from loading or editing the page.
+    pub fn remove_page(
+        &self,
+        api: &Api,
+        page: &Page,
+        summary: &str,
+    ) -> Result<bool, CategoryError> {
+        let text = page
+            .text(api)
+            .map_err(|e| CategoryError::RequestError(Box::new(e)))?;
+        let new_text = self.without_links(api, &text);
+        if new_text == text {
+            return Ok(false);
+        }
+        page.edit_text(api, new_text, summary)
+            .map_err(CategoryError::RequestError)?;
+        Ok(true)
+    }
+
+    /// Returns the full title of this category, for use in an API request
+    fn full_title(&self, api: &Api) -> Result<String, CategoryError> {
+        self.title
+            .full_pretty(api)
+            .ok_or_else(|| CategoryError::BadTitle(self.title.clone()))
+    }
+
+    /// Returns the byte ranges of the links to this category in a wikitext (not links like
+    /// `[[:Category:Foo]]`)
+    fn links_in(&self, api: &Api, text: &str) -> Vec<Range<usize>> {
+        let mut wanted = self.title.clone();
+        wanted.normalize(api);
+        category_links(api, text)
+            .into_iter()
+            .filter(|(_, title)| title.pretty() == wanted.pretty())
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    /// Inserts a category link into a wikitext: after the last category link, or else at the end
+    fn with_link(&self, api: &Api, text: &str, link: &str) -> String {
+        match category_links(api, text).last() {
+            Some((range, _)) => format!("{}\n{}{}", &text[..range.end], link, &text[range.end..]),
+            None => format!("{}\n\n{}\n", text.trim_end(), link),
+        }
+    }
+
+    /// Removes all links to this category from a wikitext, including the line break after a link on
+    /// its own line
+    fn without_links(&self, api: &Api, text: &str) -> String {
+        let mut ret = text.to_string();
+        for range in self.links_in(api, text).into_iter().rev() {
+            let line_start = ret[..range.start].ends_with('\n') || range.start == 0;
+            let end = match ret[range.end..].starts_with('\n') && line_start {
+                true => range.end + 1,
+                false => range.end,
+            };
+            ret.replace_range(range.start..end, "");
+        }
+        ret
+    }
+}
+
+/// Returns the category links in a wikitext, with their byte ranges and normalized category titles
+fn category_links(api: &Api, text: &str) -> Vec<(Range<usize>, Title)> {
+    let mut ret = vec![];
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find("[[").map(|i| pos + i) {
+        let end = match text[start..].find("]]") {
+            Some(i) => start + i + 2,
+            None => break,
+        };
+        let inner = &text[start + 2..end - 2];
+        // A nested link, e.g. in an image caption: continue inside of it
+        if inner.contains("[[") {
+            pos = start + 2;
+            continue;
+        }
+        pos = end;
+        let target = inner.split('|').next().unwrap_or_default().trim();
+        if target.starts_with(':') {
+            continue;
+        }
+        if let Ok(title) = Title::parse(api, target) {
+            if title.namespace() == Namespace::CATEGORY && title.interwiki().is_none() {
+                ret.push((start..end, title));
+            }
+        }
+    }
+    ret
+}
+
+/// Errors that can go wrong while performing operations on a `Category`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CategoryError {
+    /// The title (provided) is not in the category namespace.
+    NotACategory(Title),
+
+    /// Couldn't obtain the title for this category for use in an API request.
+    BadTitle(Title),
+
+    /// Couldn't understand the API response (provided).
+    BadResponse(Value),
+
+    /// Error while performing the request.
+    RequestError(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for CategoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CategoryError::NotACategory(title) => write!(f, "not a category: {:?}", title),
+            CategoryError::BadTitle(title) => {
+                write!(f, "invalid title for this Category: {:?}", title)
+            }
+            CategoryError::BadResponse(response) => {
+                write!(
+                    f,
+                    "bad API response while fetching category info: {:?}",
+                    response
+                )
+            }
+            CategoryError::RequestError(error) => write!(f, "request error: {}", error),
+        }
+    }
+}
+
+impl Error for CategoryError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    #[test]
+    fn new_requires_category_namespace() {
+        assert!(Category::new(Title::new("Foo", Namespace::CATEGORY)).is_ok());
+        assert!(matches!(
+            Category::new(Title::new("Foo", 0)),
+            Err(CategoryError::NotACategory(_))
+        ));
+    }
+
+    #[test]
+    fn links() {
+        let api = test_api();
+        let category = Category::new(Title::new("Mann", Namespace::CATEGORY)).unwrap();
+        let text =
+            "Text [[:Kategorie:Mann]].\n[[Datei:X.jpg|mini|[[Foo]]]]\n[[Kategorie:Deutscher]]\n\
+            [[category:mann|Sortkey]]\n[[Kategorie:Mann]]";
+        assert_eq!(category.links_in(&api, text).len(), 2);
+        assert_eq!(
+            category.without_links(&api, text),
+            "Text [[:Kategorie:Mann]].\n[[Datei:X.jpg|mini|[[Foo]]]]\n[[Kategorie:Deutscher]]\n"
+        );
+
+        let other = Category::new(Title::new("Frau", Namespace::CATEGORY)).unwrap();
+        assert_eq!(
+            other.with_link(
+                &api,
+                "Text\n[[Kategorie:A]]\n[[Kategorie:B]]\n{{Navi}}",
+                "[[Kategorie:Frau]]"
+            ),
+            "Text\n[[Kategorie:A]]\n[[Kategorie:B]]\n[[Kategorie:Frau]]\n{{Navi}}"
+        );
+        assert_eq!(
+            other.with_link(&api, "Text\n", "[[Kategorie:Frau]]"),
+            "Text\n\n[[Kategorie:Frau]]\n"
+        );
+    }
+
+    #[test]
+    fn category_info() {
+        let info: CategoryInfo = serde_json::from_value(
+            json!({"size": 5, "pages": 3, "files": 1, "subcats": 1, "hidden": true}),
+        )
+        .unwrap();
+        assert_eq!((info.size, info.subcats, info.hidden), (5, 1, true));
+    }
+}
@@ -0,0 +1,147 @@
+/*!
+Change tag definitions, from `list=tags`: their names, display names, sources and hit counts. Only
+tags that are defined manually and active can be applied to edits and other changes (see
+`ChangeTag::is_applicable`).
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use serde_json::Value;
+use std::error::Error;
+
+/// A change tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeTag {
+    /// Name of the tag, as used in API requests, e.g. "mobile edit"
+    pub name: String,
+    /// Display name, as HTML; `None` if the tag is hidden from users
+    pub display_name: Option<String>,
+    /// Description, as HTML
+    pub description: Option<String>,
+    /// Number of changes with this tag
+    pub hit_count: u64,
+    /// Whether the tag is defined, i.e. not only present on old changes
+    pub defined: bool,
+    /// Whether the tag is still applied to new changes
+    pub active: bool,
+    /// Sources of the tag: "extension" (applied by software) and/or "manual" (applied by users)
+    pub source: Vec<String>,
+}
+
+impl ChangeTag {
+    /// Parses an entry of a `list=tags` result (`formatversion=2`)
+    pub fn new_from_api_result(tag: &Value) -> Option<ChangeTag> {
+        Some(ChangeTag {
+            name: tag["name"].as_str()?.to_string(),
+            display_name: tag["displayname"].as_str().map(|s| s.to_string()),
+            description: tag["description"].as_str().map(|s| s.to_string()),
+            hit_count: tag["hitcount"].as_u64().unwrap_or_default(),
+            defined: tag["defined"].as_bool().unwrap_or(false),
+            active: tag["active"].as_bool().unwrap_or(false),
+            source: tag["source"]
+                .as_array()
+                .map(|sources| {
+                    sources
+                        .iter()
+                        .filter_map(|s| s.as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Checks if users can apply this tag, e.g. with the `tags` parameter of an edit
+    pub fn is_applicable(&self) -> bool {
+        self.defined && self.active && self.source.iter().any(|source| source == "manual")
+    }
+}
+
+impl Api {
+    /// Loads all change tags of the wiki, including undefined tags that are only present on old
+    /// changes
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn change_tags(&self) -> Result<Vec<ChangeTag>, Box<dyn Error + Send + Sync>> {
+        let params = self.params_into(&[
+            ("action", "query"),
+            ("list", "tags"),
+            (
+                "tgprop",
+                "displayname|description|hitcount|defined|source|active",
+            ),
+            ("tglimit", "max"),
+            ("formatversion", "2"),
+        ]);
+        let result = self.get_query_api_json_all(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("list=tags failed: {}", error)));
+        }
+        Ok(change_tags_from_api_result(&result))
+    }
+
+    /// Returns those of `tags` that can't be applied to changes, e.g. before a batch of edits with
+    /// these tags; empty if all are applicable
+    ///
+    /// # Errors
+    /// Returns any error from `change_tags`.
+    pub fn unapplicable_change_tags(
+        &self,
+        tags: &[&str],
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let defined = self.change_tags()?;
+        Ok(tags
+            .iter()
+            .filter(|&&tag| !defined.iter().any(|t| t.name == tag && t.is_applicable()))
+            .map(|tag| tag.to_string())
+            .collect())
+    }
+}
+
+/// Parses the tags of a `list=tags` result
+fn change_tags_from_api_result(result: &Value) -> Vec<ChangeTag> {
+    result["query"]["tags"]
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(ChangeTag::new_from_api_result)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_from_api_result() {
+        let result = json!({"query": {"tags": [
+            {"name": "mobile edit", "displayname": "Mobile edit",
+            "description": "Edit made from mobile",
+             "hitcount": 1000, "defined": true, "source": ["extension"], "active": true},
+            {"name": "AWB", "displayname": "<a href=\"/wiki/AWB\">AWB</a>", "description": "",
+             "hitcount": 50, "defined": true, "source": ["manual"], "active": true},
+            {"name": "old-tag", "displayname": false, "description": false, "hitcount": 3,
+             "defined": false, "source": [], "active": false}
+        ]}});
+        let tags = change_tags_from_api_result(&result);
+        assert_eq!(tags.len(), 3);
+        assert!(!tags[0].is_applicable());
+        assert!(tags[1].is_applicable());
+        assert_eq!(tags[1].hit_count, 50);
+        assert_eq!(tags[2].display_name, None);
+        assert!(!tags[2].defined);
+    }
+}
@@ -0,0 +1,340 @@
+/*!
+Checks with the [CheckUser](https://www.mediawiki.org/wiki/Extension:CheckUser) extension, for
+authorized anti-abuse tools: the IP addresses of a user, the edits of a user or IP address, and the
+users of an IP address (`list=checkuser`), and the log of checks (`list=checkuserlog`).
+
+Checks need the `checkuser` right, and the log needs the `checkuser-log` right. Every check is
+logged with its reason, so a reason is required. Access errors are reported as
+`CheckUserError::PermissionDenied`.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::params_map;
+use crate::timestamp::Timestamp;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// An IP address used by a user, from a `userips` check
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CheckUserIp {
+    /// The IP address
+    pub address: String,
+    /// Time of the first action from the address
+    pub start: Option<Timestamp>,
+    /// Time of the last action from the address
+    pub end: Option<Timestamp>,
+    /// Number of actions from the address
+    #[serde(default)]
+    pub editcount: u64,
+}
+
+/// An edit of a user or IP address, from an `edits` check
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CheckUserEdit {
+    /// Namespace of the page
+    #[serde(default)]
+    pub ns: NamespaceID,
+    /// Full title of the page
+    pub title: String,
+    /// User who made the edit
+    pub user: String,
+    /// Time of the edit
+    pub timestamp: Option<Timestamp>,
+    /// Edit summary
+    #[serde(default)]
+    pub summary: String,
+    /// User agent of the edit
+    pub agent: Option<String>,
+    /// Whether the edit is minor
+    #[serde(default)]
+    pub minor: bool,
+}
+
+/// A user of an IP address (or range), from an `ipusers` check
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CheckUserIpUser {
+    /// Name of the user
+    pub name: String,
+    /// Time of the first action of the user from the address
+    pub start: Option<Timestamp>,
+    /// Time of the last action of the user from the address
+    pub end: Option<Timestamp>,
+    /// Number of actions of the user from the address
+    #[serde(default)]
+    pub editcount: u64,
+    /// IP addresses of the user within the range
+    #[serde(default)]
+    pub ips: Vec<String>,
+    /// User agents of the user
+    #[serde(default)]
+    pub agents: Vec<String>,
+}
+
+/// An entry of the CheckUser log
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CheckUserLogEntry {
+    /// Time of the check
+    pub timestamp: Option<Timestamp>,
+    /// User who performed the check
+    pub checkuser: String,
+    /// Kind of check, e.g. "userips" or "ipedits"
+    #[serde(rename = "type")]
+    pub check_type: String,
+    /// Reason given for the check
+    #[serde(default)]
+    pub reason: String,
+    /// User or IP address that was checked
+    pub target: String,
+}
+
+impl Api {
+    /// Loads the IP addresses that `user` used within `period` (e.g. "-2 weeks"; the wiki's default
+    /// if `None`)
+    ///
+    /// # Errors
+    /// Returns `CheckUserError::MissingReason` if `reason` is empty,
+    /// `CheckUserError::PermissionDenied` without the `checkuser` right, `CheckUserError::ApiError`
+    /// for other API errors, or any error from the request.
+    pub fn check_user_ips(
+        &self,
+        user: &str,
+        reason: &str,
+        period: Option<&str>,
+    ) -> Result<Vec<CheckUserIp>, CheckUserError> {
+        self.check_user("userips", user, reason, period)
+    }
+
+    /// Loads the edits of `target` (a user, IP address or range) within `period` (e.g. "-2 weeks")
+    ///
+    /// # Errors
+    /// Returns `CheckUserError::MissingReason` if `reason` is empty,
+    /// `CheckUserError::PermissionDenied` without the `checkuser` right, `CheckUserError::ApiError`
+    /// for other API errors, or any error from the request.
+    pub fn check_user_edits(
+        &self,
+        target: &str,
+        reason: &str,
+        period: Option<&str>,
+    ) -> Result<Vec<CheckUserEdit>, CheckUserError> {
+        self.check_user("edits", target, reason, period)
+    }
+
+    /// Loads the users of the IP address or range `ip` within `period` (e.g. "-2 weeks")
+    ///
+    /// # Errors
+    /// Returns `CheckUserError::MissingReason` if `reason` is empty,
+    /// `CheckUserError::PermissionDenied` without the `checkuser` right, `CheckUserError::ApiError`
+    /// for other API errors, or any error from the request.
+    pub fn check_ip_users(
+        &self,
+        ip: &str,
+        reason: &str,
+        period: Option<&str>,
+    ) -> Result<Vec<CheckUserIpUser>, CheckUserError> {
+        self.check_user("ipusers", ip, reason, period)
+    }
+
+    /// Loads the CheckUser log, newest first: the checks performed by `checkuser` and/or of
+    /// `target`, if given
+    ///
+    /// # Errors
+    /// Returns `CheckUserError::PermissionDenied` without the `checkuser-log` right,
+    /// `CheckUserError::ApiError` for other API errors, or any error from the requests.
+    pub fn check_user_log(
+        &self,
+        checkuser: Option<&str>,
+        target: Option<&str>,
+    ) -> Result<Vec<CheckUserLogEntry>, CheckUserError> {
+        let params = params_map![
+            "action" => "query",
+            "list" => "checkuserlog",
+            "culuser" => checkuser,
+            "cultarget" => target,
+            "cullimit" => "max",
+            "formatversion" => "2",
+        ];
+        let mut ret = vec![];
+        for result in self.get_query_api_json_limit_iter(&params, None) {
+            let result = result.map_err(CheckUserError::RequestError)?;
+            ret.extend(entries_from_api_result::<CheckUserLogEntry>(
+                result,
+                "checkuserlog",
+                "entries",
+            )?);
+        }
+        Ok(ret)
+    }
+
+    /// Runs a `list=checkuser` check of the kind `request`
+    fn check_user<T: DeserializeOwned>(
+        &self,
+        request: &str,
+        target: &str,
+        reason: &str,
+        period: Option<&str>,
+    ) -> Result<Vec<T>, CheckUserError> {
+        if reason.trim().is_empty() {
+            return Err(CheckUserError::MissingReason);
+        }
+        let token = self
+            .get_edit_token()
+            .map_err(CheckUserError::RequestError)?;
+        let params: HashMap<String, String> = params_map![
+            "action" => "query",
+            "list" => "checkuser",
+            "curequest" => request,
+            "cutarget" => target,
+            "cureason" => reason,
+            "cutimecond" => period,
+            "culimit" => "max",
+            "token" => token,
+            "formatversion" => "2",
+        ];
+        let result = self
+            .post_query_api_json(&params)
+            .map_err(CheckUserError::RequestError)?;
+        entries_from_api_result(result, "checkuser", request)
+    }
+}
+
+/// Deserializes the entries `key` of the result of the query module `module`, or returns the API
+/// error
+fn entries_from_api_result<T: DeserializeOwned>(
+    result: Value,
+    module: &str,
+    key: &str,
+) -> Result<Vec<T>, CheckUserError> {
+    if let Some(code) = result["error"]["code"].as_str() {
+        let info = result["error"]["info"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        return Err(match code {
+            "permissiondenied" | "readapidenied" | "notloggedin" | "badtoken" => {
+                CheckUserError::PermissionDenied(info)
+            }
+            "missingparam" if info.contains("cureason") => CheckUserError::MissingReason,
+            _ => CheckUserError::ApiError(code.to_string(), info),
+        });
+    }
+    match result["query"][module].get(key) {
+        Some(entries) => {
+            Vec::<T>::deserialize(entries).map_err(|_| CheckUserError::BadResponse(result.clone()))
+        }
+        None if result["query"].get(module).is_some() => Ok(vec![]),
+        None => Err(CheckUserError::BadResponse(result)),
+    }
+}
+
+/// Errors that can go wrong while performing checks or reading the CheckUser log.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CheckUserError {
+    /// No reason was given for a check.
+    MissingReason,
+
+    /// The user may not perform checks or read the log, or is not logged in (API error info
+    /// provided).
+    PermissionDenied(String),
+
+    /// Any other API error (code and info).
+    ApiError(String, String),
+
+    /// Couldn't understand the API response (provided).
+    BadResponse(Value),
+
+    /// Error while performing the request.
+    RequestError(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for CheckUserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckUserError::MissingReason => write!(f, "a reason is required for checks"),
+            CheckUserError::PermissionDenied(info) => write!(f, "permission denied: {}", info),
+            CheckUserError::ApiError(code, info) => write!(f, "API error {}: {}", code, info),
+            CheckUserError::BadResponse(response) => {
+                write!(f, "bad API response from CheckUser: {:?}", response)
+            }
+            CheckUserError::RequestError(error) => write!(f, "request error: {}", error),
+        }
+    }
+}
+
+impl Error for CheckUserError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    #[test]
+    fn check_results() {
+        let result = json!({"query": {"checkuser": {"userips": [
+            {"address": "192.0.2.1", "end": "2020-04-14T10:22:31Z", "start": "2020-04-01T08:00:00Z",
+            "editcount": 12}
+        ]}}});
+        let ips: Vec<CheckUserIp> =
+            entries_from_api_result(result, "checkuser", "userips").unwrap();
+        assert_eq!(
+            (ips[0].address.as_str(), ips[0].editcount),
+            ("192.0.2.1", 12)
+        );
+        assert!(ips[0].start.is_some());
+
+        let result = json!({"query": {"checkuser": {"ipusers": [
+            {"name": "Example", "end": "2020-04-14T10:22:31Z", "start": "2020-04-01T08:00:00Z",
+            "editcount": 3,
+             "ips": ["192.0.2.1", "192.0.2.7"], "agents": ["Mozilla/5.0"]}
+        ]}}});
+        let users: Vec<CheckUserIpUser> =
+            entries_from_api_result(result, "checkuser", "ipusers").unwrap();
+        assert_eq!(users[0].ips.len(), 2);
+
+        let result = json!({"query": {"checkuserlog": {"entries": [
+            {"timestamp": "2020-04-14T10:22:31Z", "checkuser": "Admin", "type": "userips",
+            "reason": "Sockpuppetry",
+             "target": "Example"}
+        ]}}});
+        let log: Vec<CheckUserLogEntry> =
+            entries_from_api_result(result, "checkuserlog", "entries").unwrap();
+        assert_eq!(log[0].check_type, "userips");
+    }
+
+    #[test]
+    fn access_errors() {
+        let result = json!({"error": {"code": "permissiondenied",
+            "info": "You don't have permission to check users."}});
+        let error = entries_from_api_result::<CheckUserIp>(result, "checkuser", "userips");
+        assert!(matches!(error, Err(CheckUserError::PermissionDenied(_))));
+        let result = json!({"error": {"code": "missingparam",
+            "info": "The \"cureason\" parameter must be set."}});
+        let error = entries_from_api_result::<CheckUserIp>(result, "checkuser", "userips");
+        assert!(matches!(error, Err(CheckUserError::MissingReason)));
+        let result = json!({"error": {"code": "invalidip", "info": "Invalid IP"}});
+        let error = entries_from_api_result::<CheckUserIp>(result, "checkuser", "userips");
+        assert!(matches!(error, Err(CheckUserError::ApiError(..))));
+
+        let api = test_api();
+        assert!(matches!(
+            api.check_user_ips("Example", " ", None),
+            Err(CheckUserError::MissingReason)
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+/*!
+The `ConnectionSettings` class holds HTTP settings for the client of an `Api`: extra headers that
+are sent with every request (e.g. `X-Client-Id` for API gateways), timeouts, TCP keep-alive and
+connection pool sizes. The client is shared by requests to the action API, SPARQL and REST
+endpoints, so the settings apply to all of them.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use reqwest::blocking::ClientBuilder;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::error::Error;
+use std::time::Duration;
+
+/// HTTP settings for the client of an `Api`; unset values keep the defaults of `reqwest`
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionSettings {
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+}
+
+impl ConnectionSettings {
+    /// Returns new settings, with the defaults of `reqwest`
+    pub fn new() -> ConnectionSettings {
+        ConnectionSettings::default()
+    }
+
+    /// Adds a header that is sent with every request
+    ///
+    /// # Errors
+    /// Returns an error if the name or value is not a valid HTTP header.
+    pub fn with_header(
+        mut self,
+        name: &str,
+        value: &str,
+    ) -> Result<ConnectionSettings, Box<dyn Error + Send + Sync>> {
+        self.headers.insert(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+        Ok(self)
+    }
+
+    /// Sets the timeout for whole requests, from connecting until the response body is read
+    pub fn with_timeout(mut self, timeout: Duration) -> ConnectionSettings {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for connecting to the server
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> ConnectionSettings {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the interval of TCP keep-alive probes
+    pub fn with_tcp_keepalive(mut self, interval: Duration) -> ConnectionSettings {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Sets how long idle connections are kept open for reuse
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> ConnectionSettings {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of idle connections that are kept open per host
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> ConnectionSettings {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Returns the extra headers
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Applies the settings to a client builder
+    pub fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if !self.headers.is_empty() {
+            builder = builder.default_headers(self.headers.clone());
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings() {
+        let settings = ConnectionSettings::new()
+            .with_header("X-Client-Id", "my-tool")
+            .unwrap()
+            .with_timeout(Duration::from_secs(30))
+            .with_pool_max_idle_per_host(4);
+        assert_eq!(settings.headers()["x-client-id"], "my-tool");
+        assert!(settings.apply(ClientBuilder::new()).build().is_ok());
+        assert!(ConnectionSettings::new().with_header("X Bad", "x").is_err());
+        assert!(ConnectionSettings::new()
+            .with_header("X-Ok", "bad\n")
+            .is_err());
+    }
+}
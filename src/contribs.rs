@@ -0,0 +1,303 @@
+/*!
+Typed user contributions from `list=usercontribs`, and global contributions of a user across the
+wikis of a wiki farm (like the GUC tool): the wikis the user edited are found with
+`meta=globaluserinfo`, their contributions are loaded concurrently, and merged newest first. Wikis
+that cannot be queried are reported alongside the contributions.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::params_map;
+use crate::sitematrix::Site;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::thread;
+
+/// An edit of a user
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Contribution {
+    /// Global site ID of the wiki, e.g. "enwiki"; empty if unknown
+    #[serde(default)]
+    pub wiki: String,
+    /// Revision ID
+    pub revid: u64,
+    /// ID of the previous revision; 0 for page creations
+    #[serde(default)]
+    pub parentid: u64,
+    /// Page ID
+    pub pageid: u64,
+    /// Namespace of the page
+    pub ns: NamespaceID,
+    /// Full title of the page
+    pub title: String,
+    /// Time of the edit
+    pub timestamp: Timestamp,
+    /// Edit summary; `None` if hidden
+    pub comment: Option<String>,
+    /// Size of the page after the edit, in bytes
+    #[serde(default)]
+    pub size: u64,
+    /// Change of the page size, in bytes
+    #[serde(default)]
+    pub sizediff: i64,
+    /// Whether the edit was marked as minor
+    #[serde(default)]
+    pub minor: bool,
+    /// Whether the edit created the page
+    #[serde(default)]
+    pub new: bool,
+    /// Whether the edit is the latest revision of the page
+    #[serde(default)]
+    pub top: bool,
+}
+
+impl Api {
+    /// Loads up to `max` contributions of a user (or IP address) on this wiki, newest first
+    pub fn user_contribs(
+        &self,
+        user: &str,
+        max: Option<usize>,
+    ) -> Result<Vec<Contribution>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "list" => "usercontribs",
+            "ucuser" => user,
+            "ucprop" => "ids|title|timestamp|comment|size|sizediff|flags",
+            "uclimit" => "max",
+            "formatversion" => "2",
+        ];
+        let wiki = self
+            .get_site_info()
+            .general
+            .wikiid
+            .clone()
+            .unwrap_or_default();
+        self.get_query_api_json_items::<Contribution>(&params, "query.usercontribs[]", max)
+            .map(|contribution| {
+                Ok(Contribution {
+                    wiki: wiki.clone(),
+                    ..contribution?
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the global site IDs of the wikis where a global account has edits, with
+    /// `meta=globaluserinfo` (on wikis with the CentralAuth extension)
+    pub fn global_user_wikis(
+        &self,
+        user: &str,
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "meta" => "globaluserinfo",
+            "guiuser" => user,
+            "guiprop" => "merged",
+            "formatversion" => "2",
+        ];
+        let result = self.get_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("meta=globaluserinfo failed: {}", error)));
+        }
+        Ok(wikis_from_globaluserinfo(&result))
+    }
+
+    /// Loads the contributions of a user on all wikis of the wiki farm where the user has edits
+    /// (see `global_user_wikis`), up to `max_per_wiki` on each wiki, with up to `concurrency`
+    /// requests at a time. The contributions are sorted newest first; wikis that could not be
+    /// queried are listed as failures.
+    pub fn global_contribs(
+        &self,
+        user: &str,
+        max_per_wiki: Option<usize>,
+        concurrency: usize,
+    ) -> Result<GlobalContribs, Box<dyn Error + Send + Sync>> {
+        let matrix = self.sitematrix()?;
+        let sites: Vec<Site> = self
+            .global_user_wikis(user)?
+            .iter()
+            .filter_map(|wiki| matrix.site(wiki).cloned())
+            .collect();
+        Ok(self.contribs_on_sites(&sites, user, max_per_wiki, concurrency))
+    }
+
+    /// Loads the contributions of a user on the given wikis, like `global_contribs`. Requests to
+    /// the other wikis use the settings of this `Api` (e.g. its user agent), but no login. A
+    /// failing wiki does not stop the others; its error is collected in `GlobalContribs::failures`.
+    pub fn contribs_on_sites(
+        &self,
+        sites: &[Site],
+        user: &str,
+        max_per_wiki: Option<usize>,
+        concurrency: usize,
+    ) -> GlobalContribs {
+        let mut ret = GlobalContribs::default();
+        for batch in sites.chunks(concurrency.max(1)) {
+            let results: Vec<Result<Vec<Contribution>, Box<dyn Error + Send + Sync>>> =
+                thread::scope(|scope| {
+                    let handles: Vec<_> = batch
+                        .iter()
+                        .map(|site| {
+                            scope.spawn(move || {
+                                let api = self.for_other_wiki(&site.api_url());
+                                let contribs = api.user_contribs(user, max_per_wiki)?;
+                                Ok(contribs
+                                    .into_iter()
+                                    .map(|c| Contribution {
+                                        wiki: site.dbname.clone(),
+                                        ..c
+                                    })
+                                    .collect())
+                            })
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|handle| {
+                            handle
+                                .join()
+                                .unwrap_or_else(|_| Err(From::from("thread panicked")))
+                        })
+                        .collect()
+                });
+            for (site, result) in batch.iter().zip(results) {
+                ret.add_site_result(&site.dbname, result);
+            }
+        }
+        sort_contribs(&mut ret.contributions);
+        ret
+    }
+}
+
+/// A wiki whose contributions could not be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteFailure {
+    /// Global site ID of the wiki, e.g. "enwiki"
+    pub wiki: String,
+    /// The error message
+    pub message: String,
+}
+
+/// The contributions of a user on several wikis
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GlobalContribs {
+    /// Contributions on all wikis that could be queried, newest first
+    pub contributions: Vec<Contribution>,
+    /// Wikis that could not be queried
+    pub failures: Vec<SiteFailure>,
+}
+
+impl GlobalContribs {
+    /// Adds the contributions of a wiki, or its error
+    fn add_site_result(
+        &mut self,
+        wiki: &str,
+        result: Result<Vec<Contribution>, Box<dyn Error + Send + Sync>>,
+    ) {
+        match result {
+            Ok(contribs) => self.contributions.extend(contribs),
+            Err(e) => self.failures.push(SiteFailure {
+                wiki: wiki.to_string(),
+                message: e.to_string(),
+            }),
+        }
+    }
+}
+
+/// Returns the wikis with edits from a `meta=globaluserinfo` result
+fn wikis_from_globaluserinfo(result: &Value) -> Vec<String> {
+    result["query"]["globaluserinfo"]["merged"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|account| account["editcount"].as_u64().unwrap_or_default() > 0)
+        .filter_map(|account| account["wiki"].as_str().map(|s| s.to_string()))
+        .collect()
+}
+
+/// Sorts contributions newest first, and by wiki for edits at the same time
+fn sort_contribs(contribs: &mut [Contribution]) {
+    contribs.sort_by(|a, b| {
+        b.timestamp
+            .cmp(&a.timestamp)
+            .then_with(|| a.wiki.cmp(&b.wiki))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contributions() {
+        let contribs: Vec<Contribution> = serde_json::from_value(json!([
+            {"userid": 1, "user": "Foo", "pageid": 5, "revid": 20, "parentid": 19, "ns": 0,
+            "title": "A",
+             "timestamp": "2020-01-01T00:00:00Z", "new": false, "minor": true, "top": true,
+             "comment": "x", "size": 100, "sizediff": -5},
+            {"userid": 1, "user": "Foo", "pageid": 6, "revid": 30, "parentid": 0, "ns": 1,
+            "title": "Talk:B",
+             "timestamp": "2021-01-01T00:00:00Z", "new": true, "minor": false, "top": false,
+             "commenthidden": true, "size": 10, "sizediff": 10}
+        ]))
+        .unwrap();
+        let mut contribs: Vec<Contribution> = contribs
+            .into_iter()
+            .zip(["enwiki", "dewiki"])
+            .map(|(c, wiki)| Contribution {
+                wiki: wiki.to_string(),
+                ..c
+            })
+            .collect();
+        sort_contribs(&mut contribs);
+        assert_eq!(contribs[0].wiki, "dewiki");
+        assert_eq!(
+            Some(&contribs[0].timestamp),
+            crate::timestamp::parse_timestamp("2021-01-01T00:00:00Z").as_ref()
+        );
+        assert_eq!(contribs[0].comment, None);
+        assert_eq!(contribs[1].sizediff, -5);
+        assert!(contribs[1].minor);
+    }
+
+    #[test]
+    fn site_failures() {
+        let contrib: Contribution =
+            serde_json::from_value(json!({"pageid": 5, "revid": 20, "ns": 0, "title": "A",
+            "timestamp": "2020-01-01T00:00:00Z", "comment": "x"}))
+            .unwrap();
+        let mut global = GlobalContribs::default();
+        global.add_site_result("enwiki", Ok(vec![contrib]));
+        global.add_site_result("dewiki", Err(From::from("HTTP 503")));
+        assert_eq!(global.contributions.len(), 1);
+        assert_eq!(
+            global.failures,
+            vec![SiteFailure {
+                wiki: "dewiki".to_string(),
+                message: "HTTP 503".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn global_wikis() {
+        let result = json!({"query": {"globaluserinfo": {"home": "enwiki", "merged": [
+            {"wiki": "enwiki", "url": "https://en.wikipedia.org", "editcount": 10},
+            {"wiki": "dewiki", "url": "https://de.wikipedia.org", "editcount": 0}
+        ]}}});
+        assert_eq!(wikis_from_globaluserinfo(&result), vec!["enwiki"]);
+    }
+}
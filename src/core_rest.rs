@@ -0,0 +1,467 @@
+/*!
+Typed access to the [MediaWiki REST API](https://www.mediawiki.org/wiki/API:REST_API) of MediaWiki
+core (`rest.php/v1/`): pages and their history, page search, and files. This is a simpler
+alternative to the action API for reading and editing single pages. Pages carry their ETag, so they
+can be reloaded only if they changed.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::audit::WriteRecord;
+use crate::rest::rest_title;
+use crate::timestamp::Timestamp;
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::{HeaderMap, ETAG, IF_NONE_MATCH};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// The latest revision of a `RestPage`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[allow(missing_copy_implementations)]
+pub struct RestLatest {
+    /// Revision ID
+    pub id: u64,
+    /// Time of the revision; `None` for simulated edits
+    pub timestamp: Option<Timestamp>,
+}
+
+/// The license of a `RestPage`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RestLicense {
+    /// URL of the license
+    pub url: String,
+    /// Name of the license
+    pub title: String,
+}
+
+/// A page, from `/page/{title}`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RestPage {
+    /// Page ID
+    pub id: u64,
+    /// Title in URL form, with underscores
+    pub key: String,
+    /// Title
+    pub title: String,
+    /// The latest revision
+    pub latest: RestLatest,
+    /// Content model, e.g. "wikitext"
+    pub content_model: String,
+    /// License of the content
+    pub license: Option<RestLicense>,
+    /// Content of the latest revision, unless loaded without source
+    pub source: Option<String>,
+    /// ETag of the response, for conditional requests
+    #[serde(skip)]
+    pub etag: Option<String>,
+}
+
+/// A user in REST API results
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RestUser {
+    /// User ID; `None` for anonymous users
+    pub id: Option<u64>,
+    /// User name, or IP address; `None` if hidden
+    pub name: Option<String>,
+}
+
+/// A revision in the history of a page
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RestRevision {
+    /// Revision ID
+    pub id: u64,
+    /// Time of the revision
+    pub timestamp: Timestamp,
+    /// Whether the edit was marked as minor
+    #[serde(default)]
+    pub minor: bool,
+    /// Size of the revision, in bytes
+    pub size: u64,
+    /// Edit summary; `None` if hidden
+    pub comment: Option<String>,
+    /// Editor
+    pub user: RestUser,
+    /// Change in size, in bytes; `None` if unknown
+    pub delta: Option<i64>,
+}
+
+/// A segment of page history, from `/page/{title}/history`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RestHistory {
+    /// Revisions, newest first (at most 20)
+    pub revisions: Vec<RestRevision>,
+    /// URL of the newest segment
+    pub latest: String,
+    /// URL of the next older segment, if any
+    pub older: Option<String>,
+    /// URL of the next newer segment, if any
+    pub newer: Option<String>,
+}
+
+impl RestHistory {
+    /// Returns the ID of the oldest revision in this segment, to load the next older segment
+    pub fn oldest_id(&self) -> Option<u64> {
+        self.older.as_ref().and(self.revisions.last().map(|r| r.id))
+    }
+}
+
+/// A search result, from `/search/page`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RestSearchResult {
+    /// Page ID
+    pub id: u64,
+    /// Title in URL form, with underscores
+    pub key: String,
+    /// Title
+    pub title: String,
+    /// Excerpt with the matches highlighted, as HTML
+    pub excerpt: Option<String>,
+    /// Title of the redirect that matched, if any
+    pub matched_title: Option<String>,
+    /// Short description
+    pub description: Option<String>,
+    /// Thumbnail, if any
+    pub thumbnail: Option<Value>,
+}
+
+/// A version of a file, from `/file/{title}`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RestFileVariant {
+    /// Media type, e.g. "BITMAP"
+    pub mediatype: String,
+    /// Size in bytes
+    pub size: Option<u64>,
+    /// Width in pixels
+    pub width: Option<u64>,
+    /// Height in pixels
+    pub height: Option<u64>,
+    /// Length in seconds, for audio and video
+    pub duration: Option<f64>,
+    /// URL of the file
+    pub url: String,
+}
+
+/// The latest upload of a file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RestFileUpload {
+    /// Time of the upload
+    pub timestamp: Timestamp,
+    /// Uploader
+    pub user: RestUser,
+}
+
+/// A file, from `/file/{title}`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RestFile {
+    /// File title, without namespace prefix
+    pub title: String,
+    /// URL of the file description page
+    pub file_description_url: String,
+    /// The latest upload
+    pub latest: Option<RestFileUpload>,
+    /// The version of the file suited for display
+    pub preferred: RestFileVariant,
+    /// The original file
+    pub original: RestFileVariant,
+    /// A thumbnail, if any
+    pub thumbnail: Option<RestFileVariant>,
+}
+
+impl Api {
+    /// Returns the base URL of the MediaWiki REST API of the wiki, e.g.
+    /// "https://en.wikipedia.org/w/rest.php/v1/"
+    pub fn core_rest_url(&self) -> String {
+        let general = &self.get_site_info().general;
+        format!(
+            "{}{}/rest.php/v1/",
+            general.server.to_absolute(),
+            general.scriptpath
+        )
+    }
+
+    /// Loads a page, with or without its content
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page does not exist, or any error from the request.
+    pub fn rest_page(
+        &self,
+        title: &str,
+        with_source: bool,
+    ) -> Result<RestPage, Box<dyn Error + Send + Sync>> {
+        let url = self.rest_page_url(title, with_source);
+        let response = self.rest_get(&url, "application/json")?;
+        page_from_response(response)
+    }
+
+    /// Loads a page again, unless it is unchanged since it was loaded with the ETag `etag`.
+    /// Returns `None` if the page is unchanged.
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page does not exist, or any error from the request.
+    pub fn rest_page_if_changed(
+        &self,
+        title: &str,
+        with_source: bool,
+        etag: &str,
+    ) -> Result<Option<RestPage>, Box<dyn Error + Send + Sync>> {
+        let url = self.rest_page_url(title, with_source);
+        let response = self.rest_send(|| {
+            Ok(self
+                .request_builder(&url, &self.no_params(), "GET")?
+                .header(IF_NONE_MATCH, etag))
+        })?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        Ok(Some(page_from_response(response)?))
+    }
+
+    /// Loads up to 20 revisions of a page, newest first; `older_than` is a revision ID, e.g.
+    /// `RestHistory::oldest_id`
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page does not exist, or any error from the request.
+    pub fn rest_page_history(
+        &self,
+        title: &str,
+        older_than: Option<u64>,
+    ) -> Result<RestHistory, Box<dyn Error + Send + Sync>> {
+        let mut url = format!("{}page/{}/history", self.core_rest_url(), rest_title(title));
+        if let Some(older_than) = older_than {
+            url += &format!("?older_than={}", older_than);
+        }
+        Ok(self.rest_get(&url, "application/json")?.json()?)
+    }
+
+    /// Searches page titles and contents
+    ///
+    /// # Errors
+    /// Returns any error from the request.
+    pub fn rest_search_pages(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<RestSearchResult>, Box<dyn Error + Send + Sync>> {
+        let url = format!(
+            "{}search/page?q={}&limit={}",
+            self.core_rest_url(),
+            urlencoding::encode(query),
+            limit
+        );
+        let result: Value = self.rest_get(&url, "application/json")?.json()?;
+        Ok(Vec::<RestSearchResult>::deserialize(&result["pages"])?)
+    }
+
+    /// Loads information about a file, given its title with or without namespace prefix
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the file does not exist, or any error from the request.
+    pub fn rest_file(&self, title: &str) -> Result<RestFile, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}file/{}", self.core_rest_url(), rest_title(title));
+        Ok(self.rest_get(&url, "application/json")?.json()?)
+    }
+
+    /// Creates a page
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page exists, or any error from the request.
+    pub fn rest_create_page(
+        &self,
+        title: &str,
+        source: &str,
+        comment: &str,
+    ) -> Result<RestPage, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}page", self.core_rest_url());
+        let body = json!({"title": title, "source": source, "comment": comment});
+        self.rest_write(Method::POST, &url, body)
+    }
+
+    /// Replaces the content of a page. `latest` is the revision ID that the edit is based on,
+    /// to detect edit conflicts; if `None`, the page is created if needed.
+    ///
+    /// # Errors
+    /// Returns a `RestError` on edit conflicts (HTTP 409), or any error from the request.
+    pub fn rest_update_page(
+        &self,
+        title: &str,
+        source: &str,
+        comment: &str,
+        latest: Option<u64>,
+    ) -> Result<RestPage, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}page/{}", self.core_rest_url(), rest_title(title));
+        let mut body = json!({"source": source, "comment": comment});
+        if let Some(latest) = latest {
+            body["latest"] = json!({ "id": latest });
+        }
+        self.rest_write(Method::PUT, &url, body)
+    }
+
+    /// Returns the URL of a page, with or without its content
+    fn rest_page_url(&self, title: &str, with_source: bool) -> String {
+        let bare = if with_source { "" } else { "/bare" };
+        format!("{}page/{}{}", self.core_rest_url(), rest_title(title), bare)
+    }
+
+    /// Sends a JSON body to a REST endpoint that edits a page, and returns the page.
+    /// Without OAuth, an edit token is added to the body.
+    fn rest_write(
+        &self,
+        method: Method,
+        url: &str,
+        mut body: Value,
+    ) -> Result<RestPage, Box<dyn Error + Send + Sync>> {
+        if self.simulate() {
+            let params = self.params_into(&[
+                ("method", method.as_str()),
+                ("url", url),
+                ("body", &body.to_string()),
+            ]);
+            self.simulate_write(&params);
+            return Ok(RestPage {
+                id: 0,
+                key: String::new(),
+                title: body["title"].as_str().unwrap_or_default().to_string(),
+                latest: RestLatest {
+                    id: 0,
+                    timestamp: None,
+                },
+                content_model: "wikitext".to_string(),
+                license: None,
+                source: body["source"].as_str().map(|s| s.to_string()),
+                etag: None,
+            });
+        }
+        if self.oauth().is_none() {
+            body["token"] = json!(self.get_edit_token()?);
+        }
+        let response = self.rest_send(|| self.rest_json_request(method.clone(), url, &body))?;
+        let page = page_from_response(response)?;
+        self.audit(WriteRecord {
+            action: "edit".to_string(),
+            title: Some(page.title.clone()),
+            entity: None,
+            summary: body["comment"]
+                .as_str()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+            old_revid: body["latest"]["id"].as_u64(),
+            new_revid: Some(page.latest.id),
+            bytes_changed: None,
+        });
+        Ok(page)
+    }
+
+    /// Returns a `RequestBuilder` for a request with a JSON body, signed if OAuth is used
+    fn rest_json_request(
+        &self,
+        method: Method,
+        url: &str,
+        body: &Value,
+    ) -> Result<RequestBuilder, Box<dyn Error + Send + Sync>> {
+        let headers = match self.oauth() {
+            // A JSON body is not part of the OAuth signature
+            Some(_) => self.oauth_headers(method.as_str(), url, &HashMap::new())?,
+            None => {
+                let mut headers = HeaderMap::new();
+                headers.insert(reqwest::header::COOKIE, self.cookies_to_string().parse()?);
+                headers.insert(reqwest::header::USER_AGENT, self.user_agent_full().parse()?);
+                headers
+            }
+        };
+        Ok(self
+            .client()
+            .request(method, url)
+            .headers(headers)
+            .json(body))
+    }
+}
+
+/// Parses a page from a response, with its ETag
+fn page_from_response(response: Response) -> Result<RestPage, Box<dyn Error + Send + Sync>> {
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(|etag| etag.to_string());
+    let mut page: RestPage = response.json()?;
+    page.etag = etag;
+    Ok(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    #[test]
+    fn urls() {
+        let api = test_api();
+        assert_eq!(
+            api.core_rest_url(),
+            "https://de.wikipedia.org/w/rest.php/v1/"
+        );
+        assert_eq!(
+            api.rest_page_url("AC/DC", false),
+            "https://de.wikipedia.org/w/rest.php/v1/page/AC%2FDC/bare"
+        );
+    }
+
+    #[test]
+    fn page() {
+        let page: RestPage = serde_json::from_value(json!({
+            "id": 9228,
+            "key": "Earth",
+            "title": "Earth",
+            "latest": {"id": 963613515, "timestamp": "2020-06-20T20:05:55Z"},
+            "content_model": "wikitext",
+            "license": {"url": "//creativecommons.org/licenses/by-sa/3.0/",
+            "title": "CC BY-SA 3.0"},
+            "html_url": "https://en.wikipedia.org/w/rest.php/v1/page/Earth/html"
+        }))
+        .unwrap();
+        assert_eq!(page.latest.id, 963613515);
+        assert_eq!(
+            page.latest.timestamp,
+            crate::timestamp::parse_timestamp("2020-06-20T20:05:55Z")
+        );
+        assert_eq!(page.source, None);
+        assert_eq!(page.etag, None);
+    }
+
+    #[test]
+    fn history() {
+        let history: RestHistory = serde_json::from_value(json!({
+            "revisions": [
+                {"id": 20, "timestamp": "2020-06-20T20:05:55Z", "minor": false, "size": 100,
+                 "comment": "fix", "user": {"id": 5, "name": "Example"}, "delta": -3},
+                {"id": 10, "timestamp": "2020-06-19T20:05:55Z", "minor": true, "size": 103,
+                 "comment": null, "user": {"id": null, "name": "127.0.0.1"}, "delta": null}
+            ],
+            "latest": "https://en.wikipedia.org/w/rest.php/v1/page/Foo/history",
+            "older": "https://en.wikipedia.org/w/rest.php/v1/page/Foo/history?older_than=10"
+        }))
+        .unwrap();
+        assert_eq!(history.revisions[1].user.id, None);
+        assert!(history.revisions[0].timestamp > history.revisions[1].timestamp);
+        assert_eq!(history.oldest_id(), Some(10));
+        let history = RestHistory {
+            older: None,
+            ..history
+        };
+        assert_eq!(history.oldest_id(), None);
+    }
+}
@@ -0,0 +1,523 @@
+/*!
+Typed Wikibase data values, the values of snaks: entity IDs, strings, monolingual texts, quantities,
+times, and globe coordinates.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use serde::de::{Deserializer, Error as _};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The calendar model URI of the proleptic Gregorian calendar
+pub const GREGORIAN_CALENDAR: &str = "http://www.wikidata.org/entity/Q1985727";
+
+/// The calendar model URI of the proleptic Julian calendar
+pub const JULIAN_CALENDAR: &str = "http://www.wikidata.org/entity/Q1985786";
+
+/// The globe URI of Earth
+pub const EARTH: &str = "http://www.wikidata.org/entity/Q2";
+
+/// A reference to an entity
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EntityIdValue {
+    /// Entity type, e.g. "item"
+    #[serde(rename = "entity-type", skip_serializing_if = "Option::is_none")]
+    pub entity_type: Option<String>,
+    /// Numeric part of the ID, for items and properties
+    #[serde(rename = "numeric-id", skip_serializing_if = "Option::is_none")]
+    pub numeric_id: Option<u64>,
+    /// The entity ID, e.g. "Q42"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+impl EntityIdValue {
+    /// Creates a reference to an entity by ID, e.g. "Q42" or "L7-F1"
+    pub fn new(id: &str) -> EntityIdValue {
+        let entity_type = match id.chars().next() {
+            Some('Q') if !id.contains('-') => Some("item"),
+            Some('P') => Some("property"),
+            Some('L') => Some(match id.split('-').nth(1).and_then(|s| s.chars().next()) {
+                Some('F') => "form",
+                Some('S') => "sense",
+                _ => "lexeme",
+            }),
+            Some('M') => Some("mediainfo"),
+            _ => None,
+        };
+        let numeric_id = match entity_type {
+            Some("item") | Some("property") => id.get(1..).and_then(|n| n.parse().ok()),
+            _ => None,
+        };
+        EntityIdValue {
+            entity_type: entity_type.map(|s| s.to_string()),
+            numeric_id,
+            id: Some(id.to_string()),
+        }
+    }
+
+    /// Returns the entity ID; older data only has the type and the numeric ID
+    pub fn id(&self) -> Option<String> {
+        if let Some(id) = &self.id {
+            return Some(id.to_string());
+        }
+        let prefix = match self.entity_type.as_deref()? {
+            "item" => "Q",
+            "property" => "P",
+            _ => return None,
+        };
+        Some(format!("{}{}", prefix, self.numeric_id?))
+    }
+}
+
+/// A text in a language
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MonolingualText {
+    /// The text
+    pub text: String,
+    /// Language code
+    pub language: String,
+}
+
+/// A quantity, with optional bounds and unit
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Quantity {
+    /// The amount as a decimal string with sign, e.g. "+1.5"
+    pub amount: String,
+    /// The unit, as an entity URI, or "1" if there is no unit
+    pub unit: String,
+    /// Upper bound, as a decimal string
+    #[serde(rename = "upperBound", skip_serializing_if = "Option::is_none")]
+    pub upper_bound: Option<String>,
+    /// Lower bound, as a decimal string
+    #[serde(rename = "lowerBound", skip_serializing_if = "Option::is_none")]
+    pub lower_bound: Option<String>,
+}
+
+impl Quantity {
+    /// Creates a quantity without bounds and unit
+    pub fn new(amount: f64) -> Quantity {
+        Quantity {
+            amount: format!("{:+}", amount),
+            unit: "1".to_string(),
+            upper_bound: None,
+            lower_bound: None,
+        }
+    }
+
+    /// Creates a quantity without bounds; `unit` is an item ID, like "Q11573" (metre), and
+    /// `concept_base_uri` the `wikibase-conceptbaseuri` of the site info, e.g.
+    /// "http://www.wikidata.org/entity/"
+    pub fn new_with_unit(amount: f64, unit: &str, concept_base_uri: &str) -> Quantity {
+        Quantity {
+            unit: format!("{}{}", concept_base_uri, unit),
+            ..Quantity::new(amount)
+        }
+    }
+
+    /// Returns the amount as a number
+    pub fn amount(&self) -> Option<f64> {
+        self.amount.parse().ok()
+    }
+
+    /// Returns the upper bound as a number
+    pub fn upper_bound(&self) -> Option<f64> {
+        self.upper_bound.as_ref()?.parse().ok()
+    }
+
+    /// Returns the lower bound as a number
+    pub fn lower_bound(&self) -> Option<f64> {
+        self.lower_bound.as_ref()?.parse().ok()
+    }
+
+    /// Returns the entity ID of the unit, e.g. "Q11573", or `None` if there is no unit
+    pub fn unit_id(&self) -> Option<&str> {
+        if self.unit == "1" {
+            return None;
+        }
+        self.unit.rsplit('/').next()
+    }
+}
+
+/// A point in time, with a precision
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Time {
+    /// The time in ISO 8601 format with sign, e.g. "+2001-12-31T00:00:00Z"; parts below the
+    /// precision are zero
+    pub time: String,
+    /// Time zone offset, in minutes
+    #[serde(default)]
+    pub timezone: i64,
+    /// Uncertainty before the time, in units of the precision
+    #[serde(default)]
+    pub before: u64,
+    /// Uncertainty after the time, in units of the precision
+    #[serde(default)]
+    pub after: u64,
+    /// Precision: 9 is a year, 10 a month, 11 a day; 0 (a billion years) to 14 (a second)
+    pub precision: u8,
+    /// Calendar model URI, e.g. `GREGORIAN_CALENDAR`
+    pub calendarmodel: String,
+}
+
+impl Time {
+    /// Year precision
+    pub const PRECISION_YEAR: u8 = 9;
+    /// Month precision
+    pub const PRECISION_MONTH: u8 = 10;
+    /// Day precision
+    pub const PRECISION_DAY: u8 = 11;
+
+    /// Creates a Gregorian date with day, month, or year precision (if `day`, or `month` and `day`,
+    /// are `None`)
+    pub fn new_date(year: i64, month: Option<u8>, day: Option<u8>) -> Time {
+        let precision = match (month, day) {
+            (Some(_), Some(_)) => Time::PRECISION_DAY,
+            (Some(_), None) => Time::PRECISION_MONTH,
+            _ => Time::PRECISION_YEAR,
+        };
+        Time {
+            time: format!(
+                "{}{:04}-{:02}-{:02}T00:00:00Z",
+                if year < 0 { '-' } else { '+' },
+                year.abs(),
+                month.unwrap_or(0),
+                day.filter(|_| month.is_some()).unwrap_or(0)
+            ),
+            timezone: 0,
+            before: 0,
+            after: 0,
+            precision,
+            calendarmodel: GREGORIAN_CALENDAR.to_string(),
+        }
+    }
+
+    /// Returns the year; negative for years BCE
+    pub fn year(&self) -> Option<i64> {
+        let (sign, rest) = self.time.split_at_checked(1)?;
+        let year: i64 = rest.split('-').next()?.parse().ok()?;
+        match sign {
+            "-" => Some(-year),
+            "+" => Some(year),
+            _ => None,
+        }
+    }
+
+    /// Returns the month, if the precision is at least a month
+    pub fn month(&self) -> Option<u8> {
+        self.date_part(1, Time::PRECISION_MONTH)
+    }
+
+    /// Returns the day, if the precision is at least a day
+    pub fn day(&self) -> Option<u8> {
+        self.date_part(2, Time::PRECISION_DAY)
+    }
+
+    /// Checks if the calendar model is the Gregorian calendar
+    pub fn is_gregorian(&self) -> bool {
+        self.calendarmodel == GREGORIAN_CALENDAR
+    }
+
+    /// Returns a part of the date after the year, if the precision is high enough
+    fn date_part(&self, index: usize, precision: u8) -> Option<u8> {
+        if self.precision < precision {
+            return None;
+        }
+        let date = self.time.get(1..)?.split('T').next()?;
+        date.split('-').nth(index)?.parse().ok().filter(|&n| n > 0)
+    }
+}
+
+/// A position on a globe
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct GlobeCoordinate {
+    /// Latitude, in degrees
+    pub latitude: f64,
+    /// Longitude, in degrees
+    pub longitude: f64,
+    /// Altitude (unused)
+    #[serde(default)]
+    pub altitude: Option<f64>,
+    /// Precision, in degrees
+    pub precision: Option<f64>,
+    /// Globe URI, e.g. `EARTH`
+    pub globe: String,
+}
+
+impl GlobeCoordinate {
+    /// Creates a position on Earth
+    pub fn new(latitude: f64, longitude: f64, precision: f64) -> GlobeCoordinate {
+        GlobeCoordinate {
+            latitude,
+            longitude,
+            altitude: None,
+            precision: Some(precision),
+            globe: EARTH.to_string(),
+        }
+    }
+}
+
+/// The value of a snak. Values of unknown types are kept as `Other`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum DataValue {
+    /// An entity ID ("wikibase-entityid"), for items, properties, lexemes, forms, and senses
+    EntityId(EntityIdValue),
+    /// A string ("string"), for strings, external IDs, URLs, Commons media, etc.
+    String(String),
+    /// A text in a language ("monolingualtext")
+    MonolingualText(MonolingualText),
+    /// A quantity ("quantity")
+    Quantity(Quantity),
+    /// A point in time ("time")
+    Time(Time),
+    /// A position on a globe ("globecoordinate")
+    GlobeCoordinate(GlobeCoordinate),
+    /// Any other, or malformed, value
+    Other {
+        /// The type of the value
+        value_type: String,
+        /// The value
+        value: Value,
+    },
+}
+
+impl DataValue {
+    /// Creates a value referring to an entity, e.g. "Q42"
+    pub fn entity(id: &str) -> DataValue {
+        DataValue::EntityId(EntityIdValue::new(id))
+    }
+
+    /// Returns the type of the value, e.g. "wikibase-entityid"
+    pub fn value_type(&self) -> &str {
+        match self {
+            DataValue::EntityId(_) => "wikibase-entityid",
+            DataValue::String(_) => "string",
+            DataValue::MonolingualText(_) => "monolingualtext",
+            DataValue::Quantity(_) => "quantity",
+            DataValue::Time(_) => "time",
+            DataValue::GlobeCoordinate(_) => "globecoordinate",
+            DataValue::Other { value_type, .. } => value_type,
+        }
+    }
+
+    /// Returns the `value` part of the JSON of the value, e.g. for the `value` parameter of
+    /// `wbcreateclaim`
+    pub fn value_json(&self) -> Value {
+        match self {
+            DataValue::EntityId(v) => json!(v),
+            DataValue::String(v) => json!(v),
+            DataValue::MonolingualText(v) => json!(v),
+            DataValue::Quantity(v) => json!(v),
+            DataValue::Time(v) => json!(v),
+            DataValue::GlobeCoordinate(v) => json!(v),
+            DataValue::Other { value, .. } => value.clone(),
+        }
+    }
+
+    /// Returns the entity ID, for entity ID values
+    pub fn as_entity_id(&self) -> Option<String> {
+        match self {
+            DataValue::EntityId(v) => v.id(),
+            _ => None,
+        }
+    }
+
+    /// Returns the string, for string values
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            DataValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the text, for monolingual text values
+    pub fn as_monolingual_text(&self) -> Option<&MonolingualText> {
+        match self {
+            DataValue::MonolingualText(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the quantity, for quantity values
+    pub fn as_quantity(&self) -> Option<&Quantity> {
+        match self {
+            DataValue::Quantity(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the time, for time values
+    pub fn as_time(&self) -> Option<&Time> {
+        match self {
+            DataValue::Time(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the coordinate, for globe coordinate values
+    pub fn as_globe_coordinate(&self) -> Option<&GlobeCoordinate> {
+        match self {
+            DataValue::GlobeCoordinate(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Parses the `value` part of a data value of type `value_type`
+    fn from_parts(value_type: String, value: Value) -> DataValue {
+        let parsed = match value_type.as_str() {
+            "wikibase-entityid" => EntityIdValue::deserialize(&value).map(DataValue::EntityId),
+            "string" => String::deserialize(&value).map(DataValue::String),
+            "monolingualtext" => {
+                MonolingualText::deserialize(&value).map(DataValue::MonolingualText)
+            }
+            "quantity" => Quantity::deserialize(&value).map(DataValue::Quantity),
+            "time" => Time::deserialize(&value).map(DataValue::Time),
+            "globecoordinate" => {
+                GlobeCoordinate::deserialize(&value).map(DataValue::GlobeCoordinate)
+            }
+            _ => Err(serde_json::Error::custom("unknown type")),
+        };
+        parsed.unwrap_or(DataValue::Other { value_type, value })
+    }
+}
+
+/// The JSON form of a data value
+#[derive(Deserialize, Serialize)]
+struct RawDataValue {
+    value: Value,
+    #[serde(rename = "type")]
+    value_type: String,
+}
+
+impl<'de> Deserialize<'de> for DataValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<DataValue, D::Error> {
+        let raw = RawDataValue::deserialize(deserializer)?;
+        Ok(DataValue::from_parts(raw.value_type, raw.value))
+    }
+}
+
+impl Serialize for DataValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RawDataValue {
+            value: self.value_json(),
+            value_type: self.value_type().to_string(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: Value) -> DataValue {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn entity_ids() {
+        let value = parse(
+            json!({"value": {"entity-type": "item", "numeric-id": 5, "id": "Q5"},
+                "type": "wikibase-entityid"}),
+        );
+        assert_eq!(value.as_entity_id().as_deref(), Some("Q5"));
+        let value = parse(
+            json!({"value": {"entity-type": "property", "numeric-id": 31},
+                "type": "wikibase-entityid"}),
+        );
+        assert_eq!(value.as_entity_id().as_deref(), Some("P31"));
+        assert_eq!(
+            DataValue::entity("Q5").value_json(),
+            json!({"entity-type": "item", "numeric-id": 5, "id": "Q5"})
+        );
+        assert_eq!(
+            EntityIdValue::new("L7-F1").entity_type.as_deref(),
+            Some("form")
+        );
+        assert_eq!(EntityIdValue::new("L7").numeric_id, None);
+        assert_eq!(EntityIdValue::new("").entity_type, None);
+    }
+
+    #[test]
+    fn quantities() {
+        let value = parse(
+            json!({"value": {"amount": "+1.5", "unit": "http://www.wikidata.org/entity/Q11573",
+            "upperBound": "+1.6", "lowerBound": "+1.4"}, "type": "quantity"}),
+        );
+        let quantity = value.as_quantity().unwrap();
+        assert_eq!(quantity.amount(), Some(1.5));
+        assert_eq!(quantity.upper_bound(), Some(1.6));
+        assert_eq!(quantity.unit_id(), Some("Q11573"));
+        let quantity = Quantity::new(-3.0);
+        assert_eq!(quantity.amount, "-3");
+        assert_eq!(quantity.unit_id(), None);
+        let quantity = Quantity::new_with_unit(2.0, "Q11573", "https://wiki.example.org/entity/");
+        assert_eq!(quantity.amount, "+2");
+        assert_eq!(quantity.unit, "https://wiki.example.org/entity/Q11573");
+        assert_eq!(quantity.unit_id(), Some("Q11573"));
+    }
+
+    #[test]
+    fn times() {
+        let value = parse(
+            json!({"value": {"time": "+1952-03-11T00:00:00Z", "timezone": 0,
+            "before": 0, "after": 0, "precision": 11, "calendarmodel": GREGORIAN_CALENDAR},
+            "type": "time"}),
+        );
+        let time = value.as_time().unwrap();
+        assert_eq!(
+            (time.year(), time.month(), time.day()),
+            (Some(1952), Some(3), Some(11))
+        );
+        assert!(time.is_gregorian());
+        let time = Time::new_date(-44, Some(3), None);
+        assert_eq!(time.time, "-0044-03-00T00:00:00Z");
+        assert_eq!(time.precision, Time::PRECISION_MONTH);
+        assert_eq!(
+            (time.year(), time.month(), time.day()),
+            (Some(-44), Some(3), None)
+        );
+        for bad in ["", "é2001-01-01T00:00:00Z", "+"] {
+            let time = Time {
+                time: bad.to_string(),
+                ..Time::new_date(2001, Some(1), Some(1))
+            };
+            assert_eq!((time.year(), time.month(), time.day()), (None, None, None));
+        }
+    }
+
+    #[test]
+    fn other_values() {
+        let value = parse(
+            json!({"value": {"latitude": 52.5, "longitude": 13.4, "altitude": null,
+            "precision": 0.01, "globe": EARTH}, "type": "globecoordinate"}),
+        );
+        assert_eq!(value.as_globe_coordinate().unwrap().latitude, 52.5);
+        let value = parse(
+            json!({"value": {"text": "Berlin", "language": "de"}, "type": "monolingualtext"}),
+        );
+        assert_eq!(value.as_monolingual_text().unwrap().text, "Berlin");
+        let value = parse(json!({"value": "Example.jpg", "type": "string"}));
+        assert_eq!(value.as_str(), Some("Example.jpg"));
+        let raw = json!({"value": {"foo": 1}, "type": "musical-notation-2"});
+        let value = parse(raw.clone());
+        assert_eq!(value.value_type(), "musical-notation-2");
+        assert_eq!(json!(value), raw);
+        let malformed = json!({"value": {"amount": 5}, "type": "quantity"});
+        assert!(matches!(parse(malformed.clone()), DataValue::Other { .. }));
+        assert_eq!(json!(parse(malformed.clone())), malformed);
+    }
+}
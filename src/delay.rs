@@ -0,0 +1,87 @@
+/*!
+The `DelayPolicy` class sets how long to pause after a request, depending on what it does.
+Communities expect different rates for reads, edits, moves, deletions and uploads, so each kind of
+request can have its own delay, and single API actions (e.g. "wbsetclaim") can override it.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::action::Action;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Delays after requests, by kind of request and by API action
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DelayPolicy {
+    kinds: HashMap<Action, Duration>,
+    actions: HashMap<String, Duration>,
+}
+
+impl DelayPolicy {
+    /// Returns a new policy without any delays
+    pub fn new() -> DelayPolicy {
+        DelayPolicy::default()
+    }
+
+    /// Sets the delay after requests of a kind
+    pub fn with_delay(mut self, kind: Action, delay: Duration) -> DelayPolicy {
+        self.kinds.insert(kind, delay);
+        self
+    }
+
+    /// Sets the delay after requests with API action `action`, overriding the delay of its kind
+    pub fn with_action_delay(mut self, action: &str, delay: Duration) -> DelayPolicy {
+        self.actions.insert(action.to_string(), delay);
+        self
+    }
+
+    /// Returns the delay after a request with API action `action` of the kind `kind`, if any
+    pub fn delay(&self, action: &str, kind: Action) -> Option<Duration> {
+        self.actions
+            .get(action)
+            .or_else(|| self.kinds.get(&kind))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays() {
+        let policy = DelayPolicy::new()
+            .with_delay(Action::Edit, Duration::from_secs(5))
+            .with_delay(Action::WikibaseEdit, Duration::from_secs(2))
+            .with_delay(Action::Upload, Duration::from_secs(30))
+            .with_action_delay("wbsetclaim", Duration::from_secs(1));
+        assert_eq!(
+            policy.delay("edit", Action::Edit),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            policy.delay("wbeditentity", Action::WikibaseEdit),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            policy.delay("wbsetclaim", Action::WikibaseEdit),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            policy.delay("upload", Action::Upload),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(policy.delay("query", Action::Read), None);
+        assert_eq!(policy.delay("move", Action::Move), None);
+    }
+}
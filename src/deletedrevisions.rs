@@ -0,0 +1,177 @@
+/*!
+Deleted revisions of the whole wiki, from `list=alldeletedrevisions`, by user or namespace and time.
+Listing deleted revisions needs the `deletedhistory` right; their content, the `deletedtext` right.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::params_map;
+use crate::revision::Revision;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// A deleted page, or a page with deleted revisions, from a `formatversion=2`
+/// `list=alldeletedrevisions` result
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DeletedPage {
+    /// Page ID, if the page exists
+    #[serde(default)]
+    pub pageid: u64,
+    /// Namespace of the page
+    pub ns: NamespaceID,
+    /// Full title of the page
+    pub title: String,
+    /// The deleted revisions in this batch of results; a page can appear in several batches
+    #[serde(default)]
+    pub revisions: Vec<Revision>,
+}
+
+/// Which deleted revisions to list
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeletedRevisionsFilter {
+    user: Option<String>,
+    exclude_user: Option<String>,
+    namespaces: Vec<NamespaceID>,
+    from: Option<String>,
+    to: Option<String>,
+    content: bool,
+    max: Option<usize>,
+}
+
+impl DeletedRevisionsFilter {
+    /// Returns a filter that lists all deleted revisions
+    pub fn new() -> DeletedRevisionsFilter {
+        DeletedRevisionsFilter::default()
+    }
+
+    /// Only lists revisions by this user
+    pub fn with_user(mut self, user: &str) -> DeletedRevisionsFilter {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    /// Leaves out revisions by this user
+    pub fn without_user(mut self, user: &str) -> DeletedRevisionsFilter {
+        self.exclude_user = Some(user.to_string());
+        self
+    }
+
+    /// Only lists revisions of pages in these namespaces
+    pub fn with_namespaces(mut self, namespaces: &[NamespaceID]) -> DeletedRevisionsFilter {
+        self.namespaces = namespaces.to_vec();
+        self
+    }
+
+    /// Only lists revisions made between two times (API timestamps, e.g. "2020-04-14T10:22:31Z"),
+    /// oldest first; either bound may be left open
+    pub fn with_dates(mut self, from: Option<&str>, to: Option<&str>) -> DeletedRevisionsFilter {
+        self.from = from.map(|s| s.to_string());
+        self.to = to.map(|s| s.to_string());
+        self
+    }
+
+    /// Loads the content of the main slot, too
+    pub fn with_content(mut self) -> DeletedRevisionsFilter {
+        self.content = true;
+        self
+    }
+
+    /// Loads at most `max` revisions
+    pub fn with_max(mut self, max: usize) -> DeletedRevisionsFilter {
+        self.max = Some(max);
+        self
+    }
+}
+
+impl Api {
+    /// Lists deleted revisions, grouped by page. With a user, revisions are ordered by time;
+    /// without one, by page and then by time.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. permission denied), or any error from
+    /// the requests.
+    pub fn all_deleted_revisions(
+        &self,
+        filter: &DeletedRevisionsFilter,
+    ) -> Result<Vec<DeletedPage>, Box<dyn Error + Send + Sync>> {
+        let namespaces: Vec<String> = filter.namespaces.iter().map(|ns| ns.to_string()).collect();
+        let dated = filter.from.is_some() || filter.to.is_some();
+        let params = params_map![
+            "action" => "query",
+            "list" => "alldeletedrevisions",
+            "adrprop" => if filter.content {
+                "ids|flags|timestamp|user|userid|comment|size|sha1|tags|content"
+            } else {
+                "ids|flags|timestamp|user|userid|comment|size|sha1|tags"
+            },
+            "adrslots" => Some("main").filter(|_| filter.content),
+            "adruser" => filter.user.as_ref(),
+            "adrexcludeuser" => filter.exclude_user.as_ref(),
+            "adrnamespace" => Some(namespaces.join("|")).filter(|ns| !ns.is_empty()),
+            "adrdir" => Some("newer").filter(|_| dated),
+            "adrstart" => filter.from.as_ref(),
+            "adrend" => filter.to.as_ref(),
+            "adrlimit" => "max",
+            "formatversion" => "2",
+        ];
+        let mut ret = vec![];
+        let mut revisions = 0;
+        for result in self.get_query_api_json_limit_iter(&params, None) {
+            let result = result?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!(
+                    "list=alldeletedrevisions failed: {}",
+                    error
+                )));
+            }
+            for mut page in Vec::<DeletedPage>::deserialize(&result["query"]["alldeletedrevisions"])
+                .unwrap_or_default()
+            {
+                let remaining = filter.max.map_or(usize::MAX, |max| max - revisions);
+                page.revisions.truncate(remaining);
+                revisions += page.revisions.len();
+                ret.push(page);
+                if Some(revisions) == filter.max {
+                    return Ok(ret);
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleted_page() {
+        let pages: Vec<DeletedPage> = serde_json::from_value(json!([
+            {"ns": 0, "title": "Foo", "revisions": [
+                {"revid": 20, "parentid": 10, "user": "Example",
+                "timestamp": "2020-04-14T10:22:31Z",
+                 "comment": "spam", "size": 13, "minor": false, "tags": []},
+                {"revid": 10, "parentid": 0, "user": "Example", "timestamp": "2020-04-13T08:00:00Z",
+                "size": 5}
+            ]},
+            {"pageid": 7, "ns": 1, "title": "Talk:Bar", "revisions": [{"revid": 30,
+            "parentid": 25}]}
+        ]))
+        .unwrap();
+        assert_eq!(pages[0].pageid, 0);
+        assert_eq!(pages[0].revisions.len(), 2);
+        assert!(pages[0].revisions[1].is_page_creation());
+        assert_eq!(pages[1].ns, 1);
+    }
+}
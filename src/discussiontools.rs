@@ -0,0 +1,199 @@
+/*!
+Replies and new topics on talk pages with the
+[DiscussionTools](https://www.mediawiki.org/wiki/Extension:DiscussionTools) extension, via
+`action=discussiontoolsedit`. Comments are addressed by their IDs (e.g.
+"c-Example-20200414102200-Question"), which `Api::discussion_threads` lists; DiscussionTools adds
+the indentation and the signature, like the reply tool does.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::page::PageError;
+use crate::params_map;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A heading or a comment on a talk page, with its replies
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ThreadItem {
+    /// "heading" or "comment"
+    #[serde(rename = "type")]
+    pub item_type: String,
+    /// ID of the item, to reply to it
+    pub id: String,
+    /// Name of the item, which stays the same if the page is edited elsewhere
+    #[serde(default)]
+    pub name: String,
+    /// Level of a heading, e.g. 2 for `== Heading ==`
+    pub level: Option<u8>,
+    /// Author of a comment
+    pub author: Option<String>,
+    /// Time of the signature of a comment
+    pub timestamp: Option<Timestamp>,
+    /// HTML of the item
+    #[serde(default)]
+    pub html: String,
+    /// Replies to the item
+    #[serde(default)]
+    pub replies: Vec<ThreadItem>,
+}
+
+impl ThreadItem {
+    /// Checks if the item is a heading
+    pub fn is_heading(&self) -> bool {
+        self.item_type == "heading"
+    }
+
+    /// Returns all comments below this item, depth first
+    pub fn comments(&self) -> Vec<&ThreadItem> {
+        let mut ret = vec![];
+        let mut stack: Vec<&ThreadItem> = self.replies.iter().rev().collect();
+        while let Some(item) = stack.pop() {
+            if !item.is_heading() {
+                ret.push(item);
+            }
+            stack.extend(item.replies.iter().rev());
+        }
+        ret
+    }
+}
+
+impl Api {
+    /// Loads the threads of the talk page `page`: its headings, with the comments below them
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn discussion_threads(
+        &self,
+        page: &str,
+    ) -> Result<Vec<ThreadItem>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "discussiontoolspageinfo",
+            "page" => page,
+            "prop" => "threaditemshtml",
+            "formatversion" => "2",
+        ];
+        let result = self.get_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!(
+                "action=discussiontoolspageinfo failed: {}",
+                error
+            )));
+        }
+        Ok(Vec::<ThreadItem>::deserialize(
+            &result["discussiontoolspageinfo"]["threaditemshtml"],
+        )?)
+    }
+
+    /// Replies to the comment (or heading) with the ID `comment_id` on the talk page `page`, with
+    /// wikitext that is indented and signed by DiscussionTools. Returns the ID of the new revision.
+    ///
+    /// # Errors
+    /// Returns `PageError::EditError` if the edit failed (e.g. the comment does not exist anymore),
+    /// or any error from the request.
+    pub fn discussion_reply(
+        &self,
+        page: &str,
+        comment_id: &str,
+        wikitext: &str,
+        summary: Option<&str>,
+    ) -> Result<Option<u64>, Box<dyn Error + Send + Sync>> {
+        self.discussion_tools_edit(params_map![
+            "paction" => "addcomment",
+            "page" => page,
+            "commentid" => comment_id,
+            "wikitext" => wikitext,
+            "summary" => summary,
+        ])
+    }
+
+    /// Starts a new topic `title` on the talk page `page`, with wikitext that is signed by
+    /// DiscussionTools. Returns the ID of the new revision.
+    ///
+    /// # Errors
+    /// Returns `PageError::EditError` if the edit failed, or any error from the request.
+    pub fn discussion_new_topic(
+        &self,
+        page: &str,
+        title: &str,
+        wikitext: &str,
+        summary: Option<&str>,
+    ) -> Result<Option<u64>, Box<dyn Error + Send + Sync>> {
+        self.discussion_tools_edit(params_map![
+            "paction" => "addtopic",
+            "page" => page,
+            "sectiontitle" => title,
+            "wikitext" => wikitext,
+            "summary" => summary,
+        ])
+    }
+
+    /// Posts an `action=discussiontoolsedit` request with additional parameters, and returns the
+    /// new revision ID
+    fn discussion_tools_edit(
+        &self,
+        mut params: HashMap<String, String>,
+    ) -> Result<Option<u64>, Box<dyn Error + Send + Sync>> {
+        params.extend(params_map![
+            "action" => "discussiontoolsedit",
+            "autosubscribe" => "no",
+            "token" => self.get_edit_token()?,
+            "formatversion" => "2",
+        ]);
+        let result = self.post_query_api_json(&params)?;
+        match result["discussiontoolsedit"]["result"].as_str() {
+            Some("success") => Ok(result["discussiontoolsedit"]["newrevid"].as_u64()),
+            _ => Err(Box::new(PageError::EditError(result))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threads() {
+        let threads: Vec<ThreadItem> = serde_json::from_value(json!([{
+            "type": "heading", "level": 2, "id": "h-Question-20200414102200",
+            "name": "h-Example-20200414102200",
+            "html": "Question",
+            "replies": [{
+                "type": "comment", "id": "c-Example-20200414102200-Question",
+                "name": "c-Example-20200414102200",
+                "author": "Example", "timestamp": "2020-04-14T10:22:00Z", "html": "<p>How?</p>",
+                "replies": [{
+                    "type": "comment", "id": "c-Other-20200414110000-Example-20200414102200",
+                    "author": "Other", "timestamp": "2020-04-14T11:00:00Z", "html": "<p>So.</p>",
+                    "replies": []
+                }]
+            }, {
+                "type": "comment", "id": "c-Third-20200415000000-Question", "author": "Third",
+                "timestamp": "2020-04-15T00:00:00Z", "html": "<p>Also</p>"
+            }]
+        }]))
+        .unwrap();
+        assert!(threads[0].is_heading());
+        assert_eq!(threads[0].level, Some(2));
+        let authors: Vec<&str> = threads[0]
+            .comments()
+            .iter()
+            .filter_map(|c| c.author.as_deref())
+            .collect();
+        assert_eq!(authors, vec!["Example", "Other", "Third"]);
+        assert!(threads[0].replies[0].timestamp.is_some());
+    }
+}
@@ -0,0 +1,588 @@
+/*!
+The `DumpReader` class is a streaming parser for MediaWiki XML dumps (e.g. pages-articles,
+stub-meta-history) and for the output of `Special:Export` (see `Api::export_pages`). It yields pages
+as `PageData`, with their revisions as the same `Revision` type that the API returns. Compressed
+dumps can be opened with `open_dump`, if the `bzip2` or `gzip` feature is enabled.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::revision::{Revision, Slot};
+use crate::timestamp::parse_timestamp;
+use quick_xml::events::{BytesStart, Event};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor};
+use std::path::Path;
+
+/// The site information at the start of a dump
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpSiteInfo {
+    /// Name of the site, e.g. "Wikipedia"
+    pub sitename: String,
+    /// Database name, e.g. "enwiki"
+    pub dbname: String,
+    /// URL of the main page
+    pub base: String,
+    /// Namespace names, keyed by ID; the main namespace has an empty name
+    pub namespaces: HashMap<NamespaceID, String>,
+}
+
+/// A page in a dump, with its revisions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageData {
+    /// Full title, with namespace prefix
+    pub title: String,
+    /// Namespace ID
+    pub namespace: NamespaceID,
+    /// Page ID
+    pub id: u64,
+    /// Redirect target, if the page is a redirect
+    pub redirect: Option<String>,
+    /// Revisions, oldest first; only the latest one in "current" dumps
+    pub revisions: Vec<Revision>,
+}
+
+impl PageData {
+    /// Returns the latest revision in the dump
+    pub fn latest_revision(&self) -> Option<&Revision> {
+        self.revisions.last()
+    }
+}
+
+/// Errors from reading a dump
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DumpError {
+    /// The dump could not be read.
+    Io(io::Error),
+
+    /// The dump is not well-formed XML.
+    Xml(quick_xml::Error),
+
+    /// A value in the dump is invalid (the element name and the value are provided).
+    InvalidValue(String, String),
+
+    /// The dump is compressed in a format that is not supported, or whose feature is not enabled.
+    UnsupportedCompression(String),
+}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpError::Io(e) => write!(f, "Could not read dump: {}", e),
+            DumpError::Xml(e) => write!(f, "Invalid XML in dump: {}", e),
+            DumpError::InvalidValue(element, value) => {
+                write!(f, "Invalid <{}> in dump: {}", element, value)
+            }
+            DumpError::UnsupportedCompression(s) => {
+                write!(f, "Unsupported dump compression: {}", s)
+            }
+        }
+    }
+}
+
+impl Error for DumpError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DumpError::Io(e) => Some(e),
+            DumpError::Xml(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DumpError {
+    fn from(e: io::Error) -> Self {
+        DumpError::Io(e)
+    }
+}
+
+impl From<quick_xml::Error> for DumpError {
+    fn from(e: quick_xml::Error) -> Self {
+        DumpError::Xml(e)
+    }
+}
+
+impl From<quick_xml::events::attributes::AttrError> for DumpError {
+    fn from(e: quick_xml::events::attributes::AttrError) -> Self {
+        DumpError::Xml(e.into())
+    }
+}
+
+/// Reads pages from a dump, one at a time
+pub struct DumpReader<R: BufRead> {
+    reader: quick_xml::Reader<R>,
+    buffer: Vec<u8>,
+    site_info: Option<DumpSiteInfo>,
+    /// Names of the open elements
+    path: Vec<String>,
+    /// Text of the innermost element
+    text: String,
+    /// Namespace key of the open `<namespace>` element
+    namespace_key: Option<NamespaceID>,
+    page: Option<PageData>,
+    revision: Option<Revision>,
+    done: bool,
+}
+
+impl<R: BufRead> fmt::Debug for DumpReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DumpReader")
+            .field("site_info", &self.site_info)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl<R: BufRead> DumpReader<R> {
+    /// Creates a reader for an uncompressed dump
+    pub fn new(reader: R) -> DumpReader<R> {
+        DumpReader {
+            reader: quick_xml::Reader::from_reader(reader),
+            buffer: vec![],
+            site_info: None,
+            path: vec![],
+            text: String::new(),
+            namespace_key: None,
+            page: None,
+            revision: None,
+            done: false,
+        }
+    }
+
+    /// Returns the site information of the dump, once it has been read (i.e. after the first page)
+    pub fn site_info(&self) -> Option<&DumpSiteInfo> {
+        self.site_info.as_ref()
+    }
+
+    /// Reads the next page
+    fn read_page(&mut self) -> Result<Option<PageData>, DumpError> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event_into(&mut self.buffer)? {
+                Event::Start(start) => {
+                    let start = start.into_owned();
+                    self.start_element(&start)?;
+                }
+                Event::Empty(start) => {
+                    let start = start.into_owned();
+                    self.start_element(&start)?;
+                    if let Some(page) = self.end_element()? {
+                        return Ok(Some(page));
+                    }
+                }
+                Event::Text(text) => self.text += &text.unescape()?,
+                Event::CData(data) => self.text += &String::from_utf8_lossy(&data),
+                Event::End(_) => {
+                    if let Some(page) = self.end_element()? {
+                        return Ok(Some(page));
+                    }
+                }
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    /// Handles the start of an element, and its attributes
+    fn start_element(&mut self, start: &BytesStart) -> Result<(), DumpError> {
+        let name = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+        self.text.clear();
+        let mut attributes = HashMap::new();
+        for attribute in start.attributes() {
+            let attribute = attribute?;
+            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).to_string();
+            attributes.insert(key, attribute.unescape_value()?.to_string());
+        }
+        let deleted = attributes.contains_key("deleted");
+        match name.as_str() {
+            "siteinfo" => self.site_info = Some(DumpSiteInfo::default()),
+            "namespace" => {
+                self.namespace_key = attributes.get("key").and_then(|key| key.parse().ok())
+            }
+            "page" => self.page = Some(PageData::default()),
+            "redirect" => {
+                if let Some(page) = &mut self.page {
+                    page.redirect = attributes.get("title").cloned();
+                }
+            }
+            "revision" => self.revision = Some(new_revision()),
+            _ => {}
+        }
+        if let Some(revision) = &mut self.revision {
+            match name.as_str() {
+                "contributor" => revision.userhidden = deleted,
+                "comment" => revision.commenthidden = deleted,
+                "minor" => revision.minor = true,
+                "text" => {
+                    let slot = main_slot(revision);
+                    slot.texthidden = deleted;
+                    slot.size = attributes.get("bytes").and_then(|bytes| bytes.parse().ok());
+                    slot.sha1 = attributes.get("sha1").cloned();
+                }
+                _ => {}
+            }
+        }
+        self.path.push(name);
+        Ok(())
+    }
+
+    /// Handles the end of an element; returns the page at the end of a `<page>` element
+    fn end_element(&mut self) -> Result<Option<PageData>, DumpError> {
+        let name = match self.path.pop() {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let parent = self.path.last().map(|s| s.as_str()).unwrap_or_default();
+        let text = std::mem::take(&mut self.text);
+        match (parent, name.as_str()) {
+            ("siteinfo", _) | ("namespaces", _) => {
+                if let Some(site_info) = &mut self.site_info {
+                    match name.as_str() {
+                        "sitename" => site_info.sitename = text,
+                        "dbname" => site_info.dbname = text,
+                        "base" => site_info.base = text,
+                        "namespace" => {
+                            if let Some(key) = self.namespace_key.take() {
+                                site_info.namespaces.insert(key, text);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            ("page", _) => {
+                let revision = self.revision.take();
+                if let Some(page) = &mut self.page {
+                    match name.as_str() {
+                        "title" => page.title = text,
+                        "ns" => page.namespace = parse_number("ns", &text)?,
+                        "id" => page.id = parse_number("id", &text)?,
+                        "revision" => page.revisions.extend(revision),
+                        _ => {}
+                    }
+                }
+            }
+            ("revision", _) => {
+                if let Some(revision) = &mut self.revision {
+                    match name.as_str() {
+                        "id" => revision.revid = parse_number("id", &text)?,
+                        "parentid" => revision.parentid = Some(parse_number("parentid", &text)?),
+                        "timestamp" => revision.timestamp = parse_timestamp(&text),
+                        "comment" if !revision.commenthidden => revision.comment = Some(text),
+                        "sha1" if !text.is_empty() => revision.sha1 = Some(text),
+                        "model" => main_slot(revision).contentmodel = Some(text),
+                        "format" => main_slot(revision).contentformat = Some(text),
+                        "text" => {
+                            let slot = main_slot(revision);
+                            // Stub dumps have an empty <text> element without content
+                            if slot.size.unwrap_or_default() == 0 || !text.is_empty() {
+                                slot.content = Some(text);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            ("contributor", _) => {
+                if let Some(revision) = &mut self.revision {
+                    match name.as_str() {
+                        "username" | "ip" => revision.user = Some(text),
+                        "id" => revision.userid = Some(parse_number("id", &text)?),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        if name == "page" {
+            let mut page = self.page.take();
+            if let Some(page) = &mut page {
+                if let Some(size) = page
+                    .latest_revision()
+                    .and_then(|r| r.slots.get("main")?.size)
+                {
+                    if let Some(revision) = page.revisions.last_mut() {
+                        revision.size = Some(size);
+                    }
+                }
+            }
+            return Ok(page);
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> Iterator for DumpReader<R> {
+    type Item = Result<PageData, DumpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_page() {
+            Ok(Some(page)) => Some(Ok(page)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Opens a dump file; files ending in ".bz2" or ".gz" are decompressed, if the `bzip2` or `gzip`
+/// feature is enabled
+///
+/// # Errors
+/// Returns an error if the file can't be opened, or the compression is not supported.
+pub fn open_dump<P: AsRef<Path>>(path: P) -> Result<DumpReader<Box<dyn BufRead>>, DumpError> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let reader: Box<dyn BufRead> = match extension {
+        #[cfg(feature = "bzip2")]
+        "bz2" => Box::new(BufReader::new(bzip2::read::MultiBzDecoder::new(file))),
+        #[cfg(feature = "gzip")]
+        "gz" => Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(file))),
+        #[cfg(not(feature = "bzip2"))]
+        "bz2" => return Err(DumpError::UnsupportedCompression(extension.to_string())),
+        #[cfg(not(feature = "gzip"))]
+        "gz" => return Err(DumpError::UnsupportedCompression(extension.to_string())),
+        "7z" => return Err(DumpError::UnsupportedCompression(extension.to_string())),
+        _ => Box::new(BufReader::new(file)),
+    };
+    Ok(DumpReader::new(reader))
+}
+
+impl Api {
+    /// Exports pages as XML via `Special:Export`, with all revisions or only the latest one.
+    /// This is much faster than loading the revisions of many pages with the API.
+    ///
+    /// # Errors
+    /// Returns an error if the request failed.
+    pub fn export_pages(
+        &self,
+        titles: &[&str],
+        include_history: bool,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let (url, params) = self.export_request(titles, include_history);
+        let response = self
+            .query_raw_response(&url, &params, "POST")?
+            .error_for_status()?;
+        Ok(response.text()?)
+    }
+
+    /// Exports pages via `Special:Export`, and returns a reader for the exported pages
+    ///
+    /// # Errors
+    /// Returns an error if the request failed.
+    pub fn export_pages_reader(
+        &self,
+        titles: &[&str],
+        include_history: bool,
+    ) -> Result<DumpReader<Cursor<String>>, Box<dyn Error + Send + Sync>> {
+        let xml = self.export_pages(titles, include_history)?;
+        Ok(DumpReader::new(Cursor::new(xml)))
+    }
+
+    /// Returns the URL and parameters for `Special:Export`
+    fn export_request(
+        &self,
+        titles: &[&str],
+        include_history: bool,
+    ) -> (String, HashMap<String, String>) {
+        let general = &self.get_site_info().general;
+        let url = general.server.to_absolute() + &general.script;
+        let pages = titles.join("\n");
+        let mut params = self.params_into(&[
+            ("title", "Special:Export"),
+            ("action", "submit"),
+            ("pages", &pages),
+        ]);
+        if include_history {
+            params.insert("history".to_string(), "1".to_string());
+        } else {
+            params.insert("curonly".to_string(), "1".to_string());
+        }
+        (url, params)
+    }
+}
+
+/// Returns an empty revision
+fn new_revision() -> Revision {
+    Revision {
+        revid: 0,
+        parentid: None,
+        minor: false,
+        user: None,
+        userid: None,
+        timestamp: None,
+        size: None,
+        sha1: None,
+        comment: None,
+        tags: vec![],
+        slots: HashMap::new(),
+        userhidden: false,
+        commenthidden: false,
+    }
+}
+
+/// Returns the "main" slot of a revision, creating it if needed
+fn main_slot(revision: &mut Revision) -> &mut Slot {
+    revision
+        .slots
+        .entry("main".to_string())
+        .or_insert_with(|| Slot {
+            contentmodel: None,
+            contentformat: None,
+            content: None,
+            size: None,
+            sha1: None,
+            texthidden: false,
+        })
+}
+
+/// Parses the number in an element
+fn parse_number<T: std::str::FromStr>(element: &str, text: &str) -> Result<T, DumpError> {
+    text.trim()
+        .parse()
+        .map_err(|_| DumpError::InvalidValue(element.to_string(), text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+    use std::io::Cursor;
+
+    const DUMP: &str = r#"<mediawiki xmlns="http://www.mediawiki.org/xml/export-0.10/" version="0.10"
+  xml:lang="en">
+  <siteinfo>
+    <sitename>Wikipedia</sitename>
+    <dbname>enwiki</dbname>
+    <base>https://en.wikipedia.org/wiki/Main_Page</base>
+    <namespaces>
+      <namespace key="0" case="first-letter" />
+      <namespace key="1" case="first-letter">Talk</namespace>
+    </namespaces>
+  </siteinfo>
+  <page>
+    <title>Foo</title>
+    <ns>0</ns>
+    <id>12</id>
+    <revision>
+      <id>100</id>
+      <timestamp>2020-04-13T08:00:00Z</timestamp>
+      <contributor><ip>127.0.0.1</ip></contributor>
+      <comment>new</comment>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="5" xml:space="preserve">Hello</text>
+      <sha1>abc</sha1>
+    </revision>
+    <revision>
+      <id>101</id>
+      <parentid>100</parentid>
+      <timestamp>2020-04-14T10:22:31Z</timestamp>
+      <contributor deleted="deleted" />
+      <minor />
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="17" xml:space="preserve">Hello &amp; &lt;b&gt;bye</text>
+      <sha1 />
+    </revision>
+  </page>
+  <page>
+    <title>Talk:Bar</title>
+    <ns>1</ns>
+    <id>13</id>
+    <redirect title="Talk:Foo" />
+    <revision>
+      <id>102</id>
+      <timestamp>2020-04-14T11:00:00Z</timestamp>
+      <contributor><username>Example</username><id>5</id></contributor>
+      <text bytes="22" id="7" />
+    </revision>
+  </page>
+</mediawiki>"#;
+
+    #[test]
+    fn read_dump() {
+        let mut reader = DumpReader::new(Cursor::new(DUMP));
+        let foo = reader.next().unwrap().unwrap();
+        let site_info = reader.site_info().unwrap();
+        assert_eq!(site_info.dbname, "enwiki");
+        assert_eq!(site_info.namespaces[&0], "");
+        assert_eq!(site_info.namespaces[&1], "Talk");
+        assert_eq!((foo.title.as_str(), foo.namespace, foo.id), ("Foo", 0, 12));
+        assert_eq!(foo.revisions.len(), 2);
+        assert_eq!(foo.revisions[0].user.as_deref(), Some("127.0.0.1"));
+        assert_eq!(foo.revisions[0].main_content(), Some("Hello"));
+        assert_eq!(foo.revisions[0].sha1.as_deref(), Some("abc"));
+        let latest = foo.latest_revision().unwrap();
+        assert_eq!((latest.revid, latest.parentid), (101, Some(100)));
+        assert!(latest.minor && latest.userhidden);
+        assert_eq!(latest.user, None);
+        assert_eq!(latest.sha1, None);
+        assert_eq!(latest.size, Some(17));
+        assert_eq!(latest.main_content(), Some("Hello & <b>bye"));
+        assert_eq!(latest.timestamp, parse_timestamp("2020-04-14T10:22:31Z"));
+
+        let bar = reader.next().unwrap().unwrap();
+        assert_eq!(bar.redirect.as_deref(), Some("Talk:Foo"));
+        let revision = &bar.revisions[0];
+        assert_eq!(
+            (revision.user.as_deref(), revision.userid),
+            (Some("Example"), Some(5))
+        );
+        // Stub dumps have no content
+        assert_eq!(revision.main_content(), None);
+        assert_eq!(revision.size, Some(22));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn export_request() {
+        let api = test_api();
+        let (_, params) = api.export_request(&["Foo", "Talk:Bar"], false);
+        assert_eq!(params["title"], "Special:Export");
+        assert_eq!(params["pages"], "Foo\nTalk:Bar");
+        assert_eq!(params["curonly"], "1");
+        let (_, params) = api.export_request(&["Foo"], true);
+        assert_eq!(params["history"], "1");
+        assert!(!params.contains_key("curonly"));
+    }
+
+    #[test]
+    fn invalid_dump() {
+        let mut reader = DumpReader::new(Cursor::new(
+            "<mediawiki><page><ns>x</ns></page></mediawiki>",
+        ));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(DumpError::InvalidValue(_, _)))
+        ));
+        assert!(reader.next().is_none());
+    }
+}
@@ -0,0 +1,259 @@
+/*!
+Notifications of the [Echo](https://www.mediawiki.org/wiki/Extension:Echo) extension: listing the
+unread notifications of the logged-in user (`meta=notifications`), and marking them as read
+(`action=echomarkread`) or seen (`action=echomarkseen`), e.g. for bots that act on mentions and
+acknowledge what they handled.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use crate::timestamp::{parse_timestamp, Timestamp};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A notification
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// ID of the notification
+    pub id: u64,
+    /// Database name of the wiki of the notification (e.g. "enwiki")
+    pub wiki: String,
+    /// Type, e.g. "mention" or "edit-user-talk"
+    pub notification_type: String,
+    /// Category, e.g. "mention" or "edit-user-talk"
+    pub category: String,
+    /// Section: "alert" or "message"
+    pub section: String,
+    /// Time of the notification
+    pub timestamp: Option<Timestamp>,
+    /// Full title of the page the notification is about, if any
+    pub title: Option<String>,
+    /// The user who caused the notification, if any
+    pub agent: Option<String>,
+    /// Whether the notification has been read
+    pub read: bool,
+}
+
+impl Notification {
+    /// Parses an entry of the `list` of a `meta=notifications` result (`formatversion=2`)
+    pub fn new_from_api_result(notification: &Value) -> Option<Notification> {
+        let id = match &notification["id"] {
+            Value::String(id) => id.parse().ok()?,
+            id => id.as_u64()?,
+        };
+        Some(Notification {
+            id,
+            wiki: notification["wiki"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            notification_type: notification["type"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            category: notification["category"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            section: notification["section"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            timestamp: notification["timestamp"]["utciso8601"]
+                .as_str()
+                .and_then(parse_timestamp),
+            title: notification["title"]["full"]
+                .as_str()
+                .map(|s| s.to_string()),
+            agent: notification["agent"]["name"]
+                .as_str()
+                .map(|s| s.to_string()),
+            read: notification.get("read").is_some(),
+        })
+    }
+}
+
+impl Api {
+    /// Loads the unread notifications of the logged-in user, in `sections` ("alert", "message"; all
+    /// if empty), up to `max` notifications
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. when not logged in), or any error from
+    /// the requests.
+    pub fn unread_notifications(
+        &self,
+        sections: &[&str],
+        max: Option<usize>,
+    ) -> Result<Vec<Notification>, Box<dyn Error + Send + Sync>> {
+        let mut params = params_map![
+            "action" => "query",
+            "meta" => "notifications",
+            "notprop" => "list",
+            "notfilter" => "!read",
+            "notsections" => Some(sections.join("|")).filter(|s| !s.is_empty()),
+            "notformat" => "model",
+            "notlimit" => "max",
+            "formatversion" => "2",
+        ];
+        let mut ret = vec![];
+        loop {
+            let result = self.get_query_api_json(&params)?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!("meta=notifications failed: {}", error)));
+            }
+            let notifications = &result["query"]["notifications"];
+            ret.extend(
+                notifications["list"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Notification::new_from_api_result),
+            );
+            if let Some(max) = max {
+                if ret.len() >= max {
+                    ret.truncate(max);
+                    break;
+                }
+            }
+            // Echo continues within its own result, not with the usual `continue` object
+            match notifications["continue"].as_str() {
+                Some(cont) => {
+                    params.insert("notcontinue".to_string(), cont.to_string());
+                }
+                None => break,
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Marks notifications as read, e.g. a single one after handling it
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn mark_notifications_read(&self, ids: &[u64]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.echo_mark_read(params_map!["list" => join_ids(ids)])
+    }
+
+    /// Marks notifications as unread again
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn mark_notifications_unread(
+        &self,
+        ids: &[u64],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.echo_mark_read(params_map!["unreadlist" => join_ids(ids)])
+    }
+
+    /// Marks all notifications in `sections` ("alert", "message"; all if empty) as read
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn mark_all_notifications_read(
+        &self,
+        sections: &[&str],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.echo_mark_read(params_map![
+            "all" => true,
+            "sections" => Some(sections.join("|")).filter(|s| !s.is_empty()),
+        ])
+    }
+
+    /// Marks the notifications of type `notification_type` ("alert", "message" or "all") as seen,
+    /// which clears the badge counter without marking them as read
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn mark_notifications_seen(
+        &self,
+        notification_type: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "echomarkseen",
+            "type" => notification_type,
+            "token" => self.get_edit_token()?,
+            "formatversion" => "2",
+        ];
+        let result = self.post_query_api_json(&params)?;
+        echo_result(&result, "echomarkseen")
+    }
+
+    /// Posts an `action=echomarkread` request with additional parameters
+    fn echo_mark_read(
+        &self,
+        mut params: HashMap<String, String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        params.extend(params_map![
+            "action" => "echomarkread",
+            "token" => self.get_edit_token()?,
+            "formatversion" => "2",
+        ]);
+        let result = self.post_query_api_json(&params)?;
+        echo_result(&result, "echomarkread")
+    }
+}
+
+/// Returns notification IDs as an API parameter value
+fn join_ids(ids: &[u64]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Checks the result of an Echo action
+fn echo_result(result: &Value, action: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match result["query"][action]["result"].as_str() {
+        Some("success") => Ok(()),
+        _ => Err(From::from(format!(
+            "action={} failed: {:?}",
+            action, result
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notification_from_api_result() {
+        let notification = Notification::new_from_api_result(&json!({
+            "wiki": "enwiki", "id": "1234", "type": "mention", "category": "mention",
+            "section": "alert",
+            "timestamp": {"utciso8601": "2020-04-14T10:22:31Z", "unix": "1586859751"},
+            "title": {"full": "Talk:Foo", "namespace": "Talk", "namespace-key": 1, "text": "Foo"},
+            "agent": {"id": 5, "name": "Example"}
+        }))
+        .unwrap();
+        assert_eq!(notification.id, 1234);
+        assert_eq!(notification.title.as_deref(), Some("Talk:Foo"));
+        assert_eq!(notification.agent.as_deref(), Some("Example"));
+        assert!(notification.timestamp.is_some());
+        assert!(!notification.read);
+        assert_eq!(join_ids(&[1, 2, 3]), "1|2|3");
+    }
+
+    #[test]
+    fn results() {
+        assert!(echo_result(
+            &json!({"query": {"echomarkread": {"result": "success"}}}),
+            "echomarkread"
+        )
+        .is_ok());
+        assert!(echo_result(&json!({"error": {"code": "badtoken"}}), "echomarkread").is_err());
+    }
+}
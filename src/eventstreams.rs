@@ -0,0 +1,364 @@
+/*!
+The `EventStream` class is a client for
+[EventStreams](https://wikitech.wikimedia.org/wiki/Event_Platform/EventStreams), the server-sent
+events service of Wikimedia, e.g. for the `recentchange` stream of all wikis. Only available with
+the `eventstreams` feature.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::NamespaceID;
+use crate::recentchanges::RecentChange;
+use serde_json::Value;
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::thread;
+use std::time::Duration;
+
+/// The base URL of the Wikimedia EventStreams service
+pub const WIKIMEDIA_EVENTSTREAMS: &str = "https://stream.wikimedia.org/v2/stream/";
+
+/// An event from a stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// The ID of the event, to resume the stream from
+    pub id: Option<String>,
+    /// The event type; "message" for events of EventStreams
+    pub event_type: String,
+    /// The event data
+    pub data: Value,
+}
+
+impl Event {
+    /// Parses the event as a change, for events of the `recentchange` stream
+    pub fn recent_change(&self) -> Option<RecentChange> {
+        RecentChange::new_from_stream_event(&self.data)
+    }
+}
+
+/// A server-sent event, before the data is parsed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct SseEvent {
+    id: Option<String>,
+    event_type: Option<String>,
+    data: String,
+}
+
+/// Assembles server-sent events from lines
+#[derive(Debug, Clone, Default)]
+struct SseParser {
+    /// The last event ID, sent as `Last-Event-ID` when reconnecting
+    id: Option<String>,
+    /// The ID of the event being received, which becomes the last event ID once the event is
+    /// dispatched
+    pending_id: Option<String>,
+    event_type: Option<String>,
+    data: Vec<String>,
+    retry: Option<Duration>,
+}
+
+impl SseParser {
+    /// Processes a line (without line break); returns an event after the blank line that ends it
+    fn feed_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            if let Some(id) = self.pending_id.take() {
+                self.id = Some(id);
+            }
+            if self.data.is_empty() {
+                self.event_type = None;
+                return None;
+            }
+            return Some(SseEvent {
+                id: self.id.clone(),
+                event_type: self.event_type.take(),
+                data: self.data.drain(..).collect::<Vec<_>>().join("\n"),
+            });
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event_type = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.pending_id = Some(value.to_string()),
+            "retry" => self.retry = value.parse().ok().map(Duration::from_millis),
+            _ => {}
+        }
+        None
+    }
+
+    /// Drops a partially received event, e.g. after the connection was lost
+    fn reset(&mut self) {
+        self.pending_id = None;
+        self.event_type = None;
+        self.data.clear();
+    }
+}
+
+/// A connection to one or more streams, which reconnects automatically, resuming after the last
+/// event. Iterating over it blocks until the next event arrives. Errors while connecting and events
+/// whose data is not valid JSON are yielded as errors; iteration can continue after them.
+#[derive(Debug)]
+pub struct EventStream {
+    client: reqwest::blocking::Client,
+    url: String,
+    user_agent: String,
+    since: Option<String>,
+    wikis: Vec<String>,
+    namespaces: Vec<NamespaceID>,
+    reader: Option<BufReader<reqwest::blocking::Response>>,
+    parser: SseParser,
+    reconnect_delay: Duration,
+    failed: bool,
+}
+
+impl EventStream {
+    /// Creates a client for streams of Wikimedia EventStreams, e.g. "recentchange" or "page-create"
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client can't be created.
+    pub fn new(streams: &[&str]) -> Result<EventStream, Box<dyn Error + Send + Sync>> {
+        EventStream::new_with_base_url(WIKIMEDIA_EVENTSTREAMS, streams)
+    }
+
+    /// Creates a client for the `recentchange` stream of Wikimedia EventStreams
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client can't be created.
+    pub fn recent_changes() -> Result<EventStream, Box<dyn Error + Send + Sync>> {
+        EventStream::new(&["recentchange"])
+    }
+
+    /// Creates a client for streams of another EventStreams service, with the base URL of the
+    /// streams
+    ///
+    /// # Errors
+    /// Returns an error if the HTTP client can't be created.
+    pub fn new_with_base_url(
+        base_url: &str,
+        streams: &[&str],
+    ) -> Result<EventStream, Box<dyn Error + Send + Sync>> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(None::<Duration>)
+            .build()?;
+        Ok(EventStream {
+            client,
+            url: format!("{}{}", base_url, streams.join(",")),
+            user_agent: format!(
+                "{}-rust/{}",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ),
+            since: None,
+            wikis: vec![],
+            namespaces: vec![],
+            reader: None,
+            parser: SseParser::default(),
+            reconnect_delay: Duration::from_secs(3),
+            failed: false,
+        })
+    }
+
+    /// Sets the user agent; Wikimedia asks for contact information in it
+    pub fn set_user_agent(&mut self, user_agent: &str) {
+        self.user_agent = user_agent.to_string();
+    }
+
+    /// Starts the stream at a time in the past (an ISO 8601 timestamp, or milliseconds since the
+    /// epoch), unless resuming from a last event ID
+    pub fn set_since(&mut self, since: &str) {
+        self.since = Some(since.to_string());
+    }
+
+    /// Returns the ID of the last event, which can be saved to resume the stream later
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.parser.id.as_deref()
+    }
+
+    /// Resumes the stream after the event with this ID
+    pub fn set_last_event_id(&mut self, id: Option<String>) {
+        self.parser.id = id;
+    }
+
+    /// Only yields events of a wiki, by database name (e.g. "enwiki"); may be used several times
+    pub fn filter_wiki(&mut self, wiki: &str) {
+        self.wikis.push(wiki.to_string());
+    }
+
+    /// Only yields events in a namespace; may be used several times
+    pub fn filter_namespace(&mut self, namespace: NamespaceID) {
+        self.namespaces.push(namespace);
+    }
+
+    /// Sets the delay before reconnecting, unless the server asks for another delay (default 3
+    /// seconds)
+    pub fn set_reconnect_delay(&mut self, delay: Duration) {
+        self.reconnect_delay = delay;
+    }
+
+    /// Turns this stream of `recentchange` events into a stream of typed changes
+    pub fn into_recent_changes(
+        self,
+    ) -> impl Iterator<Item = Result<RecentChange, Box<dyn Error + Send + Sync>>> {
+        self.filter_map(|event| match event {
+            Ok(event) => event.recent_change().map(Ok),
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Connects to the stream, resuming after the last event if possible
+    fn connect(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut request = self
+            .client
+            .get(&self.url)
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .header(reqwest::header::USER_AGENT, self.user_agent.as_str());
+        match (&self.parser.id, &self.since) {
+            (Some(id), _) => request = request.header("Last-Event-ID", id.as_str()),
+            (None, Some(since)) => request = request.query(&[("since", since)]),
+            (None, None) => {}
+        }
+        let response = request.send()?.error_for_status()?;
+        self.parser.reset();
+        self.reader = Some(BufReader::new(response));
+        Ok(())
+    }
+
+    /// Checks if the event data passes the wiki and namespace filters
+    fn matches(&self, data: &Value) -> bool {
+        let wiki = data["wiki"].as_str().or_else(|| data["database"].as_str());
+        let namespace = data["namespace"]
+            .as_i64()
+            .or_else(|| data["page_namespace"].as_i64());
+        (self.wikis.is_empty() || wiki.is_some_and(|w| self.wikis.iter().any(|x| x == w)))
+            && (self.namespaces.is_empty()
+                || namespace.is_some_and(|ns| self.namespaces.contains(&ns)))
+    }
+}
+
+impl Iterator for EventStream {
+    type Item = Result<Event, Box<dyn Error + Send + Sync>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.reader.is_none() {
+                if self.failed {
+                    thread::sleep(self.parser.retry.unwrap_or(self.reconnect_delay));
+                }
+                if let Err(e) = self.connect() {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+            let mut line = String::new();
+            let read = self
+                .reader
+                .as_mut()
+                .map(|reader| reader.read_line(&mut line));
+            match read {
+                Some(Ok(n)) if n > 0 => self.failed = false,
+                _ => {
+                    // The connection was closed or lost; reconnect
+                    self.reader = None;
+                    self.failed = true;
+                    continue;
+                }
+            }
+            let event = match self.parser.feed_line(line.trim_end_matches(['\r', '\n'])) {
+                Some(event) => event,
+                None => continue,
+            };
+            let data: Value = match serde_json::from_str(&event.data) {
+                Ok(data) => data,
+                Err(e) => return Some(Err(Box::new(e))),
+            };
+            if self.matches(&data) {
+                return Some(Ok(Event {
+                    id: event.id,
+                    event_type: event.event_type.unwrap_or_else(|| "message".to_string()),
+                    data,
+                }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sse() {
+        let mut parser = SseParser::default();
+        let lines = [
+            ":ok",
+            "",
+            "event: message",
+            r#"id: [{"topic":"eqiad.mediawiki.recentchange","timestamp":1586859751001}]"#,
+            r#"data: {"wiki": "enwiki","#,
+            r#"data: "title": "Foo"}"#,
+            "retry: 2000",
+        ];
+        for line in &lines {
+            assert_eq!(parser.feed_line(line), None);
+        }
+        let event = parser.feed_line("").unwrap();
+        assert_eq!(event.event_type.as_deref(), Some("message"));
+        assert!(event.id.unwrap().contains("1586859751001"));
+        assert_eq!(event.data, "{\"wiki\": \"enwiki\",\n\"title\": \"Foo\"}");
+        assert_eq!(parser.retry, Some(Duration::from_millis(2000)));
+        assert_eq!(parser.feed_line(""), None);
+        assert_eq!(parser.feed_line("data:x"), None);
+        assert_eq!(parser.feed_line("").unwrap().data, "x");
+    }
+
+    #[test]
+    fn sse_id_on_dispatch() {
+        let mut parser = SseParser::default();
+        assert_eq!(parser.feed_line("id: 1"), None);
+        assert_eq!(parser.feed_line("data: a"), None);
+        assert_eq!(parser.id, None);
+        assert_eq!(parser.feed_line("").unwrap().id.as_deref(), Some("1"));
+        assert_eq!(parser.id.as_deref(), Some("1"));
+
+        // An event without ID keeps the last one; a partial event doesn't change it
+        assert_eq!(parser.feed_line("data: b"), None);
+        assert_eq!(parser.feed_line("").unwrap().id.as_deref(), Some("1"));
+        assert_eq!(parser.feed_line("id: 2"), None);
+        assert_eq!(parser.feed_line("data: c"), None);
+        parser.reset();
+        assert_eq!(parser.id.as_deref(), Some("1"));
+        assert_eq!(parser.feed_line("data: d"), None);
+        assert_eq!(parser.feed_line("").unwrap().id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn filters() {
+        let mut stream = EventStream::recent_changes().unwrap();
+        let data = json!({"wiki": "enwiki", "namespace": 0, "title": "Foo"});
+        assert!(stream.matches(&data));
+        stream.filter_wiki("dewiki");
+        assert!(!stream.matches(&data));
+        stream.filter_wiki("enwiki");
+        assert!(stream.matches(&data));
+        stream.filter_namespace(1);
+        assert!(!stream.matches(&data));
+        assert!(stream.matches(&json!({"database": "enwiki", "page_namespace": 1})));
+        stream.set_last_event_id(Some("[]".to_string()));
+        assert_eq!(stream.last_event_id(), Some("[]"));
+    }
+}
@@ -0,0 +1,283 @@
+/*!
+Parsers for the Atom output of `action=feedrecentchanges` and `action=feedwatchlist`, into the
+`RecentChange` type of the query modules. Feeds can be read with a watchlist token instead of a
+login, e.g. by tools that must not hold user credentials.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use crate::recentchanges::RecentChange;
+use crate::timestamp::parse_timestamp;
+use crate::title::Title;
+use quick_xml::events::Event;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// An `<entry>` of a feed, while it is being read
+#[derive(Debug, Default)]
+struct FeedEntry {
+    title: String,
+    link: String,
+    updated: String,
+    summary: String,
+    author: String,
+}
+
+impl FeedEntry {
+    /// Converts the entry to a change. The kind of change and the revision IDs are taken from the
+    /// link: edits link to a diff, page creations to a diff from revision 0, and log entries to the
+    /// page.
+    fn into_recent_change(self, api: &Api) -> Option<RecentChange> {
+        if self.title.is_empty() {
+            return None;
+        }
+        let query: HashMap<String, String> = url::Url::parse(&self.link)
+            .map(|url| url.query_pairs().into_owned().collect())
+            .unwrap_or_default();
+        let revision = |key: &str| {
+            query
+                .get(key)
+                .and_then(|id| id.parse::<u64>().ok())
+                .filter(|&id| id > 0)
+        };
+        let (revid, old_revid) = (revision("diff"), revision("oldid"));
+        let change_type = match (revid, old_revid) {
+            (Some(_), Some(_)) => "edit",
+            (Some(_), None) => "new",
+            _ => "log",
+        };
+        Some(RecentChange {
+            rcid: None,
+            change_type: change_type.to_string(),
+            namespace: Title::new_from_full(&self.title, api).namespace_id(),
+            title: self.title,
+            user: self.author,
+            bot: false,
+            minor: false,
+            patrolled: None,
+            timestamp: parse_timestamp(&self.updated),
+            comment: comment_from_summary(&self.summary),
+            old_revid,
+            revid,
+            old_length: None,
+            new_length: None,
+            log_type: None,
+            log_action: None,
+            wiki: None,
+        })
+    }
+}
+
+/// Parses an Atom feed of `action=feedrecentchanges` or `action=feedwatchlist` into changes, newest
+/// first. Feeds don't report flags, patrol status, lengths or log types, so these are unset.
+///
+/// # Errors
+/// Returns an error if the feed is not well-formed XML.
+pub fn parse_atom_feed(
+    api: &Api,
+    xml: &str,
+) -> Result<Vec<RecentChange>, Box<dyn Error + Send + Sync>> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut ret = vec![];
+    let mut path: Vec<String> = vec![];
+    let mut entry: Option<FeedEntry> = None;
+    let mut text = String::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => {
+                let name = String::from_utf8_lossy(start.local_name().as_ref()).to_string();
+                if name == "entry" {
+                    entry = Some(FeedEntry::default());
+                }
+                text.clear();
+                path.push(name);
+            }
+            Event::Empty(start) => {
+                if let (Some(entry), b"link") = (&mut entry, start.local_name().as_ref()) {
+                    let mut rel = None;
+                    let mut href = None;
+                    for attribute in start.attributes() {
+                        let attribute = attribute?;
+                        match attribute.key.local_name().as_ref() {
+                            b"rel" => rel = Some(attribute.unescape_value()?.to_string()),
+                            b"href" => href = Some(attribute.unescape_value()?.to_string()),
+                            _ => {}
+                        }
+                    }
+                    if rel.as_deref().unwrap_or("alternate") == "alternate" {
+                        entry.link = href.unwrap_or_default();
+                    }
+                }
+            }
+            Event::Text(t) => text += &t.unescape()?,
+            Event::CData(data) => text += &String::from_utf8_lossy(&data),
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                let parent = path.last().map(|s| s.as_str()).unwrap_or_default();
+                let value = std::mem::take(&mut text);
+                if name == "entry" {
+                    ret.extend(entry.take().and_then(|entry| entry.into_recent_change(api)));
+                } else if let Some(entry) = &mut entry {
+                    match (parent, name.as_str()) {
+                        ("entry", "title") => entry.title = value,
+                        ("entry", "updated") => entry.updated = value,
+                        ("entry", "summary") => entry.summary = value,
+                        ("author", "name") => entry.author = value,
+                        _ => {}
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(ret)
+}
+
+/// Returns the edit summary from the HTML summary of a feed entry: the text of its first paragraph
+fn comment_from_summary(html: &str) -> String {
+    let paragraph = match html.find("<p>") {
+        Some(start) => {
+            let rest = &html[start + 3..];
+            &rest[..rest.find("</p>").unwrap_or(rest.len())]
+        }
+        None => html,
+    };
+    let mut ret = String::new();
+    let mut in_tag = false;
+    for c in paragraph.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => ret.push(c),
+            _ => {}
+        }
+    }
+    ret.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#039;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+impl Api {
+    /// Returns the changes of `action=feedrecentchanges`, with additional parameters such as
+    /// "namespace", "days" or "hidebots"
+    ///
+    /// # Errors
+    /// Returns an error if the feed can't be parsed, or any error from the request.
+    pub fn feed_recent_changes(
+        &self,
+        params: &HashMap<String, String>,
+    ) -> Result<Vec<RecentChange>, Box<dyn Error + Send + Sync>> {
+        let mut params = params.clone();
+        params.insert("action".to_string(), "feedrecentchanges".to_string());
+        params.insert("feedformat".to_string(), "atom".to_string());
+        parse_atom_feed(self, &self.query_api_raw(&params, "GET")?)
+    }
+
+    /// Returns the watchlist changes of the user `owner` of the last `hours` (default: 72), using
+    /// the watchlist token of that user from `Special:Preferences`; no login is needed
+    ///
+    /// # Errors
+    /// Returns an error if the feed can't be parsed, or any error from the request.
+    pub fn feed_watchlist(
+        &self,
+        owner: &str,
+        token: &str,
+        hours: Option<u64>,
+    ) -> Result<Vec<RecentChange>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "feedwatchlist",
+            "feedformat" => "atom",
+            "wlowner" => owner,
+            "wltoken" => token,
+            "hours" => hours,
+        ];
+        parse_atom_feed(self, &self.query_api_raw(&params, "GET")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    const FEED: &str = r#"<?xml version="1.0"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="en">
+  <id>https://en.wikipedia.org/w/api.php?action=feedrecentchanges&amp;feedformat=atom</id>
+  <title>Wikipedia  - Recent changes [en]</title>
+  <link rel="self" type="application/atom+xml"
+    href="https://en.wikipedia.org/w/api.php?action=feedrecentchanges&amp;feedformat=atom"/>
+  <updated>2020-04-14T10:30:00Z</updated>
+  <generator>MediaWiki 1.35.0-wmf.27</generator>
+  <entry>
+    <id>https://en.wikipedia.org/w/index.php?title=Talk:Foo&amp;diff=20&amp;oldid=10</id>
+    <title>Talk:Foo</title>
+    <link rel="alternate" type="text/html"
+      href="https://en.wikipedia.org/w/index.php?title=Talk:Foo&amp;diff=20&amp;oldid=10"/>
+    <updated>2020-04-14T10:22:31Z</updated>
+    <summary type="html">
+&lt;p&gt;&lt;span dir="auto"&gt;typo &amp;amp; &lt;a href="/wiki/Bar"&gt;Bar&lt;/a&gt;&lt;/span&gt;&lt;/p&gt;
+&lt;table class="diff"&gt;&lt;/table&gt;</summary>
+    <author><name>Example</name></author>
+    <comments>https://en.wikipedia.org/wiki/Talk:Foo</comments>
+  </entry>
+  <entry>
+    <id>https://en.wikipedia.org/w/index.php?title=Baz&amp;diff=30&amp;oldid=0</id>
+    <title>Baz</title>
+    <link rel="alternate" type="text/html"
+      href="https://en.wikipedia.org/w/index.php?title=Baz&amp;diff=30&amp;oldid=0"/>
+    <updated>2020-04-14T10:20:00Z</updated>
+    <summary type="html">&lt;p&gt;new page&lt;/p&gt;</summary>
+    <author><name>192.0.2.1</name></author>
+  </entry>
+  <entry>
+    <id>https://en.wikipedia.org/wiki/Special:Log/delete</id>
+    <title>Qux</title>
+    <link rel="alternate" type="text/html" href="https://en.wikipedia.org/wiki/Qux"/>
+    <updated>2020-04-14T10:10:00Z</updated>
+    <summary type="html">&lt;p&gt;deleted &amp;quot;Qux&amp;quot;&lt;/p&gt;</summary>
+    <author><name>Admin</name></author>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn parse_feed() {
+        let api = test_api();
+        let changes = parse_atom_feed(&api, FEED).unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].title, "Talk:Foo");
+        assert_eq!(changes[0].change_type, "edit");
+        assert_eq!(
+            (changes[0].revid, changes[0].old_revid),
+            (Some(20), Some(10))
+        );
+        assert_eq!(changes[0].user, "Example");
+        assert_eq!(changes[0].comment, "typo & Bar");
+        assert_eq!(
+            changes[0].timestamp,
+            parse_timestamp("2020-04-14T10:22:31Z")
+        );
+        assert_eq!(changes[1].change_type, "new");
+        assert_eq!((changes[1].revid, changes[1].old_revid), (Some(30), None));
+        assert_eq!(changes[2].change_type, "log");
+        assert_eq!(changes[2].comment, "deleted \"Qux\"");
+        assert!(parse_atom_feed(&api, "<feed><entry></feed>").is_err());
+    }
+}
@@ -0,0 +1,620 @@
+/*!
+The `File` class deals with files (media) on a wiki, or its shared repository, like Commons.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, ApiError, NamespaceID};
+use crate::namespace::Namespace;
+use crate::page::Page;
+use crate::params_map;
+use crate::timestamp::Timestamp;
+use crate::title::Title;
+use crate::upload::{IgnoreWarnings, Upload, UploadError, UploadResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+/// The `iiprop` values for file versions in `history`
+const HISTORY_PROPS: &str = "timestamp|user|comment|url|size|sha1|mime";
+
+/// A version of a file, from `prop=imageinfo` in a `formatversion=2` result.
+/// Which fields are set depends on `iiprop` and `iiurlwidth`/`iiurlheight`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ImageInfo {
+    /// URL of the original file
+    pub url: Option<String>,
+    /// URL of the file description page
+    pub descriptionurl: Option<String>,
+    /// URL of a thumbnail, if `iiurlwidth` or `iiurlheight` was given
+    pub thumburl: Option<String>,
+    /// Width of the thumbnail
+    pub thumbwidth: Option<u64>,
+    /// Height of the thumbnail
+    pub thumbheight: Option<u64>,
+    /// Thumbnail URLs for high-density screens, keyed by pixel ratio (e.g. "1.5", "2")
+    #[serde(rename = "responsiveUrls", default)]
+    pub responsive_urls: HashMap<String, String>,
+    /// Size of the file, in bytes
+    pub size: Option<u64>,
+    /// Width of the file, in pixels
+    pub width: Option<u64>,
+    /// Height of the file, in pixels
+    pub height: Option<u64>,
+    /// SHA-1 of the file, in hexadecimal
+    pub sha1: Option<String>,
+    /// MIME type of the file
+    pub mime: Option<String>,
+    /// Upload time of this version
+    pub timestamp: Option<Timestamp>,
+    /// User who uploaded this version
+    pub user: Option<String>,
+    /// Upload summary of this version
+    pub comment: Option<String>,
+    /// Extended metadata (e.g. "Artist", "LicenseShortName", "ImageDescription"), from
+    /// `iiprop=extmetadata`
+    #[serde(default)]
+    pub extmetadata: HashMap<String, ExtMetadata>,
+}
+
+/// A field of the extended metadata of a file
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ExtMetadata {
+    /// The value; usually a string, which may contain HTML
+    pub value: Value,
+    /// Where the value comes from, e.g. "commons-desc-page" or "file-metadata"
+    pub source: Option<String>,
+    /// Whether the field is hidden by default on the file description page
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// A thumbnail of a file, as computed by the wiki for `prop=imageinfo&iiurlwidth=...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    /// URL of the thumbnail
+    pub url: String,
+    /// Width of the thumbnail, in pixels
+    pub width: Option<u64>,
+    /// Height of the thumbnail, in pixels
+    pub height: Option<u64>,
+    /// URLs for high-density screens, keyed by pixel ratio (e.g. "1.5", "2")
+    pub responsive_urls: HashMap<String, String>,
+}
+
+impl ImageInfo {
+    /// Returns an extended metadata field as text, if it is a string
+    pub fn ext_metadata_text(&self, key: &str) -> Option<&str> {
+        self.extmetadata.get(key)?.value.as_str()
+    }
+
+    /// Returns the thumbnail, if one was requested
+    pub fn thumbnail(&self) -> Option<Thumbnail> {
+        Some(Thumbnail {
+            url: self.thumburl.clone()?,
+            width: self.thumbwidth,
+            height: self.thumbheight,
+            responsive_urls: self.responsive_urls.clone(),
+        })
+    }
+}
+
+/// A page that uses a file, from a `formatversion=2` `list=imageusage` result
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ImageUsage {
+    /// ID of the page
+    pub pageid: u64,
+    /// Namespace of the page
+    pub ns: NamespaceID,
+    /// Full title of the page
+    pub title: String,
+    /// Whether the page is a redirect to the file
+    #[serde(default)]
+    pub redirect: bool,
+    /// For redirects, if redirects are followed: the pages that use the file through this redirect
+    #[serde(default)]
+    pub redirlinks: Vec<ImageUsage>,
+}
+
+impl ImageUsage {
+    /// Returns this page and the pages that use the file through it
+    pub fn pages(&self) -> impl Iterator<Item = &ImageUsage> {
+        std::iter::once(self).chain(&self.redirlinks)
+    }
+}
+
+/// Represents a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    title: Title,
+}
+
+impl File {
+    /// Creates a new `File` from a `Title`, which should be in the file namespace.
+    pub fn new(title: Title) -> Self {
+        File { title }
+    }
+
+    /// Accesses the `Title` of this `File`.
+    pub fn title(&self) -> &Title {
+        &self.title
+    }
+
+    /// Fetches the `imageinfo` of the current file version, including the URL.
+    /// Files from a shared repository (like Commons) are found as well.
+    /// If `width` is given, a thumbnail URL of that width is included.
+    ///
+    /// # Errors
+    /// Returns `FileError::Missing` if there is no such file, or any error from the request.
+    pub fn image_info(&self, api: &Api, width: Option<u64>) -> Result<ImageInfo, FileError> {
+        self.image_info_scaled(api, width, None)
+    }
+
+    /// Fetches the `imageinfo` of the current file version with its upload details and extended
+    /// metadata (description, author, license etc.), in the user language of the Api.
+    ///
+    /// # Errors
+    /// Returns `FileError::Missing` if there is no such file, or any error from the request.
+    pub fn metadata(&self, api: &Api) -> Result<ImageInfo, FileError> {
+        let params = params_map![
+            "action" => "query",
+            "prop" => "imageinfo",
+            "iiprop" => format!("{}|extmetadata", HISTORY_PROPS),
+            "titles" => self.full_title(api)?,
+            "formatversion" => "2",
+        ];
+        let result = api
+            .get_query_api_json(&params)
+            .map_err(FileError::RequestError)?;
+        File::image_info_from_result(&self.title, result)
+    }
+
+    /// Returns all versions of this file, newest first
+    ///
+    /// # Errors
+    /// Returns any error from the requests. A missing file has no versions.
+    pub fn history(&self, api: &Api) -> Result<Vec<ImageInfo>, FileError> {
+        let params = params_map![
+            "action" => "query",
+            "prop" => "imageinfo",
+            "iiprop" => HISTORY_PROPS,
+            "iilimit" => "max",
+            "titles" => self.full_title(api)?,
+            "formatversion" => "2",
+        ];
+        api.get_query_api_json_items(&params, "query.pages.0.imageinfo[]", None)
+            .collect::<Result<_, _>>()
+            .map_err(FileError::RequestError)
+    }
+
+    /// Returns the pages on this wiki that use this file, see `Api::image_usage`
+    ///
+    /// # Errors
+    /// Returns any error from the requests.
+    pub fn usage(&self, api: &Api) -> Result<Vec<Title>, FileError> {
+        api.image_usage(&self.full_title(api)?, false, &[])
+            .map(|usage| Ok(Title::new_from_full(&usage?.title, api)))
+            .collect::<Result<_, _>>()
+            .map_err(FileError::RequestError)
+    }
+
+    /// Uploads a new version of this file from disk, with the upload summary `comment`. The warning
+    /// that the file exists is expected, and ignored; all other warnings fail the upload.
+    ///
+    /// # Errors
+    /// Returns any `UploadError`, see `Upload::upload_file`.
+    pub fn upload_new_version(
+        &self,
+        api: &Api,
+        path: &Path,
+        comment: &str,
+    ) -> Result<UploadResult, UploadError> {
+        let mut upload = Upload::new(self.title.pretty());
+        upload.set_comment(comment);
+        upload.set_ignore_warnings(IgnoreWarnings::Codes(vec!["exists".to_string()]));
+        upload.upload_file(api, path)
+    }
+
+    /// Renames this file to `new_title`, moving its description page, talk page and all versions.
+    /// A redirect is left behind, so that pages using the old name keep working.
+    ///
+    /// # Errors
+    /// Returns `FileError::BadTitle` if `new_title` is not in the file namespace,
+    /// `FileError::ExtensionMismatch` if it has a different file extension (which the wiki
+    /// refuses), `FileError::BadResponse` if the move failed, or any error from the request.
+    pub fn move_to(&self, api: &Api, new_title: &Title, reason: &str) -> Result<File, FileError> {
+        if new_title.namespace() != Namespace::FILE {
+            return Err(FileError::BadTitle(new_title.clone()));
+        }
+        if !File::same_extension(self.title.pretty(), new_title.pretty()) {
+            return Err(FileError::ExtensionMismatch(new_title.clone()));
+        }
+        let to = new_title
+            .full_pretty(api)
+            .ok_or_else(|| FileError::BadTitle(new_title.clone()))?;
+        let params = params_map![
+            "action" => "move",
+            "from" => self.full_title(api)?,
+            "to" => to,
+            "reason" => reason,
+            "movetalk" => "1",
+            "formatversion" => "2",
+            "token" => api.get_edit_token().map_err(FileError::RequestError)?,
+        ];
+        let result = api
+            .post_query_api_json(&params)
+            .map_err(FileError::RequestError)?;
+        match result["move"]["to"].as_str() {
+            Some(_) => Ok(File::new(new_title.clone())),
+            None => Err(FileError::BadResponse(result)),
+        }
+    }
+
+    /// Checks if two file names have the same extension, ignoring case (e.g. "JPG" and "jpg")
+    fn same_extension(a: &str, b: &str) -> bool {
+        let extension = |name: &str| name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+        extension(a) == extension(b)
+    }
+
+    /// Returns the full title of this file, for use in an API request
+    fn full_title(&self, api: &Api) -> Result<String, FileError> {
+        self.title
+            .full_pretty(api)
+            .ok_or_else(|| FileError::BadTitle(self.title.clone()))
+    }
+
+    /// Like `image_info`, with a thumbnail URL that fits into `width` and/or `height`
+    fn image_info_scaled(
+        &self,
+        api: &Api,
+        width: Option<u64>,
+        height: Option<u64>,
+    ) -> Result<ImageInfo, FileError> {
+        let mut params = File::image_info_params(width, height);
+        params.insert("titles".to_string(), self.full_title(api)?);
+        let result = api
+            .get_query_api_json(&params)
+            .map_err(FileError::RequestError)?;
+        File::image_info_from_result(&self.title, result)
+    }
+
+    /// Returns the `prop=imageinfo` query parameters, without titles
+    fn image_info_params(width: Option<u64>, height: Option<u64>) -> HashMap<String, String> {
+        let mut params: HashMap<String, String> = [
+            ("action", "query"),
+            ("prop", "imageinfo"),
+            ("iiprop", "url|size|sha1|mime"),
+            ("formatversion", "2"),
+        ]
+        .iter()
+        .map(|&(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        if let Some(width) = width {
+            params.insert("iiurlwidth".to_string(), width.to_string());
+        }
+        if let Some(height) = height {
+            params.insert("iiurlheight".to_string(), height.to_string());
+        }
+        params
+    }
+
+    /// Returns the URL of a thumbnail that fits into `width` and/or `height`, without downloading
+    /// it. The wiki never scales images up; for small images, the thumbnail may be the original.
+    ///
+    /// # Errors
+    /// Returns `FileError::Missing` if there is no such file, `FileError::BadResponse`
+    /// if no thumbnail was returned (e.g. if neither width nor height is given),
+    /// or any error from the request.
+    pub fn thumbnail_url(
+        &self,
+        api: &Api,
+        width: Option<u64>,
+        height: Option<u64>,
+    ) -> Result<Thumbnail, FileError> {
+        self.image_info_scaled(api, width, height)?
+            .thumbnail()
+            .ok_or(FileError::BadResponse(Value::Null))
+    }
+
+    /// Returns thumbnail URLs for many files, e.g. for a gallery, using as few requests as
+    /// possible. Files that are missing, or have no thumbnail, are `None`.
+    ///
+    /// # Errors
+    /// Returns any error from the requests.
+    pub fn thumbnail_urls(
+        api: &Api,
+        files: &[File],
+        width: Option<u64>,
+        height: Option<u64>,
+    ) -> Result<Vec<Option<Thumbnail>>, Box<dyn Error + Send + Sync>> {
+        let titles: Vec<Title> = files.iter().map(|f| f.title.clone()).collect();
+        let batch = Page::fetch_batch(api, &titles, &File::image_info_params(width, height))?;
+        Ok(titles
+            .iter()
+            .map(|title| {
+                let page = batch.page(&title.full_pretty(api)?)?;
+                ImageInfo::deserialize(page["imageinfo"].get(0)?)
+                    .ok()?
+                    .thumbnail()
+            })
+            .collect())
+    }
+
+    /// Extracts the first `imageinfo` of the first page in a query result
+    fn image_info_from_result(title: &Title, result: Value) -> Result<ImageInfo, FileError> {
+        let page = &result["query"]["pages"][0];
+        match page["imageinfo"].get(0) {
+            Some(info) => {
+                ImageInfo::deserialize(info).map_err(|_| FileError::BadResponse(result.clone()))
+            }
+            None if page.is_object() => Err(FileError::Missing(title.clone())),
+            None => Err(FileError::BadResponse(result)),
+        }
+    }
+
+    /// Downloads the original file, writing it to `writer`.
+    /// Returns the number of bytes written.
+    ///
+    /// # Errors
+    /// May return a `FileError`, including errors while writing.
+    pub fn download<W: Write>(&self, api: &Api, writer: &mut W) -> Result<u64, FileError> {
+        let info = self.image_info(api, None)?;
+        let url = info
+            .url
+            .ok_or_else(|| FileError::Missing(self.title.clone()))?;
+        File::download_url(api, &url, writer)
+    }
+
+    /// Downloads a thumbnail of the file with the given width, writing it to `writer`.
+    /// Returns the number of bytes written.
+    ///
+    /// # Errors
+    /// May return a `FileError`, including errors while writing.
+    pub fn thumbnail<W: Write>(
+        &self,
+        api: &Api,
+        width: u64,
+        writer: &mut W,
+    ) -> Result<u64, FileError> {
+        let info = self.image_info(api, Some(width))?;
+        let url = info
+            .thumburl
+            .or(info.url)
+            .ok_or_else(|| FileError::Missing(self.title.clone()))?;
+        File::download_url(api, &url, writer)
+    }
+
+    /// Streams the content at `url` into `writer`, with the user agent of the Api. The media server
+    /// (often on another host) does not get the cookies or OAuth header of the session.
+    fn download_url<W: Write>(api: &Api, url: &str, writer: &mut W) -> Result<u64, FileError> {
+        let mut response = api
+            .plain_request_builder(url, &HashMap::new(), "GET")
+            .map_err(FileError::RequestError)?
+            .send()
+            .map_err(|e| FileError::RequestError(Box::new(e)))?
+            .error_for_status()
+            .map_err(|e| FileError::RequestError(Box::new(e)))?;
+        response
+            .copy_to(writer)
+            .map_err(|e| FileError::RequestError(Box::new(e)))
+    }
+}
+
+impl Api {
+    /// Iterates over the pages that use the file `file` (a full title, e.g. "File:Example.jpg"), in
+    /// `namespaces` (all if empty). If `follow_redirects` is set, redirects to the file are listed
+    /// with the pages that use the file through them (see `ImageUsage::pages`).
+    pub fn image_usage(
+        &self,
+        file: &str,
+        follow_redirects: bool,
+        namespaces: &[NamespaceID],
+    ) -> impl Iterator<Item = Result<ImageUsage, Box<dyn Error + Send + Sync>>> + '_ {
+        let namespaces: Vec<String> = namespaces.iter().map(|ns| ns.to_string()).collect();
+        let params = params_map![
+            "action" => "query",
+            "list" => "imageusage",
+            "iutitle" => file,
+            "iuredirect" => follow_redirects,
+            "iunamespace" => Some(namespaces.join("|")).filter(|ns| !ns.is_empty()),
+            "iulimit" => "max",
+            "formatversion" => "2",
+        ];
+        self.get_query_api_json_limit_iter(&params, None)
+            .flat_map(|result| match result {
+                Ok(result) => image_usage_from_api_result(&result),
+                Err(e) => vec![Err(e)],
+            })
+    }
+}
+
+/// Parses the pages of a `list=imageusage` result, or returns the API error
+fn image_usage_from_api_result(
+    result: &Value,
+) -> Vec<Result<ImageUsage, Box<dyn Error + Send + Sync>>> {
+    if let Some(error) = ApiError::new_from_api_result(result) {
+        return vec![Err(Box::new(error))];
+    }
+    result["query"]["imageusage"]
+        .as_array()
+        .map(|pages| {
+            pages
+                .iter()
+                .map(|page| ImageUsage::deserialize(page).map_err(From::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Errors that can go wrong while performing operations on a `File`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FileError {
+    /// Couldn't obtain the title for this file for use in an API request.
+    BadTitle(Title),
+
+    /// Couldn't understand the API response (provided).
+    BadResponse(Value),
+
+    /// Missing file.
+    Missing(Title),
+
+    /// The new title (provided) of a move has a different file extension.
+    ExtensionMismatch(Title),
+
+    /// Error while performing the request, or writing the file data.
+    RequestError(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::BadTitle(title) => write!(f, "invalid title for this File: {:?}", title),
+            FileError::BadResponse(response) => {
+                write!(
+                    f,
+                    "bad API response while fetching file info: {:?}",
+                    response
+                )
+            }
+            FileError::Missing(title) => write!(f, "file missing: {:?}", title),
+            FileError::ExtensionMismatch(title) => {
+                write!(f, "file extension can't be changed: {:?}", title)
+            }
+            FileError::RequestError(error) => write!(f, "request error: {}", error),
+        }
+    }
+}
+
+impl Error for FileError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespace::Namespace;
+
+    #[test]
+    fn image_info_from_shared_repository() {
+        let title = Title::new("Example.jpg", Namespace::FILE);
+        let result = json!({"query": {"pages": [{
+            "ns": 6, "title": "Datei:Example.jpg", "missing": true, "known": true,
+            "imagerepository": "shared",
+            "imageinfo": [{
+                "size": 9022, "width": 172, "height": 178,
+                "thumburl":
+                    "https://upload.wikimedia.org/wikipedia/commons/thumb/a/a9/Example.jpg/100px-Example.jpg",
+                "thumbwidth": 100, "thumbheight": 103,
+                "responsiveUrls": {"1.5":
+                    "https://upload.wikimedia.org/wikipedia/commons/thumb/a/a9/Example.jpg/150px-Example.jpg"},
+                "url": "https://upload.wikimedia.org/wikipedia/commons/a/a9/Example.jpg",
+                "descriptionurl": "https://commons.wikimedia.org/wiki/File:Example.jpg",
+                "sha1": "f8b6a3c3e4ad2b4c5c3d3e4f5a6b7c8d9e0f1a2b", "mime": "image/jpeg"
+            }]
+        }]}});
+        let info = File::image_info_from_result(&title, result).unwrap();
+        assert_eq!(info.thumbwidth, Some(100));
+        assert_eq!(info.size, Some(9022));
+        assert!(info.url.as_ref().unwrap().ends_with("/a/a9/Example.jpg"));
+        assert_eq!(info.responsive_urls.len(), 1);
+        let thumbnail = info.thumbnail().unwrap();
+        assert!(thumbnail.url.ends_with("/100px-Example.jpg"));
+        assert_eq!((thumbnail.width, thumbnail.height), (Some(100), Some(103)));
+        assert!(thumbnail.responsive_urls["1.5"].ends_with("/150px-Example.jpg"));
+    }
+
+    #[test]
+    fn image_info_params() {
+        let params = File::image_info_params(Some(120), None);
+        assert_eq!(params["iiurlwidth"], "120");
+        assert!(!params.contains_key("iiurlheight"));
+        let params = File::image_info_params(None, Some(80));
+        assert_eq!(params["iiurlheight"], "80");
+        assert!(params["iiprop"].split('|').any(|p| p == "url"));
+    }
+
+    #[test]
+    fn ext_metadata() {
+        let title = Title::new("Example.jpg", Namespace::FILE);
+        let result = json!({"query": {"pages": [{
+            "ns": 6, "title": "File:Example.jpg",
+            "imageinfo": [{
+                "timestamp": "2008-10-07T10:14:01Z", "user": "Example", "comment": "Upload",
+                "extmetadata": {
+                    "LicenseShortName": {"value": "CC0", "source": "commons-desc-page",
+                    "hidden": true},
+                    "ImageWidth": {"value": 172, "source": "mediawiki-metadata"}
+                }
+            }]
+        }]}});
+        let info = File::image_info_from_result(&title, result).unwrap();
+        assert_eq!(info.user.as_deref(), Some("Example"));
+        assert_eq!(
+            info.timestamp,
+            crate::timestamp::parse_timestamp("2008-10-07T10:14:01Z")
+        );
+        assert_eq!(info.ext_metadata_text("LicenseShortName"), Some("CC0"));
+        assert!(info.extmetadata["LicenseShortName"].hidden);
+        assert_eq!(info.ext_metadata_text("ImageWidth"), None);
+        assert_eq!(info.extmetadata["ImageWidth"].value, json!(172));
+    }
+
+    #[test]
+    fn image_usage() {
+        let result = json!({"query": {"imageusage": [
+            {"pageid": 1, "ns": 0, "title": "Foo"},
+            {"pageid": 2, "ns": 6, "title": "File:Old name.jpg", "redirect": true, "redirlinks": [
+                {"pageid": 3, "ns": 0, "title": "Bar"}
+            ]}
+        ]}});
+        let usages: Vec<ImageUsage> = image_usage_from_api_result(&result)
+            .into_iter()
+            .map(|u| u.unwrap())
+            .collect();
+        assert!(!usages[0].redirect);
+        let pages: Vec<&str> = usages[1].pages().map(|page| page.title.as_str()).collect();
+        assert_eq!(pages, vec!["File:Old name.jpg", "Bar"]);
+        let error = json!({"error": {"code": "invalidtitle", "info": "Bad title"}});
+        let results = image_usage_from_api_result(&error);
+        let error = results[0].as_ref().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ApiError>().unwrap().code,
+            "invalidtitle"
+        );
+    }
+
+    #[test]
+    fn same_extension() {
+        assert!(File::same_extension("Foo.jpg", "Bar baz.JPG"));
+        assert!(!File::same_extension("Foo.jpg", "Foo.png"));
+        assert!(!File::same_extension("Foo.jpg", "Foo"));
+    }
+
+    #[test]
+    fn image_info_missing() {
+        let title = Title::new("Nonexistent.jpg", Namespace::FILE);
+        let result = json!({"query": {"pages": [{"ns": 6, "title": "File:Nonexistent.jpg",
+            "missing": true}]}});
+        assert!(matches!(
+            File::image_info_from_result(&title, result),
+            Err(FileError::Missing(_))
+        ));
+        assert!(matches!(
+            File::image_info_from_result(&title, json!({"batchcomplete": true})),
+            Err(FileError::BadResponse(_))
+        ));
+    }
+}
@@ -0,0 +1,125 @@
+/*!
+Deleted file versions, from `list=filearchive`: e.g. to check whether a file was deleted before, by
+name or by content (SHA-1), before uploading it again. Listing archived files needs the
+`deletedhistory` right.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A deleted version of a file, from a `formatversion=2` `list=filearchive` result
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ArchivedFile {
+    /// ID of the archived version
+    pub id: u64,
+    /// File name, without namespace prefix
+    pub name: String,
+    /// Full title of the file
+    pub title: String,
+    /// Upload time of the version; the API does not report when it was deleted (see the deletion
+    /// log)
+    pub timestamp: Option<Timestamp>,
+    /// User who uploaded the version
+    pub user: Option<String>,
+    /// Size of the file, in bytes
+    pub size: Option<u64>,
+    /// SHA-1 of the file, in hexadecimal
+    pub sha1: Option<String>,
+    /// MIME type of the file
+    pub mime: Option<String>,
+    /// Upload summary of the version
+    pub description: Option<String>,
+    /// Whether the file content has been hidden (revision deleted)
+    #[serde(default)]
+    pub filehidden: bool,
+}
+
+impl Api {
+    /// Loads the deleted versions of the file `name` (without namespace prefix)
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. permission denied), or any error from
+    /// the requests.
+    pub fn archived_files(
+        &self,
+        name: &str,
+    ) -> Result<Vec<ArchivedFile>, Box<dyn Error + Send + Sync>> {
+        let name = name.replace(' ', "_");
+        self.file_archive(params_map!["fafrom" => &name, "fato" => &name])
+    }
+
+    /// Loads the deleted file versions with the SHA-1 `sha1` (in hexadecimal), under any name
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. permission denied), or any error from
+    /// the requests.
+    pub fn archived_files_by_sha1(
+        &self,
+        sha1: &str,
+    ) -> Result<Vec<ArchivedFile>, Box<dyn Error + Send + Sync>> {
+        self.file_archive(params_map!["fasha1" => sha1.to_lowercase()])
+    }
+
+    /// Runs `list=filearchive` with additional parameters
+    fn file_archive(
+        &self,
+        mut params: HashMap<String, String>,
+    ) -> Result<Vec<ArchivedFile>, Box<dyn Error + Send + Sync>> {
+        params.extend(params_map![
+            "action" => "query",
+            "list" => "filearchive",
+            "faprop" => "sha1|timestamp|user|size|description|mime",
+            "falimit" => "max",
+            "formatversion" => "2",
+        ]);
+        let mut ret = vec![];
+        for result in self.get_query_api_json_limit_iter(&params, None) {
+            let result = result?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!("list=filearchive failed: {}", error)));
+            }
+            if let Some(files) = result["query"].get("filearchive") {
+                ret.extend(Vec::<ArchivedFile>::deserialize(files)?);
+            }
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archived_file() {
+        let files: Vec<ArchivedFile> = serde_json::from_value(json!([
+            {"id": 123, "name": "Example.jpg", "ns": 6, "title": "File:Example.jpg",
+             "sha1": "f8b6a3c3e4ad2b4c5c3d3e4f5a6b7c8d9e0f1a2b",
+             "timestamp": "2020-04-14T10:22:31Z",
+             "user": "Example", "size": 9022, "description": "Upload", "mime": "image/jpeg"},
+            {"id": 124, "name": "Example.jpg", "ns": 6, "title": "File:Example.jpg",
+            "filehidden": true}
+        ]))
+        .unwrap();
+        assert_eq!(files[0].size, Some(9022));
+        assert!(files[0].timestamp.is_some());
+        assert!(files[1].filehidden);
+        assert_eq!(files[1].sha1, None);
+    }
+}
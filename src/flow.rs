@@ -0,0 +1,427 @@
+/*!
+Boards of [Structured Discussions](https://www.mediawiki.org/wiki/Extension:StructuredDiscussions)
+(Flow), via `action=flow`: listing the topics of a board, loading the posts of a topic, replying,
+starting new topics, and marking topics as resolved. On Flow boards, talk pages can't be edited as
+wikitext.
+
+A topic is a page in the "Topic" namespace (e.g. "Topic:Vbbxlh8r2ujdkitb"), whose name is the ID of
+the topic. Posts and topics are loaded in wikitext or HTML, see `FlowFormat`.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use crate::timestamp::{parse_db_timestamp, Timestamp};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Number of topics per `view-topiclist` request
+const TOPICS_LIMIT: usize = 100;
+
+/// Representation of the content of posts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowFormat {
+    /// Wikitext, e.g. to process or quote posts
+    Wikitext,
+    /// Parsed HTML, e.g. to display posts
+    Html,
+}
+
+impl FlowFormat {
+    /// Returns the format as `action=flow` expects it
+    pub fn as_str(&self) -> &str {
+        match self {
+            FlowFormat::Wikitext => "wikitext",
+            FlowFormat::Html => "html",
+        }
+    }
+}
+
+/// The content of a post or a topic title
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowContent {
+    /// The content
+    pub content: String,
+    /// The format of the content, e.g. "wikitext", "html", or "topic-title-wikitext" for topic
+    /// titles
+    pub format: String,
+}
+
+impl FlowContent {
+    /// Parses the `content` of a Flow revision
+    pub fn new_from_api_result(content: &Value) -> FlowContent {
+        FlowContent {
+            content: content["content"].as_str().unwrap_or_default().to_string(),
+            format: content["format"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// A post in a topic, in its latest revision
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowPost {
+    /// ID of the post, to reply to it
+    pub post_id: String,
+    /// ID of the latest revision of the post
+    pub revision_id: String,
+    /// Author of the post
+    pub author: Option<String>,
+    /// Time of the latest revision
+    pub timestamp: Option<Timestamp>,
+    /// Content of the post
+    pub content: FlowContent,
+    /// Moderation state of the post, e.g. "hide" or "delete", if moderated
+    pub moderation_state: Option<String>,
+    /// IDs of the posts that reply to this post
+    pub replies: Vec<String>,
+    /// Depth in the topic: 0 for posts that reply to the topic, 1 for replies to these, and so on
+    pub depth: usize,
+}
+
+impl FlowPost {
+    /// Parses a Flow revision of a post
+    pub fn new_from_api_result(revision: &Value, depth: usize) -> Option<FlowPost> {
+        Some(FlowPost {
+            post_id: revision["postId"].as_str()?.to_string(),
+            revision_id: revision["revisionId"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            author: revision["author"]["name"].as_str().map(|s| s.to_string()),
+            timestamp: revision["timestamp"].as_str().and_then(parse_db_timestamp),
+            content: FlowContent::new_from_api_result(&revision["content"]),
+            moderation_state: moderation_state(revision),
+            replies: string_array(&revision["replies"]),
+            depth,
+        })
+    }
+
+    /// Checks if the post was hidden, deleted or suppressed
+    pub fn is_moderated(&self) -> bool {
+        self.moderation_state.is_some()
+    }
+}
+
+/// A topic on a Flow board, with its posts
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowTopic {
+    /// ID of the topic; its page is "Topic:" followed by the ID
+    pub id: String,
+    /// Title of the topic
+    pub title: FlowContent,
+    /// Author of the topic
+    pub author: Option<String>,
+    /// Moderation state of the topic, e.g. "lock" if it is resolved, if moderated
+    pub moderation_state: Option<String>,
+    /// The posts of the topic, in thread order: each post is followed by its replies
+    pub posts: Vec<FlowPost>,
+}
+
+impl FlowTopic {
+    /// Parses the topic with the root post `root` from a block of a `view-topic` or
+    /// `view-topiclist` result, which has `posts` (the revision IDs of each post) and `revisions`
+    pub fn new_from_api_result(block: &Value, root: &str) -> Option<FlowTopic> {
+        let revision = latest_revision(block, root)?;
+        let mut topic = FlowTopic {
+            id: root.to_string(),
+            title: FlowContent::new_from_api_result(&revision["content"]),
+            author: revision["author"]["name"].as_str().map(|s| s.to_string()),
+            moderation_state: moderation_state(revision),
+            posts: vec![],
+        };
+        let mut stack: Vec<(String, usize)> = string_array(&revision["replies"])
+            .into_iter()
+            .rev()
+            .map(|id| (id, 0))
+            .collect();
+        while let Some((id, depth)) = stack.pop() {
+            if let Some(post) =
+                latest_revision(block, &id).and_then(|r| FlowPost::new_from_api_result(r, depth))
+            {
+                stack.extend(
+                    post.replies
+                        .iter()
+                        .rev()
+                        .map(|id| (id.to_string(), depth + 1)),
+                );
+                topic.posts.push(post);
+            }
+        }
+        Some(topic)
+    }
+
+    /// Returns the page of the topic, e.g. "Topic:Vbbxlh8r2ujdkitb"
+    pub fn page(&self) -> String {
+        format!("Topic:{}", self.id)
+    }
+
+    /// Checks if the topic is marked as resolved (locked)
+    pub fn is_resolved(&self) -> bool {
+        self.moderation_state.as_deref() == Some("lock")
+    }
+}
+
+impl Api {
+    /// Loads the topics of the Flow board `board` (e.g. "Talk:Foo"), newest first, up to `max`
+    /// topics
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the page is not a Flow board),
+    /// or any error from the requests.
+    pub fn flow_topics(
+        &self,
+        board: &str,
+        format: FlowFormat,
+        max: Option<usize>,
+    ) -> Result<Vec<FlowTopic>, Box<dyn Error + Send + Sync>> {
+        let mut params = params_map![
+            "action" => "flow",
+            "submodule" => "view-topiclist",
+            "page" => board,
+            "vtlformat" => format.as_str(),
+            "vtllimit" => TOPICS_LIMIT,
+            "formatversion" => "2",
+        ];
+        let mut ret = vec![];
+        loop {
+            let result = self.get_query_api_json(&params)?;
+            let block = &flow_result(&result, "view-topiclist")?["result"]["topiclist"];
+            let roots = string_array(&block["roots"]);
+            ret.extend(
+                roots
+                    .iter()
+                    .filter_map(|root| FlowTopic::new_from_api_result(block, root)),
+            );
+            if let Some(max) = max {
+                if ret.len() >= max {
+                    ret.truncate(max);
+                    break;
+                }
+            }
+            match roots.last() {
+                Some(last) if roots.len() >= TOPICS_LIMIT => {
+                    params.insert("vtloffset-id".to_string(), last.to_string());
+                    params.insert("vtloffset-dir".to_string(), "fwd".to_string());
+                }
+                _ => break,
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Loads a topic with all its posts; `topic` is the page of the topic (e.g.
+    /// "Topic:Vbbxlh8r2ujdkitb")
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the topic does not exist), or any error
+    /// from the request.
+    pub fn flow_topic(
+        &self,
+        topic: &str,
+        format: FlowFormat,
+    ) -> Result<FlowTopic, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "flow",
+            "submodule" => "view-topic",
+            "page" => topic,
+            "vtformat" => format.as_str(),
+            "formatversion" => "2",
+        ];
+        let result = self.get_query_api_json(&params)?;
+        let block = &flow_result(&result, "view-topic")?["result"]["topic"];
+        string_array(&block["roots"])
+            .first()
+            .and_then(|root| FlowTopic::new_from_api_result(block, root))
+            .ok_or_else(|| From::from(format!("Unexpected view-topic result: {:?}", result)))
+    }
+
+    /// Replies to the post `reply_to` (or to the topic, with the ID of the topic) in `topic`, with
+    /// wikitext. Flow takes care of indentation and signatures.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the topic is locked), or any error from
+    /// the request.
+    pub fn flow_reply(
+        &self,
+        topic: &str,
+        reply_to: &str,
+        content: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.flow_action(params_map![
+            "submodule" => "reply",
+            "page" => topic,
+            "repreplyTo" => reply_to,
+            "repcontent" => content,
+            "repformat" => "wikitext",
+        ])?;
+        Ok(())
+    }
+
+    /// Starts a new topic on the Flow board `board`, with a title and wikitext content.
+    /// Returns the page of the new topic.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn flow_new_topic(
+        &self,
+        board: &str,
+        title: &str,
+        content: &str,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let result = self.flow_action(params_map![
+            "submodule" => "new-topic",
+            "page" => board,
+            "nttopic" => title,
+            "ntcontent" => content,
+            "ntformat" => "wikitext",
+        ])?;
+        result["committed"]["topiclist"]["topic-page"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| From::from(format!("Unexpected new-topic result: {:?}", result)))
+    }
+
+    /// Marks the topic `topic` as resolved, or reopens it if `resolved` is false, with an optional
+    /// summary
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn set_flow_topic_resolved(
+        &self,
+        topic: &str,
+        resolved: bool,
+        summary: Option<&str>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.flow_action(params_map![
+            "submodule" => "lock-topic",
+            "page" => topic,
+            "cotmoderationState" => if resolved { "lock" } else { "unlock" },
+            "cotreason" => summary.unwrap_or_default(),
+        ])?;
+        Ok(())
+    }
+
+    /// Posts an `action=flow` request with a submodule that changes something, and returns its
+    /// result
+    fn flow_action(
+        &self,
+        mut params: HashMap<String, String>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        params.extend(params_map![
+            "action" => "flow",
+            "token" => self.get_edit_token()?,
+            "formatversion" => "2",
+        ]);
+        let submodule = params["submodule"].clone();
+        let result = self.post_query_api_json(&params)?;
+        let result = flow_result(&result, &submodule)?;
+        match result["status"].as_str() {
+            Some("ok") => Ok(result.clone()),
+            _ => Err(From::from(format!(
+                "action=flow submodule={} failed: {:?}",
+                submodule, result
+            ))),
+        }
+    }
+}
+
+/// Returns the result of the `action=flow` submodule `submodule`, or the API error
+fn flow_result<'a>(
+    result: &'a Value,
+    submodule: &str,
+) -> Result<&'a Value, Box<dyn Error + Send + Sync>> {
+    if let Some(error) = result["error"]["info"].as_str() {
+        return Err(From::from(format!(
+            "action=flow submodule={} failed: {}",
+            submodule, error
+        )));
+    }
+    Ok(&result["flow"][submodule])
+}
+
+/// Returns the latest revision of the post `id` in a block of a `view-topic` or `view-topiclist`
+/// result
+fn latest_revision<'a>(block: &'a Value, id: &str) -> Option<&'a Value> {
+    let revision_id = block["posts"][id].as_array()?.first()?.as_str()?;
+    block["revisions"].get(revision_id)
+}
+
+/// Returns the moderation state of a revision, if it is moderated
+fn moderation_state(revision: &Value) -> Option<String> {
+    revision["moderateState"]
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+/// Returns the strings in an array
+fn string_array(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.as_str().map(|s| s.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topic_from_api_result() {
+        let block = json!({
+            "roots": ["topic1"],
+            "posts": {"topic1": ["rev1"], "post1": ["rev2"], "post2": ["rev3"], "post3": ["rev4"]},
+            "revisions": {
+                "rev1": {"postId": "topic1", "revisionId": "rev1", "author": {"name": "Alice"},
+                         "content": {"content": "Question", "format": "topic-title-wikitext"},
+                         "moderateState": "lock", "replies": ["post1", "post3"]},
+                "rev2": {"postId": "post1", "revisionId": "rev2", "author": {"name": "Alice"},
+                         "timestamp": "20200414102231", "content": {"content": "How?",
+                         "format": "wikitext"},
+                         "moderateState": "", "replies": ["post2"]},
+                "rev3": {"postId": "post2", "revisionId": "rev3", "author": {"name": "Bob"},
+                         "content": {"content": "Like this.", "format": "wikitext"}, "replies": []},
+                "rev4": {"postId": "post3", "revisionId": "rev4", "author": {"name": "Carol"},
+                         "content": {"content": "", "format": "wikitext"}, "moderateState": "hide",
+                         "replies": []}
+            }
+        });
+        let topic = FlowTopic::new_from_api_result(&block, "topic1").unwrap();
+        assert_eq!(topic.page(), "Topic:topic1");
+        assert_eq!(topic.title.content, "Question");
+        assert!(topic.is_resolved());
+        let posts: Vec<(&str, usize)> = topic
+            .posts
+            .iter()
+            .map(|p| (p.post_id.as_str(), p.depth))
+            .collect();
+        assert_eq!(posts, vec![("post1", 0), ("post2", 1), ("post3", 0)]);
+        assert!(topic.posts[0].timestamp.is_some());
+        assert!(!topic.posts[0].is_moderated());
+        assert!(topic.posts[2].is_moderated());
+        assert_eq!(topic.posts[1].author.as_deref(), Some("Bob"));
+        assert_eq!(FlowTopic::new_from_api_result(&block, "topic2"), None);
+    }
+
+    #[test]
+    fn errors() {
+        let result = json!({"error": {"code": "invalid-page", "info": "Not a Flow board"}});
+        assert!(flow_result(&result, "view-topiclist").is_err());
+        let result = json!({"flow": {"reply": {"status": "ok"}}});
+        assert_eq!(flow_result(&result, "reply").unwrap()["status"], "ok");
+    }
+}
@@ -0,0 +1,215 @@
+/*!
+Gadgets of the [Gadgets](https://www.mediawiki.org/wiki/Extension:Gadgets) extension, from
+`list=gadgets` and `list=gadgetcategories`: their names, descriptions, resource pages and loading
+conditions.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+/// A gadget, from a `formatversion=2` `list=gadgets` result
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Gadget {
+    /// ID of the gadget, as used in preferences (e.g. "HotCat")
+    pub id: String,
+    /// Description, as HTML
+    pub desc: Option<String>,
+    /// Settings and conditions of the gadget
+    pub metadata: GadgetMetadata,
+}
+
+/// The definition of a gadget
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GadgetMetadata {
+    /// Settings of the gadget
+    #[serde(default)]
+    pub settings: GadgetSettings,
+    /// Loaded modules
+    #[serde(default)]
+    pub module: GadgetModule,
+}
+
+/// Settings and loading conditions of a gadget
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GadgetSettings {
+    /// Category of the gadget in the preferences; empty for none
+    #[serde(default)]
+    pub category: String,
+    /// Rights needed to use the gadget
+    #[serde(default)]
+    pub rights: Vec<String>,
+    /// Skins the gadget is loaded in; empty for all
+    #[serde(default)]
+    pub skins: Vec<String>,
+    /// Actions the gadget is loaded on (e.g. "edit"); empty for all
+    #[serde(default)]
+    pub actions: Vec<String>,
+    /// Namespaces the gadget is loaded in; empty for all
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    /// Whether the gadget is enabled by default
+    #[serde(default)]
+    pub default: bool,
+    /// Whether the gadget is hidden from the preferences
+    #[serde(default)]
+    pub hidden: bool,
+    /// Whether the gadget is a ResourceLoader module that can be loaded by other scripts
+    #[serde(default)]
+    pub package: bool,
+}
+
+/// Resources of a gadget; pages are given without the "MediaWiki:Gadget-" prefix
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GadgetModule {
+    /// Script pages
+    #[serde(default)]
+    pub scripts: Vec<String>,
+    /// Style pages
+    #[serde(default)]
+    pub styles: Vec<String>,
+    /// JSON data pages
+    #[serde(default)]
+    pub datas: Vec<String>,
+    /// ResourceLoader modules the gadget depends on
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Messages used by the gadget
+    #[serde(default)]
+    pub messages: Vec<String>,
+}
+
+impl Gadget {
+    /// Returns the full titles of the pages of the gadget: its definition, scripts, styles and data
+    pub fn resource_pages(&self) -> Vec<String> {
+        let module = &self.metadata.module;
+        let mut ret = vec![format!("MediaWiki:Gadget-{}", self.id)];
+        ret.extend(
+            module
+                .scripts
+                .iter()
+                .chain(&module.styles)
+                .chain(&module.datas)
+                .map(|page| format!("MediaWiki:Gadget-{}", page)),
+        );
+        ret
+    }
+}
+
+/// A category of gadgets, from a `formatversion=2` `list=gadgetcategories` result
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GadgetCategory {
+    /// Internal name of the category; empty for gadgets without category
+    pub name: String,
+    /// Display name
+    pub title: Option<String>,
+    /// Number of gadgets in the category
+    #[serde(default)]
+    pub members: u64,
+}
+
+impl Api {
+    /// Loads the gadgets of the wiki, including hidden ones; with `allowed_only`, only those the
+    /// user has the rights to use
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the Gadgets extension is not installed),
+    /// or any error from the request.
+    pub fn gadgets(&self, allowed_only: bool) -> Result<Vec<Gadget>, Box<dyn Error + Send + Sync>> {
+        let mut params = self.params_into(&[
+            ("action", "query"),
+            ("list", "gadgets"),
+            ("gaprop", "id|metadata|desc"),
+            ("formatversion", "2"),
+        ]);
+        if allowed_only {
+            params.insert("gaallowedonly".to_string(), "1".to_string());
+        }
+        let result = self.get_query_api_json(&params)?;
+        list_from_api_result(&result, "gadgets")
+    }
+
+    /// Loads the gadget categories of the wiki
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn gadget_categories(&self) -> Result<Vec<GadgetCategory>, Box<dyn Error + Send + Sync>> {
+        let params = self.params_into(&[
+            ("action", "query"),
+            ("list", "gadgetcategories"),
+            ("gcprop", "name|title|members"),
+            ("formatversion", "2"),
+        ]);
+        let result = self.get_query_api_json(&params)?;
+        list_from_api_result(&result, "gadgetcategories")
+    }
+}
+
+/// Parses the list `list` of a query result, or returns the API error
+fn list_from_api_result<T: DeserializeOwned>(
+    result: &Value,
+    list: &str,
+) -> Result<Vec<T>, Box<dyn Error + Send + Sync>> {
+    if let Some(error) = result["error"]["info"].as_str() {
+        return Err(From::from(format!("list={} failed: {}", list, error)));
+    }
+    match result["query"].get(list) {
+        Some(items) => Ok(Vec::<T>::deserialize(items)?),
+        None => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gadgets_from_api_result() {
+        let result = json!({"query": {"gadgets": [{
+            "id": "HotCat",
+            "metadata": {
+                "settings": {"rights": [], "skins": [], "actions": [], "namespaces": [],
+                "default": false,
+                             "hidden": false, "package": false, "shared": false,
+                             "category": "editing"},
+                "module": {"scripts": ["HotCat.js"], "styles": [], "datas": [],
+                           "dependencies": ["mediawiki.util"], "peers": [], "messages": []}
+            },
+            "desc": "<a href=\"/wiki/WP:HotCat\">HotCat</a>"
+        }]}});
+        let gadgets: Vec<Gadget> = list_from_api_result(&result, "gadgets").unwrap();
+        assert_eq!(gadgets[0].metadata.settings.category, "editing");
+        assert_eq!(
+            gadgets[0].metadata.module.dependencies,
+            vec!["mediawiki.util"]
+        );
+        assert_eq!(
+            gadgets[0].resource_pages(),
+            vec!["MediaWiki:Gadget-HotCat", "MediaWiki:Gadget-HotCat.js"]
+        );
+
+        let result = json!({"query": {"gadgetcategories": [{"name": "editing", "title": "Editing",
+            "members": 12}]}});
+        let categories: Vec<GadgetCategory> =
+            list_from_api_result(&result, "gadgetcategories").unwrap();
+        assert_eq!(categories[0].members, 12);
+
+        let result = json!({"error": {"code": "unknown_list",
+            "info": "Unrecognized value for parameter \"list\""}});
+        assert!(list_from_api_result::<Gadget>(&result, "gadgets").is_err());
+    }
+}
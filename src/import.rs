@@ -0,0 +1,270 @@
+/*!
+The `Import` class imports pages via `action=import`, either from an uploaded XML file (e.g. from
+`Api::export_pages`) or from another wiki (transwiki import). Imported pages can be moved to another
+namespace, or below a root page.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::params_map;
+use reqwest::blocking::multipart::Part;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// The result of importing a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedPage {
+    /// Title of the page on this wiki
+    pub title: String,
+    /// Namespace of the page on this wiki
+    pub namespace: Option<NamespaceID>,
+    /// Number of revisions that were imported; revisions that exist already are not counted
+    pub revisions: u64,
+    /// If the page could not be imported (e.g. because of an invalid title), the reason
+    pub invalid_reason: Option<String>,
+}
+
+impl ImportedPage {
+    /// Parses the `import` array of an `action=import` result (`formatversion=2`)
+    pub fn new_from_api_result(result: &Value) -> Option<Vec<ImportedPage>> {
+        let pages = result["import"].as_array()?;
+        Some(
+            pages
+                .iter()
+                .map(|page| ImportedPage {
+                    title: page["title"].as_str().unwrap_or_default().to_string(),
+                    namespace: page["ns"].as_i64(),
+                    revisions: page["revisions"].as_u64().unwrap_or_default(),
+                    invalid_reason: match page["invalid"].as_bool() {
+                        Some(true) => Some(
+                            page["invalidreason"]
+                                .as_str()
+                                .unwrap_or_default()
+                                .to_string(),
+                        ),
+                        _ => None,
+                    },
+                })
+                .collect(),
+        )
+    }
+
+    /// Checks if the page was imported
+    pub fn is_valid(&self) -> bool {
+        self.invalid_reason.is_none()
+    }
+}
+
+/// Imports pages with `action=import`. The user needs the `import` right for transwiki imports,
+/// and the `importupload` right for XML uploads.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Import {
+    summary: String,
+    namespace: Option<NamespaceID>,
+    root_page: Option<String>,
+    assign_known_users: bool,
+    tags: Vec<String>,
+    full_history: bool,
+    templates: bool,
+}
+
+impl Import {
+    /// Creates an import with default options
+    pub fn new() -> Import {
+        Import::default()
+    }
+
+    /// Sets the log summary of the import
+    pub fn set_summary(&mut self, summary: &str) {
+        self.summary = summary.to_string();
+    }
+
+    /// Imports all pages into a namespace
+    pub fn set_namespace(&mut self, namespace: Option<NamespaceID>) {
+        self.namespace = namespace;
+    }
+
+    /// Imports all pages as subpages of a page; can't be combined with `set_namespace`
+    pub fn set_root_page(&mut self, root_page: Option<String>) {
+        self.root_page = root_page;
+    }
+
+    /// Attributes edits to local users, if a user of the same name exists
+    pub fn set_assign_known_users(&mut self, assign_known_users: bool) {
+        self.assign_known_users = assign_known_users;
+    }
+
+    /// Sets change tags for the import log entry and the null revisions of the imported pages
+    pub fn set_tags(&mut self, tags: &[&str]) {
+        self.tags = tags.iter().map(|s| s.to_string()).collect();
+    }
+
+    /// Imports all revisions, instead of only the latest one (transwiki imports only)
+    pub fn set_full_history(&mut self, full_history: bool) {
+        self.full_history = full_history;
+    }
+
+    /// Also imports the templates used by the pages (transwiki imports only)
+    pub fn set_templates(&mut self, templates: bool) {
+        self.templates = templates;
+    }
+
+    /// Imports the pages of an XML file, in the format of `Special:Export`. Users in the file are
+    /// prefixed with `interwiki_prefix` (e.g. "en" for "en>Example"), unless known users are
+    /// assigned.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn import_xml(
+        &self,
+        api: &Api,
+        xml: Vec<u8>,
+        interwiki_prefix: &str,
+    ) -> Result<Vec<ImportedPage>, ImportError> {
+        let token = api.get_edit_token().map_err(ImportError::RequestError)?;
+        let mut params = self.params(&token);
+        params.extend(params_map!["interwikiprefix" => interwiki_prefix]);
+        let part = Part::bytes(xml).file_name("import.xml");
+        let result = api
+            .post_multipart_api_json(&params, "xml", part)
+            .map_err(ImportError::RequestError)?;
+        Import::parse_response(result)
+    }
+
+    /// Imports a page from another wiki, given as the interwiki prefix of the wiki (one of
+    /// `$wgImportSources`)
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn import_transwiki(
+        &self,
+        api: &Api,
+        source_wiki: &str,
+        page: &str,
+    ) -> Result<Vec<ImportedPage>, ImportError> {
+        let token = api.get_edit_token().map_err(ImportError::RequestError)?;
+        let mut params = self.params(&token);
+        params.extend(params_map![
+            "interwikisource" => source_wiki,
+            "interwikipage" => page,
+            "fullhistory" => self.full_history,
+            "templates" => self.templates,
+        ]);
+        let result = api
+            .post_query_api_json(&params)
+            .map_err(ImportError::RequestError)?;
+        Import::parse_response(result)
+    }
+
+    /// Returns the parameters shared by both kinds of import
+    fn params(&self, token: &str) -> HashMap<String, String> {
+        params_map![
+            "action" => "import",
+            "summary" => self.summary.as_str(),
+            "token" => token,
+            "formatversion" => "2",
+            "namespace" => self.namespace,
+            "rootpage" => self.root_page.as_deref(),
+            "assignknownusers" => self.assign_known_users,
+            "tags" => &self.tags,
+        ]
+    }
+
+    /// Interprets the result of an import
+    fn parse_response(result: Value) -> Result<Vec<ImportedPage>, ImportError> {
+        if let Some(error) = result.get("error") {
+            return Err(ImportError::ApiError {
+                code: error["code"].as_str().unwrap_or_default().to_string(),
+                info: error["info"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+        ImportedPage::new_from_api_result(&result).ok_or(ImportError::BadResponse(result))
+    }
+}
+
+/// Errors that can go wrong while importing pages
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ImportError {
+    /// The API returned an error, e.g. "cantimport" or "badinterwiki".
+    ApiError {
+        /// Error code
+        code: String,
+        /// Error message
+        info: String,
+    },
+
+    /// Couldn't understand the API response (provided).
+    BadResponse(Value),
+
+    /// Error while performing the API request.
+    RequestError(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::ApiError { code, info } => write!(f, "import failed: {} ({})", info, code),
+            ImportError::BadResponse(response) => {
+                write!(f, "bad API response while importing: {:?}", response)
+            }
+            ImportError::RequestError(error) => write!(f, "request error: {}", error),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses() {
+        let pages = Import::parse_response(json!({"import": [
+            {"ns": 0, "title": "Foo", "revisions": 3},
+            {"title": "Bad:<>", "invalid": true, "invalidreason": "Invalid title"}
+        ]}))
+        .unwrap();
+        assert_eq!(pages[0].title, "Foo");
+        assert_eq!((pages[0].namespace, pages[0].revisions), (Some(0), 3));
+        assert!(pages[0].is_valid());
+        assert_eq!(pages[1].invalid_reason.as_deref(), Some("Invalid title"));
+        assert!(matches!(
+            Import::parse_response(json!({"error": {"code": "cantimport",
+                "info": "No permission"}})),
+            Err(ImportError::ApiError { code, .. }) if code == "cantimport"
+        ));
+        assert!(matches!(
+            Import::parse_response(json!({})),
+            Err(ImportError::BadResponse(_))
+        ));
+    }
+
+    #[test]
+    fn params() {
+        let mut import = Import::new();
+        import.set_summary("Migration");
+        import.set_root_page(Some("Archive".to_string()));
+        import.set_tags(&["migration", "bot"]);
+        let params = import.params("TOKEN");
+        assert_eq!(params["action"], "import");
+        assert_eq!(params["rootpage"], "Archive");
+        assert_eq!(params["tags"], "migration|bot");
+        assert!(!params.contains_key("namespace"));
+        assert!(!params.contains_key("assignknownusers"));
+    }
+}
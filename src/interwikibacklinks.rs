@@ -0,0 +1,158 @@
+/*!
+Pages that link to a page on another wiki, from `list=iwbacklinks` (interwiki links such as
+`[[wikt:foo]]`) and `list=langbacklinks` (language links such as `[[de:Foo]]`), e.g. to clean up
+after a page was moved there.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::params_map;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A page with an interwiki or language link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterwikiBacklink {
+    /// ID of the linking page
+    pub pageid: u64,
+    /// Namespace of the linking page
+    pub ns: NamespaceID,
+    /// Full title of the linking page
+    pub title: String,
+    /// Interwiki prefix or language code of the link, e.g. "wikt" or "de"
+    pub prefix: String,
+    /// Title of the link target on the other wiki
+    pub target: String,
+}
+
+impl InterwikiBacklink {
+    /// Parses an entry of a `list=iwbacklinks` or `list=langbacklinks` result (`formatversion=2`)
+    pub fn new_from_api_result(link: &Value) -> Option<InterwikiBacklink> {
+        Some(InterwikiBacklink {
+            pageid: link["pageid"].as_u64()?,
+            ns: link["ns"].as_i64().unwrap_or_default(),
+            title: link["title"].as_str()?.to_string(),
+            prefix: link["iwprefix"]
+                .as_str()
+                .or_else(|| link["lllang"].as_str())?
+                .to_string(),
+            target: link["iwtitle"]
+                .as_str()
+                .or_else(|| link["lltitle"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+}
+
+impl Api {
+    /// Loads the pages that link to `title` on the wiki with the interwiki prefix `prefix`,
+    /// or to any page there if `title` is `None`
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the requests.
+    pub fn interwiki_backlinks(
+        &self,
+        prefix: &str,
+        title: Option<&str>,
+    ) -> Result<Vec<InterwikiBacklink>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "list" => "iwbacklinks",
+            "iwblprefix" => prefix,
+            "iwbltitle" => title,
+            "iwblprop" => "iwprefix|iwtitle",
+            "iwbllimit" => "max",
+            "formatversion" => "2",
+        ];
+        self.interwiki_backlinks_list(&params, "iwbacklinks")
+    }
+
+    /// Loads the pages with a language link to `title` on the wiki in language `lang`,
+    /// or to any page there if `title` is `None`
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the requests.
+    pub fn language_backlinks(
+        &self,
+        lang: &str,
+        title: Option<&str>,
+    ) -> Result<Vec<InterwikiBacklink>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "list" => "langbacklinks",
+            "lbllang" => lang,
+            "lbltitle" => title,
+            "lblprop" => "lllang|lltitle",
+            "lbllimit" => "max",
+            "formatversion" => "2",
+        ];
+        self.interwiki_backlinks_list(&params, "langbacklinks")
+    }
+
+    /// Loads all results of the list module `list`
+    fn interwiki_backlinks_list(
+        &self,
+        params: &HashMap<String, String>,
+        list: &str,
+    ) -> Result<Vec<InterwikiBacklink>, Box<dyn Error + Send + Sync>> {
+        let mut ret = vec![];
+        for result in self.get_query_api_json_limit_iter(params, None) {
+            let result = result?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!("list={} failed: {}", list, error)));
+            }
+            ret.extend(
+                result["query"][list]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(InterwikiBacklink::new_from_api_result),
+            );
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backlinks_from_api_result() {
+        let link = InterwikiBacklink::new_from_api_result(
+            &json!({"pageid": 12, "ns": 0, "title": "Foo", "iwprefix": "wikt", "iwtitle": "foo"}),
+        )
+        .unwrap();
+        assert_eq!(
+            (link.prefix.as_str(), link.target.as_str()),
+            ("wikt", "foo")
+        );
+        let link = InterwikiBacklink::new_from_api_result(
+            &json!({"pageid": 13, "ns": 14, "title": "Category:Bar", "lllang": "de",
+                "lltitle": "Kategorie:Bar"}),
+        )
+        .unwrap();
+        assert_eq!(link.ns, 14);
+        assert_eq!(
+            (link.prefix.as_str(), link.target.as_str()),
+            ("de", "Kategorie:Bar")
+        );
+        assert_eq!(
+            InterwikiBacklink::new_from_api_result(&json!({"pageid": 14, "title": "Baz"})),
+            None
+        );
+    }
+}
@@ -0,0 +1,338 @@
+/*!
+The `EditJournal` class records write operations in a file, so an interrupted batch run can be
+resumed without repeating or skipping edits. Each operation is keyed by the page (or entity) and the
+SHA-1 of its content. It is marked as pending before it is sent, and as done after it succeeded;
+each record is written to disk immediately. An operation that is pending but not done after a crash
+may or may not have been saved, and has to be resolved before it is retried.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::path::Path;
+
+/// The state of a write operation in an `EditJournal`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalState {
+    /// The operation was about to be sent; it may or may not have been saved
+    Pending,
+    /// The operation succeeded
+    Done,
+    /// The operation was not saved, and may be sent again
+    Discarded,
+}
+
+/// A record of a write operation in an `EditJournal`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct JournalEntry {
+    /// The page title or entity ID
+    pub page: String,
+    /// SHA-1 of the content that was written, in hexadecimal
+    pub hash: String,
+    /// The state of the operation
+    pub state: JournalState,
+    /// The ID of the revision that was saved, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revid: Option<u64>,
+}
+
+/// A journal of write operations, stored in a file with one JSON record per line
+#[derive(Debug)]
+pub struct EditJournal {
+    file: File,
+    entries: HashMap<(String, String), JournalEntry>,
+}
+
+impl EditJournal {
+    /// Opens a journal file, creating it if it does not exist. Truncated records
+    /// (e.g. after a crash while writing them) are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or created, or contains an invalid record.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<EditJournal, JournalError> {
+        let path = path.as_ref();
+        let mut entries = HashMap::new();
+        match File::open(path) {
+            Ok(file) => {
+                let lines = BufReader::new(file)
+                    .lines()
+                    .collect::<Result<Vec<_>, _>>()?;
+                for line in &lines {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str::<JournalEntry>(line) {
+                        Ok(entry) => {
+                            entries.insert((entry.page.clone(), entry.hash.clone()), entry);
+                        }
+                        Err(e) if e.is_eof() => {}
+                        Err(_) => return Err(JournalError::InvalidRecord(line.clone())),
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(JournalError::Io(e)),
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        // Start on a new line, in case the last record was truncated
+        if file.metadata()?.len() > 0 {
+            writeln!(file)?;
+        }
+        Ok(EditJournal { file, entries })
+    }
+
+    /// Returns the SHA-1 of some content, in hexadecimal
+    pub fn hash(content: &str) -> String {
+        Sha1::digest(content.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Returns the state of the operation that writes `content` to `page`, if it is in the journal
+    pub fn state(&self, page: &str, content: &str) -> Option<JournalState> {
+        self.entries
+            .get(&(page.to_string(), EditJournal::hash(content)))
+            .map(|entry| entry.state)
+    }
+
+    /// Checks if writing `content` to `page` has succeeded before
+    pub fn is_done(&self, page: &str, content: &str) -> bool {
+        self.state(page, content) == Some(JournalState::Done)
+    }
+
+    /// Returns the operations that were started but not finished, e.g. because the run was
+    /// interrupted. Each of them has to be checked on the wiki, and resolved with `mark_done` or
+    /// `discard`.
+    pub fn pending(&self) -> Vec<&JournalEntry> {
+        let mut pending: Vec<&JournalEntry> = self
+            .entries
+            .values()
+            .filter(|entry| entry.state == JournalState::Pending)
+            .collect();
+        pending.sort_by(|a, b| (&a.page, &a.hash).cmp(&(&b.page, &b.hash)));
+        pending
+    }
+
+    /// Records that writing `content` to `page` is about to be sent
+    ///
+    /// # Errors
+    /// Returns an error if the record can't be written to disk.
+    pub fn mark_pending(&mut self, page: &str, content: &str) -> Result<(), JournalError> {
+        self.record(
+            page,
+            EditJournal::hash(content),
+            JournalState::Pending,
+            None,
+        )
+    }
+
+    /// Records that writing `content` to `page` succeeded, optionally with the new revision ID
+    ///
+    /// # Errors
+    /// Returns an error if the record can't be written to disk.
+    pub fn mark_done(
+        &mut self,
+        page: &str,
+        content: &str,
+        revid: Option<u64>,
+    ) -> Result<(), JournalError> {
+        self.record(page, EditJournal::hash(content), JournalState::Done, revid)
+    }
+
+    /// Records that a pending operation was not saved, so that it is sent again
+    ///
+    /// # Errors
+    /// Returns an error if the record can't be written to disk.
+    pub fn discard(&mut self, page: &str, content: &str) -> Result<(), JournalError> {
+        self.record(
+            page,
+            EditJournal::hash(content),
+            JournalState::Discarded,
+            None,
+        )
+    }
+
+    /// Performs a write operation exactly once: `write` is called unless writing `content` to
+    /// `page` is done, and the operation is marked as pending before and as done after it. `write`
+    /// returns the new revision ID, if known. Returns whether `write` was called.
+    ///
+    /// # Errors
+    /// Returns `JournalError::InDoubt` if a previous attempt was interrupted (see `pending`),
+    /// `JournalError::Write` if `write` failed, or an error if the journal can't be written.
+    /// A failed operation is discarded, unless the request itself failed; then it stays pending.
+    pub fn perform<F>(&mut self, page: &str, content: &str, write: F) -> Result<bool, JournalError>
+    where
+        F: FnOnce() -> Result<Option<u64>, Box<dyn Error + Send + Sync>>,
+    {
+        match self.state(page, content) {
+            Some(JournalState::Done) => return Ok(false),
+            Some(JournalState::Pending) => {
+                return Err(JournalError::InDoubt {
+                    page: page.to_string(),
+                    hash: EditJournal::hash(content),
+                })
+            }
+            Some(JournalState::Discarded) | None => {}
+        }
+        self.mark_pending(page, content)?;
+        match write() {
+            Ok(revid) => {
+                self.mark_done(page, content, revid)?;
+                Ok(true)
+            }
+            // The request may have been saved even though no response arrived
+            Err(e) if e.downcast_ref::<reqwest::Error>().is_some() => Err(JournalError::Write(e)),
+            Err(e) => {
+                self.discard(page, content)?;
+                Err(JournalError::Write(e))
+            }
+        }
+    }
+
+    /// Stores an entry, and appends it to the file
+    fn record(
+        &mut self,
+        page: &str,
+        hash: String,
+        state: JournalState,
+        revid: Option<u64>,
+    ) -> Result<(), JournalError> {
+        let entry = JournalEntry {
+            page: page.to_string(),
+            hash,
+            state,
+            revid,
+        };
+        self.write_line(&serde_json::to_string(&entry)?)?;
+        self.entries
+            .insert((entry.page.clone(), entry.hash.clone()), entry);
+        Ok(())
+    }
+
+    /// Appends a line to the file, and waits until it is on disk
+    fn write_line(&mut self, line: &str) -> Result<(), JournalError> {
+        writeln!(self.file, "{}", line)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Errors that can go wrong while using an `EditJournal`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JournalError {
+    /// The journal file can't be read or written
+    Io(io::Error),
+
+    /// A record in the journal file (provided) can't be parsed
+    InvalidRecord(String),
+
+    /// An earlier attempt of the operation was interrupted, and it is unknown whether it was saved
+    InDoubt {
+        /// The page title or entity ID
+        page: String,
+        /// SHA-1 of the content, in hexadecimal
+        hash: String,
+    },
+
+    /// The write operation failed
+    Write(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::Io(error) => write!(f, "error accessing journal: {}", error),
+            JournalError::InvalidRecord(line) => write!(f, "invalid journal record: {}", line),
+            JournalError::InDoubt { page, hash } => write!(
+                f,
+                "an earlier write of {} to {} was interrupted; check the page and resolve it",
+                hash, page
+            ),
+            JournalError::Write(error) => write!(f, "write failed: {}", error),
+        }
+    }
+}
+
+impl Error for JournalError {}
+
+impl From<io::Error> for JournalError {
+    fn from(error: io::Error) -> Self {
+        JournalError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for JournalError {
+    fn from(error: serde_json::Error) -> Self {
+        JournalError::Io(error.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume() {
+        let path =
+            std::env::temp_dir().join(format!("mediawiki-edit-journal-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut journal = EditJournal::open(&path).unwrap();
+        assert!(journal.perform("Q42", "{}", || Ok(Some(5))).unwrap());
+        assert!(!journal
+            .perform("Q42", "{}", || panic!("done operations are skipped"))
+            .unwrap());
+        assert!(journal
+            .perform("Q1", "x", || Err(From::from("failed")))
+            .is_err());
+        journal.mark_pending("Q2", "y").unwrap();
+        drop(journal);
+        // A record that was cut off by a crash
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"page\":")
+            .unwrap();
+
+        let mut journal = EditJournal::open(&path).unwrap();
+        assert!(journal.is_done("Q42", "{}"));
+        assert_eq!(journal.state("Q1", "x"), Some(JournalState::Discarded));
+        let pending: Vec<&str> = journal
+            .pending()
+            .iter()
+            .map(|entry| entry.page.as_str())
+            .collect();
+        assert_eq!(pending, vec!["Q2"]);
+        assert!(matches!(
+            journal.perform("Q2", "y", || Ok(None)),
+            Err(JournalError::InDoubt { .. })
+        ));
+        journal.mark_done("Q2", "y", None).unwrap();
+        journal.mark_pending("Q3", "z").unwrap();
+        drop(journal);
+
+        let journal = EditJournal::open(&path).unwrap();
+        assert!(journal.is_done("Q2", "y"));
+        assert_eq!(journal.pending().len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
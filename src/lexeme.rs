@@ -0,0 +1,349 @@
+/*!
+The `Lexeme` class gives typed access to Wikibase lexemes (as used on Wikidata), with their forms
+and senses, and wraps the WikibaseLexeme API modules to edit them.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use crate::wikibase::{map_or_empty_array, Statement, Term, WikibaseError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A form of a lexeme, e.g. a plural
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Form {
+    /// Form ID, e.g. "L7-F1"
+    pub id: String,
+    /// Representations, keyed by spelling variant (language code)
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub representations: HashMap<String, Term>,
+    /// Item IDs of the grammatical features, e.g. "Q146786" (plural)
+    #[serde(rename = "grammaticalFeatures", default)]
+    pub grammatical_features: Vec<String>,
+    /// Statements, keyed by property ID
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub claims: HashMap<String, Vec<Statement>>,
+}
+
+impl Form {
+    /// Returns the representation in a spelling variant
+    pub fn representation(&self, variant: &str) -> Option<&str> {
+        self.representations.get(variant).map(|t| t.value.as_str())
+    }
+}
+
+/// A sense of a lexeme
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Sense {
+    /// Sense ID, e.g. "L7-S1"
+    pub id: String,
+    /// Glosses, keyed by language
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub glosses: HashMap<String, Term>,
+    /// Statements, keyed by property ID
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub claims: HashMap<String, Vec<Statement>>,
+}
+
+impl Sense {
+    /// Returns the gloss in a language
+    pub fn gloss(&self, language: &str) -> Option<&str> {
+        self.glosses.get(language).map(|t| t.value.as_str())
+    }
+}
+
+/// A lexeme, from `wbgetentities`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Lexeme {
+    /// Lexeme ID, e.g. "L7"
+    pub id: String,
+    /// Last revision ID of the lexeme
+    pub lastrevid: Option<u64>,
+    /// Lemmas, keyed by spelling variant (language code)
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub lemmas: HashMap<String, Term>,
+    /// Item ID of the lexical category, e.g. "Q1084" (noun)
+    #[serde(rename = "lexicalCategory")]
+    pub lexical_category: String,
+    /// Item ID of the language, e.g. "Q1860" (English)
+    pub language: String,
+    /// Statements, keyed by property ID
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub claims: HashMap<String, Vec<Statement>>,
+    /// Forms
+    #[serde(default)]
+    pub forms: Vec<Form>,
+    /// Senses
+    #[serde(default)]
+    pub senses: Vec<Sense>,
+}
+
+impl Lexeme {
+    /// Loads a lexeme by ID (e.g. "L7")
+    ///
+    /// # Errors
+    /// Returns `WikibaseError::Missing` if the lexeme does not exist, `WikibaseError::BadResponse`
+    /// if it can't be parsed, or any error from the API or the request.
+    pub fn load(api: &Api, id: &str) -> Result<Lexeme, WikibaseError> {
+        let params = params_map!["action" => "wbgetentities", "ids" => id];
+        let result = api
+            .get_query_api_json(&params)
+            .map_err(WikibaseError::RequestError)?;
+        Lexeme::parse_api_result(&result, id)
+    }
+
+    /// Parses a lexeme from a `wbgetentities` result
+    pub fn new_from_api_result(result: &Value, id: &str) -> Option<Lexeme> {
+        Lexeme::parse_api_result(result, id).ok()
+    }
+
+    /// Parses a lexeme from a `wbgetentities` result, telling a missing lexeme apart from a
+    /// result that can't be parsed
+    fn parse_api_result(result: &Value, id: &str) -> Result<Lexeme, WikibaseError> {
+        if let Some(error) = WikibaseError::new_from_api_result(result) {
+            return Err(error);
+        }
+        let entity = result["entities"]
+            .get(id)
+            .ok_or_else(|| WikibaseError::BadResponse(result.clone()))?;
+        if entity.get("missing").is_some() {
+            return Err(WikibaseError::Missing(id.to_string()));
+        }
+        Lexeme::deserialize(entity).map_err(|_| WikibaseError::BadResponse(result.clone()))
+    }
+
+    /// Returns the lemma in a spelling variant
+    pub fn lemma(&self, variant: &str) -> Option<&str> {
+        self.lemmas.get(variant).map(|t| t.value.as_str())
+    }
+
+    /// Returns a form by ID
+    pub fn form(&self, id: &str) -> Option<&Form> {
+        self.forms.iter().find(|form| form.id == id)
+    }
+
+    /// Returns a sense by ID
+    pub fn sense(&self, id: &str) -> Option<&Sense> {
+        self.senses.iter().find(|sense| sense.id == id)
+    }
+}
+
+impl Api {
+    /// Adds a form to a lexeme, with `wbladdform`, and returns it
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbladdform(
+        &self,
+        lexeme_id: &str,
+        representations: &[Term],
+        grammatical_features: &[&str],
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Form, WikibaseError> {
+        let params = params_map![
+            "lexemeId" => lexeme_id,
+            "data" => form_data(representations, grammatical_features).to_string(),
+        ];
+        let result = self.wikibase_edit("wbladdform", params, summary, baserevid)?;
+        lexeme_result(&result, "form")
+    }
+
+    /// Replaces the representations and grammatical features of a form, with `wbleditformelements`,
+    /// and returns the changed form
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbleditformelements(
+        &self,
+        form_id: &str,
+        representations: &[Term],
+        grammatical_features: &[&str],
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Form, WikibaseError> {
+        let params = params_map![
+            "formId" => form_id,
+            "data" => form_data(representations, grammatical_features).to_string(),
+        ];
+        let result = self.wikibase_edit("wbleditformelements", params, summary, baserevid)?;
+        lexeme_result(&result, "form")
+    }
+
+    /// Removes a form, with `wblremoveform`
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wblremoveform(
+        &self,
+        form_id: &str,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<(), WikibaseError> {
+        let params = params_map!["id" => form_id];
+        self.wikibase_edit("wblremoveform", params, summary, baserevid)?;
+        Ok(())
+    }
+
+    /// Adds a sense to a lexeme, with `wbladdsense`, and returns it
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbladdsense(
+        &self,
+        lexeme_id: &str,
+        glosses: &[Term],
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Sense, WikibaseError> {
+        let params = params_map![
+            "lexemeId" => lexeme_id,
+            "data" => sense_data(glosses).to_string(),
+        ];
+        let result = self.wikibase_edit("wbladdsense", params, summary, baserevid)?;
+        lexeme_result(&result, "sense")
+    }
+
+    /// Sets glosses of a sense, with `wbleditsenseelements`, and returns the changed sense.
+    /// Glosses in other languages are kept.
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbleditsenseelements(
+        &self,
+        sense_id: &str,
+        glosses: &[Term],
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Sense, WikibaseError> {
+        let params = params_map![
+            "senseId" => sense_id,
+            "data" => sense_data(glosses).to_string(),
+        ];
+        let result = self.wikibase_edit("wbleditsenseelements", params, summary, baserevid)?;
+        lexeme_result(&result, "sense")
+    }
+
+    /// Removes a sense, with `wblremovesense`
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wblremovesense(
+        &self,
+        sense_id: &str,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<(), WikibaseError> {
+        let params = params_map!["id" => sense_id];
+        self.wikibase_edit("wblremovesense", params, summary, baserevid)?;
+        Ok(())
+    }
+}
+
+/// Returns the `data` parameter for adding or editing a form
+fn form_data(representations: &[Term], grammatical_features: &[&str]) -> Value {
+    json!({
+        "representations": terms_by_language(representations),
+        "grammaticalFeatures": grammatical_features,
+    })
+}
+
+/// Returns the `data` parameter for adding or editing a sense
+fn sense_data(glosses: &[Term]) -> Value {
+    json!({ "glosses": terms_by_language(glosses) })
+}
+
+/// Returns terms as a JSON object keyed by language
+fn terms_by_language(terms: &[Term]) -> Value {
+    Value::Object(
+        terms
+            .iter()
+            .map(|term| (term.language.to_string(), json!(term)))
+            .collect(),
+    )
+}
+
+/// Deserializes the form or sense of the result of a lexeme edit
+fn lexeme_result<T: serde::de::DeserializeOwned>(
+    result: &Value,
+    key: &str,
+) -> Result<T, WikibaseError> {
+    T::deserialize(&result[key]).map_err(|_| WikibaseError::BadResponse(result.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lexeme() {
+        let result = json!({"entities": {"L7": {
+            "pageid": 54387043, "ns": 146, "title": "Lexeme:L7", "lastrevid": 1234,
+            "type": "lexeme", "id": "L7",
+            "lemmas": {"en": {"language": "en", "value": "cat"}},
+            "lexicalCategory": "Q1084", "language": "Q1860", "claims": [],
+            "forms": [{"id": "L7-F1", "representations": {"en": {"language": "en",
+            "value": "cats"}},
+                "grammaticalFeatures": ["Q146786"], "claims": []}],
+            "senses": [{"id": "L7-S1", "glosses": {"en": {"language": "en",
+            "value": "domesticated animal"}},
+                "claims": {"P5137": [{"mainsnak": {"snaktype": "value", "property": "P5137",
+                    "datavalue": {"value": {"entity-type": "item", "numeric-id": 146, "id": "Q146"},
+                    "type": "wikibase-entityid"}},
+                    "type": "statement", "id": "L7-S1$abc", "rank": "normal"}]}}]
+        }}, "success": 1});
+        let lexeme = Lexeme::new_from_api_result(&result, "L7").unwrap();
+        assert_eq!(lexeme.lemma("en"), Some("cat"));
+        assert_eq!(lexeme.lexical_category, "Q1084");
+        assert_eq!(
+            lexeme.form("L7-F1").unwrap().representation("en"),
+            Some("cats")
+        );
+        assert_eq!(lexeme.forms[0].grammatical_features, vec!["Q146786"]);
+        let sense = lexeme.sense("L7-S1").unwrap();
+        assert_eq!(sense.gloss("en"), Some("domesticated animal"));
+        assert_eq!(sense.claims["P5137"].len(), 1);
+        assert!(matches!(
+            Lexeme::parse_api_result(
+                &json!({"entities": {"L0": {"id": "L0", "missing": ""}}}),
+                "L0"
+            ),
+            Err(WikibaseError::Missing(id)) if id == "L0"
+        ));
+        assert!(matches!(
+            Lexeme::parse_api_result(&json!({"entities": {"L7": {"id": "L7"}}}), "L7"),
+            Err(WikibaseError::BadResponse(_))
+        ));
+    }
+
+    #[test]
+    fn edit_data() {
+        assert_eq!(
+            form_data(&[Term::new("en", "cats")], &["Q146786"]),
+            json!({"representations": {"en": {"language": "en", "value": "cats"}},
+                "grammaticalFeatures": ["Q146786"]})
+        );
+        assert_eq!(
+            sense_data(&[Term::new("de", "Hauskatze")]),
+            json!({"glosses": {"de": {"language": "de", "value": "Hauskatze"}}})
+        );
+        let result = json!({"lastrevid": 5, "success": 1,
+            "sense": {"id": "L7-S2", "glosses": {"de": {"language": "de", "value": "Hauskatze"}},
+            "claims": []}});
+        let sense: Sense = lexeme_result(&result, "sense").unwrap();
+        assert_eq!(sense.id, "L7-S2");
+    }
+}
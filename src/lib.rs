@@ -5,10 +5,66 @@ extern crate serde_json;
 
 pub use reqwest;
 
+pub mod abusefilter;
+pub mod action;
+pub mod alllinks;
 pub mod api;
+pub mod audit;
+pub mod batch;
+pub mod bot;
+pub mod category;
+pub mod changetags;
+pub mod checkuser;
+pub mod connection;
+pub mod contribs;
+pub mod core_rest;
+pub mod datavalue;
+pub mod delay;
+pub mod deletedrevisions;
+pub mod discussiontools;
+pub mod dump;
+pub mod echo;
+#[cfg(feature = "eventstreams")]
+pub mod eventstreams;
+pub mod feed;
+pub mod file;
+pub mod filearchive;
+pub mod flow;
+pub mod gadgets;
+pub mod import;
+pub mod interwikibacklinks;
+pub mod journal;
+pub mod lexeme;
+pub mod liftwing;
+pub mod linter;
+pub mod maxlag;
+pub mod mediainfo;
+pub mod namespace;
+pub mod newcontent;
 pub mod page;
+pub mod pagegenerator;
+pub mod pageprops;
+pub mod pagetriage;
+pub mod pageviews;
+pub mod paraminfo;
+pub mod parsoid;
+pub mod preferences;
+pub mod proofreadpage;
+pub mod recentchanges;
+pub mod rest;
+pub mod revision;
+pub mod site_info;
+pub mod sitematrix;
+pub mod sparql;
+pub mod timestamp;
 pub mod title;
+pub mod traits;
+pub mod translate;
+pub mod upload;
+pub mod urlshortener;
 pub mod user;
+pub mod watchlist;
+pub mod wikibase;
 
 lazy_static! {
     static ref JUSTIFY_LAZY_STATIC_MACRO_USE: u8 = 0;
@@ -0,0 +1,229 @@
+/*!
+Revision scores from [Lift Wing](https://wikitech.wikimedia.org/wiki/Machine_Learning/LiftWing), the
+successor of ORES: e.g. the probability that an edit is damaging or made in bad faith, or the
+quality class of an article. Patrol bots can use them to prioritize recent changes.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::thread;
+
+/// The base URL of the Lift Wing models on the Wikimedia API gateway
+pub const LIFT_WING_MODELS: &str = "https://api.wikimedia.org/service/lw/inference/v1/models/";
+
+/// The default number of concurrent requests in `Api::lift_wing_scores`
+pub const DEFAULT_LIFT_WING_CONCURRENCY: usize = 4;
+
+/// A Lift Wing model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ScoreModel {
+    /// Whether an edit is damaging (ORES model)
+    Damaging,
+    /// Whether an edit was made in good faith (ORES model)
+    Goodfaith,
+    /// The quality class of an article revision, e.g. "FA" or "Stub" (ORES model)
+    ArticleQuality,
+    /// Whether a new article is spam, vandalism or an attack (ORES model)
+    DraftQuality,
+    /// Whether an edit will be reverted, on any wiki
+    RevertRiskLanguageAgnostic,
+}
+
+impl ScoreModel {
+    /// Returns the name of the model, as in ORES results
+    pub fn as_str(&self) -> &str {
+        match self {
+            ScoreModel::Damaging => "damaging",
+            ScoreModel::Goodfaith => "goodfaith",
+            ScoreModel::ArticleQuality => "articlequality",
+            ScoreModel::DraftQuality => "draftquality",
+            ScoreModel::RevertRiskLanguageAgnostic => "revertrisk-language-agnostic",
+        }
+    }
+
+    /// Returns the URL of the model for a wiki (database name, e.g. "enwiki")
+    pub fn url(&self, wiki: &str) -> String {
+        match self {
+            ScoreModel::RevertRiskLanguageAgnostic => {
+                format!("{}{}:predict", LIFT_WING_MODELS, self.as_str())
+            }
+            _ => format!("{}{}-{}:predict", LIFT_WING_MODELS, wiki, self.as_str()),
+        }
+    }
+}
+
+/// The score of a revision by a model
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevisionScore {
+    /// Revision ID
+    pub revid: u64,
+    /// The predicted class, e.g. `true` or "Stub"
+    pub prediction: Value,
+    /// The probabilities of the classes, keyed by class (e.g. "true" and "false")
+    pub probabilities: HashMap<String, f64>,
+}
+
+impl RevisionScore {
+    /// Parses the result of a Lift Wing model, in ORES or in revert risk format
+    pub fn new_from_api_result(
+        result: &Value,
+        model: ScoreModel,
+        revid: u64,
+    ) -> Option<RevisionScore> {
+        let score = match result.get("output") {
+            Some(output) => output,
+            None => {
+                let (_wiki, scores) = result.as_object()?.iter().next()?;
+                &scores["scores"][revid.to_string()][model.as_str()]["score"]
+            }
+        };
+        let probabilities = score
+            .get("probability")
+            .or_else(|| score.get("probabilities"))?
+            .as_object()?
+            .iter()
+            .filter_map(|(class, p)| Some((class.to_string(), p.as_f64()?)))
+            .collect();
+        Some(RevisionScore {
+            revid,
+            prediction: score["prediction"].clone(),
+            probabilities,
+        })
+    }
+
+    /// Returns the probability of a class
+    pub fn probability(&self, class: &str) -> Option<f64> {
+        self.probabilities.get(class).copied()
+    }
+
+    /// Returns the probability of "true", for binary models such as `Damaging`
+    pub fn true_probability(&self) -> Option<f64> {
+        self.probability("true")
+    }
+}
+
+impl Api {
+    /// Scores a revision of this wiki with a Lift Wing model
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the model is not available for the wiki, or the revision does not
+    /// exist, or any error from the request.
+    pub fn lift_wing_score(
+        &self,
+        model: ScoreModel,
+        revid: u64,
+    ) -> Result<RevisionScore, Box<dyn Error + Send + Sync>> {
+        let general = &self.get_site_info().general;
+        let wiki = general.wikiid.clone().unwrap_or_default();
+        let url = model.url(&wiki);
+        let mut body = json!({ "rev_id": revid });
+        if model == ScoreModel::RevertRiskLanguageAgnostic {
+            body["lang"] = json!(general.lang);
+        }
+        let response = self.rest_send(|| {
+            Ok(self
+                .client()
+                .post(&url)
+                .header(reqwest::header::USER_AGENT, self.user_agent_full())
+                .json(&body))
+        })?;
+        let result: Value = response.json()?;
+        RevisionScore::new_from_api_result(&result, model, revid)
+            .ok_or_else(|| From::from(format!("Unexpected Lift Wing result: {}", result)))
+    }
+
+    /// Scores revisions of this wiki with a Lift Wing model, sending up to `concurrency` requests
+    /// at a time (`DEFAULT_LIFT_WING_CONCURRENCY` is a sensible value). Returns the result for
+    /// every revision, keyed by revision ID.
+    pub fn lift_wing_scores(
+        &self,
+        model: ScoreModel,
+        revids: &[u64],
+        concurrency: usize,
+    ) -> HashMap<u64, Result<RevisionScore, Box<dyn Error + Send + Sync>>> {
+        let mut ret = HashMap::new();
+        for batch in revids.chunks(concurrency.max(1)) {
+            thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .iter()
+                    .map(|&revid| {
+                        (
+                            revid,
+                            scope.spawn(move || self.lift_wing_score(model, revid)),
+                        )
+                    })
+                    .collect();
+                ret.extend(handles.into_iter().map(|(revid, handle)| {
+                    let result = handle
+                        .join()
+                        .unwrap_or_else(|_| Err(From::from("thread panicked")));
+                    (revid, result)
+                }));
+            });
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ores_scores() {
+        let result = json!({"enwiki": {
+            "models": {"damaging": {"version": "0.5.1"}},
+            "scores": {"12345": {"damaging": {"score": {
+                "prediction": false,
+                "probability": {"false": 0.9, "true": 0.1}
+            }}}}
+        }});
+        let score =
+            RevisionScore::new_from_api_result(&result, ScoreModel::Damaging, 12345).unwrap();
+        assert_eq!(score.prediction, json!(false));
+        assert_eq!(score.true_probability(), Some(0.1));
+        assert!(
+            RevisionScore::new_from_api_result(&result, ScoreModel::Goodfaith, 12345).is_none()
+        );
+        assert_eq!(
+            ScoreModel::ArticleQuality.url("enwiki"),
+            "https://api.wikimedia.org/service/lw/inference/v1/models/enwiki-articlequality:predict"
+        );
+    }
+
+    #[test]
+    fn revert_risk_scores() {
+        let result = json!({
+            "model_name": "revertrisk-language-agnostic",
+            "model_version": "3",
+            "wiki_db": "enwiki",
+            "revision_id": 12345,
+            "output": {"prediction": true, "probabilities": {"true": 0.8, "false": 0.2}}
+        });
+        let score = RevisionScore::new_from_api_result(
+            &result,
+            ScoreModel::RevertRiskLanguageAgnostic,
+            12345,
+        )
+        .unwrap();
+        assert_eq!(score.probability("false"), Some(0.2));
+        assert!(ScoreModel::RevertRiskLanguageAgnostic
+            .url("enwiki")
+            .ends_with("/revertrisk-language-agnostic:predict"));
+    }
+}
@@ -0,0 +1,151 @@
+/*!
+Lint errors found by the [Linter](https://www.mediawiki.org/wiki/Extension:Linter) extension, from
+`list=linterrors`: e.g. obsolete HTML tags or misnested markup, with their position in the wikitext
+and the template that causes them, if any.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use serde_json::Value;
+use std::error::Error;
+
+/// A lint error on a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintError {
+    /// ID of the lint error
+    pub lint_id: u64,
+    /// Category, e.g. "obsolete-tag" or "misnested-tag"
+    pub category: String,
+    /// ID of the page
+    pub page_id: u64,
+    /// Namespace of the page
+    pub namespace: NamespaceID,
+    /// Full title of the page
+    pub title: String,
+    /// Start and end of the error in the wikitext, as byte offsets
+    pub location: Option<(u64, u64)>,
+    /// The template that causes the error, if any
+    pub template: Option<String>,
+    /// Whether the error is caused by several templates together (e.g. a table start and end
+    /// template)
+    pub multi_part_template: bool,
+    /// Details, depending on the category (e.g. the tag name)
+    pub params: Value,
+}
+
+impl LintError {
+    /// Parses an entry of a `list=linterrors` result
+    pub fn new_from_api_result(lint: &Value) -> Option<LintError> {
+        let location = match lint["location"].as_array().map(|a| a.as_slice()) {
+            Some([start, end]) => Some((start.as_u64()?, end.as_u64()?)),
+            _ => None,
+        };
+        Some(LintError {
+            lint_id: lint["lintId"].as_u64()?,
+            category: lint["category"].as_str()?.to_string(),
+            page_id: lint["pageid"].as_u64().unwrap_or_default(),
+            namespace: lint["ns"].as_i64().unwrap_or_default(),
+            title: lint["title"].as_str()?.to_string(),
+            location,
+            template: lint["templateInfo"]["name"].as_str().map(|s| s.to_string()),
+            multi_part_template: lint["templateInfo"]["multiPartTemplateBlock"]
+                .as_bool()
+                .unwrap_or(false),
+            params: lint["params"].clone(),
+        })
+    }
+
+    /// Checks if the error comes from a template, rather than from the page itself
+    pub fn is_template_affected(&self) -> bool {
+        self.template.is_some() || self.multi_part_template
+    }
+}
+
+impl Api {
+    /// Loads lint errors in the `categories` (all if empty) and `namespaces` (all if empty), up to
+    /// `max` errors. If `template_affected` is set, only errors that do (or do not) come from
+    /// templates are returned; this filter is applied after loading, so fewer than `max` errors may
+    /// be returned.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the Linter extension is not installed),
+    /// or any error from the request.
+    pub fn lint_errors(
+        &self,
+        categories: &[&str],
+        namespaces: &[NamespaceID],
+        template_affected: Option<bool>,
+        max: Option<usize>,
+    ) -> Result<Vec<LintError>, Box<dyn Error + Send + Sync>> {
+        let namespaces: Vec<String> = namespaces.iter().map(|ns| ns.to_string()).collect();
+        let mut params = self.params_into(&[
+            ("action", "query"),
+            ("list", "linterrors"),
+            ("lntlimit", "max"),
+            ("formatversion", "2"),
+        ]);
+        if !categories.is_empty() {
+            params.insert("lntcategories".to_string(), categories.join("|"));
+        }
+        if !namespaces.is_empty() {
+            params.insert("lntnamespace".to_string(), namespaces.join("|"));
+        }
+        let result = self.get_query_api_json_limit(&params, max)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("list=linterrors failed: {}", error)));
+        }
+        Ok(lint_errors_from_api_result(&result, template_affected))
+    }
+}
+
+/// Parses the lint errors of a `list=linterrors` result, filtered by template involvement
+fn lint_errors_from_api_result(result: &Value, template_affected: Option<bool>) -> Vec<LintError> {
+    result["query"]["linterrors"]
+        .as_array()
+        .map(|lints| {
+            lints
+                .iter()
+                .filter_map(LintError::new_from_api_result)
+                .filter(|lint| template_affected.is_none_or(|t| lint.is_template_affected() == t))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lint_errors() {
+        let result = json!({"query": {"linterrors": [
+            {"pageid": 1, "ns": 0, "title": "Foo", "lintId": 10, "category": "obsolete-tag",
+             "location": [5, 22], "templateInfo": [], "params": {"name": "center"}},
+            {"pageid": 2, "ns": 0, "title": "Bar", "lintId": 11, "category": "misnested-tag",
+             "location": [0, 8], "templateInfo": {"name": "Template:Box"}, "params": {"name": "b"}},
+            {"pageid": 3, "ns": 0, "title": "Baz", "lintId": 12, "category": "fostered",
+             "location": [3, 4], "templateInfo": {"multiPartTemplateBlock": true}, "params": {}}
+        ]}});
+        let all = lint_errors_from_api_result(&result, None);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].location, Some((5, 22)));
+        assert_eq!(all[0].params["name"], "center");
+        assert!(!all[0].is_template_affected());
+        assert_eq!(all[1].template.as_deref(), Some("Template:Box"));
+        let templates = lint_errors_from_api_result(&result, Some(true));
+        let ids: Vec<u64> = templates.iter().map(|lint| lint.lint_id).collect();
+        assert_eq!(ids, vec![11, 12]);
+        assert_eq!(lint_errors_from_api_result(&result, Some(false)).len(), 1);
+    }
+}
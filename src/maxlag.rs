@@ -0,0 +1,190 @@
+/*!
+Strategies for retrying queries that failed because the database replication lag is above `maxlag`.
+A strategy set with `Api::set_maxlag_strategy` decides, for every failed attempt, how long to wait
+before the next one, or whether to give up. Any `Fn(u64, u64, Duration) -> Option<Duration>` is a
+strategy, too.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Decides how to handle a `maxlag` error
+pub trait MaxlagStrategy: Send + Sync {
+    /// Returns how long to wait before the next attempt, or `None` to give up. `lag` is the current
+    /// lag in seconds, `attempt` the number of failed attempts so far (starting at 1), and `waited`
+    /// the time waited so far.
+    fn wait(&self, lag: u64, attempt: u64, waited: Duration) -> Option<Duration>;
+}
+
+impl<F: Fn(u64, u64, Duration) -> Option<Duration> + Send + Sync> MaxlagStrategy for F {
+    fn wait(&self, lag: u64, attempt: u64, waited: Duration) -> Option<Duration> {
+        self(lag, attempt, waited)
+    }
+}
+
+/// Waits as long as the reported lag, up to `max_attempts` times. This is the default behavior,
+/// with `Api::max_retry_attempts` attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitForLag {
+    /// Maximum number of retries
+    pub max_attempts: u64,
+}
+
+impl MaxlagStrategy for WaitForLag {
+    fn wait(&self, lag: u64, attempt: u64, _waited: Duration) -> Option<Duration> {
+        Some(Duration::from_secs(lag)).filter(|_| attempt <= self.max_attempts)
+    }
+}
+
+/// Waits a fixed time, up to `max_attempts` times
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedDelay {
+    /// Time to wait before every retry
+    pub delay: Duration,
+    /// Maximum number of retries
+    pub max_attempts: u64,
+}
+
+impl MaxlagStrategy for FixedDelay {
+    fn wait(&self, _lag: u64, attempt: u64, _waited: Duration) -> Option<Duration> {
+        Some(self.delay).filter(|_| attempt <= self.max_attempts)
+    }
+}
+
+/// Doubles the wait after every attempt, starting at `initial` and capped at `max_delay`,
+/// until `max_total` was waited in total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+    /// Time to wait before the first retry
+    pub initial: Duration,
+    /// Longest time to wait before a single retry
+    pub max_delay: Duration,
+    /// Longest time to wait in total
+    pub max_total: Duration,
+}
+
+impl MaxlagStrategy for ExponentialBackoff {
+    fn wait(&self, _lag: u64, attempt: u64, waited: Duration) -> Option<Duration> {
+        if waited >= self.max_total {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1).min(u64::from(u32::MAX)) as u32);
+        let delay = self.initial.saturating_mul(factor).min(self.max_delay);
+        Some(delay.min(self.max_total - waited))
+    }
+}
+
+/// Defers work to an off-peak window, given as UTC hours `[start_hour, end_hour)` (which may wrap
+/// around midnight): within the window, waits as long as the reported lag; outside of it, waits
+/// until the window begins. Never gives up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffPeak {
+    /// First hour of the window (0-23, UTC)
+    pub start_hour: u32,
+    /// First hour after the window (0-23, UTC)
+    pub end_hour: u32,
+}
+
+impl OffPeak {
+    /// Returns how long to wait from `seconds_of_day` (UTC) until the window begins; zero within
+    /// the window
+    fn wait_from(&self, seconds_of_day: u64) -> Duration {
+        let hour = (seconds_of_day / 3600) as u32;
+        let in_window = if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        };
+        if in_window {
+            return Duration::ZERO;
+        }
+        let start = u64::from(self.start_hour) * 3600;
+        Duration::from_secs((start + 86400 - seconds_of_day) % 86400)
+    }
+}
+
+impl MaxlagStrategy for OffPeak {
+    fn wait(&self, lag: u64, _attempt: u64, _waited: Duration) -> Option<Duration> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let until_window = self.wait_from(now.as_secs() % 86400);
+        Some(until_window.max(Duration::from_secs(lag)))
+    }
+}
+
+/// A strategy, as set in an `Api`
+#[derive(Clone)]
+pub(crate) struct MaxlagHandler(pub(crate) Arc<dyn MaxlagStrategy>);
+
+impl fmt::Debug for MaxlagHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MaxlagHandler")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies() {
+        let wait = WaitForLag { max_attempts: 2 };
+        assert_eq!(
+            wait.wait(5, 2, Duration::ZERO),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(wait.wait(5, 3, Duration::ZERO), None);
+
+        let backoff = ExponentialBackoff {
+            initial: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+            max_total: Duration::from_secs(10),
+        };
+        let delays: Vec<_> = (1..=4)
+            .map(|attempt| backoff.wait(0, attempt, Duration::ZERO).unwrap().as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 5]);
+        assert_eq!(
+            backoff.wait(0, 5, Duration::from_secs(8)),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(backoff.wait(0, 6, Duration::from_secs(10)), None);
+
+        let closure = |lag: u64, _: u64, _: Duration| Some(Duration::from_secs(lag * 2));
+        assert_eq!(
+            closure.wait(3, 1, Duration::ZERO),
+            Some(Duration::from_secs(6))
+        );
+    }
+
+    #[test]
+    fn off_peak() {
+        let night = OffPeak {
+            start_hour: 22,
+            end_hour: 6,
+        };
+        assert_eq!(night.wait_from(23 * 3600), Duration::ZERO);
+        assert_eq!(night.wait_from(3600), Duration::ZERO);
+        assert_eq!(night.wait_from(21 * 3600 + 1800), Duration::from_secs(1800));
+        assert_eq!(night.wait_from(6 * 3600), Duration::from_secs(16 * 3600));
+        let morning = OffPeak {
+            start_hour: 2,
+            end_hour: 5,
+        };
+        assert_eq!(morning.wait_from(12 * 3600), Duration::from_secs(14 * 3600));
+    }
+}
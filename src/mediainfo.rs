@@ -0,0 +1,288 @@
+/*!
+The `MediaInfo` class gives typed access to the structured data of files (MediaInfo entities),
+as used on Wikimedia Commons: captions and statements, like `depicts`.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::file::File;
+use crate::wikibase::{map_or_empty_array, Label, Statement, WikibaseError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// The property for "depicts" statements on Commons
+pub const DEPICTS: &str = "P180";
+
+/// A MediaInfo entity, from `wbgetentities`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MediaInfo {
+    /// Entity ID, "M" followed by the page ID of the file
+    pub id: String,
+    /// Last revision ID of the file page
+    pub lastrevid: Option<u64>,
+    /// Captions, keyed by language
+    #[serde(rename = "labels", default, deserialize_with = "map_or_empty_array")]
+    pub captions: HashMap<String, Label>,
+    /// Statements, keyed by property ID
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub statements: HashMap<String, Vec<Statement>>,
+}
+
+impl MediaInfo {
+    /// Loads a MediaInfo entity by ID (e.g. "M12345")
+    ///
+    /// # Errors
+    /// Returns `MediaInfoError::Missing` if the entity does not exist, or any error from the
+    /// request.
+    pub fn load(api: &Api, id: &str) -> Result<MediaInfo, Box<dyn Error + Send + Sync>> {
+        let params = api.params_into(&[("action", "wbgetentities"), ("ids", id)]);
+        let result = api.get_query_api_json(&params)?;
+        Ok(MediaInfo::new_from_api_result(&result)?)
+    }
+
+    /// Loads the MediaInfo entity of a file on the Api's wiki
+    ///
+    /// # Errors
+    /// Returns `MediaInfoError::Missing` if the file has no MediaInfo entity, or any error from the
+    /// request.
+    pub fn load_for_file(
+        api: &Api,
+        file: &File,
+    ) -> Result<MediaInfo, Box<dyn Error + Send + Sync>> {
+        let title = file
+            .title()
+            .full_pretty(api)
+            .ok_or_else(|| MediaInfoError::BadTitle(file.title().pretty().to_string()))?;
+        let site = api
+            .get_site_info()
+            .general
+            .wikiid
+            .clone()
+            .ok_or("No 'wikiid' value in site info")?;
+        let params = api.params_into(&[
+            ("action", "wbgetentities"),
+            ("sites", &site),
+            ("titles", &title),
+        ]);
+        let result = api.get_query_api_json(&params)?;
+        Ok(MediaInfo::new_from_api_result(&result)?)
+    }
+
+    /// Parses the first entity of a `wbgetentities` result
+    pub fn new_from_api_result(result: &Value) -> Result<MediaInfo, MediaInfoError> {
+        let entity = result["entities"]
+            .as_object()
+            .and_then(|entities| entities.values().next())
+            .ok_or_else(|| MediaInfoError::BadResponse(result.clone()))?;
+        if entity.get("missing").is_some() {
+            let id = entity["id"].as_str().or_else(|| entity["title"].as_str());
+            return Err(MediaInfoError::Missing(id.unwrap_or_default().to_string()));
+        }
+        MediaInfo::deserialize(entity).map_err(|_| MediaInfoError::BadResponse(result.clone()))
+    }
+
+    /// Returns the caption in a language
+    pub fn caption(&self, language: &str) -> Option<&str> {
+        self.captions.get(language).map(|c| c.value.as_str())
+    }
+
+    /// Returns the item IDs of the values of all statements for a property, like `DEPICTS`
+    pub fn item_values(&self, property: &str) -> Vec<String> {
+        self.statements
+            .get(property)
+            .map(|statements| {
+                statements
+                    .iter()
+                    .filter_map(|s| s.mainsnak.datavalue.as_ref()?.as_entity_id())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the item IDs of what the file depicts
+    pub fn depicts(&self) -> Vec<String> {
+        self.item_values(DEPICTS)
+    }
+
+    /// Sets the caption in a language, and updates this `MediaInfo` on success
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn set_caption(
+        &mut self,
+        api: &Api,
+        language: &str,
+        caption: &str,
+        summary: &str,
+    ) -> Result<(), WikibaseError> {
+        let params =
+            api.params_into(&[("id", &self.id), ("language", language), ("value", caption)]);
+        let result = api.wikibase_edit("wbsetlabel", params, summary, self.lastrevid)?;
+        self.lastrevid = result["entity"]["lastrevid"].as_u64().or(self.lastrevid);
+        self.captions
+            .insert(language.to_string(), Label::new(language, caption));
+        Ok(())
+    }
+
+    /// Adds a statement with an item value (e.g. "Q146") for a property, like `DEPICTS`,
+    /// and updates this `MediaInfo` on success
+    ///
+    /// # Errors
+    /// Returns `MediaInfoError::BadItemId` if `item_id` is not an item ID, a `WikibaseError` if the
+    /// edit failed, or any error from the request.
+    pub fn add_item_statement(
+        &mut self,
+        api: &Api,
+        property: &str,
+        item_id: &str,
+        summary: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let numeric_id = item_numeric_id(item_id)
+            .ok_or_else(|| MediaInfoError::BadItemId(item_id.to_string()))?;
+        let value = json!({"entity-type": "item", "numeric-id": numeric_id}).to_string();
+        let params = api.params_into(&[
+            ("entity", &self.id),
+            ("property", property),
+            ("snaktype", "value"),
+            ("value", &value),
+        ]);
+        let result = api.wikibase_edit("wbcreateclaim", params, summary, self.lastrevid)?;
+        self.lastrevid = result["pageinfo"]["lastrevid"].as_u64().or(self.lastrevid);
+        let statement = Statement::deserialize(&result["claim"])
+            .map_err(|_| WikibaseError::BadResponse(result.clone()))?;
+        self.statements
+            .entry(property.to_string())
+            .or_default()
+            .push(statement);
+        Ok(())
+    }
+
+    /// Adds a `depicts` statement for an item
+    ///
+    /// # Errors
+    /// See `add_item_statement`.
+    pub fn add_depicts(
+        &mut self,
+        api: &Api,
+        item_id: &str,
+        summary: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.add_item_statement(api, DEPICTS, item_id, summary)
+    }
+}
+
+/// Returns the number of an item ID, e.g. 146 for "Q146"
+fn item_numeric_id(item_id: &str) -> Option<u64> {
+    item_id
+        .strip_prefix('Q')
+        .filter(|id| id.bytes().all(|b| b.is_ascii_digit()))?
+        .parse()
+        .ok()
+}
+
+/// Errors that can go wrong while performing operations on a `MediaInfo`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MediaInfoError {
+    /// Couldn't obtain the title of the file for use in an API request.
+    BadTitle(String),
+
+    /// Not a valid item ID.
+    BadItemId(String),
+
+    /// Couldn't understand the API response (provided).
+    BadResponse(Value),
+
+    /// Missing entity; the ID or title is provided.
+    Missing(String),
+}
+
+impl fmt::Display for MediaInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaInfoError::BadTitle(title) => write!(f, "invalid file title: {}", title),
+            MediaInfoError::BadItemId(id) => write!(f, "invalid item ID: {}", id),
+            MediaInfoError::BadResponse(response) => {
+                write!(
+                    f,
+                    "bad API response while fetching MediaInfo: {:?}",
+                    response
+                )
+            }
+            MediaInfoError::Missing(id) => write!(f, "MediaInfo entity missing: {}", id),
+        }
+    }
+}
+
+impl Error for MediaInfoError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mediainfo() {
+        let result = json!({"entities": {"M123": {
+            "pageid": 123, "ns": 6, "title": "File:Cat.jpg", "lastrevid": 456, "type": "mediainfo",
+            "id": "M123",
+            "labels": {"en": {"language": "en", "value": "A cat"}},
+            "statements": {"P180": [
+                {"mainsnak": {"snaktype": "value", "property": "P180", "datavalue": {
+                    "value": {"entity-type": "item", "numeric-id": 146, "id": "Q146"},
+                    "type": "wikibase-entityid"}},
+                 "type": "statement", "id": "M123$abc", "rank": "preferred"},
+                {"mainsnak": {"snaktype": "somevalue", "property": "P180"}, "type": "statement",
+                "id": "M123$def", "rank": "normal"}
+            ]}
+        }}, "success": 1});
+        let mi = MediaInfo::new_from_api_result(&result).unwrap();
+        assert_eq!(mi.id, "M123");
+        assert_eq!(mi.lastrevid, Some(456));
+        assert_eq!(mi.caption("en"), Some("A cat"));
+        assert_eq!(mi.caption("de"), None);
+        assert_eq!(mi.depicts(), vec!["Q146"]);
+    }
+
+    #[test]
+    fn parse_empty_mediainfo() {
+        let result = json!({"entities": {"M124": {
+            "pageid": 124, "ns": 6, "title": "File:Dog.jpg", "lastrevid": 457, "type": "mediainfo",
+            "id": "M124",
+            "labels": [], "statements": []
+        }}, "success": 1});
+        let mi = MediaInfo::new_from_api_result(&result).unwrap();
+        assert!(mi.captions.is_empty());
+        assert!(mi.depicts().is_empty());
+    }
+
+    #[test]
+    fn parse_missing_mediainfo() {
+        let result = json!({"entities": {"M1": {"id": "M1", "missing": ""}}, "success": 1});
+        match MediaInfo::new_from_api_result(&result) {
+            Err(MediaInfoError::Missing(id)) => assert_eq!(id, "M1"),
+            x => panic!("expected missing error, found {:?}", x),
+        }
+    }
+
+    #[test]
+    fn item_ids() {
+        assert_eq!(item_numeric_id("Q146"), Some(146));
+        for id in ["QQ5", "qQ5", "q5", "5", "Q", "Q+5", "P5"] {
+            assert_eq!(item_numeric_id(id), None, "{}", id);
+        }
+    }
+}
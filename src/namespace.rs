@@ -0,0 +1,169 @@
+/*!
+The `Namespace` type wraps a `NamespaceID`, with constants for the well-known namespaces.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::NamespaceID;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A namespace. Any `NamespaceID` is a valid `Namespace`; the constants cover
+/// the namespaces that are the same on every wiki (and `MODULE`, from Scribunto).
+///
+/// # Examples
+///
+/// ```
+/// use mediawiki::namespace::Namespace;
+/// assert_eq!(Namespace::USER_TALK.subject(), Namespace::USER);
+/// assert_eq!(Namespace::TEMPLATE.talk(), Some(Namespace::TEMPLATE_TALK));
+/// assert_eq!(Namespace::from(4), Namespace::PROJECT);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Namespace(NamespaceID);
+
+impl Namespace {
+    /// `Media:`, for direct links to files
+    pub const MEDIA: Namespace = Namespace(-2);
+    /// `Special:`
+    pub const SPECIAL: Namespace = Namespace(-1);
+    /// The main (article) namespace
+    pub const MAIN: Namespace = Namespace(0);
+    /// `Talk:`
+    pub const TALK: Namespace = Namespace(1);
+    /// `User:`
+    pub const USER: Namespace = Namespace(2);
+    /// `User talk:`
+    pub const USER_TALK: Namespace = Namespace(3);
+    /// The project namespace, e.g. `Wikipedia:`
+    pub const PROJECT: Namespace = Namespace(4);
+    /// The project talk namespace, e.g. `Wikipedia talk:`
+    pub const PROJECT_TALK: Namespace = Namespace(5);
+    /// `File:`
+    pub const FILE: Namespace = Namespace(6);
+    /// `File talk:`
+    pub const FILE_TALK: Namespace = Namespace(7);
+    /// `MediaWiki:`
+    pub const MEDIAWIKI: Namespace = Namespace(8);
+    /// `MediaWiki talk:`
+    pub const MEDIAWIKI_TALK: Namespace = Namespace(9);
+    /// `Template:`
+    pub const TEMPLATE: Namespace = Namespace(10);
+    /// `Template talk:`
+    pub const TEMPLATE_TALK: Namespace = Namespace(11);
+    /// `Help:`
+    pub const HELP: Namespace = Namespace(12);
+    /// `Help talk:`
+    pub const HELP_TALK: Namespace = Namespace(13);
+    /// `Category:`
+    pub const CATEGORY: Namespace = Namespace(14);
+    /// `Category talk:`
+    pub const CATEGORY_TALK: Namespace = Namespace(15);
+    /// `Module:`, on wikis with Scribunto
+    pub const MODULE: Namespace = Namespace(828);
+    /// `Module talk:`, on wikis with Scribunto
+    pub const MODULE_TALK: Namespace = Namespace(829);
+
+    /// Creates a `Namespace` from a namespace ID
+    pub const fn new(id: NamespaceID) -> Namespace {
+        Namespace(id)
+    }
+
+    /// Returns the namespace ID
+    pub const fn id(self) -> NamespaceID {
+        self.0
+    }
+
+    /// Checks if this is a virtual namespace (`Special:` or `Media:`), which has no talk namespace
+    pub fn is_virtual(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Checks if this is a talk namespace
+    pub fn is_talk(self) -> bool {
+        self.0 > 0 && self.0 % 2 == 1
+    }
+
+    /// Checks if this is a subject (non-talk, non-virtual) namespace
+    pub fn is_subject(self) -> bool {
+        self.0 >= 0 && self.0 % 2 == 0
+    }
+
+    /// Returns the subject namespace for a talk namespace; other namespaces are returned unchanged
+    pub fn subject(self) -> Namespace {
+        if self.is_talk() {
+            Namespace(self.0 - 1)
+        } else {
+            self
+        }
+    }
+
+    /// Returns the talk namespace for a subject namespace, or the namespace itself if it is a talk
+    /// namespace. Virtual namespaces have no talk namespace.
+    pub fn talk(self) -> Option<Namespace> {
+        if self.is_virtual() {
+            None
+        } else if self.is_talk() {
+            Some(self)
+        } else {
+            Some(Namespace(self.0 + 1))
+        }
+    }
+}
+
+impl From<NamespaceID> for Namespace {
+    fn from(id: NamespaceID) -> Self {
+        Namespace(id)
+    }
+}
+
+impl From<Namespace> for NamespaceID {
+    fn from(namespace: Namespace) -> Self {
+        namespace.0
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn talk_and_subject() {
+        assert!(Namespace::TALK.is_talk());
+        assert!(!Namespace::MAIN.is_talk());
+        assert!(Namespace::MAIN.is_subject());
+        assert!(!Namespace::SPECIAL.is_subject());
+        assert_eq!(Namespace::MODULE.talk(), Some(Namespace::MODULE_TALK));
+        assert_eq!(Namespace::MODULE_TALK.talk(), Some(Namespace::MODULE_TALK));
+        assert_eq!(Namespace::CATEGORY_TALK.subject(), Namespace::CATEGORY);
+        assert_eq!(Namespace::MEDIA.talk(), None);
+        assert_eq!(Namespace::SPECIAL.subject(), Namespace::SPECIAL);
+    }
+
+    #[test]
+    fn conversions() {
+        let id: NamespaceID = Namespace::FILE.into();
+        assert_eq!(id, 6);
+        assert_eq!(Namespace::new(14), Namespace::CATEGORY);
+        assert_eq!(Namespace::HELP.id(), 12);
+        assert_eq!(Namespace::USER.to_string(), "2");
+        assert_eq!(serde_json::to_value(Namespace::PROJECT).unwrap(), json!(4));
+    }
+}
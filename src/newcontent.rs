@@ -0,0 +1,261 @@
+/*!
+Listings of new pages (`list=recentchanges&rctype=new`) and new files (`list=allimages`, by upload
+time), for new-content review queues. A `NewContentFilter` selects by namespace, patrol status and
+the groups of the creator or uploader.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::file::ImageInfo;
+use crate::params_map;
+use crate::recentchanges::RecentChange;
+use crate::user::UserInfo;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Which changes to list, by patrol status. Filtering by patrol status needs the `patrol` or
+/// `patrolmarks` right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PatrolFilter {
+    /// All changes
+    #[default]
+    Any,
+    /// Only patrolled (including autopatrolled) changes
+    Patrolled,
+    /// Only changes that were not patrolled yet
+    Unpatrolled,
+}
+
+/// Filters for new pages and new files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NewContentFilter {
+    namespaces: Vec<NamespaceID>,
+    patrol: PatrolFilter,
+    hide_bots: bool,
+    user_groups: Vec<String>,
+    excluded_user_groups: Vec<String>,
+    max: Option<usize>,
+}
+
+impl NewContentFilter {
+    /// Returns a filter that selects everything
+    pub fn new() -> NewContentFilter {
+        NewContentFilter::default()
+    }
+
+    /// Only selects new pages in these namespaces
+    pub fn with_namespaces(mut self, namespaces: &[NamespaceID]) -> NewContentFilter {
+        self.namespaces = namespaces.to_vec();
+        self
+    }
+
+    /// Only selects new pages with this patrol status
+    pub fn with_patrol(mut self, patrol: PatrolFilter) -> NewContentFilter {
+        self.patrol = patrol;
+        self
+    }
+
+    /// Leaves out new pages created by bots
+    pub fn without_bots(mut self) -> NewContentFilter {
+        self.hide_bots = true;
+        self
+    }
+
+    /// Only selects content by users in any of these groups (including implicit groups, e.g.
+    /// "autoconfirmed"). Anonymous users are only in the group "*".
+    pub fn with_user_groups(mut self, groups: &[&str]) -> NewContentFilter {
+        self.user_groups = groups.iter().map(|group| group.to_string()).collect();
+        self
+    }
+
+    /// Leaves out content by users in any of these groups, e.g. "autoconfirmed" to review content
+    /// of new users only
+    pub fn without_user_groups(mut self, groups: &[&str]) -> NewContentFilter {
+        self.excluded_user_groups = groups.iter().map(|group| group.to_string()).collect();
+        self
+    }
+
+    /// Loads at most the newest `max` pages or files; user group filters apply to these
+    pub fn with_max(mut self, max: usize) -> NewContentFilter {
+        self.max = Some(max);
+        self
+    }
+
+    /// Returns the `rcshow` value for the patrol and bot filters, if any
+    fn rcshow(&self) -> Option<String> {
+        let mut show = vec![];
+        match self.patrol {
+            PatrolFilter::Any => {}
+            PatrolFilter::Patrolled => show.push("patrolled"),
+            PatrolFilter::Unpatrolled => show.push("!patrolled"),
+        }
+        if self.hide_bots {
+            show.push("!bot");
+        }
+        Some(show.join("|")).filter(|show| !show.is_empty())
+    }
+
+    /// Checks if the user group filters are set
+    fn filters_user_groups(&self) -> bool {
+        !self.user_groups.is_empty() || !self.excluded_user_groups.is_empty()
+    }
+
+    /// Checks if a user passes the user group filters; `None` is an anonymous user
+    fn accepts_user(&self, user: Option<&UserInfo>) -> bool {
+        let in_group = |group: &String| match user {
+            Some(user) => user.in_group(group),
+            None => group == "*",
+        };
+        (self.user_groups.is_empty() || self.user_groups.iter().any(in_group))
+            && !self.excluded_user_groups.iter().any(in_group)
+    }
+
+    /// Keeps the items whose user passes the user group filters, loading the groups of all users at
+    /// once
+    fn filter_by_user<T>(
+        &self,
+        api: &Api,
+        items: Vec<T>,
+        user: impl Fn(&T) -> &str,
+    ) -> Result<Vec<T>, Box<dyn Error + Send + Sync>> {
+        if !self.filters_user_groups() {
+            return Ok(items);
+        }
+        let mut names: Vec<&str> = items.iter().map(&user).collect();
+        names.sort_unstable();
+        names.dedup();
+        let users: HashMap<String, UserInfo> = names
+            .iter()
+            .zip(api.users_info(&names)?)
+            .filter_map(|(name, info)| Some((name.to_string(), info?)))
+            .collect();
+        Ok(items
+            .into_iter()
+            .filter(|item| self.accepts_user(users.get(user(item))))
+            .collect())
+    }
+}
+
+/// A new file, from a `list=allimages` result (`formatversion=2`)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NewFile {
+    /// File name, without namespace prefix
+    pub name: String,
+    /// Full title of the file description page
+    pub title: String,
+    /// Upload details of the current file version
+    #[serde(flatten)]
+    pub info: ImageInfo,
+}
+
+impl Api {
+    /// Returns the newest page creations, newest first
+    ///
+    /// # Errors
+    /// Returns any error from the requests.
+    pub fn new_pages(
+        &self,
+        filter: &NewContentFilter,
+    ) -> Result<Vec<RecentChange>, Box<dyn Error + Send + Sync>> {
+        let namespaces: Vec<String> = filter.namespaces.iter().map(|ns| ns.to_string()).collect();
+        let params = params_map![
+            "action" => "query",
+            "list" => "recentchanges",
+            "rctype" => "new",
+            "rcprop" => "title|ids|sizes|flags|user|timestamp|comment|patrolled",
+            "rcnamespace" => Some(namespaces.join("|")).filter(|ns| !ns.is_empty()),
+            "rcshow" => filter.rcshow(),
+            "rclimit" => "max",
+            "formatversion" => "2",
+        ];
+        let changes = self
+            .get_query_api_json_items::<Value>(&params, "query.recentchanges[]", filter.max)
+            .map(|rc| Ok(RecentChange::new_from_api_result(&rc?)))
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>, Box<dyn Error + Send + Sync>>>()?;
+        filter.filter_by_user(self, changes, |change| change.user.as_str())
+    }
+
+    /// Returns the newest files, by upload time of their current version, newest first.
+    /// Files have no patrol status here, so the patrol filter does not apply.
+    ///
+    /// # Errors
+    /// Returns any error from the requests.
+    pub fn new_files(
+        &self,
+        filter: &NewContentFilter,
+    ) -> Result<Vec<NewFile>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "list" => "allimages",
+            "aisort" => "timestamp",
+            "aidir" => "older",
+            "aiprop" => "timestamp|user|comment|url|size|sha1|mime",
+            "aifilterbots" => Some("nobots").filter(|_| filter.hide_bots),
+            "ailimit" => "max",
+            "formatversion" => "2",
+        ];
+        let files = self
+            .get_query_api_json_items(&params, "query.allimages[]", filter.max)
+            .collect::<Result<Vec<NewFile>, _>>()?;
+        filter.filter_by_user(self, files, |file| {
+            file.info.user.as_deref().unwrap_or_default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let filter = NewContentFilter::new()
+            .with_patrol(PatrolFilter::Unpatrolled)
+            .without_bots();
+        assert_eq!(filter.rcshow().as_deref(), Some("!patrolled|!bot"));
+        assert_eq!(NewContentFilter::new().rcshow(), None);
+
+        let filter = NewContentFilter::new().without_user_groups(&["autoconfirmed"]);
+        let mut user = UserInfo {
+            name: "Example".to_string(),
+            ..Default::default()
+        };
+        user.implicit_groups = vec!["*".to_string(), "user".to_string()];
+        assert!(filter.accepts_user(Some(&user)));
+        assert!(filter.accepts_user(None));
+        user.implicit_groups.push("autoconfirmed".to_string());
+        assert!(!filter.accepts_user(Some(&user)));
+
+        let filter = NewContentFilter::new().with_user_groups(&["user"]);
+        assert!(!filter.accepts_user(None));
+        assert!(filter.accepts_user(Some(&user)));
+    }
+
+    #[test]
+    fn new_file() {
+        let file: NewFile = serde_json::from_value(json!({
+            "name": "Example.jpg", "title": "File:Example.jpg", "ns": 6,
+            "timestamp": "2020-04-14T10:22:31Z", "user": "Example", "comment": "Upload",
+            "url": "https://upload.wikimedia.org/wikipedia/commons/a/a9/Example.jpg",
+            "size": 9022, "width": 172, "height": 178, "mime": "image/jpeg"
+        }))
+        .unwrap();
+        assert_eq!(file.name, "Example.jpg");
+        assert_eq!(file.info.user.as_deref(), Some("Example"));
+        assert_eq!(file.info.width, Some(172));
+    }
+}
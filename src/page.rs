@@ -18,6 +18,8 @@ extern crate lazy_static;
 
 use crate::api::Api;
 use crate::title::Title;
+use crate::traits::Mergeable;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::error::Error;
@@ -50,7 +52,9 @@ impl Page {
     ///
     /// [`Api::get_query_api_json`]: ../api/struct.Api.html#method.get_query_api_json
     pub fn text(&self, api: &Api) -> Result<String, PageError> {
-        let title = self.title.full_pretty(api)
+        let title = self
+            .title
+            .full_pretty(api)
             .ok_or_else(|| PageError::BadTitle(self.title.clone()))?;
         let params = [
             ("action", "query"),
@@ -63,8 +67,9 @@ impl Page {
         .iter()
         .map(|&(k, v)| (k.to_string(), v.to_string()))
         .collect();
-        let result = api.get_query_api_json(&params)
-            .map_err(|e| PageError::RequestError(e))?;
+        let result = api
+            .get_query_api_json(&params)
+            .map_err(PageError::RequestError)?;
 
         let page = &result["query"]["pages"][0];
         if page["missing"].as_bool() == Some(true) {
@@ -91,6 +96,42 @@ impl Page {
         }
     }
 
+    /// Fetches many pages at once with `action=query&titles=...`, in batches of 50 titles,
+    /// following continuation. `params` can add further parameters, like `prop`.
+    /// The returned `PageBatch` includes the normalizations, conversions, and redirects
+    /// the API applied, so pages can be matched to the requested titles.
+    ///
+    /// # Errors
+    /// Returns `PageError::BadTitle` if a title has a namespace unknown to the Api,
+    /// or any error from [`Api::get_query_api_json`].
+    ///
+    /// [`Api::get_query_api_json`]: ../api/struct.Api.html#method.get_query_api_json
+    pub fn fetch_batch(
+        api: &Api,
+        titles: &[Title],
+        params: &HashMap<String, String>,
+    ) -> Result<PageBatch, Box<dyn Error + Send + Sync>> {
+        let titles = titles
+            .iter()
+            .map(|title| {
+                title
+                    .full_pretty(api)
+                    .ok_or_else(|| PageError::BadTitle(title.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut batch = PageBatch::default();
+        for chunk in titles.chunks(50) {
+            let mut params = params.clone();
+            params.insert("action".to_string(), "query".to_string());
+            params.insert("formatversion".to_string(), "2".to_string());
+            params.insert("titles".to_string(), chunk.join("|"));
+            for result in api.get_query_api_json_limit_iter(&params, None) {
+                batch.add_api_result(result?)?;
+            }
+        }
+        Ok(batch)
+    }
+
     /// Edits this `Page` with the given parameters and edit summary.
     ///
     /// # Errors
@@ -99,11 +140,13 @@ impl Page {
     /// [`Api::post_query_api_json`]: ../api/struct.Api.html#method.post_query_api_json
     pub fn edit_text(
         &self,
-        api: &mut Api,
+        api: &Api,
         text: impl Into<String>,
         summary: impl Into<String>,
-    ) -> Result<(), Box<dyn Error>> {
-        let title = self.title.full_pretty(api)
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let title = self
+            .title
+            .full_pretty(api)
             .ok_or_else(|| PageError::BadTitle(self.title.clone()))?;
         let bot = if api.user().is_bot() { "true" } else { "false" };
         let mut params: HashMap<String, String> = [
@@ -131,6 +174,133 @@ impl Page {
     }
 }
 
+/// A change the API made to a requested title, from the `normalized`, `converted`,
+/// or `redirects` array of a `formatversion=2` query result
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TitleChange {
+    /// The title before the change
+    pub from: String,
+    /// The title after the change
+    pub to: String,
+    /// For redirects to a section, the section
+    pub tofragment: Option<String>,
+    /// For redirects to another wiki, the interwiki prefix
+    pub tointerwiki: Option<String>,
+}
+
+/// All changes the API made to requested titles before looking up the pages.
+/// The API first normalizes titles, then converts them to another language variant
+/// if the page only exists under that name, then resolves redirects (with `redirects=1`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TitleMapping {
+    /// Normalized titles, e.g. "foo_bar" to "Foo bar"
+    #[serde(default)]
+    pub normalized: Vec<TitleChange>,
+    /// Titles converted to another language variant
+    #[serde(default)]
+    pub converted: Vec<TitleChange>,
+    /// Resolved redirects
+    #[serde(default)]
+    pub redirects: Vec<TitleChange>,
+}
+
+impl TitleMapping {
+    /// Parses the title changes in a `formatversion=2` query result.
+    /// Returns an empty mapping if the result contains no changes.
+    pub fn new_from_api_result(result: &Value) -> Result<TitleMapping, serde_json::Error> {
+        match result.get("query") {
+            Some(query) => TitleMapping::deserialize(query),
+            None => Ok(TitleMapping::default()),
+        }
+    }
+
+    /// Adds the changes of another mapping, e.g. from another batch of titles
+    pub fn extend(&mut self, other: TitleMapping) {
+        for (changes, new_changes) in [
+            (&mut self.normalized, other.normalized),
+            (&mut self.converted, other.converted),
+            (&mut self.redirects, other.redirects),
+        ] {
+            for change in new_changes {
+                if !changes.contains(&change) {
+                    changes.push(change);
+                }
+            }
+        }
+    }
+
+    /// Returns the title of the page the API returned for a requested title,
+    /// following normalization, variant conversion, and (possibly chained) redirects.
+    /// Titles that were not changed are returned as they are.
+    pub fn resolve(&self, title: &str) -> String {
+        let mut title = self.before_redirects(title);
+        // Bounded, in case of redirect loops
+        for _ in 0..=self.redirects.len() {
+            match TitleMapping::find(&self.redirects, &title) {
+                Some(redirect) => title = redirect.to.clone(),
+                None => break,
+            }
+        }
+        title
+    }
+
+    /// Returns the redirect a requested title was resolved through, if any
+    pub fn redirect_for(&self, title: &str) -> Option<&TitleChange> {
+        TitleMapping::find(&self.redirects, &self.before_redirects(title))
+    }
+
+    /// Applies normalization and variant conversion to a requested title
+    fn before_redirects(&self, title: &str) -> String {
+        let title = TitleMapping::find(&self.normalized, title).map_or(title, |c| c.to.as_str());
+        let title = TitleMapping::find(&self.converted, title).map_or(title, |c| c.to.as_str());
+        title.to_string()
+    }
+
+    /// Finds the change of a title
+    fn find<'a>(changes: &'a [TitleChange], title: &str) -> Option<&'a TitleChange> {
+        changes.iter().find(|change| change.from == title)
+    }
+}
+
+/// The result of `Page::fetch_batch`: the page objects of a `formatversion=2` query
+/// result, and the changes the API made to the requested titles
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageBatch {
+    /// The page objects, from `query.pages`
+    pub pages: Vec<Value>,
+    /// How the requested titles were changed
+    pub title_mapping: TitleMapping,
+}
+
+impl PageBatch {
+    /// Returns the page object for a requested title (as passed to the API, i.e.
+    /// namespace-prefixed)
+    pub fn page(&self, requested_title: &str) -> Option<&Value> {
+        let title = self.title_mapping.resolve(requested_title);
+        self.pages
+            .iter()
+            .find(|page| page["title"].as_str() == Some(&title))
+    }
+
+    /// Adds the pages and title changes of a query result. Page objects for a page that is already
+    /// in the batch (e.g. when continuing a `prop`) are merged.
+    fn add_api_result(&mut self, result: Value) -> Result<(), serde_json::Error> {
+        self.title_mapping
+            .extend(TitleMapping::new_from_api_result(&result)?);
+        let pages = match result["query"]["pages"].as_array() {
+            Some(pages) => pages.clone(),
+            None => return Ok(()),
+        };
+        for page in pages {
+            match self.pages.iter_mut().find(|p| p["title"] == page["title"]) {
+                Some(existing) => existing.merge(page),
+                None => self.pages.push(page),
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Errors that can go wrong while performing operations on a `Page`.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -148,15 +318,18 @@ pub enum PageError {
     EditError(Value),
 
     /// Error while performing the API request.
-    RequestError(Box<dyn Error>),
+    RequestError(Box<dyn Error + Send + Sync>),
 }
 
 impl fmt::Display for PageError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PageError::BadTitle(title) => write!(f, "invalid title for this Page: {:?}", title),
-            PageError::BadResponse(response) =>
-                write!(f, "bad API response while fetching revision content: {:?}", response),
+            PageError::BadResponse(response) => write!(
+                f,
+                "bad API response while fetching revision content: {:?}",
+                response
+            ),
             PageError::Missing(title) => write!(f, "page missing: {:?}", title),
             PageError::EditError(response) => write!(f, "edit resulted in error: {:?}", response),
             PageError::RequestError(error) => write!(f, "request error: {}", error),
@@ -194,4 +367,64 @@ mod tests {
             x => panic!("expected missing error, found {:?}", x),
         }
     }
+
+    #[test]
+    fn title_mapping() {
+        let result = json!({"batchcomplete": true, "query": {
+            "normalized": [{"fromencoded": false, "from": "foo_bar", "to": "Foo bar"}],
+            "converted": [{"from": "Foo bar", "to": "Föo bar"}],
+            "redirects": [
+                {"from": "Föo bar", "to": "Baz", "tofragment": "Section"},
+                {"from": "Baz", "to": "Qux"},
+                {"from": "Loop", "to": "Loop"}
+            ],
+            "pages": [{"pageid": 1, "ns": 0, "title": "Qux"}]
+        }});
+        let mapping = TitleMapping::new_from_api_result(&result).unwrap();
+        assert_eq!(mapping.resolve("foo_bar"), "Qux");
+        assert_eq!(mapping.resolve("Baz"), "Qux");
+        assert_eq!(mapping.resolve("Other"), "Other");
+        assert_eq!(mapping.resolve("Loop"), "Loop");
+        assert_eq!(
+            mapping
+                .redirect_for("foo_bar")
+                .unwrap()
+                .tofragment
+                .as_deref(),
+            Some("Section")
+        );
+        assert_eq!(mapping.redirect_for("Qux"), None);
+        assert_eq!(
+            TitleMapping::new_from_api_result(&json!({"batchcomplete": true})).unwrap(),
+            TitleMapping::default()
+        );
+    }
+
+    #[test]
+    fn page_batch_merges_continued_pages() {
+        let mut batch = PageBatch::default();
+        batch
+            .add_api_result(json!({"query": {
+                "normalized": [{"from": "a", "to": "A"}],
+                "pages": [
+                    {"pageid": 1, "ns": 0, "title": "A", "categories": [{"ns": 14,
+                    "title": "Kategorie:X"}]},
+                    {"ns": 0, "title": "B", "missing": true}
+                ]
+            }}))
+            .unwrap();
+        batch
+            .add_api_result(json!({"query": {
+                "normalized": [{"from": "a", "to": "A"}],
+                "pages": [{"pageid": 1, "ns": 0, "title": "A", "categories": [{"ns": 14,
+                "title": "Kategorie:Y"}]}]
+            }}))
+            .unwrap();
+        assert_eq!(batch.pages.len(), 2);
+        assert_eq!(batch.title_mapping.normalized.len(), 1);
+        let page = batch.page("a").unwrap();
+        assert_eq!(page["categories"].as_array().unwrap().len(), 2);
+        assert_eq!(batch.page("B").unwrap()["missing"], json!(true));
+        assert_eq!(batch.page("C"), None);
+    }
 }
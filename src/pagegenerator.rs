@@ -0,0 +1,337 @@
+/*!
+The `PageGenerator` class yields `Page`s lazily from a source (category members, search results,
+backlinks, a SPARQL result, a list of titles, recent changes), and combines generators, like the
+page generators of pywikibot. API results are loaded one batch at a time, as the pages are consumed.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::page::Page;
+use crate::title::Title;
+use crate::wikibase::EntityId;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+use std::iter;
+
+/// A page from a generator, or the error that occurred while loading pages
+pub type PageResult = Result<Page, Box<dyn Error + Send + Sync>>;
+
+/// A lazy sequence of pages
+pub struct PageGenerator<'a> {
+    inner: Box<dyn Iterator<Item = PageResult> + 'a>,
+}
+
+impl fmt::Debug for PageGenerator<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageGenerator").finish()
+    }
+}
+
+impl<'a> PageGenerator<'a> {
+    /// Creates a generator from any iterator of pages
+    pub fn new<I: Iterator<Item = PageResult> + 'a>(pages: I) -> PageGenerator<'a> {
+        PageGenerator {
+            inner: Box::new(pages),
+        }
+    }
+
+    /// Yields pages with the given titles
+    pub fn from_titles(titles: Vec<Title>) -> PageGenerator<'a> {
+        PageGenerator::new(titles.into_iter().map(|title| Ok(Page::new(title))))
+    }
+
+    /// Yields the pages whose titles are listed in a text file, one per line.
+    /// Empty lines are skipped, and links (e.g. "* [[Foo]]") are reduced to their target.
+    pub fn from_lines<R: BufRead + 'a>(api: &'a Api, reader: R) -> PageGenerator<'a> {
+        PageGenerator::new(reader.lines().filter_map(move |line| match line {
+            Ok(line) => {
+                title_from_line(&line).map(|title| Ok(Page::new(Title::new_from_full(title, api))))
+            }
+            Err(e) => Some(Err(From::from(e))),
+        }))
+    }
+
+    /// Yields the members of a category (given without namespace prefix), optionally only in some
+    /// namespaces
+    pub fn category_members(
+        api: &'a Api,
+        category: &str,
+        namespaces: &[NamespaceID],
+    ) -> PageGenerator<'a> {
+        let title = Title::new(category, 14)
+            .full_pretty(api)
+            .unwrap_or_else(|| category.to_string());
+        let mut params = api.params_into(&[
+            ("action", "query"),
+            ("list", "categorymembers"),
+            ("cmtitle", &title),
+            ("cmlimit", "max"),
+        ]);
+        add_namespaces(&mut params, "cmnamespace", namespaces);
+        PageGenerator::from_list(api, params, "categorymembers")
+    }
+
+    /// Yields the results of a full-text search, optionally only in some namespaces
+    pub fn search(api: &'a Api, query: &str, namespaces: &[NamespaceID]) -> PageGenerator<'a> {
+        let mut params = api.params_into(&[
+            ("action", "query"),
+            ("list", "search"),
+            ("srsearch", query),
+            ("srprop", ""),
+            ("srlimit", "max"),
+        ]);
+        add_namespaces(&mut params, "srnamespace", namespaces);
+        PageGenerator::from_list(api, params, "search")
+    }
+
+    /// Yields the pages that link to a page, optionally only in some namespaces
+    pub fn backlinks(api: &'a Api, title: &Title, namespaces: &[NamespaceID]) -> PageGenerator<'a> {
+        let title = title
+            .full_pretty(api)
+            .unwrap_or_else(|| title.pretty().to_string());
+        let mut params = api.params_into(&[
+            ("action", "query"),
+            ("list", "backlinks"),
+            ("bltitle", &title),
+            ("bllimit", "max"),
+        ]);
+        add_namespaces(&mut params, "blnamespace", namespaces);
+        PageGenerator::from_list(api, params, "backlinks")
+    }
+
+    /// Yields the pages of recently changed pages, newest first, optionally only in some
+    /// namespaces. Pages that were changed several times are yielded several times; see `dedup`.
+    pub fn recent_changes(api: &'a Api, namespaces: &[NamespaceID]) -> PageGenerator<'a> {
+        let mut params = api.params_into(&[
+            ("action", "query"),
+            ("list", "recentchanges"),
+            ("rcprop", "title"),
+            ("rclimit", "max"),
+        ]);
+        add_namespaces(&mut params, "rcnamespace", namespaces);
+        PageGenerator::from_list(api, params, "recentchanges")
+    }
+
+    /// Yields the pages of the Wikibase entities in the variable `variable` of a SPARQL result:
+    /// items in the main namespace, properties and lexemes with their namespace prefix (e.g.
+    /// "Property:P31"), and the lexeme pages of forms and senses. MediaInfo entities are skipped.
+    pub fn sparql(api: &'a Api, sparql_result: &Value, variable: &str) -> PageGenerator<'a> {
+        let entities = match api.sparql_entities(sparql_result, variable) {
+            Ok(entities) => entities,
+            Err(e) => return PageGenerator::new(iter::once(Err(From::from(e)))),
+        };
+        PageGenerator::new(entities.into_iter().filter_map(move |id| {
+            let title = match id {
+                EntityId::Item(_) => id.to_string(),
+                EntityId::Property(_) => format!("Property:{}", id),
+                EntityId::Lexeme(lexeme)
+                | EntityId::Form(lexeme, _)
+                | EntityId::Sense(lexeme, _) => {
+                    format!("Lexeme:L{}", lexeme)
+                }
+                EntityId::MediaInfo(_) => return None,
+            };
+            Some(Ok(Page::new(Title::new_from_full(&title, api))))
+        }))
+    }
+
+    /// Yields the pages of a `list=` query, loading more results as needed
+    fn from_list(
+        api: &'a Api,
+        params: HashMap<String, String>,
+        list: &'a str,
+    ) -> PageGenerator<'a> {
+        PageGenerator::new(api.get_query_api_json_limit_iter(&params, None).flat_map(
+            move |result| {
+                match result {
+                    Ok(result) => pages_from_result(&result, list)
+                        .into_iter()
+                        .map(Ok)
+                        .collect::<Vec<_>>(),
+                    Err(e) => vec![Err(e)],
+                }
+            },
+        ))
+    }
+
+    /// Only yields pages in the given namespaces
+    pub fn filter_namespaces(self, namespaces: &[NamespaceID]) -> PageGenerator<'a> {
+        let namespaces = namespaces.to_vec();
+        PageGenerator::new(self.inner.filter(move |page| match page {
+            Ok(page) => namespaces.contains(&page.title().namespace_id()),
+            Err(_) => true,
+        }))
+    }
+
+    /// Skips pages that were yielded before
+    pub fn dedup(self) -> PageGenerator<'a> {
+        let mut seen = HashSet::new();
+        PageGenerator::new(self.inner.filter(move |page| match page {
+            Ok(page) => seen.insert(page.title().clone()),
+            Err(_) => true,
+        }))
+    }
+
+    /// Only yields pages that are also yielded by `other`. `other` is loaded completely first.
+    pub fn intersect(self, other: PageGenerator<'a>) -> PageGenerator<'a> {
+        self.set_operation(other, true)
+    }
+
+    /// Only yields pages that are not yielded by `other`. `other` is loaded completely first.
+    pub fn subtract(self, other: PageGenerator<'a>) -> PageGenerator<'a> {
+        self.set_operation(other, false)
+    }
+
+    /// Filters pages by whether they are yielded by `other`; errors of `other` are yielded first
+    fn set_operation(self, other: PageGenerator<'a>, keep_if_contained: bool) -> PageGenerator<'a> {
+        let mut other = Some(other);
+        let mut titles = HashSet::new();
+        let mut errors: VecDeque<Box<dyn Error + Send + Sync>> = VecDeque::new();
+        let mut inner = self.inner;
+        PageGenerator::new(iter::from_fn(move || {
+            if let Some(other) = other.take() {
+                for page in other {
+                    match page {
+                        Ok(page) => {
+                            titles.insert(page.title().clone());
+                        }
+                        Err(e) => errors.push_back(e),
+                    }
+                }
+            }
+            if let Some(e) = errors.pop_front() {
+                return Some(Err(e));
+            }
+            loop {
+                match inner.next()? {
+                    Ok(page) if titles.contains(page.title()) != keep_if_contained => continue,
+                    page => return Some(page),
+                }
+            }
+        }))
+    }
+}
+
+impl Iterator for PageGenerator<'_> {
+    type Item = PageResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// Returns the pages in the `list` of a query result
+fn pages_from_result(result: &Value, list: &str) -> Vec<Page> {
+    result["query"][list]
+        .as_array()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter(|entry| entry["title"].is_string())
+                .map(|entry| Page::new(Title::new_from_api_result(entry)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Adds a namespace filter parameter, if there are namespaces
+fn add_namespaces(params: &mut HashMap<String, String>, key: &str, namespaces: &[NamespaceID]) {
+    if !namespaces.is_empty() {
+        let namespaces: Vec<String> = namespaces.iter().map(|ns| ns.to_string()).collect();
+        params.insert(key.to_string(), namespaces.join("|"));
+    }
+}
+
+/// Returns the title in a line of a title list, without list markup or link brackets
+fn title_from_line(line: &str) -> Option<&str> {
+    let line = line.trim().trim_start_matches(['*', '#']).trim();
+    let line = match line.strip_prefix("[[") {
+        Some(link) => link.split(['|', ']']).next().unwrap_or_default().trim(),
+        None => line,
+    };
+    if line.is_empty() {
+        None
+    } else {
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+    use std::io::Cursor;
+
+    fn titles(generator: PageGenerator) -> Vec<String> {
+        generator
+            .map(|page| page.unwrap().title().pretty().to_string())
+            .collect()
+    }
+
+    fn from_titles(titles: &[&str]) -> PageGenerator<'static> {
+        PageGenerator::from_titles(titles.iter().map(|t| Title::new(t, 0)).collect())
+    }
+
+    #[test]
+    fn combinators() {
+        let generator = from_titles(&["A", "B", "A", "C"]).dedup();
+        assert_eq!(titles(generator), vec!["A", "B", "C"]);
+        let generator = from_titles(&["A", "B", "C"]).intersect(from_titles(&["C", "A", "D"]));
+        assert_eq!(titles(generator), vec!["A", "C"]);
+        let generator = from_titles(&["A", "B", "C"]).subtract(from_titles(&["B"]));
+        assert_eq!(titles(generator), vec!["A", "C"]);
+        let generator = PageGenerator::from_titles(vec![Title::new("A", 0), Title::new("B", 1)])
+            .filter_namespaces(&[1]);
+        assert_eq!(titles(generator), vec!["B"]);
+    }
+
+    #[test]
+    fn errors_in_set_operations() {
+        let other = PageGenerator::new(iter::once(Err(From::from("failed"))));
+        let mut generator = from_titles(&["A"]).subtract(other);
+        assert!(generator.next().unwrap().is_err());
+        assert_eq!(titles(generator), vec!["A"]);
+    }
+
+    #[test]
+    fn lines() {
+        let api = test_api();
+        let file = Cursor::new("Foo\n\n* [[Diskussion:Bar|bar]]\n# Baz \n");
+        let pages: Vec<Title> = PageGenerator::from_lines(&api, file)
+            .map(|page| page.unwrap().title().clone())
+            .collect();
+        assert_eq!(
+            pages,
+            vec![
+                Title::new("Foo", 0),
+                Title::new("Bar", 1),
+                Title::new("Baz", 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn list_results() {
+        let result = json!({"query": {"categorymembers": [
+            {"pageid": 1, "ns": 0, "title": "Foo"},
+            {"pageid": 2, "ns": 14, "title": "Category:Bar"}
+        ]}});
+        let pages = pages_from_result(&result, "categorymembers");
+        assert_eq!(pages[1].title(), &Title::new("Bar", 14));
+        assert!(pages_from_result(&result, "search").is_empty());
+    }
+}
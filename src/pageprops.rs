@@ -0,0 +1,72 @@
+/*!
+Page properties, which pages set with magic words or extensions (e.g. "wikibase_item",
+"disambiguation", "defaultsort"). `Api::page_prop_names` lists the names in use on a wiki, from
+`list=pagepropnames`.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use serde_json::Value;
+use std::error::Error;
+
+impl Api {
+    /// Loads the names of all page properties in use on the wiki, in alphabetical order
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn page_prop_names(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let params = self.params_into(&[
+            ("action", "query"),
+            ("list", "pagepropnames"),
+            ("ppnlimit", "max"),
+            ("formatversion", "2"),
+        ]);
+        let result = self.get_query_api_json_all(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("list=pagepropnames failed: {}", error)));
+        }
+        Ok(prop_names_from_api_result(&result))
+    }
+}
+
+/// Parses the names of a `list=pagepropnames` result
+fn prop_names_from_api_result(result: &Value) -> Vec<String> {
+    result["query"]["pagepropnames"]
+        .as_array()
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|name| name["propname"].as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_from_api_result() {
+        let result = json!({"query": {"pagepropnames": [
+            {"propname": "defaultsort"}, {"propname": "disambiguation"},
+            {"propname": "wikibase_item"}
+        ]}});
+        assert_eq!(
+            prop_names_from_api_result(&result),
+            vec!["defaultsort", "disambiguation", "wikibase_item"]
+        );
+        assert!(prop_names_from_api_result(&json!({"batchcomplete": true})).is_empty());
+    }
+}
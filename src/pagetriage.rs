@@ -0,0 +1,281 @@
+/*!
+The new pages feed of the [PageTriage](https://www.mediawiki.org/wiki/Extension:PageTriage)
+extension (the new page patrol queue on the English Wikipedia): listing the queue via
+`action=pagetriagelist`, and marking pages as reviewed or unreviewed via `action=pagetriageaction`,
+which needs the `patrol` right.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::newcontent::PatrolFilter;
+use crate::params_map;
+use crate::timestamp::{parse_db_timestamp, Timestamp};
+use serde_json::Value;
+use std::error::Error;
+
+/// Review status of a page in the queue: not reviewed yet
+pub const UNREVIEWED: u8 = 0;
+/// Review status of a page in the queue: reviewed
+pub const REVIEWED: u8 = 1;
+/// Review status of a page in the queue: patrolled via Special:NewPages
+pub const PATROLLED: u8 = 2;
+/// Review status of a page in the queue: created by an autopatrolled user
+pub const AUTOPATROLLED: u8 = 3;
+
+/// A page in the new pages feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriagePage {
+    /// ID of the page
+    pub page_id: u64,
+    /// Full title of the page
+    pub title: String,
+    /// Time of the creation of the page
+    pub creation_date: Option<Timestamp>,
+    /// Review status, see `UNREVIEWED`, `REVIEWED`, `PATROLLED` and `AUTOPATROLLED`
+    pub patrol_status: u8,
+    /// Whether the page is a redirect
+    pub is_redirect: bool,
+    /// Size of the page, in bytes
+    pub page_len: u64,
+    /// User who created the page
+    pub user_name: Option<String>,
+    /// User who last reviewed the page, if any
+    pub reviewer: Option<String>,
+    /// Whether the page is nominated for deletion (speedy deletion, proposed deletion or deletion
+    /// discussion)
+    pub nominated_for_deletion: bool,
+}
+
+impl TriagePage {
+    /// Parses an entry of the `pages` of an `action=pagetriagelist` result.
+    /// PageTriage returns most numbers as strings.
+    pub fn new_from_api_result(page: &Value) -> Option<TriagePage> {
+        Some(TriagePage {
+            page_id: lenient_u64(&page["page_id"])?,
+            title: page["title"].as_str()?.to_string(),
+            creation_date: page["creation_date"].as_str().and_then(parse_db_timestamp),
+            patrol_status: lenient_u64(&page["patrol_status"]).unwrap_or_default() as u8,
+            is_redirect: lenient_u64(&page["is_redirect"]) == Some(1),
+            page_len: lenient_u64(&page["page_len"]).unwrap_or_default(),
+            user_name: non_empty_string(&page["user_name"]),
+            reviewer: non_empty_string(&page["reviewer"]),
+            nominated_for_deletion: ["afd_status", "csd_status", "prod_status", "blp_prod_status"]
+                .iter()
+                .any(|status| lenient_u64(&page[*status]) == Some(1)),
+        })
+    }
+
+    /// Checks if the page has been reviewed, patrolled or autopatrolled
+    pub fn is_reviewed(&self) -> bool {
+        self.patrol_status != UNREVIEWED
+    }
+}
+
+/// Filters for the new pages feed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageTriageFilter {
+    namespace: Option<NamespaceID>,
+    patrol: PatrolFilter,
+    redirects: bool,
+    nominated: bool,
+    oldest_first: bool,
+    max: Option<usize>,
+}
+
+impl PageTriageFilter {
+    /// Returns a filter for all pages in the main namespace that are neither redirects
+    /// nor nominated for deletion, newest first
+    pub fn new() -> PageTriageFilter {
+        PageTriageFilter::default()
+    }
+
+    /// Only selects pages in this namespace (the main namespace by default)
+    pub fn with_namespace(mut self, namespace: NamespaceID) -> PageTriageFilter {
+        self.namespace = Some(namespace);
+        self
+    }
+
+    /// Only selects pages with this review status
+    pub fn with_patrol(mut self, patrol: PatrolFilter) -> PageTriageFilter {
+        self.patrol = patrol;
+        self
+    }
+
+    /// Also selects redirects
+    pub fn with_redirects(mut self) -> PageTriageFilter {
+        self.redirects = true;
+        self
+    }
+
+    /// Also selects pages that are nominated for deletion
+    pub fn with_deletion_nominations(mut self) -> PageTriageFilter {
+        self.nominated = true;
+        self
+    }
+
+    /// Lists the oldest pages first, like the backlog view
+    pub fn oldest_first(mut self) -> PageTriageFilter {
+        self.oldest_first = true;
+        self
+    }
+
+    /// Loads at most `max` pages
+    pub fn with_max(mut self, max: usize) -> PageTriageFilter {
+        self.max = Some(max);
+        self
+    }
+}
+
+impl Api {
+    /// Loads the pages in the new pages feed that match `filter`
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the extension is not installed),
+    /// or any error from the requests.
+    pub fn page_triage_list(
+        &self,
+        filter: &PageTriageFilter,
+    ) -> Result<Vec<TriagePage>, Box<dyn Error + Send + Sync>> {
+        let mut params = params_map![
+            "action" => "pagetriagelist",
+            "namespace" => filter.namespace,
+            "showreviewed" => filter.patrol != PatrolFilter::Unpatrolled,
+            "showunreviewed" => filter.patrol != PatrolFilter::Patrolled,
+            "showothers" => true,
+            "showredirs" => filter.redirects,
+            "showdeleted" => filter.nominated,
+            "dir" => if filter.oldest_first { "oldestfirst" } else { "newestfirst" },
+            "limit" => "200",
+            "formatversion" => "2",
+        ];
+        let mut ret = vec![];
+        loop {
+            let result = self.get_query_api_json(&params)?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!(
+                    "action=pagetriagelist failed: {}",
+                    error
+                )));
+            }
+            let pages = result["pagetriagelist"]["pages"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            ret.extend(pages.iter().filter_map(TriagePage::new_from_api_result));
+            if let Some(max) = filter.max {
+                if ret.len() >= max {
+                    ret.truncate(max);
+                    break;
+                }
+            }
+            // The feed continues from the creation date and ID of the last page
+            match pages.last() {
+                Some(last) if pages.len() >= 200 => {
+                    let offset = last["creation_date"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string();
+                    let page_offset = lenient_u64(&last["page_id"])
+                        .unwrap_or_default()
+                        .to_string();
+                    params.insert("offset".to_string(), offset);
+                    params.insert("pageoffset".to_string(), page_offset);
+                }
+                _ => break,
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Marks the page with the ID `page_id` as reviewed, or as unreviewed if `reviewed` is false,
+    /// with an optional note to the page creator
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. permission denied), or any error from
+    /// the request.
+    pub fn page_triage_review(
+        &self,
+        page_id: u64,
+        reviewed: bool,
+        note: Option<&str>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "pagetriageaction",
+            "pageid" => page_id,
+            "reviewed" => if reviewed { "1" } else { "0" },
+            "note" => note,
+            "token" => self.get_edit_token()?,
+            "formatversion" => "2",
+        ];
+        let result = self.post_query_api_json(&params)?;
+        match result["pagetriageaction"]["result"].as_str() {
+            Some("success") => Ok(()),
+            _ => Err(From::from(format!(
+                "action=pagetriageaction failed: {:?}",
+                result
+            ))),
+        }
+    }
+}
+
+/// Returns a number that may be given as a string
+fn lenient_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        Value::Bool(b) => Some(*b as u64),
+        _ => value.as_u64(),
+    }
+}
+
+/// Returns a string, unless it is empty
+fn non_empty_string(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triage_page() {
+        let page = TriagePage::new_from_api_result(&json!({
+            "page_id": "63947216", "title": "Example article", "creation_date": "20200414102231",
+            "patrol_status": "0", "is_redirect": "0", "page_len": "2345", "user_name": "Example",
+            "reviewer": "", "afd_status": "0", "csd_status": "1", "prod_status": "0",
+            "blp_prod_status": "0"
+        }))
+        .unwrap();
+        assert_eq!(page.page_id, 63947216);
+        assert!(!page.is_reviewed());
+        assert!(!page.is_redirect);
+        assert!(page.nominated_for_deletion);
+        assert_eq!(page.reviewer, None);
+        assert_eq!(
+            page.creation_date,
+            crate::timestamp::parse_timestamp("2020-04-14T10:22:31Z")
+        );
+        assert!(page.creation_date.is_some());
+
+        let page = TriagePage::new_from_api_result(&json!({
+            "page_id": 5, "title": "Foo", "patrol_status": 3, "is_redirect": true,
+            "reviewer": "Admin"
+        }))
+        .unwrap();
+        assert_eq!(page.patrol_status, AUTOPATROLLED);
+        assert!(page.is_reviewed() && page.is_redirect);
+    }
+}
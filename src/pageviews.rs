@@ -0,0 +1,244 @@
+/*!
+Typed access to the [pageviews](https://wikitech.wikimedia.org/wiki/Analytics/AQS/Pageviews)
+endpoints of the Wikimedia REST API: daily or monthly views of an article, and the most viewed
+articles of a wiki.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::rest::rest_title;
+use crate::timestamp::{parse_db_timestamp, parse_timestamp, Timestamp};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+/// The kind of access to count views for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PageviewsAccess {
+    /// All access methods
+    AllAccess,
+    /// Desktop website
+    Desktop,
+    /// Mobile apps
+    MobileApp,
+    /// Mobile website
+    MobileWeb,
+}
+
+impl PageviewsAccess {
+    /// Returns the value used in URLs
+    pub fn as_str(&self) -> &str {
+        match self {
+            PageviewsAccess::AllAccess => "all-access",
+            PageviewsAccess::Desktop => "desktop",
+            PageviewsAccess::MobileApp => "mobile-app",
+            PageviewsAccess::MobileWeb => "mobile-web",
+        }
+    }
+}
+
+/// The kind of agent to count views for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PageviewsAgent {
+    /// All agents
+    AllAgents,
+    /// Human users
+    User,
+    /// Search engine crawlers
+    Spider,
+    /// Automated traffic that is not a known crawler
+    Automated,
+}
+
+impl PageviewsAgent {
+    /// Returns the value used in URLs
+    pub fn as_str(&self) -> &str {
+        match self {
+            PageviewsAgent::AllAgents => "all-agents",
+            PageviewsAgent::User => "user",
+            PageviewsAgent::Spider => "spider",
+            PageviewsAgent::Automated => "automated",
+        }
+    }
+}
+
+/// The time span of a data point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PageviewsGranularity {
+    /// Views per day
+    Daily,
+    /// Views per month
+    Monthly,
+}
+
+impl PageviewsGranularity {
+    /// Returns the value used in URLs
+    pub fn as_str(&self) -> &str {
+        match self {
+            PageviewsGranularity::Daily => "daily",
+            PageviewsGranularity::Monthly => "monthly",
+        }
+    }
+}
+
+/// The views of an article in a day or month
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ArticleViews {
+    /// Article title, with underscores
+    pub article: String,
+    /// Start of the day or month
+    #[serde(deserialize_with = "hour_timestamp")]
+    pub timestamp: Timestamp,
+    /// Number of views
+    pub views: u64,
+}
+
+/// An article in a list of most viewed articles
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TopArticle {
+    /// Article title, with underscores
+    pub article: String,
+    /// Number of views
+    pub views: u64,
+    /// Rank, starting with 1
+    pub rank: u64,
+}
+
+impl Api {
+    /// Returns the project name for the pageviews endpoints, e.g. "en.wikipedia.org"
+    pub fn pageviews_project(&self) -> String {
+        let general = &self.get_site_info().general;
+        match &general.servername {
+            Some(servername) => servername.clone(),
+            None => general
+                .server
+                .to_absolute()
+                .split("//")
+                .last()
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+
+    /// Returns the views of an article of this wiki per day or month, between `start` and `end`
+    /// (inclusive, as "YYYYMMDD")
+    ///
+    /// # Errors
+    /// Returns a `RestError` if there is no data (HTTP 404), or any error from the request.
+    pub fn pageviews_per_article(
+        &self,
+        title: &str,
+        access: PageviewsAccess,
+        agent: PageviewsAgent,
+        granularity: PageviewsGranularity,
+        start: &str,
+        end: &str,
+    ) -> Result<Vec<ArticleViews>, Box<dyn Error + Send + Sync>> {
+        let path = format!(
+            "pageviews/per-article/{}/{}/{}/{}/{}/{}/{}",
+            self.pageviews_project(),
+            access.as_str(),
+            agent.as_str(),
+            rest_title(title),
+            granularity.as_str(),
+            start,
+            end
+        );
+        let result = self.metrics(&path)?;
+        Ok(Vec::<ArticleViews>::deserialize(&result["items"])?)
+    }
+
+    /// Returns the (up to 1000) most viewed articles of this wiki in a day, or in a month if `day`
+    /// is `None`
+    ///
+    /// # Errors
+    /// Returns a `RestError` if there is no data (HTTP 404), or any error from the request.
+    pub fn pageviews_top(
+        &self,
+        access: PageviewsAccess,
+        year: u32,
+        month: u32,
+        day: Option<u32>,
+    ) -> Result<Vec<TopArticle>, Box<dyn Error + Send + Sync>> {
+        let day = match day {
+            Some(day) => format!("{:02}", day),
+            None => "all-days".to_string(),
+        };
+        let path = format!(
+            "pageviews/top/{}/{}/{}/{:02}/{}",
+            self.pageviews_project(),
+            access.as_str(),
+            year,
+            month,
+            day
+        );
+        let result = self.metrics(&path)?;
+        top_articles_from_result(&result)
+    }
+}
+
+/// Deserializes a timestamp of the metrics API, "YYYYMMDDHH" (or ISO 8601, as serialized by this
+/// crate)
+fn hour_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_db_timestamp(&format!("{}0000", s))
+        .or_else(|| parse_timestamp(&s))
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp: {}", s)))
+}
+
+/// Parses the articles of a `pageviews/top` result
+fn top_articles_from_result(
+    result: &Value,
+) -> Result<Vec<TopArticle>, Box<dyn Error + Send + Sync>> {
+    match result["items"].get(0) {
+        Some(item) => Ok(Vec::<TopArticle>::deserialize(&item["articles"])?),
+        None => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    #[test]
+    fn project() {
+        let api = test_api();
+        assert_eq!(api.pageviews_project(), "de.wikipedia.org");
+    }
+
+    #[test]
+    fn results() {
+        let views: Vec<ArticleViews> = serde_json::from_value(json!([{
+            "project": "en.wikipedia", "article": "Albert_Einstein", "granularity": "daily",
+            "timestamp": "2015100100", "access": "all-access", "agent": "all-agents", "views": 18860
+        }]))
+        .unwrap();
+        assert_eq!(views[0].views, 18860);
+        assert_eq!(
+            Some(&views[0].timestamp),
+            parse_timestamp("2015-10-01T00:00:00Z").as_ref()
+        );
+        let roundtrip: Vec<ArticleViews> = serde_json::from_value(json!(views)).unwrap();
+        assert_eq!(roundtrip, views);
+        let top = top_articles_from_result(&json!({"items": [{
+            "project": "en.wikipedia", "access": "all-access", "year": "2020", "month": "01",
+            "day": "all-days",
+            "articles": [{"article": "Main_Page", "views": 500000000, "rank": 1}]
+        }]}))
+        .unwrap();
+        assert_eq!(top[0].article, "Main_Page");
+        assert!(top_articles_from_result(&json!({})).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,575 @@
+/*!
+Metadata of API modules from `action=paraminfo`: their parameters, with types and allowed values.
+With `Api::set_param_validation`, the parameters of every query are checked against this metadata
+before it is sent, so that typos like `titels=` are caught locally instead of via API warnings.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Maximum number of modules per `action=paraminfo` request
+const PARAMINFO_MODULES_LIMIT: usize = 50;
+
+/// Modules whose parameters are allowed in every query
+const GLOBAL_MODULES: &[&str] = &["main", "json"];
+
+/// The type of an API parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamType {
+    /// One of a list of values (or several, for multi-value parameters)
+    Values(Vec<String>),
+    /// An integer, optionally with limits
+    Integer {
+        /// Minimum value
+        min: Option<i64>,
+        /// Maximum value
+        max: Option<i64>,
+    },
+    /// A number of results, or "max"
+    Limit,
+    /// A flag, which is set if the parameter is present
+    Boolean,
+    /// A namespace ID
+    Namespace,
+    /// Any other type, e.g. "string", "timestamp" or "user"
+    Other(String),
+}
+
+/// A parameter of an API module
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamInfo {
+    /// Name, without the module prefix
+    pub name: String,
+    /// Type of the value
+    pub param_type: ParamType,
+    /// Whether several values can be given, separated by "|"
+    pub multi: bool,
+    /// Whether the parameter must be given
+    pub required: bool,
+    /// Whether the parameter is deprecated
+    pub deprecated: bool,
+    /// Default value, if any
+    pub default: Option<Value>,
+}
+
+impl ParamInfo {
+    /// Parses a parameter of a `formatversion=2` paraminfo result
+    pub fn new_from_api_result(param: &Value) -> Option<ParamInfo> {
+        let param_type = match &param["type"] {
+            Value::Array(values) => ParamType::Values(
+                values
+                    .iter()
+                    .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                    .collect(),
+            ),
+            Value::String(t) => match t.as_str() {
+                "integer" => ParamType::Integer {
+                    min: param["min"].as_i64(),
+                    max: param["highmax"].as_i64().or_else(|| param["max"].as_i64()),
+                },
+                "limit" => ParamType::Limit,
+                "boolean" => ParamType::Boolean,
+                "namespace" => ParamType::Namespace,
+                other => ParamType::Other(other.to_string()),
+            },
+            _ => ParamType::Other(String::new()),
+        };
+        Some(ParamInfo {
+            name: param["name"].as_str()?.to_string(),
+            param_type,
+            multi: param["multi"].as_bool().unwrap_or(false),
+            required: param["required"].as_bool().unwrap_or(false),
+            deprecated: param["deprecated"].as_bool().unwrap_or(false),
+            default: param.get("default").cloned(),
+        })
+    }
+
+    /// Checks a value of this parameter; returns a description of the expected values if it is
+    /// invalid
+    pub fn check_value(&self, value: &str) -> Result<(), String> {
+        let values: Vec<&str> = if !self.multi {
+            vec![value]
+        } else if let Some(value) = value.strip_prefix('\u{1f}') {
+            value.split('\u{1f}').collect()
+        } else {
+            value.split('|').collect()
+        };
+        for value in values {
+            let valid = match &self.param_type {
+                ParamType::Values(allowed) => {
+                    allowed.iter().any(|v| v == value)
+                        || (self.multi && value == "*" && !allowed.is_empty())
+                }
+                ParamType::Integer { min, max } => value.parse::<i64>().is_ok_and(|i| {
+                    min.is_none_or(|min| i >= min) && max.is_none_or(|max| i <= max)
+                }),
+                ParamType::Limit => value == "max" || value.parse::<u64>().is_ok(),
+                ParamType::Namespace => value == "*" || value.parse::<i64>().is_ok(),
+                ParamType::Boolean | ParamType::Other(_) => true,
+            };
+            if !valid {
+                return Err(self.expected());
+            }
+        }
+        Ok(())
+    }
+
+    /// Describes the values this parameter accepts
+    fn expected(&self) -> String {
+        match &self.param_type {
+            ParamType::Values(allowed) => format!("one of {}", allowed.join(", ")),
+            ParamType::Integer { min, max } => match (min, max) {
+                (Some(min), Some(max)) => format!("an integer from {} to {}", min, max),
+                (Some(min), None) => format!("an integer of at least {}", min),
+                (None, Some(max)) => format!("an integer of at most {}", max),
+                (None, None) => "an integer".to_string(),
+            },
+            ParamType::Limit => "a number or \"max\"".to_string(),
+            ParamType::Namespace => "a namespace ID".to_string(),
+            ParamType::Boolean => "a flag".to_string(),
+            ParamType::Other(t) => t.clone(),
+        }
+    }
+}
+
+/// The metadata of an API module
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleInfo {
+    /// Name, e.g. "categorymembers"
+    pub name: String,
+    /// Path, e.g. "query+categorymembers"
+    pub path: String,
+    /// Prefix of the parameters, e.g. "cm"
+    pub prefix: String,
+    /// Whether the module must be used with POST
+    pub must_be_posted: bool,
+    /// Whether the module changes data
+    pub write: bool,
+    /// Parameters
+    pub parameters: Vec<ParamInfo>,
+    /// Names of parameters that depend on other parameters, with placeholders in braces, e.g.
+    /// "{module}-{key}"
+    pub templated_parameters: Vec<String>,
+}
+
+impl ModuleInfo {
+    /// Parses a module of a `formatversion=2` paraminfo result
+    pub fn new_from_api_result(module: &Value) -> Option<ModuleInfo> {
+        let prefix = module["prefix"].as_str().unwrap_or_default().to_string();
+        Some(ModuleInfo {
+            name: module["name"].as_str()?.to_string(),
+            path: module["path"].as_str()?.to_string(),
+            must_be_posted: module["mustbeposted"].as_bool().unwrap_or(false),
+            write: module["writerights"].as_bool().unwrap_or(false),
+            parameters: module["parameters"]
+                .as_array()
+                .map(|params| {
+                    params
+                        .iter()
+                        .filter_map(ParamInfo::new_from_api_result)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            templated_parameters: module["templatedparameters"]
+                .as_array()
+                .map(|params| {
+                    params
+                        .iter()
+                        .filter_map(|param| param["name"].as_str())
+                        .map(|name| format!("{}{}", prefix, name))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            prefix,
+        })
+    }
+
+    /// Returns the parameter with the full name (including prefix) `name`, if any
+    pub fn parameter(&self, name: &str) -> Option<&ParamInfo> {
+        let name = name.strip_prefix(&self.prefix)?;
+        self.parameters.iter().find(|param| param.name == name)
+    }
+
+    /// Checks if `name` matches one of the templated parameters
+    fn matches_templated(&self, name: &str) -> bool {
+        self.templated_parameters
+            .iter()
+            .any(|template| matches_template(template, name))
+    }
+}
+
+/// A parameter error found by `Api::validate_params`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParamError {
+    /// No module of the query has this parameter
+    UnknownParameter {
+        /// The parameter name
+        name: String,
+        /// A known parameter with a similar name, if any
+        suggestion: Option<String>,
+    },
+
+    /// The value is not valid for the parameter
+    InvalidValue {
+        /// The parameter name
+        name: String,
+        /// The value
+        value: String,
+        /// A description of the valid values
+        expected: String,
+    },
+
+    /// A required parameter is missing
+    MissingParameter(String),
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::UnknownParameter {
+                name,
+                suggestion: Some(suggestion),
+            } => {
+                write!(
+                    f,
+                    "unknown parameter {} (did you mean {}?)",
+                    name, suggestion
+                )
+            }
+            ParamError::UnknownParameter {
+                name,
+                suggestion: None,
+            } => write!(f, "unknown parameter {}", name),
+            ParamError::InvalidValue {
+                name,
+                value,
+                expected,
+            } => {
+                write!(
+                    f,
+                    "invalid value {:?} for parameter {}, expected {}",
+                    value, name, expected
+                )
+            }
+            ParamError::MissingParameter(name) => write!(f, "missing required parameter {}", name),
+        }
+    }
+}
+
+impl Error for ParamError {}
+
+impl Api {
+    /// Loads the metadata of API modules, given by path (e.g. "edit" or "query+categorymembers").
+    /// Unknown modules are left out.
+    pub fn paraminfo(
+        &self,
+        modules: &[&str],
+    ) -> Result<Vec<ModuleInfo>, Box<dyn Error + Send + Sync>> {
+        let mut ret = vec![];
+        for chunk in modules.chunks(PARAMINFO_MODULES_LIMIT) {
+            let params = self.params_into(&[
+                ("action", "paraminfo"),
+                ("modules", &chunk.join("|")),
+                ("formatversion", "2"),
+            ]);
+            let result = self.get_query_api_json(&params)?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!("action=paraminfo failed: {}", error)));
+            }
+            if let Some(modules) = result["paraminfo"]["modules"].as_array() {
+                ret.extend(modules.iter().filter_map(ModuleInfo::new_from_api_result));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Checks the names and values of query parameters against the metadata of the modules they
+    /// use, which is loaded once and cached.
+    ///
+    /// # Errors
+    /// Returns a `ParamError` for the first problem found, or any error from loading the metadata.
+    pub fn validate_params(
+        &self,
+        params: &HashMap<String, String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Validating a paraminfo query would require a paraminfo query
+        if params
+            .get("action")
+            .is_some_and(|action| action == "paraminfo")
+        {
+            return Ok(());
+        }
+        let paths = module_paths(params);
+        let missing: Vec<&str> = {
+            let cache = self.param_info_cache();
+            paths
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .filter(|path| !cache.contains_key(*path))
+                .collect()
+        };
+        if !missing.is_empty() {
+            let loaded = self.paraminfo(&missing)?;
+            let mut cache = self.param_info_cache();
+            for path in missing {
+                let module = loaded.iter().find(|module| module.path == path).cloned();
+                cache.insert(path.to_string(), module);
+            }
+        }
+        let cache = self.param_info_cache();
+        let modules: Vec<(&ModuleInfo, &str)> = paths
+            .iter()
+            .filter_map(|(path, prefix)| Some((cache.get(path)?.as_ref()?, prefix.as_str())))
+            .collect();
+        check_params(params, &modules).map_err(From::from)
+    }
+}
+
+/// Returns the paths of the modules used by a query, with the extra prefix of their parameters
+/// ("g" for generators)
+fn module_paths(params: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut paths: Vec<(String, String)> = GLOBAL_MODULES
+        .iter()
+        .map(|m| (m.to_string(), String::new()))
+        .collect();
+    let action = params.get("action").map(|s| s.as_str()).unwrap_or("help");
+    paths.push((action.to_string(), String::new()));
+    if action == "query" {
+        for key in ["prop", "list", "meta"] {
+            if let Some(value) = params.get(key) {
+                for module in value.split('|').filter(|m| !m.is_empty()) {
+                    paths.push((format!("query+{}", module), String::new()));
+                }
+            }
+        }
+        if let Some(generator) = params.get("generator") {
+            paths.push((format!("query+{}", generator), "g".to_string()));
+        }
+    }
+    paths
+}
+
+/// Checks query parameters against modules, each with an extra prefix for its parameters
+fn check_params(
+    params: &HashMap<String, String>,
+    modules: &[(&ModuleInfo, &str)],
+) -> Result<(), ParamError> {
+    let mut names: Vec<&String> = params.keys().collect();
+    names.sort();
+    for name in names {
+        let value = &params[name];
+        let param = modules
+            .iter()
+            .find_map(|(module, extra)| module.parameter(name.strip_prefix(extra)?));
+        match param {
+            Some(param) => {
+                param
+                    .check_value(value)
+                    .map_err(|expected| ParamError::InvalidValue {
+                        name: name.clone(),
+                        value: value.clone(),
+                        expected,
+                    })?
+            }
+            None if modules.iter().any(|(module, extra)| {
+                name.strip_prefix(extra)
+                    .is_some_and(|n| module.matches_templated(n))
+            }) => {}
+            None => {
+                return Err(ParamError::UnknownParameter {
+                    name: name.clone(),
+                    suggestion: suggestion(name, modules),
+                })
+            }
+        }
+    }
+    for (module, extra) in modules {
+        for param in module.parameters.iter().filter(|param| param.required) {
+            let name = format!("{}{}{}", extra, module.prefix, param.name);
+            if !params.contains_key(&name) {
+                return Err(ParamError::MissingParameter(name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the known parameter name that is most similar to `name`, if it differs in at most two
+/// characters
+fn suggestion(name: &str, modules: &[(&ModuleInfo, &str)]) -> Option<String> {
+    modules
+        .iter()
+        .flat_map(|(module, extra)| {
+            module
+                .parameters
+                .iter()
+                .map(move |param| format!("{}{}{}", extra, module.prefix, param.name))
+        })
+        .map(|known| (edit_distance(name, &known), known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+/// Returns the Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// Checks if a parameter name matches a template with placeholders in braces, e.g. "{module}-{key}"
+fn matches_template(template: &str, name: &str) -> bool {
+    let mut pieces = vec![];
+    let mut rest = template;
+    while let Some((fixed, after)) = rest.split_once('{') {
+        pieces.push(fixed);
+        rest = after
+            .split_once('}')
+            .map(|(_, after)| after)
+            .unwrap_or_default();
+    }
+    pieces.push(rest);
+    let (first, last) = (pieces[0], pieces[pieces.len() - 1]);
+    if pieces.len() == 1 {
+        return template == name;
+    }
+    let mut middle = match name
+        .strip_prefix(first)
+        .and_then(|name| name.strip_suffix(last))
+    {
+        Some(middle) => middle,
+        None => return false,
+    };
+    for piece in &pieces[1..pieces.len() - 1] {
+        match middle.find(piece) {
+            Some(pos) => middle = &middle[pos + piece.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(value: Value) -> ModuleInfo {
+        ModuleInfo::new_from_api_result(&value).unwrap()
+    }
+
+    #[test]
+    fn validation() {
+        let main = module(
+            json!({"name": "main", "path": "main", "prefix": "", "parameters": [
+                {"name": "action", "type": ["query", "edit"]},
+                {"name": "maxlag", "type": "integer"}
+            ]}),
+        );
+        let query = module(
+            json!({"name": "query", "path": "query", "prefix": "", "parameters": [
+                {"name": "list", "type": ["categorymembers", "search"], "multi": true},
+                {"name": "titles", "type": "string", "multi": true}
+            ]}),
+        );
+        let members = module(
+            json!({"name": "categorymembers", "path": "query+categorymembers", "prefix": "cm",
+                "parameters": [
+                    {"name": "title", "type": "string"},
+                    {"name": "limit", "type": "limit"},
+                    {"name": "namespace", "type": "namespace", "multi": true}
+                ],
+                "templatedparameters": [{"name": "x-{key}", "type": "string"}]
+            }),
+        );
+        let modules = vec![(&main, ""), (&query, ""), (&members, ""), (&members, "g")];
+        let params = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let valid = params(&[
+            ("action", "query"),
+            ("list", "categorymembers|search"),
+            ("cmlimit", "max"),
+            ("gcmnamespace", "0|14"),
+            ("cmx-foo", "1"),
+        ]);
+        assert_eq!(check_params(&valid, &modules), Ok(()));
+        let typo = params(&[("action", "query"), ("titels", "Foo")]);
+        assert_eq!(
+            check_params(&typo, &modules),
+            Err(ParamError::UnknownParameter {
+                name: "titels".to_string(),
+                suggestion: Some("titles".to_string())
+            })
+        );
+        let invalid = params(&[("action", "query"), ("list", "categorymembers|nope")]);
+        assert!(matches!(
+            check_params(&invalid, &modules),
+            Err(ParamError::InvalidValue { .. })
+        ));
+        let invalid = params(&[("action", "query"), ("maxlag", "five")]);
+        assert!(matches!(
+            check_params(&invalid, &modules),
+            Err(ParamError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn paths() {
+        let params: HashMap<String, String> = vec![
+            ("action", "query"),
+            ("prop", "info|revisions"),
+            ("generator", "allpages"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let paths: Vec<String> = module_paths(&params)
+            .into_iter()
+            .map(|(path, extra)| format!("{}{}", extra, path))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "main",
+                "json",
+                "query",
+                "query+info",
+                "query+revisions",
+                "gquery+allpages"
+            ]
+        );
+    }
+}
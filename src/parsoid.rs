@@ -0,0 +1,179 @@
+/*!
+Round-tripping of page content through [Parsoid](https://www.mediawiki.org/wiki/Parsoid) HTML, via
+the REST API: load the HTML of a page, edit its DOM, and convert the edited HTML back to wikitext,
+the way VisualEditor does. The HTML is stashed on the server when it is loaded, so that unchanged
+parts keep their exact wikitext.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::rest::rest_title;
+use reqwest::header::{ETAG, IF_MATCH};
+use serde_json::Value;
+use std::error::Error;
+
+/// The Parsoid HTML of a page revision
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsoidHtml {
+    /// Page title
+    pub title: String,
+    /// The HTML document
+    pub html: String,
+    /// ETag of the HTML, which identifies the revision and the render
+    pub etag: Option<String>,
+}
+
+impl ParsoidHtml {
+    /// Returns the revision ID, from the ETag
+    pub fn revision(&self) -> Option<u64> {
+        parse_etag(self.etag.as_deref()?)?.0.parse().ok()
+    }
+
+    /// Returns the ID of the render (time UUID), from the ETag
+    pub fn render_id(&self) -> Option<&str> {
+        Some(parse_etag(self.etag.as_deref()?)?.1)
+    }
+}
+
+impl Api {
+    /// Loads the Parsoid HTML of a page, of the latest revision or of `revision`
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page or revision does not exist, or any error from the request.
+    pub fn parsoid_html(
+        &self,
+        title: &str,
+        revision: Option<u64>,
+    ) -> Result<ParsoidHtml, Box<dyn Error + Send + Sync>> {
+        let mut url = format!("{}page/html/{}", self.rest_v1_url(), rest_title(title));
+        if let Some(revision) = revision {
+            url += &format!("/{}", revision);
+        }
+        url += "?stash=true";
+        let response = self.rest_get(&url, "text/html; charset=utf-8")?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_string());
+        Ok(ParsoidHtml {
+            title: title.to_string(),
+            html: response.text()?,
+            etag,
+        })
+    }
+
+    /// Loads the `data-parsoid` information of Parsoid HTML, which maps the HTML to wikitext source
+    /// ranges
+    ///
+    /// # Errors
+    /// Returns an error if the HTML has no ETag, or any error from the request.
+    pub fn parsoid_data(&self, html: &ParsoidHtml) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let (revision, render_id) = match html.etag.as_deref().and_then(parse_etag) {
+            Some(parts) => parts,
+            None => return Err(From::from("Parsoid HTML has no ETag")),
+        };
+        let url = format!(
+            "{}page/data-parsoid/{}/{}/{}",
+            self.rest_v1_url(),
+            rest_title(&html.title),
+            revision,
+            render_id
+        );
+        Ok(self.rest_get(&url, "application/json")?.json()?)
+    }
+
+    /// Converts edited Parsoid HTML of a page back to wikitext. `original` is the HTML as loaded;
+    /// its ETag lets Parsoid keep the exact wikitext of unchanged parts.
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the HTML can't be converted (e.g. the stash expired), or any error
+    /// from the request.
+    pub fn parsoid_html_to_wikitext(
+        &self,
+        original: &ParsoidHtml,
+        edited_html: &str,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut url = format!(
+            "{}transform/html/to/wikitext/{}",
+            self.rest_v1_url(),
+            rest_title(&original.title)
+        );
+        if let Some(revision) = original.revision() {
+            url += &format!("/{}", revision);
+        }
+        let params = self.params_into(&[("html", edited_html)]);
+        let response = self.rest_send(|| {
+            let builder = self.request_builder(&url, &params, "POST")?;
+            Ok(match &original.etag {
+                Some(etag) => builder.header(IF_MATCH, etag.as_str()),
+                None => builder,
+            })
+        })?;
+        Ok(response.text()?)
+    }
+
+    /// Converts wikitext to Parsoid HTML, as if it were the content of the page `title`
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the wikitext can't be converted, or any error from the request.
+    pub fn parsoid_wikitext_to_html(
+        &self,
+        title: &str,
+        wikitext: &str,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let url = format!(
+            "{}transform/wikitext/to/html/{}",
+            self.rest_v1_url(),
+            rest_title(title)
+        );
+        let params = self.params_into(&[("wikitext", wikitext)]);
+        let response = self.rest_send(|| self.request_builder(&url, &params, "POST"))?;
+        Ok(response.text()?)
+    }
+}
+
+/// Splits an ETag of Parsoid HTML, e.g. `W/"951281331/1a4c0c30-9b6a-11ea-8f0e-9d3d1b2b9c1a"`,
+/// into the revision ID and the render ID
+fn parse_etag(etag: &str) -> Option<(&str, &str)> {
+    let etag = etag.trim_start_matches("W/").trim_matches('"');
+    let (revision, render_id) = etag.split_once('/')?;
+    let render_id = render_id.split('/').next()?;
+    Some((revision, render_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etags() {
+        let mut html = ParsoidHtml {
+            title: "Foo".to_string(),
+            html: "<html></html>".to_string(),
+            etag: Some(r#"W/"951281331/1a4c0c30-9b6a-11ea-8f0e-9d3d1b2b9c1a""#.to_string()),
+        };
+        assert_eq!(html.revision(), Some(951281331));
+        assert_eq!(
+            html.render_id(),
+            Some("1a4c0c30-9b6a-11ea-8f0e-9d3d1b2b9c1a")
+        );
+        html.etag = Some(r#""951281331/1a4c0c30/html/2.1.0""#.to_string());
+        assert_eq!(html.render_id(), Some("1a4c0c30"));
+        html.etag = Some("invalid".to_string());
+        assert_eq!(html.revision(), None);
+        html.etag = None;
+        assert_eq!(html.render_id(), None);
+    }
+}
@@ -0,0 +1,306 @@
+/*!
+Preferences of the logged-in user, read via `meta=userinfo&uiprop=options` and changed via
+`action=options`, e.g. for bots that configure their own accounts. With the
+[GlobalPreferences](https://www.mediawiki.org/wiki/Extension:GlobalPreferences) extension,
+preferences can be set for all wikis of a farm at once, via `meta=globalpreferences` and
+`action=globalpreferences`.
+
+# Examples
+
+```no_run
+# use mediawiki::api::Api;
+# let api = Api::new("https://en.wikipedia.org/w/api.php").unwrap();
+# api.login("MyBot@Task", "password").unwrap();
+let preferences = api.user_preferences().unwrap();
+if !preferences.get_bool("disablemail") {
+    api.set_user_preferences(&[
+        ("disablemail", Some("1")),
+        ("timecorrection", Some("ZoneInfo|60|Europe/Berlin")),
+    ])
+    .unwrap();
+}
+```
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Separator for multiple values of a parameter, if values may contain "|"
+const MULTI_VALUE_SEPARATOR: char = '\u{1f}';
+
+/// The preferences of a user, by name (e.g. "language", "timecorrection", "disablemail")
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserPreferences {
+    options: HashMap<String, Value>,
+}
+
+impl UserPreferences {
+    /// Parses the `options` object of a `meta=userinfo` result
+    pub fn new_from_api_result(options: &Value) -> UserPreferences {
+        UserPreferences {
+            options: options
+                .as_object()
+                .map(|options| {
+                    options
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.clone()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns the raw value of a preference
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.options.get(name)
+    }
+
+    /// Returns a preference as a string; numbers and booleans are converted as `action=options`
+    /// expects them
+    pub fn get_string(&self, name: &str) -> Option<String> {
+        match self.options.get(name)? {
+            Value::String(s) => Some(s.to_string()),
+            Value::Bool(b) => Some(if *b { "1" } else { "0" }.to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Returns a checkbox preference; unset, empty, "0" and `false` are off
+    pub fn get_bool(&self, name: &str) -> bool {
+        match self.options.get(name) {
+            Some(Value::Bool(b)) => *b,
+            Some(Value::Number(n)) => n.as_f64() != Some(0.0),
+            Some(Value::String(s)) => !s.is_empty() && s != "0",
+            _ => false,
+        }
+    }
+
+    /// Returns the names of all preferences
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.options.keys().map(|name| name.as_str())
+    }
+}
+
+/// The global preferences of a user, from `meta=globalpreferences`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GlobalPreferences {
+    /// Preferences that apply to all wikis
+    pub preferences: UserPreferences,
+    /// Preferences of the current wiki that override global ones
+    pub local_overrides: UserPreferences,
+}
+
+impl GlobalPreferences {
+    /// Parses the `globalpreferences` object of a `meta=globalpreferences` result
+    pub fn new_from_api_result(result: &Value) -> GlobalPreferences {
+        GlobalPreferences {
+            preferences: UserPreferences::new_from_api_result(&result["preferences"]),
+            local_overrides: UserPreferences::new_from_api_result(&result["localoverrides"]),
+        }
+    }
+}
+
+impl Api {
+    /// Loads the preferences of the logged-in user
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. when not logged in), or any error from
+    /// the request.
+    pub fn user_preferences(&self) -> Result<UserPreferences, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "meta" => "userinfo",
+            "uiprop" => "options",
+            "formatversion" => "2",
+        ];
+        let result = self.get_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("Could not load preferences: {}", error)));
+        }
+        Ok(UserPreferences::new_from_api_result(
+            &result["query"]["userinfo"]["options"],
+        ))
+    }
+
+    /// Changes preferences of the logged-in user; a value of `None` resets a preference to its
+    /// default
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn set_user_preferences(
+        &self,
+        changes: &[(&str, Option<&str>)],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let change = options_change_param(changes);
+        let token = self.get_edit_token()?;
+        self.post_options(params_map!["action" => "options", "change" => change, "token" => token])
+    }
+
+    /// Loads the global preferences of the logged-in user, and the local overrides on this wiki
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the user has no global preferences,
+    /// or the GlobalPreferences extension is not installed), or any error from the request.
+    pub fn global_preferences(&self) -> Result<GlobalPreferences, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "meta" => "globalpreferences",
+            "gprprop" => "preferences|localoverrides",
+            "formatversion" => "2",
+        ];
+        let result = self.get_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!(
+                "Could not load global preferences: {}",
+                error
+            )));
+        }
+        Ok(GlobalPreferences::new_from_api_result(
+            &result["query"]["globalpreferences"],
+        ))
+    }
+
+    /// Changes global preferences of the logged-in user, for all wikis; a value of `None` resets a
+    /// preference
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn set_global_preferences(
+        &self,
+        changes: &[(&str, Option<&str>)],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let change = options_change_param(changes);
+        let token = self.get_edit_token()?;
+        self.post_options(
+            params_map!["action" => "globalpreferences", "change" => change, "token" => token],
+        )
+    }
+
+    /// Resets preferences of the logged-in user to their defaults, for `kinds` of preferences
+    /// ("registered", "registered-multiselect", "registered-checkmatrix", "userjs", "special",
+    /// "unused"), or all preferences if `kinds` is empty
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn reset_user_preferences(
+        &self,
+        kinds: &[&str],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let kinds = if kinds.is_empty() {
+            "all".to_string()
+        } else {
+            kinds.join("|")
+        };
+        let token = self.get_edit_token()?;
+        self.post_options(params_map![
+            "action" => "options",
+            "reset" => true,
+            "resetkinds" => kinds,
+            "token" => token,
+        ])
+    }
+
+    /// Posts an `action=options` or `action=globalpreferences` request, and checks the result
+    fn post_options(
+        &self,
+        params: HashMap<String, String>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let action = params["action"].clone();
+        let result = self.post_query_api_json(&params)?;
+        match result[&action].as_str() {
+            Some("success") => Ok(()),
+            _ => Err(From::from(format!(
+                "action={} failed: {:?}",
+                action, result
+            ))),
+        }
+    }
+}
+
+/// Returns the `change` parameter for `action=options`: "name=value" or just "name" (to reset),
+/// separated by "|", or by U+001F if a value contains "|"
+fn options_change_param(changes: &[(&str, Option<&str>)]) -> String {
+    let changes: Vec<String> = changes
+        .iter()
+        .map(|(name, value)| match value {
+            Some(value) => format!("{}={}", name, value),
+            None => name.to_string(),
+        })
+        .collect();
+    if changes.iter().any(|change| change.contains('|')) {
+        changes
+            .iter()
+            .map(|change| format!("{}{}", MULTI_VALUE_SEPARATOR, change))
+            .collect()
+    } else {
+        changes.join("|")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preferences() {
+        let preferences = UserPreferences::new_from_api_result(&json!({
+            "language": "de", "disablemail": 1, "minordefault": false, "watchdefault": "1",
+            "timecorrection": "ZoneInfo|60|Europe/Berlin", "rclimit": 50, "gender": ""
+        }));
+        assert_eq!(preferences.get_string("language").as_deref(), Some("de"));
+        assert!(preferences.get_bool("disablemail"));
+        assert!(preferences.get_bool("watchdefault"));
+        assert!(!preferences.get_bool("minordefault"));
+        assert!(!preferences.get_bool("gender"));
+        assert!(!preferences.get_bool("nonexistent"));
+        assert_eq!(preferences.get_string("rclimit").as_deref(), Some("50"));
+        assert_eq!(preferences.names().count(), 7);
+    }
+
+    #[test]
+    fn global_preferences() {
+        let preferences = GlobalPreferences::new_from_api_result(&json!({
+            "preferences": {"language": "de", "disablemail": "1"},
+            "localoverrides": {"language": "en"}
+        }));
+        assert!(preferences.preferences.get_bool("disablemail"));
+        assert_eq!(
+            preferences
+                .local_overrides
+                .get_string("language")
+                .as_deref(),
+            Some("en")
+        );
+    }
+
+    #[test]
+    fn change_param() {
+        assert_eq!(
+            options_change_param(&[("disablemail", Some("1")), ("skin", None)]),
+            "disablemail=1|skin"
+        );
+        assert_eq!(
+            options_change_param(&[
+                ("disablemail", Some("1")),
+                ("timecorrection", Some("ZoneInfo|60|Europe/Berlin"))
+            ]),
+            "\u{1f}disablemail=1\u{1f}timecorrection=ZoneInfo|60|Europe/Berlin"
+        );
+    }
+}
@@ -0,0 +1,352 @@
+/*!
+Proofreading on Wikisource, with the
+[ProofreadPage](https://www.mediawiki.org/wiki/Extension:Proofread_Page) extension: the quality
+levels of pages in the Page namespace (`prop=proofread`), also for all pages of an Index page, and
+`ProofreadPageText` to read and set the quality level in the text of a page when editing it.
+
+# Examples
+
+```no_run
+# use mediawiki::api::Api;
+# use mediawiki::page::Page;
+# use mediawiki::proofreadpage::{ProofreadPageText, QualityLevel};
+# use mediawiki::title::Title;
+# let api = Api::new("https://en.wikisource.org/w/api.php").unwrap();
+let title = "Page:Example.djvu/12";
+let page = Page::new(Title::new_from_full(title, &api));
+let mut text = ProofreadPageText::parse(&page.text(&api).unwrap());
+text.body = text.body.replace("tlie", "the");
+text.set_quality(QualityLevel::Proofread, "MyBot");
+api.edit_proofread_page(title, &text, "proofread").unwrap();
+```
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::page::PageError;
+use crate::params_map;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Maximum number of titles per `prop=proofread` request
+const TITLES_LIMIT: usize = 50;
+
+/// The proofreading status of a page in the Page namespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QualityLevel {
+    /// The page has no text to transcribe (level 0)
+    WithoutText,
+    /// The text has not been proofread (level 1)
+    NotProofread,
+    /// The text has a problem that needs attention (level 2)
+    Problematic,
+    /// The text has been proofread once (level 3)
+    Proofread,
+    /// The text has been proofread by a second user (level 4)
+    Validated,
+}
+
+impl QualityLevel {
+    /// Returns the quality level with the number `level`, if any
+    pub fn new_from_level(level: u64) -> Option<QualityLevel> {
+        match level {
+            0 => Some(QualityLevel::WithoutText),
+            1 => Some(QualityLevel::NotProofread),
+            2 => Some(QualityLevel::Problematic),
+            3 => Some(QualityLevel::Proofread),
+            4 => Some(QualityLevel::Validated),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of the quality level, as used in page text and API results
+    pub fn level(&self) -> u8 {
+        match self {
+            QualityLevel::WithoutText => 0,
+            QualityLevel::NotProofread => 1,
+            QualityLevel::Problematic => 2,
+            QualityLevel::Proofread => 3,
+            QualityLevel::Validated => 4,
+        }
+    }
+}
+
+impl fmt::Display for QualityLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QualityLevel::WithoutText => "Without text",
+            QualityLevel::NotProofread => "Not proofread",
+            QualityLevel::Problematic => "Problematic",
+            QualityLevel::Proofread => "Proofread",
+            QualityLevel::Validated => "Validated",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The quality level of a page in the Page namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageQuality {
+    /// Full title of the page
+    pub title: String,
+    /// The quality level, or `None` if the page does not exist yet
+    pub quality: Option<QualityLevel>,
+}
+
+impl PageQuality {
+    /// Parses a page of a `prop=proofread` result (`formatversion=2`)
+    pub fn new_from_api_result(page: &Value) -> Option<PageQuality> {
+        Some(PageQuality {
+            title: page["title"].as_str()?.to_string(),
+            quality: page["proofread"]["quality"]
+                .as_u64()
+                .and_then(QualityLevel::new_from_level),
+        })
+    }
+}
+
+/// The text of a page in the Page namespace: a header and a footer (not transcluded) around the
+/// body, and the quality level with the user who set it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofreadPageText {
+    /// The header, e.g. a running header template
+    pub header: String,
+    /// The transcribed text
+    pub body: String,
+    /// The footer, e.g. `<references/>`
+    pub footer: String,
+    /// The quality level
+    pub quality: QualityLevel,
+    /// The user who set the quality level
+    pub user: String,
+}
+
+impl ProofreadPageText {
+    /// Parses the wikitext of a page in the Page namespace, as returned by the API:
+    /// `<noinclude><pagequality level="1" user="..."
+    /// />header</noinclude>body<noinclude>footer</noinclude>`. Text without this structure is the
+    /// body of a page that is not proofread.
+    pub fn parse(text: &str) -> ProofreadPageText {
+        let mut ret = ProofreadPageText {
+            header: String::new(),
+            body: text.to_string(),
+            footer: String::new(),
+            quality: QualityLevel::NotProofread,
+            user: String::new(),
+        };
+        let rest = match text.strip_prefix("<noinclude>") {
+            Some(rest) => rest,
+            None => return ret,
+        };
+        let (header, rest) = match rest.split_once("</noinclude>") {
+            Some(parts) => parts,
+            None => return ret,
+        };
+        let (body, footer) = match rest.rfind("<noinclude>") {
+            Some(pos) if rest.ends_with("</noinclude>") => (
+                &rest[..pos],
+                &rest[pos + "<noinclude>".len()..rest.len() - "</noinclude>".len()],
+            ),
+            _ => (rest, ""),
+        };
+        ret.header = header.to_string();
+        if let Some(start) = header.find("<pagequality ") {
+            if let Some(len) = header[start..].find("/>") {
+                let tag = &header[start..start + len];
+                if let Some(quality) = tag_attribute(tag, "level")
+                    .and_then(|level| level.parse().ok())
+                    .and_then(QualityLevel::new_from_level)
+                {
+                    ret.quality = quality;
+                }
+                ret.user = tag_attribute(tag, "user").unwrap_or_default().to_string();
+                ret.header = format!("{}{}", &header[..start], &header[start + len + 2..]);
+            }
+        }
+        ret.body = body.to_string();
+        ret.footer = footer.to_string();
+        ret
+    }
+
+    /// Sets the quality level, and the user who sets it (usually the user that edits)
+    pub fn set_quality(&mut self, quality: QualityLevel, user: &str) {
+        self.quality = quality;
+        self.user = user.to_string();
+    }
+
+    /// Returns the wikitext of the page
+    pub fn to_wikitext(&self) -> String {
+        format!(
+            "<noinclude><pagequality level=\"{}\" user=\"{}\" />{}</noinclude>\
+             {}<noinclude>{}</noinclude>",
+            self.quality.level(),
+            self.user.replace('"', "&quot;"),
+            self.header,
+            self.body,
+            self.footer
+        )
+    }
+}
+
+impl Api {
+    /// Loads the quality levels of pages in the Page namespace, in batches.
+    /// The results are in the order of the API, not of `titles`.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the extension is not installed),
+    /// or any error from the requests.
+    pub fn page_qualities(
+        &self,
+        titles: &[&str],
+    ) -> Result<Vec<PageQuality>, Box<dyn Error + Send + Sync>> {
+        let mut ret = Vec::with_capacity(titles.len());
+        for chunk in titles.chunks(TITLES_LIMIT) {
+            let params = params_map![
+                "action" => "query",
+                "prop" => "proofread",
+                "titles" => chunk,
+                "formatversion" => "2",
+            ];
+            ret.extend(self.page_qualities_query(&params)?);
+        }
+        Ok(ret)
+    }
+
+    /// Loads the quality levels of all pages of the Index page `index` (e.g. "Index:Example.djvu")
+    /// that exist. The results are in the order of the API, not in the order of the index.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the index does not exist),
+    /// or any error from the requests.
+    pub fn index_page_qualities(
+        &self,
+        index: &str,
+    ) -> Result<Vec<PageQuality>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "generator" => "proofreadpagesinindex",
+            "gprppiititle" => index,
+            "prop" => "proofread",
+            "formatversion" => "2",
+        ];
+        self.page_qualities_query(&params)
+    }
+
+    /// Saves the text of a page in the Page namespace, with its quality level
+    ///
+    /// # Errors
+    /// Returns `PageError::EditError` if the edit failed (e.g. the user may not set this quality
+    /// level), or any error from the request.
+    pub fn edit_proofread_page(
+        &self,
+        title: &str,
+        text: &ProofreadPageText,
+        summary: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "edit",
+            "title" => title,
+            "text" => text.to_wikitext(),
+            "summary" => summary,
+            "formatversion" => "2",
+            "token" => self.get_edit_token()?,
+        ];
+        let result = self.post_query_api_json(&params)?;
+        match result["edit"]["result"].as_str() {
+            Some("Success") => Ok(()),
+            _ => Err(Box::new(PageError::EditError(result))),
+        }
+    }
+
+    /// Runs a query with `prop=proofread`, and parses the qualities of all pages
+    fn page_qualities_query(
+        &self,
+        params: &HashMap<String, String>,
+    ) -> Result<Vec<PageQuality>, Box<dyn Error + Send + Sync>> {
+        let mut ret = vec![];
+        for result in self.get_query_api_json_limit_iter(params, None) {
+            let result = result?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!("prop=proofread failed: {}", error)));
+            }
+            ret.extend(qualities_from_api_result(&result));
+        }
+        Ok(ret)
+    }
+}
+
+/// Parses the pages of a `prop=proofread` result
+fn qualities_from_api_result(result: &Value) -> Vec<PageQuality> {
+    result["query"]["pages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(PageQuality::new_from_api_result)
+        .collect()
+}
+
+/// Returns the value of the attribute `name` of an HTML-like tag, in double quotes
+fn tag_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualities() {
+        let result = json!({"query": {"pages": [
+            {"pageid": 1, "ns": 104, "title": "Page:Example.djvu/1",
+             "proofread": {"quality": 4, "quality_text": "Validated"}},
+            {"ns": 104, "title": "Page:Example.djvu/2", "missing": true}
+        ]}});
+        let pages = qualities_from_api_result(&result);
+        assert_eq!(pages[0].quality, Some(QualityLevel::Validated));
+        assert_eq!(pages[1].quality, None);
+        assert_eq!(QualityLevel::Proofread.to_string(), "Proofread");
+        assert!(QualityLevel::Validated > QualityLevel::Proofread);
+    }
+
+    #[test]
+    fn page_text() {
+        let wikitext =
+            "<noinclude><pagequality level=\"1\" user=\"Example\" />{{rh||Title|}}</noinclude>\
+                        Some text.<noinclude><references/></noinclude>";
+        let mut text = ProofreadPageText::parse(wikitext);
+        assert_eq!(text.header, "{{rh||Title|}}");
+        assert_eq!(text.body, "Some text.");
+        assert_eq!(text.footer, "<references/>");
+        assert_eq!(
+            (text.quality, text.user.as_str()),
+            (QualityLevel::NotProofread, "Example")
+        );
+        assert_eq!(text.to_wikitext(), wikitext);
+
+        text.set_quality(QualityLevel::Proofread, "MyBot");
+        assert!(text
+            .to_wikitext()
+            .starts_with("<noinclude><pagequality level=\"3\" user=\"MyBot\" />"));
+
+        let text = ProofreadPageText::parse("Just text");
+        assert_eq!(
+            (text.body.as_str(), text.quality),
+            ("Just text", QualityLevel::NotProofread)
+        );
+    }
+}
@@ -0,0 +1,402 @@
+/*!
+The `RecentChange` class is a typed change event, as found in `list=recentchanges` results
+and in the `recentchange` stream of EventStreams.
+The `RecentChangesPoller` class yields new changes of a wiki by polling `list=recentchanges`,
+for wikis without EventStreams.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::timestamp::{parse_timestamp, timestamp_from_unix, Timestamp};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+
+/// A change on a wiki: an edit, a page creation, a log entry, or a category change
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RecentChange {
+    /// ID of the recent change
+    pub rcid: Option<u64>,
+    /// Type of the change: "edit", "new", "log", "categorize", or "external"
+    pub change_type: String,
+    /// Namespace of the page
+    pub namespace: NamespaceID,
+    /// Full title of the page, with namespace prefix
+    pub title: String,
+    /// Name of the user, or the IP address
+    pub user: String,
+    /// Whether the change was made by a bot
+    pub bot: bool,
+    /// Whether the edit was marked as minor
+    pub minor: bool,
+    /// Whether the change is patrolled, if known
+    pub patrolled: Option<bool>,
+    /// Time of the change
+    pub timestamp: Option<Timestamp>,
+    /// Edit summary, or log comment
+    pub comment: String,
+    /// Revision ID before the edit
+    pub old_revid: Option<u64>,
+    /// Revision ID of the edit
+    pub revid: Option<u64>,
+    /// Page length before the edit, in bytes
+    pub old_length: Option<u64>,
+    /// Page length after the edit, in bytes
+    pub new_length: Option<u64>,
+    /// Log type, for log entries
+    pub log_type: Option<String>,
+    /// Log action, for log entries
+    pub log_action: Option<String>,
+    /// Database name of the wiki (e.g. "enwiki"), if known
+    pub wiki: Option<String>,
+}
+
+impl RecentChange {
+    /// Parses a change from a `list=recentchanges` result entry (`formatversion=2`).
+    /// Returns `None` if there is no title.
+    pub fn new_from_api_result(rc: &Value) -> Option<RecentChange> {
+        Some(RecentChange {
+            rcid: rc["rcid"].as_u64(),
+            change_type: rc["type"].as_str().unwrap_or_default().to_string(),
+            namespace: rc["ns"].as_i64().unwrap_or_default(),
+            title: rc["title"].as_str()?.to_string(),
+            user: string(&rc["user"]),
+            bot: flag(&rc["bot"]),
+            minor: flag(&rc["minor"]),
+            patrolled: rc
+                .get("patrolled")
+                .map(flag)
+                .or_else(|| rc.get("unpatrolled").map(|_| false)),
+            timestamp: rc["timestamp"].as_str().and_then(parse_timestamp),
+            comment: string(&rc["comment"]),
+            old_revid: nonzero(&rc["old_revid"]),
+            revid: nonzero(&rc["revid"]),
+            old_length: rc["oldlen"].as_u64(),
+            new_length: rc["newlen"].as_u64(),
+            log_type: rc["logtype"].as_str().map(|s| s.to_string()),
+            log_action: rc["logaction"].as_str().map(|s| s.to_string()),
+            wiki: None,
+        })
+    }
+
+    /// Parses a change from an event of the `recentchange` stream of EventStreams.
+    /// Returns `None` if there is no title.
+    pub fn new_from_stream_event(event: &Value) -> Option<RecentChange> {
+        Some(RecentChange {
+            rcid: event["id"].as_u64(),
+            change_type: event["type"].as_str().unwrap_or_default().to_string(),
+            namespace: event["namespace"].as_i64().unwrap_or_default(),
+            title: event["title"].as_str()?.to_string(),
+            user: string(&event["user"]),
+            bot: flag(&event["bot"]),
+            minor: flag(&event["minor"]),
+            patrolled: event["patrolled"].as_bool(),
+            timestamp: event["timestamp"].as_i64().and_then(timestamp_from_unix),
+            comment: string(&event["comment"]),
+            old_revid: nonzero(&event["revision"]["old"]),
+            revid: nonzero(&event["revision"]["new"]),
+            old_length: event["length"]["old"].as_u64(),
+            new_length: event["length"]["new"].as_u64(),
+            log_type: event["log_type"].as_str().map(|s| s.to_string()),
+            log_action: event["log_action"].as_str().map(|s| s.to_string()),
+            wiki: event["wiki"].as_str().map(|s| s.to_string()),
+        })
+    }
+}
+
+/// Yields new changes of a wiki, by polling `list=recentchanges`. It remembers the time of the last
+/// change, skips changes it has already seen, and waits longer between polls while the wiki is
+/// idle. Iterating over it blocks until the next change arrives.
+#[derive(Debug)]
+pub struct RecentChangesPoller<'a> {
+    api: &'a Api,
+    params: HashMap<String, String>,
+    last_timestamp: Option<String>,
+    seen: HashSet<u64>,
+    queue: VecDeque<RecentChange>,
+    min_delay: Duration,
+    max_delay: Duration,
+    delay: Duration,
+    wiki: Option<String>,
+}
+
+impl<'a> RecentChangesPoller<'a> {
+    /// Creates a poller that starts after the newest change
+    pub fn new(api: &'a Api) -> RecentChangesPoller<'a> {
+        let params = api.params_into(&[
+            ("action", "query"),
+            ("list", "recentchanges"),
+            (
+                "rcprop",
+                "title|ids|sizes|flags|user|timestamp|comment|loginfo",
+            ),
+            ("rclimit", "max"),
+            ("formatversion", "2"),
+        ]);
+        RecentChangesPoller {
+            api,
+            params,
+            last_timestamp: None,
+            seen: HashSet::new(),
+            queue: VecDeque::new(),
+            min_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+            delay: Duration::from_secs(2),
+            wiki: api.get_site_info().general.wikiid.clone(),
+        }
+    }
+
+    /// Starts at a time (an API timestamp, e.g. "2020-04-14T10:22:31Z") instead of after the newest
+    /// change, e.g. the `last_timestamp` of an earlier run
+    pub fn set_start(&mut self, timestamp: &str) {
+        self.last_timestamp = Some(timestamp.to_string());
+        self.seen.clear();
+    }
+
+    /// Returns the time of the last change that was seen
+    pub fn last_timestamp(&self) -> Option<&str> {
+        self.last_timestamp.as_deref()
+    }
+
+    /// Only yields changes in these namespaces
+    pub fn set_namespaces(&mut self, namespaces: &[NamespaceID]) {
+        let namespaces: Vec<String> = namespaces.iter().map(|ns| ns.to_string()).collect();
+        self.params
+            .insert("rcnamespace".to_string(), namespaces.join("|"));
+    }
+
+    /// Only yields changes of these types, e.g. "edit", "new", "log"
+    pub fn set_types(&mut self, types: &[&str]) {
+        self.params.insert("rctype".to_string(), types.join("|"));
+    }
+
+    /// Sets the minimum and maximum delay between polls; the delay doubles after each poll without
+    /// new changes
+    pub fn set_delays(&mut self, min_delay: Duration, max_delay: Duration) {
+        self.min_delay = min_delay;
+        self.max_delay = max_delay.max(min_delay);
+        self.delay = min_delay;
+    }
+
+    /// Loads the changes since the last one, and queues the new ones
+    fn poll(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut params = self.params.clone();
+        match &self.last_timestamp {
+            Some(timestamp) => {
+                params.insert("rcdir".to_string(), "newer".to_string());
+                params.insert("rcstart".to_string(), timestamp.to_string());
+            }
+            None => {
+                // Start after the newest change
+                params.insert("rcdir".to_string(), "older".to_string());
+                params.insert("rclimit".to_string(), "1".to_string());
+                let result = self.api.get_query_api_json(&params)?;
+                self.accept(&result["query"]["recentchanges"]);
+                self.queue.clear();
+                return Ok(());
+            }
+        }
+        for result in self.api.get_query_api_json_limit_iter(&params, None) {
+            self.accept(&result?["query"]["recentchanges"]);
+        }
+        Ok(())
+    }
+
+    /// Returns how long to wait before the next poll, and adjusts the delay. There is no wait after
+    /// new changes, or after the first poll if it found the newest change to start from; without
+    /// any changes on the wiki, the delay applies from the start.
+    fn delay_after_poll(&mut self, first_poll: bool) -> Option<Duration> {
+        if !self.queue.is_empty() {
+            self.delay = self.min_delay;
+            return None;
+        }
+        if first_poll && self.last_timestamp.is_some() {
+            return None;
+        }
+        let delay = self.delay;
+        self.delay = (self.delay * 2).min(self.max_delay);
+        Some(delay)
+    }
+
+    /// Queues the changes of a result that were not seen yet, oldest first, and remembers them
+    fn accept(&mut self, changes: &Value) {
+        for rc in changes.as_array().into_iter().flatten() {
+            let timestamp = match rc["timestamp"].as_str() {
+                Some(timestamp) => timestamp,
+                None => continue,
+            };
+            if self.last_timestamp.as_deref() != Some(timestamp) {
+                self.last_timestamp = Some(timestamp.to_string());
+                self.seen.clear();
+            }
+            if let Some(rcid) = rc["rcid"].as_u64() {
+                if !self.seen.insert(rcid) {
+                    continue;
+                }
+            }
+            if let Some(mut change) = RecentChange::new_from_api_result(rc) {
+                change.wiki = self.wiki.clone();
+                self.queue.push_back(change);
+            }
+        }
+    }
+}
+
+impl Iterator for RecentChangesPoller<'_> {
+    type Item = Result<RecentChange, Box<dyn Error + Send + Sync>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(change) = self.queue.pop_front() {
+                return Some(Ok(change));
+            }
+            let first_poll = self.last_timestamp.is_none();
+            if let Err(e) = self.poll() {
+                return Some(Err(e));
+            }
+            if let Some(delay) = self.delay_after_poll(first_poll) {
+                thread::sleep(delay);
+            }
+        }
+    }
+}
+
+impl Api {
+    /// Returns a poller for new changes of the wiki, as an alternative to EventStreams
+    pub fn recent_changes_poller(&self) -> RecentChangesPoller<'_> {
+        RecentChangesPoller::new(self)
+    }
+}
+
+/// Returns a string value, or an empty string
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+/// Returns a flag, which is a boolean, or an empty string if set in `formatversion=1`
+fn flag(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Null => false,
+        _ => true,
+    }
+}
+
+/// Returns a revision ID; 0 means there is none
+fn nonzero(value: &Value) -> Option<u64> {
+    value.as_u64().filter(|&id| id > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    #[test]
+    fn from_api_result() {
+        let rc = json!({"type": "edit", "ns": 1, "title": "Talk:Foo", "pageid": 12, "revid": 101,
+            "old_revid": 100,
+            "rcid": 555, "user": "Example", "bot": false, "new": false, "minor": true, "oldlen": 10,
+            "newlen": 12,
+            "timestamp": "2020-04-14T10:22:31Z", "comment": "typo", "patrolled": true});
+        let change = RecentChange::new_from_api_result(&rc).unwrap();
+        assert_eq!(change.rcid, Some(555));
+        assert_eq!(change.namespace, 1);
+        assert!(change.minor && !change.bot);
+        assert_eq!((change.old_revid, change.revid), (Some(100), Some(101)));
+        assert_eq!(change.patrolled, Some(true));
+        assert_eq!(change.timestamp, parse_timestamp("2020-04-14T10:22:31Z"));
+
+        let rc = json!({"type": "new", "ns": 0, "title": "Bar", "revid": 7, "old_revid": 0,
+            "rcid": 9,
+            "user": "Bot", "bot": true, "timestamp": "2020-04-14T10:22:31Z", "comment": "",
+            "unpatrolled": true});
+        let change = RecentChange::new_from_api_result(&rc).unwrap();
+        assert_eq!(change.old_revid, None);
+        assert_eq!(change.patrolled, Some(false));
+    }
+
+    #[test]
+    fn from_stream_event() {
+        let event = json!({"$schema": "/mediawiki/recentchange/1.0.0", "id": 555, "type": "edit",
+            "namespace": 1,
+            "title": "Talk:Foo", "comment": "typo", "timestamp": 1586859751, "user": "Example",
+            "bot": false,
+            "minor": true, "patrolled": true, "length": {"old": 10, "new": 12},
+            "revision": {"old": 100, "new": 101},
+            "server_name": "en.wikipedia.org", "wiki": "enwiki",
+            "meta": {"domain": "en.wikipedia.org", "stream": "mediawiki.recentchange"}});
+        let change = RecentChange::new_from_stream_event(&event).unwrap();
+        let rc = json!({"type": "edit", "ns": 1, "title": "Talk:Foo", "revid": 101,
+            "old_revid": 100,
+            "rcid": 555, "user": "Example", "bot": false, "minor": true, "oldlen": 10, "newlen": 12,
+            "timestamp": "2020-04-14T10:22:31Z", "comment": "typo", "patrolled": true});
+        let from_api = RecentChange::new_from_api_result(&rc).unwrap();
+        assert_eq!(change.wiki.as_deref(), Some("enwiki"));
+        assert_eq!(
+            RecentChange {
+                wiki: None,
+                ..change
+            },
+            from_api
+        );
+    }
+
+    #[test]
+    fn poller_dedupes() {
+        let api = test_api();
+        let mut poller = api.recent_changes_poller();
+        poller.set_start("2020-04-14T10:22:31Z");
+        let rc = |rcid: u64, timestamp: &str| {
+            json!({"type": "edit", "ns": 0,
+            "title": format!("Page {}", rcid), "rcid": rcid, "timestamp": timestamp})
+        };
+        poller.accept(&json!([
+            rc(1, "2020-04-14T10:22:31Z"),
+            rc(2, "2020-04-14T10:22:31Z")
+        ]));
+        // rcstart is inclusive, so the last changes are returned again
+        poller.accept(&json!([
+            rc(2, "2020-04-14T10:22:31Z"),
+            rc(3, "2020-04-14T10:22:32Z")
+        ]));
+        poller.accept(&json!([rc(3, "2020-04-14T10:22:32Z")]));
+        let rcids: Vec<u64> = poller.queue.iter().filter_map(|c| c.rcid).collect();
+        assert_eq!(rcids, vec![1, 2, 3]);
+        assert_eq!(poller.last_timestamp(), Some("2020-04-14T10:22:32Z"));
+        assert_eq!(poller.next().unwrap().unwrap().title, "Page 1");
+    }
+
+    #[test]
+    fn poller_delays() {
+        let api = test_api();
+        let mut poller = api.recent_changes_poller();
+        poller.set_delays(Duration::from_secs(1), Duration::from_secs(3));
+        // No changes on the wiki yet: wait before polling again
+        assert_eq!(poller.delay_after_poll(true), Some(Duration::from_secs(1)));
+        assert_eq!(poller.delay_after_poll(true), Some(Duration::from_secs(2)));
+        // The first poll found the newest change to start from
+        poller.set_start("2020-04-14T10:22:31Z");
+        assert_eq!(poller.delay_after_poll(true), None);
+        assert_eq!(poller.delay_after_poll(false), Some(Duration::from_secs(3)));
+        assert_eq!(poller.delay_after_poll(false), Some(Duration::from_secs(3)));
+        poller.accept(&json!([{"type": "edit", "ns": 0, "title": "Foo", "rcid": 1,
+            "timestamp": "2020-04-14T10:22:32Z"}]));
+        assert_eq!(poller.delay_after_poll(false), None);
+        assert_eq!(poller.delay, Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,378 @@
+/*!
+Access to the [Wikimedia REST API](https://www.mediawiki.org/wiki/Wikimedia_REST_API)
+(`/api/rest_v1/`) of a wiki: page summaries, mobile HTML, media lists and related pages, as well as
+the metrics endpoints (e.g. pageviews) on wikimedia.org. Requests share the user agent and retry
+settings of the `Api`; the cookies and OAuth header of the session are only sent to the wiki itself,
+not to wikimedia.org.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::sparql;
+use crate::timestamp::Timestamp;
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+/// The base URL of the REST API on wikimedia.org, for endpoints that are not specific to a wiki
+/// (e.g. metrics)
+pub const WIKIMEDIA_REST_API: &str = "https://wikimedia.org/api/rest_v1/";
+
+/// Errors from REST endpoints
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RestError {
+    /// Too many requests (HTTP 429 or 503), and the retry attempts are used up.
+    /// The wait time requested by the server is provided, if any.
+    TooManyRequests(Option<Duration>),
+
+    /// Any other HTTP error, e.g. 404 for a missing page; the status code and the response body are
+    /// provided.
+    Http {
+        /// HTTP status code
+        status: u16,
+        /// Response body, usually a JSON problem description
+        body: String,
+    },
+}
+
+impl RestError {
+    /// Returns the HTTP status code of the error
+    pub fn status(&self) -> u16 {
+        match self {
+            RestError::TooManyRequests(_) => 429,
+            RestError::Http { status, .. } => *status,
+        }
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestError::TooManyRequests(Some(wait)) => {
+                write!(
+                    f,
+                    "REST API: too many requests, retry after {} s",
+                    wait.as_secs()
+                )
+            }
+            RestError::TooManyRequests(None) => write!(f, "REST API: too many requests"),
+            RestError::Http { status, body } => {
+                write!(f, "REST API returned HTTP {}: {}", status, body)
+            }
+        }
+    }
+}
+
+impl Error for RestError {}
+
+/// An image in a page summary
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RestImage {
+    /// URL of the image
+    pub source: String,
+    /// Width in pixels
+    pub width: u64,
+    /// Height in pixels
+    pub height: u64,
+}
+
+/// The summary of a page, from `page/summary`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PageSummary {
+    /// Page title, with underscores
+    pub title: String,
+    /// Display title, as HTML
+    #[serde(default)]
+    pub displaytitle: String,
+    /// Type of summary: "standard", "disambiguation", "mainpage", or "no-extract"
+    #[serde(rename = "type", default)]
+    pub summary_type: String,
+    /// Page ID
+    pub pageid: Option<u64>,
+    /// Short description (e.g. from Wikidata)
+    pub description: Option<String>,
+    /// Plain-text extract of the lead section
+    #[serde(default)]
+    pub extract: String,
+    /// Extract as HTML
+    pub extract_html: Option<String>,
+    /// Thumbnail of the lead image
+    pub thumbnail: Option<RestImage>,
+    /// Original of the lead image
+    pub originalimage: Option<RestImage>,
+    /// Language code of the page content
+    pub lang: Option<String>,
+    /// Revision ID of the summarized revision
+    pub revision: Option<String>,
+    /// Time of the summarized revision
+    pub timestamp: Option<Timestamp>,
+}
+
+/// A caption of a media item
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MediaCaption {
+    /// Caption as HTML
+    #[serde(default)]
+    pub html: String,
+    /// Caption as plain text
+    #[serde(default)]
+    pub text: String,
+}
+
+/// A source URL of a media item, for a pixel density
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MediaSource {
+    /// URL of the image
+    pub src: String,
+    /// Pixel density, e.g. "1x"
+    pub scale: String,
+}
+
+/// An image, video or audio file used on a page, from `page/media-list`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MediaItem {
+    /// File title, with namespace prefix
+    pub title: Option<String>,
+    /// Media type: "image", "video", or "audio"
+    #[serde(rename = "type")]
+    pub media_type: String,
+    /// ID of the section the file is used in
+    pub section_id: Option<i64>,
+    /// Whether the file is the lead image of the page
+    #[serde(rename = "leadImage", default)]
+    pub lead_image: bool,
+    /// Whether the file should be shown in a gallery
+    #[serde(rename = "showInGallery", default)]
+    pub show_in_gallery: bool,
+    /// Caption on the page
+    pub caption: Option<MediaCaption>,
+    /// Thumbnail URLs
+    #[serde(default)]
+    pub srcset: Vec<MediaSource>,
+}
+
+impl Api {
+    /// Returns the base URL of the REST API of the wiki, e.g.
+    /// "https://en.wikipedia.org/api/rest_v1/"
+    pub fn rest_v1_url(&self) -> String {
+        format!(
+            "{}/api/rest_v1/",
+            self.get_site_info().general.server.to_absolute()
+        )
+    }
+
+    /// Returns the summary of a page
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page does not exist, or any error from the request.
+    pub fn page_summary(&self, title: &str) -> Result<PageSummary, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}page/summary/{}", self.rest_v1_url(), rest_title(title));
+        Ok(self.rest_get(&url, "application/json")?.json()?)
+    }
+
+    /// Returns the HTML of a page, as optimized for mobile devices
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page does not exist, or any error from the request.
+    pub fn page_mobile_html(&self, title: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let url = format!(
+            "{}page/mobile-html/{}",
+            self.rest_v1_url(),
+            rest_title(title)
+        );
+        Ok(self.rest_get(&url, "text/html")?.text()?)
+    }
+
+    /// Returns the images, videos and audio files used on a page
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page does not exist, or any error from the request.
+    pub fn page_media_list(
+        &self,
+        title: &str,
+    ) -> Result<Vec<MediaItem>, Box<dyn Error + Send + Sync>> {
+        let url = format!(
+            "{}page/media-list/{}",
+            self.rest_v1_url(),
+            rest_title(title)
+        );
+        let result: Value = self.rest_get(&url, "application/json")?.json()?;
+        Ok(Vec::<MediaItem>::deserialize(&result["items"])?)
+    }
+
+    /// Returns summaries of pages related to a page
+    ///
+    /// # Errors
+    /// Returns a `RestError` if the page does not exist, or any error from the request.
+    pub fn page_related(
+        &self,
+        title: &str,
+    ) -> Result<Vec<PageSummary>, Box<dyn Error + Send + Sync>> {
+        let url = format!("{}page/related/{}", self.rest_v1_url(), rest_title(title));
+        let result: Value = self.rest_get(&url, "application/json")?.json()?;
+        Ok(Vec::<PageSummary>::deserialize(&result["pages"])?)
+    }
+
+    /// Queries a metrics endpoint on wikimedia.org, given its path below `metrics/`,
+    /// e.g. "pageviews/aggregate/en.wikipedia/all-access/user/daily/20200101/20200131"
+    ///
+    /// # Errors
+    /// Returns a `RestError` if there is no data, or any error from the request.
+    pub fn metrics(&self, path: &str) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let url = format!(
+            "{}metrics/{}",
+            WIKIMEDIA_REST_API,
+            path.trim_start_matches('/')
+        );
+        Ok(self.rest_get_off_wiki(&url, "application/json")?.json()?)
+    }
+
+    /// Sends a GET request to a REST endpoint; see `rest_send`
+    pub(crate) fn rest_get(
+        &self,
+        url: &str,
+        accept: &str,
+    ) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        self.rest_send(|| {
+            Ok(self
+                .request_builder(url, &self.no_params(), "GET")?
+                .header(reqwest::header::ACCEPT, accept))
+        })
+    }
+
+    /// Sends a GET request to a REST endpoint that is not on the wiki (e.g. on wikimedia.org),
+    /// without the cookies or OAuth header of the session; see `rest_send`
+    pub(crate) fn rest_get_off_wiki(
+        &self,
+        url: &str,
+        accept: &str,
+    ) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        self.rest_send(|| {
+            Ok(self
+                .plain_request_builder(url, &self.no_params(), "GET")?
+                .header(reqwest::header::ACCEPT, accept))
+        })
+    }
+
+    /// Sends a request to a REST endpoint, built by `build` for every attempt. Retries up to
+    /// `max_retry_attempts` times if the server is overloaded. Unsuccessful responses (except "304
+    /// Not Modified") are returned as a `RestError`.
+    pub(crate) fn rest_send<F>(&self, build: F) -> Result<Response, Box<dyn Error + Send + Sync>>
+    where
+        F: Fn() -> Result<RequestBuilder, Box<dyn Error + Send + Sync>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let response = build()?.send()?;
+            let status = response.status();
+            if status.is_success() || status == StatusCode::NOT_MODIFIED {
+                return Ok(response);
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+            {
+                let retry_after = sparql::retry_after(response.headers());
+                if attempt >= self.max_retry_attempts() {
+                    return Err(Box::new(RestError::TooManyRequests(retry_after)));
+                }
+                attempt += 1;
+                thread::sleep(retry_after.unwrap_or(sparql::DEFAULT_RETRY_AFTER));
+                continue;
+            }
+            let body = response.text().unwrap_or_default();
+            return Err(Box::new(RestError::Http {
+                status: status.as_u16(),
+                body,
+            }));
+        }
+    }
+}
+
+/// Encodes a title for a REST URL path: spaces become underscores, and everything else
+/// (including slashes) is percent-encoded
+pub(crate) fn rest_title(title: &str) -> String {
+    urlencoding::encode(&title.trim().replace(' ', "_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    #[test]
+    fn urls() {
+        let api = test_api();
+        assert_eq!(api.rest_v1_url(), "https://de.wikipedia.org/api/rest_v1/");
+        assert_eq!(rest_title("AC/DC"), "AC%2FDC");
+        assert_eq!(rest_title("Main Page"), "Main_Page");
+    }
+
+    #[test]
+    fn summary() {
+        let summary: PageSummary = serde_json::from_value(json!({
+            "type": "standard",
+            "title": "Foo_Bar",
+            "displaytitle": "<i>Foo</i> Bar",
+            "pageid": 123,
+            "lang": "en",
+            "revision": "950842311",
+            "description": "Example",
+            "thumbnail": {"source": "https://upload.wikimedia.org/x.jpg", "width": 320,
+            "height": 240},
+            "extract": "Foo Bar is an example.",
+            "timestamp": "2020-04-10T18:42:15Z",
+            "content_urls": {}
+        }))
+        .unwrap();
+        assert_eq!(summary.summary_type, "standard");
+        assert_eq!(
+            summary.timestamp,
+            crate::timestamp::parse_timestamp("2020-04-10T18:42:15Z")
+        );
+        assert_eq!(summary.thumbnail.unwrap().width, 320);
+        assert_eq!(summary.originalimage, None);
+    }
+
+    #[test]
+    fn media_list() {
+        let items: Vec<MediaItem> = serde_json::from_value(json!([{
+            "title": "File:Example.jpg",
+            "leadImage": true,
+            "section_id": 0,
+            "type": "image",
+            "showInGallery": true,
+            "caption": {"html": "<b>Example</b>", "text": "Example"},
+            "srcset": [{"src": "//upload.wikimedia.org/x.jpg", "scale": "1x"}]
+        }]))
+        .unwrap();
+        assert!(items[0].lead_image);
+        assert_eq!(items[0].caption.as_ref().unwrap().text, "Example");
+        assert_eq!(items[0].srcset[0].scale, "1x");
+    }
+
+    #[test]
+    fn errors() {
+        let error = RestError::Http {
+            status: 404,
+            body: "{}".to_string(),
+        };
+        assert_eq!(error.status(), 404);
+        assert_eq!(RestError::TooManyRequests(None).status(), 429);
+    }
+}
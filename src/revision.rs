@@ -0,0 +1,233 @@
+/*!
+The `Revision` struct is a typed representation of a page revision, as returned by `prop=revisions`.
+Its content and the diff to its parent revision can be loaded lazily.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A revision of a page, from a `formatversion=2` `prop=revisions` result.
+/// Which fields are set depends on `rvprop`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Revision {
+    /// Revision ID
+    pub revid: u64,
+    /// ID of the previous revision; 0 for page creations
+    pub parentid: Option<u64>,
+    /// Whether the revision was marked as minor
+    #[serde(default)]
+    pub minor: bool,
+    /// Name of the editor
+    pub user: Option<String>,
+    /// User ID of the editor; 0 for anonymous edits
+    pub userid: Option<u64>,
+    /// Time of the revision
+    pub timestamp: Option<Timestamp>,
+    /// Size of the revision, in bytes
+    pub size: Option<u64>,
+    /// SHA-1 hash of the revision content, in hexadecimal
+    pub sha1: Option<String>,
+    /// Edit summary
+    pub comment: Option<String>,
+    /// Change tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Content slots, keyed by role (usually just "main")
+    #[serde(default)]
+    pub slots: HashMap<String, Slot>,
+    /// Whether the editor has been hidden (revision deleted)
+    #[serde(default)]
+    pub userhidden: bool,
+    /// Whether the edit summary has been hidden (revision deleted)
+    #[serde(default)]
+    pub commenthidden: bool,
+}
+
+/// A content slot of a `Revision`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Slot {
+    /// Content model, e.g. "wikitext"
+    pub contentmodel: Option<String>,
+    /// Content format, e.g. "text/x-wiki"
+    pub contentformat: Option<String>,
+    /// Content, if requested via `rvprop=content`
+    pub content: Option<String>,
+    /// Size of the slot content, in bytes
+    pub size: Option<u64>,
+    /// SHA-1 hash of the slot content, in hexadecimal
+    pub sha1: Option<String>,
+    /// Whether the content has been hidden (revision deleted)
+    #[serde(default)]
+    pub texthidden: bool,
+}
+
+impl Revision {
+    /// Parses the `revisions` array of a page object in a `formatversion=2` API result
+    pub fn new_from_page_result(page: &Value) -> Result<Vec<Revision>, serde_json::Error> {
+        match page.get("revisions") {
+            Some(revisions) => Vec::<Revision>::deserialize(revisions),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns the content of the "main" slot, or of the only slot, if loaded
+    pub fn main_content(&self) -> Option<&str> {
+        let slot = match self.slots.get("main") {
+            Some(slot) => slot,
+            None if self.slots.len() == 1 => self.slots.values().next()?,
+            None => return None,
+        };
+        slot.content.as_deref()
+    }
+
+    /// Checks if this revision created the page
+    pub fn is_page_creation(&self) -> bool {
+        self.parentid == Some(0)
+    }
+
+    /// Returns the content of the "main" slot; it is loaded from the API unless already loaded
+    ///
+    /// # Errors
+    /// Returns an error if the content is hidden or missing, or any error from the request.
+    pub fn content(&self, api: &Api) -> Result<String, Box<dyn Error + Send + Sync>> {
+        if let Some(content) = self.main_content() {
+            return Ok(content.to_string());
+        }
+        let params = params_map![
+            "action" => "query",
+            "prop" => "revisions",
+            "revids" => self.revid,
+            "rvprop" => "content",
+            "rvslots" => "main",
+            "formatversion" => "2",
+        ];
+        let result = api.get_query_api_json(&params)?;
+        Revision::new_from_page_result(&result["query"]["pages"][0])?
+            .into_iter()
+            .find_map(|revision| revision.main_content().map(|content| content.to_string()))
+            .ok_or_else(|| {
+                From::from(format!(
+                    "No content for revision {}: {:?}",
+                    self.revid, result
+                ))
+            })
+    }
+
+    /// Returns the diff from the previous revision of the page to this one, as HTML table rows
+    /// (as on a diff page, without the surrounding table). Returns `None` for page creations.
+    ///
+    /// # Errors
+    /// Returns an error if the diff can't be generated (e.g. for hidden content), or any error from
+    /// the requests.
+    pub fn diff_to_parent(
+        &self,
+        api: &Api,
+    ) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+        let parentid = match self.parentid {
+            Some(parentid) => parentid,
+            None => self.load_parentid(api)?,
+        };
+        if parentid == 0 {
+            return Ok(None);
+        }
+        let params = params_map![
+            "action" => "compare",
+            "fromrev" => parentid,
+            "torev" => self.revid,
+            "prop" => "diff",
+            "formatversion" => "2",
+        ];
+        let result = api.get_query_api_json(&params)?;
+        match result["compare"]["body"].as_str() {
+            Some(body) => Ok(Some(body.to_string())),
+            None => Err(From::from(format!(
+                "No diff for revision {}: {:?}",
+                self.revid, result
+            ))),
+        }
+    }
+
+    /// Loads the ID of the previous revision, if `rvprop` did not include "ids"
+    fn load_parentid(&self, api: &Api) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "prop" => "revisions",
+            "revids" => self.revid,
+            "rvprop" => "ids",
+            "formatversion" => "2",
+        ];
+        let result = api.get_query_api_json(&params)?;
+        Revision::new_from_page_result(&result["query"]["pages"][0])?
+            .first()
+            .and_then(|revision| revision.parentid)
+            .ok_or_else(|| From::from(format!("No revision {}: {:?}", self.revid, result)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+    use crate::timestamp::parse_timestamp;
+
+    #[test]
+    fn revisions_from_page() {
+        let page = json!({"pageid": 1, "ns": 0, "title": "Foo", "revisions": [
+            {
+                "revid": 20, "parentid": 10, "minor": true, "user": "Example", "userid": 5,
+                "timestamp": "2020-04-14T10:22:31Z", "size": 13, "comment": "typo",
+                "tags": ["mobile edit"],
+                "slots": {"main": {"contentmodel": "wikitext", "contentformat": "text/x-wiki",
+                "content": "Hello, world!"}}
+            },
+            {"revid": 10, "parentid": 0, "userhidden": true, "timestamp": "2020-04-13T08:00:00Z"}
+        ]});
+        let revisions = Revision::new_from_page_result(&page).unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert!(revisions[0].minor);
+        assert_eq!(revisions[0].main_content(), Some("Hello, world!"));
+        assert_eq!(
+            revisions[0].timestamp,
+            parse_timestamp("2020-04-14T10:22:31Z")
+        );
+        assert!(revisions[1].timestamp < revisions[0].timestamp);
+        assert!(revisions[1].is_page_creation());
+        assert!(revisions[1].userhidden);
+        assert_eq!(revisions[1].main_content(), None);
+    }
+
+    #[test]
+    fn loaded_content() {
+        let api = test_api();
+        let page = json!({"ns": 0, "title": "Foo", "revisions": [
+            {"revid": 10, "parentid": 0, "slots": {"main": {"content": "Hello"}}}
+        ]});
+        let revision = &Revision::new_from_page_result(&page).unwrap()[0];
+        // Neither needs a request
+        assert_eq!(revision.content(&api).unwrap(), "Hello");
+        assert_eq!(revision.diff_to_parent(&api).unwrap(), None);
+    }
+
+    #[test]
+    fn no_revisions() {
+        let page = json!({"ns": 0, "title": "Foo", "missing": true});
+        assert!(Revision::new_from_page_result(&page).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,983 @@
+/*!
+The `SiteInfo` struct is a typed representation of the `meta=siteinfo` API result.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// The `siprop` values loaded by `SiteInfo::load_default`, and when an `Api` is created. Other
+/// sections (e.g. "usergroups" or "interwikimap") can be added with `Api::load_site_info_sections`.
+pub const DEFAULT_SIPROP: &str =
+    "general|namespaces|namespacealiases|libraries|extensions|statistics";
+
+/// Typed site info, as returned by `action=query&meta=siteinfo&formatversion=2`.
+/// Sections that were not requested via `siprop` are empty.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SiteInfo {
+    /// `siprop=general`
+    pub general: GeneralSiteInfo,
+    /// `siprop=namespaces`, keyed by namespace ID
+    #[serde(default)]
+    pub namespaces: HashMap<NamespaceID, NamespaceInfo>,
+    /// `siprop=namespacealiases`
+    #[serde(default)]
+    pub namespacealiases: Vec<NamespaceAlias>,
+    /// `siprop=libraries`
+    #[serde(default)]
+    pub libraries: Vec<LibraryInfo>,
+    /// `siprop=extensions`
+    #[serde(default)]
+    pub extensions: Vec<ExtensionInfo>,
+    /// `siprop=statistics`
+    pub statistics: Option<Statistics>,
+    /// `siprop=usergroups`
+    #[serde(default)]
+    pub usergroups: Vec<UserGroup>,
+    /// `siprop=rightsinfo`
+    pub rightsinfo: Option<RightsInfo>,
+    /// `siprop=fileextensions`
+    #[serde(default)]
+    pub fileextensions: Vec<FileExtension>,
+    /// `siprop=skins`
+    #[serde(default)]
+    pub skins: Vec<Skin>,
+    /// `siprop=interwikimap`
+    #[serde(default)]
+    pub interwikimap: Vec<Interwiki>,
+    /// `siprop=variables`; magic word IDs of the available variables
+    #[serde(default)]
+    pub variables: Vec<String>,
+    /// `siprop=functionhooks`; names of the available parser functions
+    #[serde(default)]
+    pub functionhooks: Vec<String>,
+    /// `siprop=extensiontags`; available parser extension tags, including angle brackets (e.g.
+    /// "<ref>")
+    #[serde(default)]
+    pub extensiontags: Vec<String>,
+    /// All other sections, e.g. those added by extensions
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// The `general` section of the site info.
+/// Only fields that every MediaWiki installation reports are mandatory; everything else,
+/// including Wikimedia-specific configuration, is optional or ends up in `extra`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct GeneralSiteInfo {
+    /// Title of the main page
+    pub mainpage: String,
+    /// Full URL of the main page
+    pub base: SiteUrl,
+    /// Name of the site
+    pub sitename: String,
+    /// Software version, e.g. "MediaWiki 1.35.0"
+    pub generator: String,
+    /// PHP version
+    pub phpversion: Option<String>,
+    /// PHP server API
+    pub phpsapi: Option<String>,
+    /// Database type, e.g. "mysql"
+    pub dbtype: Option<String>,
+    /// Database version
+    pub dbversion: Option<String>,
+    /// Characters allowed in titles, as a regex character class
+    pub legaltitlechars: Option<String>,
+    /// Characters not allowed in user names
+    pub invalidusernamechars: Option<String>,
+    /// Git commit hash of the installation, if installed from git
+    #[serde(rename = "git-hash")]
+    pub git_hash: Option<String>,
+    /// Git branch of the installation, if installed from git
+    #[serde(rename = "git-branch")]
+    pub git_branch: Option<String>,
+    /// Default case sensitivity of titles
+    pub case: CaseSensitivity,
+    /// Content language code
+    pub lang: String,
+    /// Whether the content language is written right-to-left
+    #[serde(default)]
+    pub rtl: bool,
+    /// Whether the wiki is in read-only mode
+    #[serde(default)]
+    pub readonly: bool,
+    /// Whether the write API is enabled
+    #[serde(default)]
+    pub writeapi: bool,
+    /// Maximum page size, in bytes
+    pub maxarticlesize: Option<u64>,
+    /// Time zone of the wiki
+    pub timezone: Option<String>,
+    /// Offset of the time zone from UTC, in minutes
+    #[serde(default)]
+    pub timeoffset: i64,
+    /// Article path, e.g. "/wiki/$1"
+    pub articlepath: String,
+    /// Script path, e.g. "/w"
+    pub scriptpath: String,
+    /// Path to `index.php`
+    pub script: String,
+    /// Server URL, possibly protocol-relative
+    pub server: SiteUrl,
+    /// Server name
+    pub servername: Option<String>,
+    /// Wiki ID, e.g. "enwiki"
+    pub wikiid: Option<String>,
+    /// Server time at the time of the request
+    pub time: Option<Timestamp>,
+    /// Whether uploads are enabled
+    #[serde(default)]
+    pub uploadsenabled: bool,
+    /// Maximum upload size, in bytes
+    pub maxuploadsize: Option<u64>,
+    /// Minimum size of an upload chunk, in bytes
+    pub minuploadchunksize: Option<u64>,
+    /// Wikibase concept base URI, on Wikibase installations
+    #[serde(rename = "wikibase-conceptbaseuri")]
+    pub wikibase_conceptbaseuri: Option<SiteUrl>,
+    /// Wikibase SPARQL endpoint, on Wikibase installations
+    #[serde(rename = "wikibase-sparql")]
+    pub wikibase_sparql: Option<SiteUrl>,
+    /// Mobile site server, on wikis with MobileFrontend
+    pub mobileserver: Option<SiteUrl>,
+    /// Linter error categories by priority, on wikis with the Linter extension
+    pub linter: Option<HashMap<String, Vec<String>>>,
+    /// Metrics supported by the pageview service, on Wikimedia wikis
+    #[serde(rename = "pageviewservice-supported-metrics")]
+    pub pageviewservice_supported_metrics: Option<Value>,
+    /// Wikimedia site configuration, on Wikimedia wikis
+    #[serde(rename = "wmf-config")]
+    pub wmf_config: Option<Value>,
+    /// All other fields, e.g. those added by extensions
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Case sensitivity of titles, per wiki or per namespace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaseSensitivity {
+    /// The first letter of titles is always capitalized ("first-letter")
+    #[default]
+    FirstLetter,
+    /// Titles are fully case-sensitive ("case-sensitive")
+    CaseSensitive,
+}
+
+/// A namespace, from the `namespaces` section of the site info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NamespaceInfo {
+    /// Namespace ID
+    pub id: NamespaceID,
+    /// Case sensitivity of titles in this namespace
+    pub case: CaseSensitivity,
+    /// Local namespace name
+    pub name: String,
+    /// Canonical (English) namespace name; not set for the main namespace
+    pub canonical: Option<String>,
+    /// Whether subpages are enabled in this namespace
+    #[serde(default)]
+    pub subpages: bool,
+    /// Whether this is a content namespace
+    #[serde(default)]
+    pub content: bool,
+    /// Whether pages in this namespace can not be transcluded
+    #[serde(default)]
+    pub nonincludable: bool,
+    /// Default content model of this namespace, if it differs from wikitext
+    pub defaultcontentmodel: Option<String>,
+}
+
+/// A namespace alias, from the `namespacealiases` section of the site info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NamespaceAlias {
+    /// Namespace ID the alias refers to
+    pub id: NamespaceID,
+    /// The alias
+    pub alias: String,
+}
+
+/// A library, from the `libraries` section of the site info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LibraryInfo {
+    /// Library name
+    pub name: String,
+    /// Library version
+    pub version: Version,
+}
+
+/// An extension, from the `extensions` section of the site info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExtensionInfo {
+    /// Extension type, e.g. "parserhook"
+    #[serde(rename = "type")]
+    pub extension_type: String,
+    /// Extension name
+    pub name: String,
+    /// Extension version, if known
+    pub version: Option<Version>,
+    /// Extension URL, if known
+    pub url: Option<SiteUrl>,
+    /// Message key of the description, if any
+    pub descriptionmsg: Option<String>,
+    /// Authors, as free text
+    pub author: Option<String>,
+    /// License name, if known
+    #[serde(rename = "license-name")]
+    pub license_name: Option<String>,
+}
+
+/// The `statistics` section of the site info
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Statistics {
+    /// Number of pages
+    pub pages: u64,
+    /// Number of content pages
+    pub articles: u64,
+    /// Number of edits
+    pub edits: u64,
+    /// Number of files
+    pub images: u64,
+    /// Number of registered users
+    pub users: u64,
+    /// Number of recently active users
+    pub activeusers: u64,
+    /// Number of administrators
+    pub admins: u64,
+    /// Length of the job queue
+    pub jobs: u64,
+}
+
+/// A user group, from the `usergroups` section of the site info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserGroup {
+    /// Group name, e.g. "sysop"; "*" is everyone
+    pub name: String,
+    /// Rights granted to members of this group
+    #[serde(default)]
+    pub rights: Vec<String>,
+    /// Number of members, if requested via `sinumberingroup`
+    pub number: Option<u64>,
+    /// Groups members of this group can add users to
+    #[serde(default)]
+    pub add: Vec<String>,
+    /// Groups members of this group can remove users from
+    #[serde(default)]
+    pub remove: Vec<String>,
+    /// Groups members of this group can add themselves to
+    #[serde(default, rename = "add-self")]
+    pub add_self: Vec<String>,
+    /// Groups members of this group can remove themselves from
+    #[serde(default, rename = "remove-self")]
+    pub remove_self: Vec<String>,
+}
+
+impl UserGroup {
+    /// Checks if this group grants a right
+    pub fn has_right(&self, right: &str) -> bool {
+        self.rights.iter().any(|r| r == right)
+    }
+}
+
+/// The `rightsinfo` section of the site info, describing the content license
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RightsInfo {
+    /// License URL
+    pub url: SiteUrl,
+    /// License name
+    pub text: String,
+}
+
+/// A permitted file extension, from the `fileextensions` section of the site info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileExtension {
+    /// The extension, without leading dot
+    pub ext: String,
+}
+
+/// A skin, from the `skins` section of the site info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Skin {
+    /// Internal skin name, e.g. "vector"
+    pub code: String,
+    /// Localized display name
+    pub name: String,
+    /// Whether this is the default skin
+    #[serde(default)]
+    pub default: bool,
+    /// Whether this skin can not be selected by users
+    #[serde(default)]
+    pub unusable: bool,
+}
+
+/// An interwiki prefix, from the `interwikimap` section of the site info
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Interwiki {
+    /// The prefix, e.g. "en" or "commons"
+    pub prefix: String,
+    /// URL pattern, with `$1` standing for the (URL-encoded) page title
+    pub url: String,
+    /// Whether the target is considered local to the wiki farm
+    #[serde(default)]
+    pub local: bool,
+    /// Whether the prefix may be used for transclusion
+    #[serde(default)]
+    pub trans: bool,
+    /// Whether the URL is protocol-relative
+    #[serde(default)]
+    pub protorel: bool,
+    /// Language name, for interlanguage link prefixes
+    pub language: Option<String>,
+    /// BCP 47 language code, for interlanguage link prefixes
+    pub bcp47: Option<String>,
+}
+
+impl Interwiki {
+    /// Returns the URL of `title` on the target site
+    pub fn url_for(&self, title: &str) -> String {
+        self.url.replace("$1", title)
+    }
+}
+
+impl GeneralSiteInfo {
+    /// Returns the MediaWiki version, parsed from `generator`
+    pub fn mediawiki_version(&self) -> Option<Version> {
+        self.generator
+            .strip_prefix("MediaWiki ")
+            .map(Version::parse)
+    }
+}
+
+/// A URL from the site info.
+/// The original string is always kept; protocol-relative URLs (e.g. "//en.wikipedia.org")
+/// are parsed as HTTPS. If the string can not be parsed as a URL, only the string is available.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SiteUrl {
+    raw: String,
+    url: Option<Url>,
+}
+
+impl SiteUrl {
+    /// Creates a `SiteUrl` from a string, parsing it if possible
+    pub fn new(raw: &str) -> SiteUrl {
+        let url = if raw.starts_with("//") {
+            Url::parse(&format!("https:{}", raw)).ok()
+        } else {
+            Url::parse(raw).ok()
+        };
+        SiteUrl {
+            raw: raw.to_string(),
+            url,
+        }
+    }
+
+    /// Returns the original string
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Returns the parsed URL, if the string is a valid URL
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    /// Checks if the original string is protocol-relative
+    pub fn is_protocol_relative(&self) -> bool {
+        self.raw.starts_with("//")
+    }
+
+    /// Returns the string with HTTPS added to protocol-relative URLs
+    pub fn to_absolute(&self) -> String {
+        if self.is_protocol_relative() {
+            format!("https:{}", self.raw)
+        } else {
+            self.raw.clone()
+        }
+    }
+}
+
+impl fmt::Display for SiteUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for SiteUrl {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(SiteUrl::new(&String::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for SiteUrl {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+/// A version string, such as "1.35.0-wmf.27" or "v2.0.0".
+/// The dot-separated numeric part and the pre-release suffix are parsed if possible;
+/// strings that do not start with a number (e.g. "dev-master") are only available as strings.
+/// Versions are ordered by their numeric parts, with pre-releases before releases;
+/// unparsed versions come first, ordered as strings.
+#[derive(Debug, Clone, Default)]
+pub struct Version {
+    raw: String,
+    numbers: Vec<u64>,
+    pre_release: Option<String>,
+}
+
+impl Version {
+    /// Parses a version string; never fails
+    pub fn parse(raw: &str) -> Version {
+        let trimmed = raw.trim().trim_start_matches(['v', 'V']);
+        let (numeric, pre_release) = match trimmed.find(['-', '+']) {
+            Some(pos) => (&trimmed[..pos], Some(trimmed[pos + 1..].to_string())),
+            None => (trimmed, None),
+        };
+        let numbers = numeric
+            .split('.')
+            .map(|n| n.parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()
+            .unwrap_or_default();
+        Version {
+            raw: raw.to_string(),
+            pre_release: if numbers.is_empty() {
+                None
+            } else {
+                pre_release
+            },
+            numbers,
+        }
+    }
+
+    /// Returns the original string
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Checks if the numeric part of the version could be parsed
+    pub fn is_parsed(&self) -> bool {
+        !self.numbers.is_empty()
+    }
+
+    /// Returns the major version number, if parsed
+    pub fn major(&self) -> Option<u64> {
+        self.numbers.first().copied()
+    }
+
+    /// Returns the minor version number, if parsed; 0 if the version has only a major number
+    pub fn minor(&self) -> Option<u64> {
+        self.major()?;
+        Some(self.numbers.get(1).copied().unwrap_or(0))
+    }
+
+    /// Returns the patch version number, if parsed; 0 if the version has no patch number
+    pub fn patch(&self) -> Option<u64> {
+        self.major()?;
+        Some(self.numbers.get(2).copied().unwrap_or(0))
+    }
+
+    /// Returns the pre-release or build suffix, e.g. "wmf.27"
+    pub fn pre_release(&self) -> Option<&str> {
+        self.pre_release.as_deref()
+    }
+
+    /// Numeric parts without trailing zeros, so that "1.35" and "1.35.0" compare equal
+    fn significant_numbers(&self) -> &[u64] {
+        let len = self
+            .numbers
+            .iter()
+            .rposition(|&n| n != 0)
+            .map_or(0, |pos| pos + 1);
+        &self.numbers[..len]
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.is_parsed()
+            .cmp(&other.is_parsed())
+            .then_with(|| self.significant_numbers().cmp(other.significant_numbers()))
+            .then_with(|| match (&self.pre_release, &other.pre_release) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+            .then_with(|| {
+                if self.is_parsed() {
+                    Ordering::Equal
+                } else {
+                    self.raw.cmp(&other.raw)
+                }
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Version::parse(&String::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+/// On-disk representation of a cached `SiteInfo`
+#[derive(Debug, Deserialize, Serialize)]
+struct CachedSiteInfo {
+    /// Seconds since the UNIX epoch when the site info was loaded from the API
+    fetched: u64,
+    site_info: SiteInfo,
+}
+
+impl SiteInfo {
+    /// Loads the site info sections listed in `siprop` (e.g. "general|usergroups") from the API
+    pub fn load(api: &Api, siprop: &str) -> Result<SiteInfo, Box<dyn Error + Send + Sync>> {
+        let params = api.params_into(&[
+            ("action", "query"),
+            ("meta", "siteinfo"),
+            ("siprop", siprop),
+            ("formatversion", "2"),
+        ]);
+        let result = api.get_query_api_json(&params)?;
+        Ok(SiteInfo::new_from_api_result(&result)?)
+    }
+
+    /// Loads the site info sections in `DEFAULT_SIPROP` from the API
+    pub fn load_default(api: &Api) -> Result<SiteInfo, Box<dyn Error + Send + Sync>> {
+        SiteInfo::load(api, DEFAULT_SIPROP)
+    }
+
+    /// Parses a `formatversion=2` site info API result
+    pub fn new_from_api_result(result: &Value) -> Result<SiteInfo, serde_json::Error> {
+        SiteInfo::deserialize(&result["query"])
+    }
+
+    /// Adds the sections of a `formatversion=2` site info API result to this site info, replacing
+    /// sections that were loaded before
+    pub fn add_sections_from_api_result(
+        &mut self,
+        result: &Value,
+    ) -> Result<(), serde_json::Error> {
+        let mut data = serde_json::to_value(&*self)?;
+        if let (Some(data), Some(sections)) = (data.as_object_mut(), result["query"].as_object()) {
+            data.extend(sections.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        *self = SiteInfo::deserialize(&data)?;
+        Ok(())
+    }
+
+    /// Returns a file name, unique to `api_url`, for caching its site info
+    pub fn cache_file_name(api_url: &str) -> String {
+        let name: String = api_url
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("siteinfo_{}.json", name)
+    }
+
+    /// Reads site info cached by `write_cache` from `path`. Returns `None` if the file can not be
+    /// read or parsed, or if the cached data is older than `ttl`.
+    pub fn read_cache(path: &Path, ttl: Duration) -> Option<SiteInfo> {
+        let data = fs::read_to_string(path).ok()?;
+        let cached: CachedSiteInfo = serde_json::from_str(&data).ok()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        if now.saturating_sub(cached.fetched) >= ttl.as_secs() {
+            return None;
+        }
+        Some(cached.site_info)
+    }
+
+    /// Writes this site info, with the current time, to `path`
+    pub fn write_cache(&self, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let cached = CachedSiteInfo {
+            fetched: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            site_info: self.clone(),
+        };
+        fs::write(path, serde_json::to_string(&cached)?)?;
+        Ok(())
+    }
+
+    /// Returns the user group with the given name, if defined
+    pub fn user_group(&self, name: &str) -> Option<&UserGroup> {
+        self.usergroups.iter().find(|g| g.name == name)
+    }
+
+    /// Checks if a file with the given name has a permitted extension.
+    /// Extensions are compared case-insensitively, as MediaWiki does.
+    /// Requires `siprop=fileextensions`; returns false if no extensions were loaded.
+    pub fn allows_file_extension(&self, filename: &str) -> bool {
+        let ext = match filename.rsplit_once('.') {
+            Some((_, ext)) => ext.to_lowercase(),
+            None => return false,
+        };
+        self.fileextensions
+            .iter()
+            .any(|e| e.ext.to_lowercase() == ext)
+    }
+
+    /// Checks if an upload of `size` bytes is enabled and within the upload size limit.
+    /// If the wiki does not report a limit, any size is allowed.
+    pub fn allows_upload_size(&self, size: u64) -> bool {
+        self.general.uploadsenabled && self.general.maxuploadsize.is_none_or(|max| size <= max)
+    }
+
+    /// Returns the interwiki with the given prefix (case-insensitive), if `siprop=interwikimap` was
+    /// loaded
+    pub fn interwiki(&self, prefix: &str) -> Option<&Interwiki> {
+        let prefix = prefix.trim().to_lowercase();
+        self.interwikimap.iter().find(|iw| iw.prefix == prefix)
+    }
+
+    /// Returns the default skin, if `siprop=skins` was loaded
+    pub fn default_skin(&self) -> Option<&Skin> {
+        self.skins.iter().find(|s| s.default)
+    }
+
+    /// Checks if a variable (magic word ID, e.g. "currentyear") is available
+    pub fn has_variable(&self, name: &str) -> bool {
+        self.variables.iter().any(|v| v == name)
+    }
+
+    /// Checks if a parser function (e.g. "#invoke" or "invoke") is available
+    pub fn has_function_hook(&self, name: &str) -> bool {
+        let name = name.trim_start_matches('#');
+        self.functionhooks.iter().any(|f| f == name)
+    }
+
+    /// Checks if a parser extension tag (e.g. "ref" or "<ref>") is available
+    pub fn has_extension_tag(&self, name: &str) -> bool {
+        let name = name.trim_start_matches('<').trim_end_matches('>');
+        self.extensiontags
+            .iter()
+            .any(|t| t.trim_start_matches('<').trim_end_matches('>') == name)
+    }
+
+    /// Returns the names of all user groups that grant `right`
+    pub fn groups_with_right(&self, right: &str) -> Vec<&str> {
+        self.usergroups
+            .iter()
+            .filter(|g| g.has_right(right))
+            .map(|g| g.name.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn general() -> Value {
+        json!({
+            "mainpage": "Main Page",
+            "base": "https://www.example.org/wiki/Main_Page",
+            "sitename": "Example",
+            "generator": "MediaWiki 1.35.0",
+            "phpversion": "7.3.14",
+            "phpsapi": "fpm-fcgi",
+            "dbtype": "mysql",
+            "dbversion": "10.1.43-MariaDB",
+            "legaltitlechars": " %!\"$&'()*,\\-.\\/0-9:;=?@A-Z\\\\^_`a-z~\\x80-\\xFF+",
+            "invalidusernamechars": "@:",
+            "git-hash": "0123456789abcdef",
+            "git-branch": "master",
+            "case": "first-letter",
+            "lang": "en",
+            "rtl": false,
+            "readonly": false,
+            "writeapi": true,
+            "maxarticlesize": 2097152,
+            "timezone": "UTC",
+            "timeoffset": 0,
+            "articlepath": "/wiki/$1",
+            "scriptpath": "/w",
+            "script": "/w/index.php",
+            "server": "https://www.example.org",
+            "servername": "www.example.org",
+            "wikiid": "examplewiki",
+            "time": "2020-04-14T12:00:00Z",
+            "uploadsenabled": true
+        })
+    }
+
+    #[test]
+    fn usergroups_and_rightsinfo() {
+        let result = json!({"batchcomplete": true, "query": {
+            "general": general(),
+            "usergroups": [
+                {"name": "*", "rights": ["read", "createaccount"]},
+                {"name": "sysop", "rights": ["block", "delete"], "number": 3, "add": ["bot"]}
+            ],
+            "rightsinfo": {"url": "https://creativecommons.org/licenses/by-sa/3.0/",
+            "text": "CC BY-SA 3.0"}
+        }});
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        assert_eq!(si.usergroups.len(), 2);
+        let sysop = si.user_group("sysop").unwrap();
+        assert_eq!(sysop.number, Some(3));
+        assert_eq!(sysop.add, vec!["bot".to_string()]);
+        assert!(sysop.remove.is_empty());
+        assert_eq!(si.groups_with_right("delete"), vec!["sysop"]);
+        assert_eq!(si.rightsinfo.unwrap().text, "CC BY-SA 3.0");
+    }
+
+    #[test]
+    fn add_sections() {
+        let result = json!({"query": {"general": general(),
+            "libraries": [{"name": "wikimedia/cdb", "version": "1.4.1"}]}});
+        let mut si = SiteInfo::new_from_api_result(&result).unwrap();
+        let result = json!({"batchcomplete": true, "query": {
+            "interwikimap": [{"prefix": "wikt", "url": "https://en.wiktionary.org/wiki/$1"}]
+        }});
+        si.add_sections_from_api_result(&result).unwrap();
+        assert_eq!(si.general.sitename, general()["sitename"]);
+        assert_eq!(si.libraries[0].version.as_str(), "1.4.1");
+        assert!(si.interwiki("Wikt").is_some());
+    }
+
+    #[test]
+    fn upload_configuration() {
+        let mut general = general();
+        general["maxuploadsize"] = json!(4294967296u64);
+        general["minuploadchunksize"] = json!(1024);
+        let result = json!({"query": {
+            "general": general,
+            "fileextensions": [{"ext": "png"}, {"ext": "jpg"}, {"ext": "svg"}]
+        }});
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        assert_eq!(si.general.minuploadchunksize, Some(1024));
+        assert!(si.allows_file_extension("Example.PNG"));
+        assert!(si.allows_file_extension("a.b.svg"));
+        assert!(!si.allows_file_extension("virus.exe"));
+        assert!(!si.allows_file_extension("no_extension"));
+        assert!(si.allows_upload_size(4294967296));
+        assert!(!si.allows_upload_size(4294967297));
+    }
+
+    #[test]
+    fn parser_constructs() {
+        let result = json!({"query": {
+            "general": general(),
+            "skins": [
+                {"code": "vector", "name": "Vector", "default": true},
+                {"code": "fallback", "name": "Fallback", "unusable": true}
+            ],
+            "variables": ["!", "currentyear", "pagename"],
+            "functionhooks": ["ns", "if", "invoke"],
+            "extensiontags": ["<pre>", "<nowiki>", "<ref>"]
+        }});
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        assert_eq!(si.default_skin().unwrap().code, "vector");
+        assert!(si.skins[1].unusable);
+        assert!(si.has_variable("currentyear"));
+        assert!(!si.has_variable("currentmonth"));
+        assert!(si.has_function_hook("#invoke"));
+        assert!(si.has_function_hook("ns"));
+        assert!(!si.has_function_hook("#switch"));
+        assert!(si.has_extension_tag("ref"));
+        assert!(si.has_extension_tag("<nowiki>"));
+        assert!(!si.has_extension_tag("gallery"));
+    }
+
+    /// Site info results from different kinds of wikis, with their sitenames
+    const CORPUS: &[(&str, &str)] = &[
+        (
+            "Wikidata",
+            include_str!("../test_data/site_info/wikidata.json"),
+        ),
+        (
+            "My Wiki",
+            include_str!("../test_data/site_info/mediawiki_1_35_tarball.json"),
+        ),
+        (
+            "Beispielwiki",
+            include_str!("../test_data/site_info/mediawiki_1_27_farm.json"),
+        ),
+    ];
+
+    #[test]
+    fn compatibility_corpus() {
+        for (sitename, data) in CORPUS {
+            let result: Value = serde_json::from_str(data).unwrap();
+            let si = SiteInfo::new_from_api_result(&result)
+                .unwrap_or_else(|e| panic!("{}: {}", sitename, e));
+            assert_eq!(&si.general.sitename, sitename);
+            assert!(si.namespaces.contains_key(&0));
+        }
+    }
+
+    #[test]
+    fn wikimedia_specific_fields() {
+        let result: Value = serde_json::from_str(CORPUS[0].1).unwrap();
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        let general = &si.general;
+        assert_eq!(
+            general.mobileserver.as_ref().unwrap().as_str(),
+            "https://m.wikidata.org"
+        );
+        assert!(general.linter.as_ref().unwrap()["low"].contains(&"obsolete-tag".to_string()));
+        assert_eq!(
+            general.wmf_config.as_ref().unwrap()["wmfMasterDatacenter"],
+            "eqiad"
+        );
+        assert_eq!(general.extra["categorycollation"], "uppercase");
+        assert_eq!(si.statistics.unwrap().admins, 63);
+    }
+
+    #[test]
+    fn third_party_fields() {
+        let result: Value = serde_json::from_str(CORPUS[2].1).unwrap();
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        let general = &si.general;
+        assert!(general.git_hash.is_none());
+        assert!(general.mobileserver.is_none());
+        assert!(!general.readonly);
+        assert_eq!(general.timeoffset, 120);
+        assert_eq!(general.extra["semanticmediawiki"]["version"], "2.5.8");
+        assert!(si.namespaces[&1].subpages);
+        assert!(!si.namespaces[&6].subpages);
+        assert_eq!(si.namespaces[&6].case, CaseSensitivity::FirstLetter);
+        assert!(si.statistics.is_none());
+    }
+
+    #[test]
+    fn cache_roundtrip() {
+        let result: Value = serde_json::from_str(CORPUS[0].1).unwrap();
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        let path = std::env::temp_dir().join(SiteInfo::cache_file_name(&format!(
+            "cache_roundtrip_{}",
+            std::process::id()
+        )));
+        si.write_cache(&path).unwrap();
+        let cached = SiteInfo::read_cache(&path, Duration::from_secs(3600)).unwrap();
+        assert_eq!(cached.general.sitename, "Wikidata");
+        assert_eq!(cached.general.extra["categorycollation"], "uppercase");
+        assert_eq!(
+            cached.namespaces[&120].canonical.as_deref(),
+            Some("Property")
+        );
+        assert!(SiteInfo::read_cache(&path, Duration::from_secs(0)).is_none());
+        fs::remove_file(&path).unwrap();
+        assert!(SiteInfo::read_cache(&path, Duration::from_secs(3600)).is_none());
+    }
+
+    #[test]
+    fn cache_file_name() {
+        assert_eq!(
+            SiteInfo::cache_file_name("https://www.wikidata.org/w/api.php"),
+            "siteinfo_https___www_wikidata_org_w_api_php.json"
+        );
+    }
+
+    #[test]
+    fn site_urls() {
+        let url = SiteUrl::new("//en.wikipedia.org");
+        assert!(url.is_protocol_relative());
+        assert_eq!(url.as_str(), "//en.wikipedia.org");
+        assert_eq!(url.url().unwrap().host_str(), Some("en.wikipedia.org"));
+        assert_eq!(url.to_absolute(), "https://en.wikipedia.org");
+        assert_eq!(
+            SiteUrl::new("http://example.org").to_absolute(),
+            "http://example.org"
+        );
+        let url = SiteUrl::new("not a url");
+        assert_eq!(url.url(), None);
+        assert_eq!(url.to_string(), "not a url");
+
+        let result: Value = serde_json::from_str(CORPUS[0].1).unwrap();
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        let sparql = si.general.wikibase_sparql.unwrap();
+        assert_eq!(sparql.url().unwrap().path(), "/sparql");
+        assert_eq!(
+            si.general.base.url().unwrap().as_str(),
+            "https://www.wikidata.org/wiki/Wikidata:Main_Page"
+        );
+    }
+
+    #[test]
+    fn versions() {
+        let v = Version::parse("1.35.0-wmf.27");
+        assert_eq!(
+            (v.major(), v.minor(), v.patch()),
+            (Some(1), Some(35), Some(0))
+        );
+        assert_eq!(v.pre_release(), Some("wmf.27"));
+        assert!(v < Version::parse("1.35.0"));
+        assert!(Version::parse("1.9") < Version::parse("1.10"));
+        assert_eq!(Version::parse("v2.0"), Version::parse("2.0.0"));
+        let dev = Version::parse("dev-master");
+        assert!(!dev.is_parsed());
+        assert_eq!(dev.major(), None);
+        assert!(dev < Version::parse("0.1"));
+        assert_eq!(dev.as_str(), "dev-master");
+
+        let result: Value = serde_json::from_str(CORPUS[0].1).unwrap();
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        assert_eq!(si.general.mediawiki_version().unwrap().minor(), Some(35));
+        assert_eq!(si.libraries[0].version, Version::parse("1.5.0"));
+    }
+
+    #[test]
+    fn namespaces() {
+        let result = json!({"query": {
+            "general": general(),
+            "namespaces": {
+                "0": {"id": 0, "case": "first-letter", "name": "", "subpages": false,
+                "content": true, "nonincludable": false},
+                "1": {"id": 1, "case": "first-letter", "name": "Talk", "canonical": "Talk",
+                "subpages": true, "content": false, "nonincludable": false}
+            },
+            "namespacealiases": [{"id": 4, "alias": "WP"}]
+        }});
+        let si = SiteInfo::new_from_api_result(&result).unwrap();
+        assert_eq!(si.namespaces[&0].canonical, None);
+        assert!(si.namespaces[&1].subpages);
+        assert_eq!(si.namespacealiases[0].alias, "WP");
+        assert!(si.rightsinfo.is_none());
+    }
+}
@@ -0,0 +1,217 @@
+/*!
+The `SiteMatrix` class lists the wikis of a wiki farm (like those of Wikimedia), from
+`action=sitematrix`.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+
+/// A wiki in the site matrix
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Site {
+    /// URL of the wiki, e.g. "https://en.wikipedia.org"
+    pub url: String,
+    /// Database name, which is also the global site ID, e.g. "enwiki"
+    pub dbname: String,
+    /// Project code, e.g. "wiki" or "wiktionary"; for special wikis, e.g. "commons"
+    pub code: String,
+    /// Name of the site, e.g. "Wikipedia"
+    pub sitename: Option<String>,
+    /// Language code, for language projects
+    #[serde(skip_deserializing)]
+    pub language: Option<String>,
+    /// Whether the wiki is closed
+    #[serde(default, deserialize_with = "flag")]
+    pub closed: bool,
+    /// Whether the wiki is private
+    #[serde(default, deserialize_with = "flag")]
+    pub private: bool,
+}
+
+impl Site {
+    /// Returns the host name of the wiki, e.g. "en.wikipedia.org"
+    pub fn host(&self) -> &str {
+        host_of(&self.url)
+    }
+
+    /// Returns the URL of the action API of the wiki, assuming the Wikimedia script path, e.g.
+    /// "https://en.wikipedia.org/w/api.php"
+    pub fn api_url(&self) -> String {
+        let url = self.url.trim_end_matches('/');
+        match url.strip_prefix("//") {
+            Some(url) => format!("https://{}/w/api.php", url),
+            None => format!("{}/w/api.php", url),
+        }
+    }
+}
+
+/// The wikis of a wiki farm
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SiteMatrix {
+    sites: Vec<Site>,
+}
+
+impl SiteMatrix {
+    /// Parses an `action=sitematrix` result
+    pub fn new_from_api_result(result: &Value) -> SiteMatrix {
+        let mut sites = vec![];
+        if let Some(matrix) = result["sitematrix"].as_object() {
+            for (key, group) in matrix {
+                match key.as_str() {
+                    "count" => {}
+                    "specials" => sites.extend(
+                        group
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|site| Site::deserialize(site).ok()),
+                    ),
+                    _ => {
+                        let language = group["code"].as_str().map(|s| s.to_string());
+                        sites.extend(
+                            group["site"]
+                                .as_array()
+                                .into_iter()
+                                .flatten()
+                                .filter_map(|site| Site::deserialize(site).ok())
+                                .map(|site| Site {
+                                    language: language.clone(),
+                                    ..site
+                                }),
+                        )
+                    }
+                }
+            }
+        }
+        SiteMatrix { sites }
+    }
+
+    /// Returns all wikis
+    pub fn sites(&self) -> &[Site] {
+        &self.sites
+    }
+
+    /// Finds a wiki by global site ID (e.g. "enwiki"), host name (e.g. "en.wikipedia.org"), or URL
+    pub fn site(&self, id: &str) -> Option<&Site> {
+        let id = id.trim();
+        let dbname = id.to_lowercase().replace('-', "_");
+        let host = host_of(id).to_lowercase();
+        self.sites
+            .iter()
+            .find(|site| site.dbname == dbname)
+            .or_else(|| self.sites.iter().find(|site| site.host() == host))
+    }
+
+    /// Returns the global site ID (e.g. "enwiki") for a site ID, host name, or URL, as used by
+    /// Wikibase sitelinks
+    pub fn normalize_site_id(&self, id: &str) -> Option<&str> {
+        self.site(id).map(|site| site.dbname.as_str())
+    }
+}
+
+impl Api {
+    /// Loads the site matrix of the wiki farm, with `action=sitematrix`
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn sitematrix(&self) -> Result<SiteMatrix, Box<dyn Error + Send + Sync>> {
+        let params = self.params_into(&[("action", "sitematrix"), ("smlimit", "max")]);
+        let result = self.get_query_api_json_all(&params)?;
+        if let Some(error) = result.get("error") {
+            return Err(From::from(format!("sitematrix failed: {}", error)));
+        }
+        Ok(SiteMatrix::new_from_api_result(&result))
+    }
+}
+
+/// Returns the host name of a URL (which may be protocol-relative) or host name
+fn host_of(url: &str) -> &str {
+    let url = url.split("//").nth(1).unwrap_or(url);
+    url.split(['/', '?', '#']).next().unwrap_or(url)
+}
+
+/// Deserializes a boolean flag, which is given as an empty string if set in `formatversion=1`
+fn flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Bool(b) => b,
+        Value::Null => false,
+        _ => true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sitematrix() -> SiteMatrix {
+        SiteMatrix::new_from_api_result(&json!({"sitematrix": {
+            "count": 4,
+            "0": {"code": "de", "name": "Deutsch", "site": [
+                {"url": "https://de.wikipedia.org", "dbname": "dewiki", "code": "wiki",
+                "sitename": "Wikipedia"},
+                {"url": "https://de.wiktionary.org", "dbname": "dewiktionary", "code": "wiktionary",
+                "sitename": "Wiktionary"}
+            ]},
+            "1": {"code": "be-tarask", "name": "беларуская (тарашкевіца)", "site": [
+                {"url": "https://be-tarask.wikipedia.org", "dbname": "be_x_oldwiki", "code": "wiki",
+                "sitename": "Вікіпэдыя"}
+            ]},
+            "specials": [
+                {"url": "https://commons.wikimedia.org", "dbname": "commonswiki", "code": "commons",
+                "sitename": "Wikimedia Commons"},
+                {"url": "https://wikimania2005.wikimedia.org", "dbname": "wikimania2005wiki",
+                "code": "wikimania2005", "closed": ""}
+            ]
+        }}))
+    }
+
+    #[test]
+    fn parse_sitematrix() {
+        let matrix = sitematrix();
+        assert_eq!(matrix.sites().len(), 5);
+        let site = matrix.site("dewiktionary").unwrap();
+        assert_eq!(site.language.as_deref(), Some("de"));
+        assert_eq!(site.host(), "de.wiktionary.org");
+        assert!(!site.closed);
+        assert!(matrix.site("wikimania2005wiki").unwrap().closed);
+        assert_eq!(matrix.site("commonswiki").unwrap().language, None);
+    }
+
+    #[test]
+    fn normalize_site_id() {
+        let matrix = sitematrix();
+        assert_eq!(matrix.normalize_site_id("dewiki"), Some("dewiki"));
+        assert_eq!(matrix.normalize_site_id(" DEWIKI "), Some("dewiki"));
+        assert_eq!(
+            matrix.normalize_site_id("be-x-oldwiki"),
+            Some("be_x_oldwiki")
+        );
+        assert_eq!(
+            matrix.normalize_site_id("be-tarask.wikipedia.org"),
+            Some("be_x_oldwiki")
+        );
+        assert_eq!(
+            matrix.normalize_site_id("https://commons.wikimedia.org/wiki/Main_Page"),
+            Some("commonswiki")
+        );
+        assert_eq!(
+            matrix.normalize_site_id("//de.wikipedia.org"),
+            Some("dewiki")
+        );
+        assert_eq!(matrix.normalize_site_id("frwiki"), None);
+    }
+}
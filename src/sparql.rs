@@ -0,0 +1,605 @@
+/*!
+Helpers for SPARQL queries against a Wikibase query service, like the Wikidata Query Service.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::site_info::SiteUrl;
+use crate::wikibase::EntityId;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, BufReader};
+use std::time::Duration;
+
+/// How long to wait before retrying if the query service does not send a usable `Retry-After`
+/// header
+pub(crate) const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Errors from the query service
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SparqlError {
+    /// No SPARQL endpoint is set, and the site info has none.
+    NoEndpoint,
+
+    /// The site info has no `wikibase-conceptbaseuri`, so entity URIs can't be recognized.
+    NoConceptBaseUri,
+
+    /// A value that is not the URI of an entity on this Wikibase (provided).
+    NotAnEntity(String),
+
+    /// The query took too long, and was cancelled by the query service.
+    Timeout,
+
+    /// Too many requests (HTTP 429 or 503), and the retry budget is used up.
+    /// The wait time requested by the query service is provided, if any.
+    TooManyRequests(Option<Duration>),
+
+    /// Any other HTTP error; the status code and the response body are provided.
+    Http {
+        /// HTTP status code
+        status: u16,
+        /// Response body, which usually contains the error message
+        body: String,
+    },
+}
+
+impl SparqlError {
+    /// Returns the error for an unsuccessful response from the query service
+    pub(crate) fn from_response(status: u16, body: String) -> SparqlError {
+        // Blazegraph reports timeouts as a Java exception in the body, with status 500
+        if body.contains("TimeoutException") {
+            SparqlError::Timeout
+        } else {
+            SparqlError::Http { status, body }
+        }
+    }
+}
+
+impl fmt::Display for SparqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SparqlError::NoEndpoint => write!(
+                f,
+                "no SPARQL endpoint set, and no 'wikibase-sparql' value in site info"
+            ),
+            SparqlError::NoConceptBaseUri => {
+                write!(f, "no 'wikibase-conceptbaseuri' value in site info")
+            }
+            SparqlError::NotAnEntity(value) => {
+                write!(f, "not an entity URI of this Wikibase: {}", value)
+            }
+            SparqlError::Timeout => write!(f, "SPARQL query timed out"),
+            SparqlError::TooManyRequests(Some(wait)) => {
+                write!(
+                    f,
+                    "too many SPARQL requests; retry after {} seconds",
+                    wait.as_secs()
+                )
+            }
+            SparqlError::TooManyRequests(None) => write!(f, "too many SPARQL requests"),
+            SparqlError::Http { status, body } => {
+                write!(
+                    f,
+                    "SPARQL query failed with HTTP status {}: {}",
+                    status, body
+                )
+            }
+        }
+    }
+}
+
+impl Error for SparqlError {}
+
+/// A tabular format for SPARQL results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SparqlFormat {
+    /// Comma-separated values (`text/csv`): plain values, URIs without angle brackets
+    Csv,
+    /// Tab-separated values (`text/tab-separated-values`): values in RDF syntax, e.g.
+    /// `<http://...>` or `"text"@en`
+    Tsv,
+}
+
+impl SparqlFormat {
+    /// Returns the MIME type of the format
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            SparqlFormat::Csv => "text/csv",
+            SparqlFormat::Tsv => "text/tab-separated-values",
+        }
+    }
+}
+
+/// An iterator over the rows of a SPARQL result in CSV or TSV, which are read as needed.
+/// Each row has one value per variable, in the order of `header`; unbound values are empty.
+#[derive(Debug)]
+pub struct SparqlRows<R> {
+    reader: R,
+    format: SparqlFormat,
+    header: Vec<String>,
+}
+
+impl<R: BufRead> SparqlRows<R> {
+    /// Reads the header of a SPARQL result in CSV or TSV
+    ///
+    /// # Errors
+    /// Returns any error while reading.
+    pub fn new(reader: R, format: SparqlFormat) -> io::Result<SparqlRows<R>> {
+        let mut rows = SparqlRows {
+            reader,
+            format,
+            header: vec![],
+        };
+        rows.header = rows
+            .read_row()?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| name.trim_start_matches('?').to_string())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Returns the variable names, without "?"
+    pub fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    /// Reads the next row, or `None` at the end
+    fn read_row(&mut self) -> io::Result<Option<Vec<String>>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match self.format {
+            SparqlFormat::Tsv => Ok(Some(
+                trim_line_end(&line)
+                    .split('\t')
+                    .map(|s| s.to_string())
+                    .collect(),
+            )),
+            SparqlFormat::Csv => self.read_csv_row(line).map(Some),
+        }
+    }
+
+    /// Parses a CSV row starting with `line`; quoted values may continue on the following lines
+    fn read_csv_row(&mut self, mut line: String) -> io::Result<Vec<String>> {
+        let mut fields = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (c, quoted) {
+                    ('"', true) if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    ('"', _) => quoted = !quoted,
+                    (',', false) => fields.push(std::mem::take(&mut field)),
+                    ('\r', false) | ('\n', false) => {}
+                    (c, _) => field.push(c),
+                }
+            }
+            if !quoted {
+                break;
+            }
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "unterminated quoted CSV value",
+                ));
+            }
+        }
+        fields.push(field);
+        Ok(fields)
+    }
+}
+
+impl<R: BufRead> Iterator for SparqlRows<R> {
+    type Item = io::Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_row().transpose()
+    }
+}
+
+/// Removes the line break at the end of a line
+fn trim_line_end(line: &str) -> &str {
+    line.trim_end_matches(['\r', '\n'])
+}
+
+impl Api {
+    /// Performs a SPARQL query, and returns an iterator over the result rows in CSV or TSV. Unlike
+    /// `sparql_query`, the result is not loaded into memory at once, so this works for huge
+    /// results.
+    ///
+    /// # Errors
+    /// Returns a `SparqlError` if the query failed, or any error from the request.
+    pub fn sparql_query_streaming(
+        &self,
+        query: &str,
+        format: SparqlFormat,
+    ) -> Result<SparqlRows<BufReader<reqwest::blocking::Response>>, Box<dyn Error + Send + Sync>>
+    {
+        let response = self.sparql_request(query, "POST", format.mime_type())?;
+        Ok(SparqlRows::new(BufReader::new(response), format)?)
+    }
+}
+
+/// How `SparqlPages` splits a query into pages
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SparqlPagination {
+    /// Appends `LIMIT` and `OFFSET` to the query. The query should have an `ORDER BY` clause,
+    /// and no `LIMIT` or `OFFSET`.
+    Offset,
+    /// Wraps the query, and orders and filters the results by the string value of a variable
+    /// (without "?"), continuing after the last value of the previous page. This is faster than
+    /// large offsets, but the values of the variable should be unique. The query must not have
+    /// `PREFIX` declarations.
+    Key(String),
+}
+
+/// An iterator over the pages of results of a SPARQL query; each page is a JSON result
+#[derive(Debug)]
+pub struct SparqlPages<'a> {
+    api: &'a Api,
+    query: String,
+    page_size: usize,
+    pagination: SparqlPagination,
+    offset: usize,
+    last_key: Option<String>,
+    done: bool,
+}
+
+impl SparqlPages<'_> {
+    /// Returns the query for the next page
+    fn page_query(&self) -> String {
+        match &self.pagination {
+            SparqlPagination::Offset => {
+                format!(
+                    "{}\nLIMIT {} OFFSET {}",
+                    self.query, self.page_size, self.offset
+                )
+            }
+            SparqlPagination::Key(variable) => {
+                let filter = match &self.last_key {
+                    Some(key) => format!("FILTER(STR(?{}) > {})", variable, json!(key)),
+                    None => String::new(),
+                };
+                format!(
+                    "SELECT * WHERE {{\n{{\n{}\n}}\n{}\n}}\nORDER BY STR(?{})\nLIMIT {}",
+                    self.query, filter, variable, self.page_size
+                )
+            }
+        }
+    }
+
+    /// Remembers where the next page starts, after a page with `bindings`
+    fn advance(&mut self, bindings: &[Value]) {
+        self.offset += bindings.len();
+        if let SparqlPagination::Key(variable) = &self.pagination {
+            self.last_key = bindings
+                .last()
+                .and_then(|b| b[variable]["value"].as_str())
+                .map(|s| s.to_string());
+        }
+        let no_cursor =
+            matches!(self.pagination, SparqlPagination::Key(_)) && self.last_key.is_none();
+        self.done = bindings.len() < self.page_size || no_cursor;
+    }
+}
+
+impl Iterator for SparqlPages<'_> {
+    type Item = Result<Value, Box<dyn Error + Send + Sync>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = match self.api.sparql_query(&self.page_query()) {
+            Ok(result) => result,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        let bindings = result["results"]["bindings"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        self.advance(&bindings);
+        Some(Ok(result))
+    }
+}
+
+impl Api {
+    /// Runs a SPARQL query page by page, with `page_size` results per page, since the query service
+    /// limits the size of results. Returns an iterator over the JSON results of the pages.
+    pub fn sparql_query_pages(
+        &self,
+        query: &str,
+        page_size: usize,
+        pagination: SparqlPagination,
+    ) -> SparqlPages<'_> {
+        SparqlPages {
+            api: self,
+            query: query.to_string(),
+            page_size: page_size.max(1),
+            pagination,
+            offset: 0,
+            last_key: None,
+            done: false,
+        }
+    }
+
+    /// Runs a SPARQL query page by page, like `sparql_query_pages`, and merges the pages into one
+    /// JSON result
+    ///
+    /// # Errors
+    /// Returns the first error of any page.
+    pub fn sparql_query_paginated(
+        &self,
+        query: &str,
+        page_size: usize,
+        pagination: SparqlPagination,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        let mut merged = Value::Null;
+        for page in self.sparql_query_pages(query, page_size, pagination) {
+            merge_sparql_results(&mut merged, page?);
+        }
+        Ok(merged)
+    }
+}
+
+impl Api {
+    /// Returns the entity IDs bound to a variable (without "?") in a SPARQL JSON result.
+    /// Rows where the variable is unbound are skipped.
+    ///
+    /// # Errors
+    /// Returns `SparqlError::NotAnEntity` for a value that is not the URI of an entity of this
+    /// Wikibase, e.g. a literal, or the URI of a statement.
+    pub fn sparql_entities(
+        &self,
+        sparql_result: &Value,
+        variable_name: &str,
+    ) -> Result<Vec<EntityId>, SparqlError> {
+        let concept_base_uri = self
+            .get_site_info()
+            .general
+            .wikibase_conceptbaseuri
+            .as_ref()
+            .map(SiteUrl::as_str)
+            .ok_or(SparqlError::NoConceptBaseUri)?;
+        entities_from_bindings(sparql_result, variable_name, concept_base_uri)
+    }
+}
+
+/// Returns the entity IDs bound to a variable, given the concept base URI
+fn entities_from_bindings(
+    sparql_result: &Value,
+    variable_name: &str,
+    concept_base_uri: &str,
+) -> Result<Vec<EntityId>, SparqlError> {
+    let bindings = match sparql_result["results"]["bindings"].as_array() {
+        Some(bindings) => bindings,
+        None => return Ok(vec![]),
+    };
+    bindings
+        .iter()
+        .filter_map(|binding| binding.get(variable_name))
+        .map(|value| {
+            let not_an_entity = || {
+                SparqlError::NotAnEntity(value["value"].as_str().unwrap_or_default().to_string())
+            };
+            if value["type"] != "uri" {
+                return Err(not_an_entity());
+            }
+            value["value"]
+                .as_str()
+                .and_then(|uri| uri.strip_prefix(concept_base_uri))
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(not_an_entity)
+        })
+        .collect()
+}
+
+/// Adds the bindings of a SPARQL JSON result to another one
+fn merge_sparql_results(merged: &mut Value, mut page: Value) {
+    if merged.is_null() {
+        *merged = page;
+        return;
+    }
+    let bindings = page["results"]["bindings"].take();
+    if let (Some(merged), Value::Array(bindings)) =
+        (merged["results"]["bindings"].as_array_mut(), bindings)
+    {
+        merged.extend(bindings);
+    }
+}
+
+/// Returns the wait time from the `Retry-After` header, if it is given in seconds
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn retry_after_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test]
+    fn errors_from_response() {
+        let body = "SPARQL-QUERY: queryStr=SELECT ...\njava.util.concurrent.TimeoutException\n\
+            \tat java.util.concurrent.FutureTask.get";
+        assert!(matches!(
+            SparqlError::from_response(500, body.to_string()),
+            SparqlError::Timeout
+        ));
+        match SparqlError::from_response(400, "MalformedQueryException".to_string()) {
+            SparqlError::Http { status, body } => {
+                assert_eq!(status, 400);
+                assert_eq!(body, "MalformedQueryException");
+            }
+            e => panic!("expected HTTP error, found {:?}", e),
+        }
+    }
+
+    #[test]
+    fn csv_rows() {
+        let csv = "q,qLabel,note\r\nhttp://www.wikidata.org/entity/Q42,Douglas Adams,\r\n\
+            http://www.wikidata.org/entity/Q1,\"Universe, the\",\"line one\nline \"\"two\"\"\"\r\n";
+        let mut rows = SparqlRows::new(csv.as_bytes(), SparqlFormat::Csv).unwrap();
+        assert_eq!(rows.header(), ["q", "qLabel", "note"]);
+        assert_eq!(
+            rows.next().unwrap().unwrap(),
+            vec!["http://www.wikidata.org/entity/Q42", "Douglas Adams", ""]
+        );
+        assert_eq!(
+            rows.next().unwrap().unwrap(),
+            vec![
+                "http://www.wikidata.org/entity/Q1",
+                "Universe, the",
+                "line one\nline \"two\""
+            ]
+        );
+        assert!(rows.next().is_none());
+
+        let mut rows =
+            SparqlRows::new("a\n\"unterminated\n".as_bytes(), SparqlFormat::Csv).unwrap();
+        assert!(rows.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn tsv_rows() {
+        let tsv = "?q\t?qLabel\n<http://www.wikidata.org/entity/Q42>\t\"Douglas Adams\"@en\n\
+            <http://www.wikidata.org/entity/Q1>\t\n";
+        let rows = SparqlRows::new(tsv.as_bytes(), SparqlFormat::Tsv).unwrap();
+        assert_eq!(rows.header(), ["q", "qLabel"]);
+        let rows: Vec<Vec<String>> = rows.map(Result::unwrap).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][1], "\"Douglas Adams\"@en");
+        assert_eq!(rows[1], vec!["<http://www.wikidata.org/entity/Q1>", ""]);
+    }
+
+    #[test]
+    fn page_queries() {
+        let api = test_api();
+        let mut pages = api.sparql_query_pages(
+            "SELECT ?q { ?q wdt:P31 wd:Q5 } ORDER BY ?q",
+            2,
+            SparqlPagination::Offset,
+        );
+        assert!(pages
+            .page_query()
+            .ends_with("ORDER BY ?q\nLIMIT 2 OFFSET 0"));
+        pages.advance(&[json!({"q": {"value": "a"}}), json!({"q": {"value": "b"}})]);
+        assert!(pages.page_query().ends_with("LIMIT 2 OFFSET 2"));
+        assert!(!pages.done);
+        pages.advance(&[json!({"q": {"value": "c"}})]);
+        assert!(pages.done);
+
+        let mut pages = api.sparql_query_pages(
+            "SELECT ?q { ?q wdt:P31 wd:Q5 }",
+            2,
+            SparqlPagination::Key("q".to_string()),
+        );
+        let query = pages.page_query();
+        assert!(query.contains("{\nSELECT ?q { ?q wdt:P31 wd:Q5 }\n}"));
+        assert!(query.ends_with("ORDER BY STR(?q)\nLIMIT 2"));
+        assert!(!query.contains("FILTER"));
+        pages.advance(&[
+            json!({"q": {"value": "http://www.wikidata.org/entity/Q1"}}),
+            json!({"q": {"value": "http://www.wikidata.org/entity/Q2\""}}),
+        ]);
+        assert!(pages
+            .page_query()
+            .contains(r#"FILTER(STR(?q) > "http://www.wikidata.org/entity/Q2\"")"#));
+        assert!(!pages.done);
+    }
+
+    #[test]
+    fn merge_pages() {
+        let mut merged = Value::Null;
+        merge_sparql_results(
+            &mut merged,
+            json!({"head": {"vars": ["q"]}, "results": {"bindings": [{"q": {"value": "a"}}]}}),
+        );
+        merge_sparql_results(
+            &mut merged,
+            json!({"head": {"vars": ["q"]}, "results": {"bindings": [{"q": {"value": "b"}}]}}),
+        );
+        assert_eq!(merged["head"]["vars"], json!(["q"]));
+        assert_eq!(merged["results"]["bindings"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn entities_from_sparql_bindings() {
+        let base = "http://www.wikidata.org/entity/";
+        let result = json!({"head": {"vars": ["x"]}, "results": {"bindings": [
+            {"x": {"type": "uri", "value": "http://www.wikidata.org/entity/Q42"}},
+            {"x": {"type": "uri", "value": "http://www.wikidata.org/entity/P31"}},
+            {"y": {"type": "literal", "value": "unbound x"}},
+            {"x": {"type": "uri", "value": "http://www.wikidata.org/entity/L7-F1"}}
+        ]}});
+        assert_eq!(
+            entities_from_bindings(&result, "x", base).unwrap(),
+            vec![
+                EntityId::Item(42),
+                EntityId::Property(31),
+                EntityId::Form(7, 1)
+            ]
+        );
+        for value in [
+            json!({"type": "uri", "value": "http://example.org/entity/Q42"}),
+            json!({"type": "uri", "value": "http://www.wikidata.org/entity/statement/Q42-abc"}),
+            json!({"type": "literal", "value": "Q42"}),
+        ] {
+            let result = json!({"results": {"bindings": [{"x": value}]}});
+            assert!(matches!(
+                entities_from_bindings(&result, "x", base),
+                Err(SparqlError::NotAnEntity(_))
+            ));
+        }
+        let api = test_api();
+        assert!(matches!(
+            api.sparql_entities(&result, "x"),
+            Err(SparqlError::NoConceptBaseUri)
+        ));
+    }
+}
@@ -0,0 +1,304 @@
+/*!
+Types for timestamps and expiry values in API results.
+
+With the (default) `chrono` feature, `Timestamp` is a `chrono::DateTime<Utc>`;
+without it, timestamps are kept as the ISO 8601 strings the API returns.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A point in time, as returned by the API (e.g. "2020-04-14T10:22:31Z")
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::Utc>;
+
+/// A point in time, as returned by the API (e.g. "2020-04-14T10:22:31Z")
+#[cfg(not(feature = "chrono"))]
+pub type Timestamp = String;
+
+/// Parses an API timestamp
+#[cfg(feature = "chrono")]
+pub fn parse_timestamp(s: &str) -> Option<Timestamp> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|t| t.with_timezone(&chrono::Utc))
+}
+
+/// Parses an API timestamp
+#[cfg(not(feature = "chrono"))]
+pub fn parse_timestamp(s: &str) -> Option<Timestamp> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+/// Parses a timestamp in the database format of MediaWiki (e.g. "20200414102231"),
+/// as some extensions return it instead of ISO 8601
+pub fn parse_db_timestamp(s: &str) -> Option<Timestamp> {
+    if s.len() != 14 || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    parse_timestamp(&format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &s[0..4],
+        &s[4..6],
+        &s[6..8],
+        &s[8..10],
+        &s[10..12],
+        &s[12..14]
+    ))
+}
+
+/// Formats a timestamp the way the API expects it in parameters
+#[cfg(feature = "chrono")]
+pub fn format_timestamp(t: &Timestamp) -> String {
+    t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Formats a timestamp the way the API expects it in parameters
+#[cfg(not(feature = "chrono"))]
+pub fn format_timestamp(t: &Timestamp) -> String {
+    t.clone()
+}
+
+/// Converts seconds since the Unix epoch (as used by EventStreams) to a timestamp
+#[cfg(feature = "chrono")]
+pub fn timestamp_from_unix(seconds: i64) -> Option<Timestamp> {
+    use chrono::TimeZone;
+    chrono::Utc.timestamp_opt(seconds, 0).single()
+}
+
+/// Converts seconds since the Unix epoch (as used by EventStreams) to a timestamp
+#[cfg(not(feature = "chrono"))]
+pub fn timestamp_from_unix(seconds: i64) -> Option<Timestamp> {
+    // Civil date from days since the epoch, after Howard Hinnant's `civil_from_days`
+    let days = seconds.div_euclid(86400);
+    let secs = seconds.rem_euclid(86400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    if !(0..=9999).contains(&year) {
+        return None;
+    }
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    ))
+}
+
+/// Converts an API timestamp (e.g. "2020-04-14T10:22:31Z") to seconds since the Unix epoch
+#[cfg(feature = "chrono")]
+pub fn unix_from_timestamp(s: &str) -> Option<i64> {
+    parse_timestamp(s).map(|t| t.timestamp())
+}
+
+/// Converts an API timestamp (e.g. "2020-04-14T10:22:31Z") to seconds since the Unix epoch
+#[cfg(not(feature = "chrono"))]
+pub fn unix_from_timestamp(s: &str) -> Option<i64> {
+    let (date, time) = s.strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month) || !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+    // Days since the epoch from a civil date, after Howard Hinnant's `days_from_civil`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// An expiry value, as used for blocks, protections, and watchlist entries. The API uses several
+/// spellings (`infinity`, `infinite`, `indefinite`, `never`) for "does not expire".
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "chrono", derive(Copy))]
+pub enum Expiry {
+    /// Does not expire
+    Infinite,
+    /// Expires at the given time
+    At(Timestamp),
+}
+
+impl Expiry {
+    /// Parses an expiry value from the API
+    pub fn parse(s: &str) -> Option<Expiry> {
+        match s {
+            "infinity" | "infinite" | "indefinite" | "never" => Some(Expiry::Infinite),
+            s => parse_timestamp(s).map(Expiry::At),
+        }
+    }
+
+    /// Checks if this expiry is infinite
+    pub fn is_infinite(&self) -> bool {
+        *self == Expiry::Infinite
+    }
+
+    /// Returns the expiry time, or `None` if infinite
+    pub fn timestamp(&self) -> Option<&Timestamp> {
+        match self {
+            Expiry::Infinite => None,
+            Expiry::At(t) => Some(t),
+        }
+    }
+}
+
+impl fmt::Display for Expiry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expiry::Infinite => write!(f, "infinity"),
+            Expiry::At(t) => write!(f, "{}", format_timestamp(t)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Expiry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Expiry::parse(&s).ok_or_else(|| de::Error::custom(format!("invalid expiry: {}", s)))
+    }
+}
+
+impl Serialize for Expiry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_infinite() {
+        for s in &["infinity", "infinite", "indefinite", "never"] {
+            assert_eq!(Expiry::parse(s), Some(Expiry::Infinite));
+        }
+        let e: Expiry = serde_json::from_value(json!("infinity")).unwrap();
+        assert!(e.is_infinite());
+        assert_eq!(e.timestamp(), None);
+    }
+
+    #[test]
+    fn expiry_timestamp() {
+        let e: Expiry = serde_json::from_value(json!("2020-04-14T10:22:31Z")).unwrap();
+        assert_eq!(
+            e.timestamp(),
+            parse_timestamp("2020-04-14T10:22:31Z").as_ref()
+        );
+        assert_eq!(e.to_string(), "2020-04-14T10:22:31Z");
+        assert_eq!(json!(e), json!("2020-04-14T10:22:31Z"));
+        assert!(serde_json::from_value::<Expiry>(json!("")).is_err());
+    }
+
+    #[test]
+    fn unix_timestamps() {
+        assert_eq!(
+            timestamp_from_unix(1586859751),
+            parse_timestamp("2020-04-14T10:22:31Z")
+        );
+        assert_eq!(
+            timestamp_from_unix(0),
+            parse_timestamp("1970-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            timestamp_from_unix(951782400),
+            parse_timestamp("2000-02-29T00:00:00Z")
+        );
+        assert_eq!(
+            unix_from_timestamp("2020-04-14T10:22:31Z"),
+            Some(1586859751)
+        );
+        assert_eq!(unix_from_timestamp("2000-02-29T00:00:00Z"), Some(951782400));
+        assert_eq!(unix_from_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(unix_from_timestamp("not a timestamp"), None);
+        assert_eq!(
+            parse_db_timestamp("20200414102231"),
+            parse_timestamp("2020-04-14T10:22:31Z")
+        );
+        assert_eq!(parse_db_timestamp("2020-04-14"), None);
+    }
+
+    #[test]
+    fn unix_timestamp_boundaries() {
+        let cases = [
+            ("1969-12-31T23:59:59Z", -1),
+            ("1970-01-01T00:00:00Z", 0),
+            ("2024-02-29T23:59:59Z", 1709251199),
+            ("2024-03-01T00:00:00Z", 1709251200),
+            ("2038-01-19T03:14:08Z", 2147483648),
+            // 2100 is not a leap year, 2400 is
+            ("2100-02-28T23:59:59Z", 4107542399),
+            ("2100-03-01T00:00:00Z", 4107542400),
+            ("2400-02-29T12:00:00Z", 13574606400),
+            ("9999-12-31T23:59:59Z", 253402300799),
+        ];
+        for (timestamp, seconds) in cases {
+            assert_eq!(
+                unix_from_timestamp(timestamp),
+                Some(seconds),
+                "{}",
+                timestamp
+            );
+            assert_eq!(
+                timestamp_from_unix(seconds)
+                    .as_ref()
+                    .map(format_timestamp)
+                    .as_deref(),
+                Some(timestamp),
+                "{}",
+                seconds
+            );
+        }
+        assert_eq!(unix_from_timestamp("2100-02-29T00:00:00Z"), None);
+        assert_eq!(unix_from_timestamp("2023-04-31T00:00:00Z"), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn timestamp_chrono() {
+        use chrono::{Datelike, Timelike};
+        let t = parse_timestamp("2020-04-14T10:22:31Z").unwrap();
+        assert_eq!((t.year(), t.month(), t.day()), (2020, 4, 14));
+        assert_eq!(t.second(), 31);
+        assert!(parse_timestamp("not a timestamp").is_none());
+    }
+}
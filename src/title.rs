@@ -16,7 +16,14 @@ The `Title` class deals with page titles and namespaces
 
 extern crate lazy_static;
 
+use crate::api::Api;
+use crate::namespace::Namespace;
+use crate::site_info::{CaseSensitivity, SiteUrl};
+use std::error::Error;
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
 
 /// Shortcut for crate::api::NamespaceID
 type NamespaceID = crate::api::NamespaceID;
@@ -48,111 +55,215 @@ pub fn toggle_namespace_id(id: NamespaceID) -> Option<NamespaceID> {
 pub struct Title {
     title: String, // Always stored without underscores
     namespace_id: NamespaceID,
+    interwiki: Option<String>, // Always lowercase
+    fragment: Option<String>,
 }
 
 impl Hash for Title {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.namespace_id.hash(state);
         self.title.hash(state);
+        self.interwiki.hash(state);
+        self.fragment.hash(state);
     }
 }
 
 impl Title {
     /// Constructor, where un-prefixed title and namespace are known.
-    /// Assumes title has correct capitalization
-    pub fn new(title: &str, namespace_id: NamespaceID) -> Title {
+    /// The namespace can be a `NamespaceID` or a `Namespace`.
+    /// Whitespace is normalized (see `normalize_text`), but capitalization is not changed;
+    /// use `new_normalized` for that.
+    pub fn new<N: Into<NamespaceID>>(title: &str, namespace: N) -> Title {
         Title {
-            title: Title::underscores_to_spaces(&title),
-            namespace_id: namespace_id,
-        }
-    }
-
-    /// Constructor, where full namespace-prefixed title is known.
-    /// Uses Api to parse valid namespaces
-    pub fn new_from_full(full_title: &str, api: &crate::api::Api) -> Self {
-        let mut v: Vec<&str> = full_title.split(":").collect();
-        if v.len() == 1 {
-            return Self::new(&full_title, 0);
-        }
-        let namespace_name = Title::first_letter_uppercase(&v.remove(0));
-        let title = Title::underscores_to_spaces(&v.join(":"));
-        let site_info = api.get_site_info();
-
-        // Canonical namespaces
-        match site_info["query"]["namespaces"].as_object() {
-            Some(namespaces) => {
-                for (_, ns) in namespaces {
-                    match ns["*"].as_str() {
-                        Some(namespace) => {
-                            if Title::underscores_to_spaces(&namespace)
-                                == namespace_name
-                            {
-                                return Self::new_from_namespace_object(title, ns);
-                            }
-                        }
-                        None => {}
-                    }
-                    match ns["canonical"].as_str() {
-                        Some(namespace) => {
-                            if Title::underscores_to_spaces(&namespace)
-                                == namespace_name
-                            {
-                                return Self::new_from_namespace_object(title, ns);
-                            }
-                        }
-                        None => {}
-                    }
-                }
-            }
-            None => {}
-        }
-
-        // Aliases
-        match site_info["query"]["namespacealiases"].as_array() {
-            Some(namespaces) => {
-                for ns in namespaces {
-                    match ns["*"].as_str() {
-                        Some(namespace) => {
-                            if Title::underscores_to_spaces(&namespace)
-                                == namespace_name
-                            {
-                                let namespace_id = ns["id"].as_i64().unwrap();
-                                let title = match ns["case"].as_str() {
-                                    Some("first-letter") => Title::first_letter_uppercase(&title),
-                                    _ => title.to_string(),
-                                };
-                                return Self::new(&title, namespace_id);
-                            }
-                        }
-                        None => {}
+            title: Title::normalize_text(title),
+            namespace_id: namespace.into(),
+            interwiki: None,
+            fragment: None,
+        }
+    }
+
+    /// Constructor, where un-prefixed title and namespace are known.
+    /// Fully normalizes the title, including first-letter capitalization if the namespace
+    /// is not case-sensitive, so it compares equal to titles returned by the API.
+    pub fn new_normalized<N: Into<NamespaceID>>(api: &Api, title: &str, namespace: N) -> Title {
+        let mut ret = Title::new(title, namespace);
+        ret.normalize(api);
+        ret
+    }
+
+    /// Constructor, where full namespace-prefixed title is known. Uses Api to parse valid
+    /// namespaces; falls back to the main namespace if the title can not be parsed
+    pub fn new_from_full(full_title: &str, api: &Api) -> Self {
+        Title::parse(api, full_title).unwrap_or_else(|_| Self::new(full_title, 0))
+    }
+
+    /// Parses a full, namespace-prefixed title (e.g. "Diskussion:Foo/Bar").
+    /// Local namespace names, canonical names, and aliases are recognized case-insensitively.
+    /// A prefix that is not a namespace is part of a main namespace title; a leading colon
+    /// (e.g. ":Kategorie:Foo") is removed.
+    /// The first letter is capitalized, unless the namespace is case-sensitive.
+    ///
+    /// An interwiki prefix known to the Api (e.g. "en:Foo") is stored separately; the rest of
+    /// the title is kept as-is, since only the target wiki can parse it. Interwiki prefixes are
+    /// only known after `Api::load_site_info_sections("interwikimap")`.
+    /// Anything after a `#` is stored as the fragment.
+    ///
+    /// # Errors
+    /// Returns `TitleError::Empty` if there is no title after the namespace prefix.
+    pub fn parse(api: &Api, full_title: &str) -> Result<Title, TitleError> {
+        let full_title = Title::normalize_text(full_title);
+        let (full_title, fragment) = match full_title.split_once('#') {
+            Some((title, fragment)) => (title.trim_end(), Some(fragment.trim())),
+            None => (full_title.as_str(), None),
+        };
+        let full_title = full_title
+            .strip_prefix(':')
+            .map_or(full_title, str::trim_start);
+        let mut ret = match full_title.split_once(':') {
+            Some((prefix, title)) => match api.get_namespace_id(prefix) {
+                Some(namespace_id) => Title::parse_local(api, title.trim(), namespace_id)?,
+                None => match api.get_site_info().interwiki(prefix) {
+                    Some(interwiki) => {
+                        let mut ret = Title::new(title, 0);
+                        ret.interwiki = Some(interwiki.prefix.clone());
+                        ret
                     }
-                }
-            }
-            None => {}
+                    None => Title::parse_local(api, full_title, 0)?,
+                },
+            },
+            None => Title::parse_local(api, full_title, 0)?,
+        };
+        ret.set_fragment(fragment);
+        Ok(ret)
+    }
+
+    /// Parses the title from a page URL of the Api's wiki. Recognizes article path URLs (e.g.
+    /// "https://de.wikipedia.org/wiki/Foo_bar"), script URLs with a `title` parameter (e.g.
+    /// ".../w/index.php?title=Foo&action=history"), URLs on the mobile domain, and
+    /// protocol-relative URLs. A URL fragment becomes the title's fragment.
+    ///
+    /// # Errors
+    /// Returns `TitleError::UnknownUrl` if the URL is not a page URL of the wiki,
+    /// or the errors of `parse` for the title in the URL.
+    pub fn from_url(api: &Api, url: &str) -> Result<Title, TitleError> {
+        let url = url.trim();
+        let url = match url.strip_prefix("//") {
+            Some(rest) => Url::parse(&format!("https://{}", rest)),
+            None => Url::parse(url),
+        }
+        .map_err(|_| TitleError::UnknownUrl)?;
+        let general = &api.get_site_info().general;
+        let host = url.host_str();
+        let host_of = |server: &SiteUrl| server.url().and_then(Url::host_str) == host;
+        if host.is_none()
+            || !(host_of(&general.server) || general.mobileserver.as_ref().is_some_and(host_of))
+        {
+            return Err(TitleError::UnknownUrl);
         }
 
-        // Fallback
-        Self::new(&full_title, 0)
+        let path = url.path();
+        let title = if path == general.script
+            || path.strip_prefix(general.scriptpath.as_str()) == Some("/index.php")
+        {
+            url.query_pairs()
+                .find(|(key, _)| key == "title")
+                .map(|(_, title)| title.into_owned())
+        } else {
+            general
+                .articlepath
+                .split_once("$1")
+                .and_then(|(prefix, suffix)| {
+                    let title = path.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                    Some(
+                        String::from_utf8_lossy(&urlencoding::decode_binary(title.as_bytes()))
+                            .into_owned(),
+                    )
+                })
+        };
+        let mut title = title.ok_or(TitleError::UnknownUrl)?;
+        if let Some(fragment) = url.fragment() {
+            title += "#";
+            title += &String::from_utf8_lossy(&urlencoding::decode_binary(fragment.as_bytes()));
+        }
+        Title::parse(api, &title)
+    }
+
+    /// Creates a normalized title in the given namespace, or fails if `title` is empty
+    fn parse_local(api: &Api, title: &str, namespace_id: NamespaceID) -> Result<Title, TitleError> {
+        if title.is_empty() {
+            return Err(TitleError::Empty);
+        }
+        Ok(Self::new_normalized(api, title, namespace_id))
     }
 
-    /// Constructor, used internally by `new_from_full`
-    fn new_from_namespace_object(title: String, ns: &serde_json::Value) -> Self {
-        let namespace_id = ns["id"].as_i64().unwrap();
-        let title = match ns["case"].as_str() {
-            Some("first-letter") => Title::first_letter_uppercase(&title),
-            _ => title.to_string(),
+    /// Checks if this title would be accepted by the wiki, using its `legaltitlechars`.
+    /// Checks for empty titles, illegal characters, percent-encoded characters, HTML entities,
+    /// relative paths (`.`, `..`, `./`, `/../`, ...), `~~~`, and the maximum length in bytes.
+    ///
+    /// # Errors
+    /// Returns the first problem found as a `TitleError`.
+    pub fn validate(&self, api: &Api) -> Result<(), TitleError> {
+        let title = self.title.as_str();
+        if title.is_empty() {
+            return Err(TitleError::Empty);
+        }
+        let legal_chars = parse_char_class(
+            api.get_site_info()
+                .general
+                .legaltitlechars
+                .as_deref()
+                .unwrap_or(DEFAULT_LEGAL_TITLE_CHARS),
+        );
+        if let Some(c) = title.chars().find(|&c| {
+            !legal_chars
+                .iter()
+                .any(|(from, to)| (*from..=*to).contains(&c))
+        }) {
+            return Err(TitleError::IllegalCharacter(c));
+        }
+        if contains_percent_encoding(title) {
+            return Err(TitleError::PercentEncoded);
+        }
+        if contains_html_entity(title) {
+            return Err(TitleError::HtmlEntity);
+        }
+        if title == "."
+            || title == ".."
+            || title.starts_with("./")
+            || title.starts_with("../")
+            || title.contains("/./")
+            || title.contains("/../")
+            || title.ends_with("/.")
+            || title.ends_with("/..")
+        {
+            return Err(TitleError::RelativePath);
+        }
+        if title.contains("~~~") {
+            return Err(TitleError::MagicTildes);
+        }
+        let max_length = if self.namespace_id == Namespace::SPECIAL.id() {
+            MAX_SPECIAL_TITLE_LENGTH
+        } else {
+            MAX_TITLE_LENGTH
         };
-        return Self::new(&title, namespace_id);
+        if title.len() > max_length {
+            return Err(TitleError::TooLong {
+                length: title.len(),
+                max_length,
+            });
+        }
+        Ok(())
     }
 
     /// Constructor, used by ``Api::result_array_to_titles``
     pub fn new_from_api_result(data: &serde_json::Value) -> Title {
-        let namespace_id = data["ns"].as_i64().unwrap_or(0).into();
+        let namespace_id = data["ns"].as_i64().unwrap_or(0);
         let mut title = data["title"].as_str().unwrap_or("").to_string();
 
-        // If namespace!=0, remove namespace prefix. THIS IS NOT IDEAL AND SHOULD USE Api AS IN new_from_full!
+        // If namespace!=0, remove namespace prefix. THIS IS NOT IDEAL AND SHOULD USE Api AS IN
+        // new_from_full!
         if namespace_id > 0 {
-            let mut v: Vec<&str> = title.split(":").collect();
+            let mut v: Vec<&str> = title.split(':').collect();
             if v.len() > 1 {
                 v.remove(0);
                 title = v.join(":");
@@ -161,7 +272,9 @@ impl Title {
 
         Title {
             title: Title::underscores_to_spaces(&title),
-            namespace_id: namespace_id,
+            namespace_id,
+            interwiki: None,
+            fragment: None,
         }
     }
 
@@ -170,13 +283,47 @@ impl Title {
         self.namespace_id
     }
 
+    /// Returns the namespace
+    pub fn namespace(&self) -> Namespace {
+        Namespace::new(self.namespace_id)
+    }
+
+    /// Returns the interwiki prefix (lowercase), if this title is on another wiki
+    pub fn interwiki(&self) -> Option<&str> {
+        self.interwiki.as_deref()
+    }
+
+    /// Sets the interwiki prefix; it is stored in lowercase
+    pub fn set_interwiki(&mut self, interwiki: Option<&str>) {
+        self.interwiki = interwiki
+            .map(|iw| iw.trim().to_lowercase())
+            .filter(|iw| !iw.is_empty());
+    }
+
+    /// Checks if this title has an interwiki prefix
+    pub fn is_external(&self) -> bool {
+        self.interwiki.is_some()
+    }
+
+    /// Returns the fragment (the part after `#`, with spaces instead of underscores), if any
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.as_deref()
+    }
+
+    /// Sets the fragment; an empty fragment is the same as none
+    pub fn set_fragment(&mut self, fragment: Option<&str>) {
+        self.fragment = fragment
+            .map(Title::underscores_to_spaces)
+            .filter(|f| !f.is_empty());
+    }
+
     /// Returns the canonical namespace text, based on the Api
-    pub fn namespace_name<'a>(&self, api: &'a crate::api::Api) -> Option<&'a str> {
+    pub fn namespace_name<'a>(&self, api: &'a Api) -> Option<&'a str> {
         api.get_canonical_namespace_name(self.namespace_id)
     }
 
     /// Returns the local namespace text, based on the Api
-    pub fn local_namespace_name<'a>(&self, api: &'a crate::api::Api) -> Option<&'a str> {
+    pub fn local_namespace_name<'a>(&self, api: &'a Api) -> Option<&'a str> {
         api.get_local_namespace_name(self.namespace_id)
     }
 
@@ -190,24 +337,120 @@ impl Title {
         &self.title // was Title::underscores_to_spaces(&self.title) but always storing without underscores
     }
 
-    /// Returns the namespace-prefixed title, with underscores
-    pub fn full_with_underscores(&self, api: &crate::api::Api) -> Option<String> {
+    /// Returns the namespace-prefixed title, with underscores.
+    /// Includes the interwiki prefix and fragment, if any.
+    pub fn full_with_underscores(&self, api: &Api) -> Option<String> {
+        let title = self.prefixed_with_underscores(api)?;
+        Some(self.add_interwiki_and_fragment(title, Title::spaces_to_underscores))
+    }
+
+    /// Returns the namespace-prefixed title, with underscores, but without interwiki prefix and
+    /// fragment
+    fn prefixed_with_underscores(&self, api: &Api) -> Option<String> {
         Some(
-            match Title::spaces_to_underscores(&self.local_namespace_name(api)?).as_str() {
+            match Title::spaces_to_underscores(self.local_namespace_name(api)?).as_str() {
                 "" => self.with_underscores(),
                 ns => ns.to_owned() + ":" + &self.with_underscores(),
             },
         )
     }
 
-    /// Returns the namespace-prefixed title, with spaces instead of underscores
-    pub fn full_pretty(&self, api: &crate::api::Api) -> Option<String> {
-        Some(
-            match Title::underscores_to_spaces(&self.local_namespace_name(api)?).as_str() {
-                "" => self.pretty().to_string(),
-                ns => ns.to_owned() + ":" + &self.pretty(),
-            },
-        )
+    /// Returns the URL of the page, based on the `server` and `articlepath` of the Api's site info.
+    /// For interwiki titles, the URL pattern of the interwiki prefix is used.
+    /// Returns `None` if the namespace or interwiki prefix is unknown to the Api.
+    pub fn full_url(&self, api: &Api) -> Option<String> {
+        let title = Title::wiki_urlencode(&self.prefixed_with_underscores(api)?);
+        let mut url = match &self.interwiki {
+            Some(prefix) => api.get_site_info().interwiki(prefix)?.url_for(&title),
+            None => {
+                let general = &api.get_site_info().general;
+                general.server.to_absolute() + &general.articlepath.replace("$1", &title)
+            }
+        };
+        if let Some(fragment) = &self.fragment {
+            url += "#";
+            url += &Title::wiki_urlencode(&Title::spaces_to_underscores(fragment));
+        }
+        Some(url)
+    }
+
+    /// Returns the URL for editing the page, based on the `server` and `script` of the Api's site
+    /// info. Returns `None` for interwiki titles, and if the namespace is unknown to the Api.
+    pub fn edit_url(&self, api: &Api) -> Option<String> {
+        if self.is_external() {
+            return None;
+        }
+        let title = Title::wiki_urlencode(&self.prefixed_with_underscores(api)?);
+        let general = &api.get_site_info().general;
+        Some(format!(
+            "{}{}?title={}&action=edit",
+            general.server.to_absolute(),
+            general.script,
+            title
+        ))
+    }
+
+    /// Percent-encodes a string for use in a URL the way MediaWiki does (`wfUrlencode`):
+    /// letters, digits, and `-_.~;@$!*(),/:` are kept as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mediawiki::title::Title;
+    /// assert_eq!(Title::wiki_urlencode("User:Foo/Ä?&"), "User:Foo/%C3%84%3F%26");
+    /// ```
+    pub fn wiki_urlencode(s: &str) -> String {
+        let mut ret = String::with_capacity(s.len());
+        for byte in s.bytes() {
+            match byte {
+                b'A'..=b'Z'
+                | b'a'..=b'z'
+                | b'0'..=b'9'
+                | b'-'
+                | b'_'
+                | b'.'
+                | b'~'
+                | b';'
+                | b'@'
+                | b'$'
+                | b'!'
+                | b'*'
+                | b'('
+                | b')'
+                | b','
+                | b'/'
+                | b':' => ret.push(byte as char),
+                _ => ret += &format!("%{:02X}", byte),
+            }
+        }
+        ret
+    }
+
+    /// Returns the namespace-prefixed title, with spaces instead of underscores.
+    /// Includes the interwiki prefix and fragment, if any.
+    pub fn full_pretty(&self, api: &Api) -> Option<String> {
+        let title = match Title::underscores_to_spaces(self.local_namespace_name(api)?).as_str() {
+            "" => self.pretty().to_string(),
+            ns => ns.to_owned() + ":" + self.pretty(),
+        };
+        Some(self.add_interwiki_and_fragment(title, |f| f.to_string()))
+    }
+
+    /// Adds the interwiki prefix and the fragment (formatted with `format_fragment`) to `title`
+    fn add_interwiki_and_fragment(
+        &self,
+        title: String,
+        format_fragment: impl Fn(&str) -> String,
+    ) -> String {
+        let mut ret = match &self.interwiki {
+            Some(interwiki) => interwiki.to_owned() + ":" + &title,
+            None => title,
+        };
+        if let Some(fragment) = &self.fragment {
+            ret += "#";
+            ret += &format_fragment(fragment);
+        }
+        ret
     }
 
     /// Changes all spaces to underscores
@@ -220,6 +463,57 @@ impl Title {
         s.replace("_", " ").trim().to_string()
     }
 
+    /// Normalizes title text the way MediaWiki does, apart from capitalization:
+    /// applies Unicode NFC, removes directional marks, turns underscores and
+    /// all kinds of whitespace into spaces, collapses runs of spaces, and trims.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mediawiki::title::Title;
+    /// assert_eq!(Title::normalize_text(" A_little \u{a0} test_"), "A little test");
+    /// assert_eq!(Title::normalize_text("Cafe\u{301}"), "Caf\u{e9}");
+    /// ```
+    pub fn normalize_text(s: &str) -> String {
+        let mut ret = String::with_capacity(s.len());
+        let mut pending_space = false;
+        for c in s.nfc() {
+            match c {
+                // Left-to-right mark, right-to-left mark, bidi embedding and override characters
+                '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' => {}
+                c if c == '_' || c.is_whitespace() => pending_space = true,
+                c => {
+                    if pending_space && !ret.is_empty() {
+                        ret.push(' ');
+                    }
+                    pending_space = false;
+                    ret.push(c);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Fully normalizes title text (see `normalize_text`) and capitalizes
+    /// the first letter, unless `case` is `CaseSensitive`
+    pub fn normalize_text_with_case(s: &str, case: CaseSensitivity) -> String {
+        let s = Title::normalize_text(s);
+        match case {
+            CaseSensitivity::FirstLetter => Title::first_letter_uppercase(&s),
+            CaseSensitivity::CaseSensitive => s,
+        }
+    }
+
+    /// Normalizes this title, using the case sensitivity of its namespace from the Api.
+    /// Namespaces unknown to the Api use the wiki's default case sensitivity.
+    pub fn normalize(&mut self, api: &Api) {
+        let case = match api.get_namespace_info(self.namespace_id) {
+            Some(ns) => ns.case,
+            None => api.get_site_info().general.case,
+        };
+        self.title = Title::normalize_text_with_case(&self.title, case);
+    }
+
     /// Changes the first letter to uppercase.
     /// Enforces spaces instead of underscores.
     pub fn first_letter_uppercase(s: &str) -> String {
@@ -231,6 +525,65 @@ impl Title {
         }
     }
 
+    /// Checks if this title is a subpage, i.e. contains a `/` and is in a namespace
+    /// that has subpages enabled, according to the Api
+    pub fn is_subpage(&self, api: &Api) -> bool {
+        self.title.contains('/')
+            && api
+                .get_namespace_info(self.namespace_id)
+                .is_some_and(|ns| ns.subpages)
+    }
+
+    /// Returns the parent page of a subpage, e.g. "Foo/Bar" for "Foo/Bar/Baz".
+    /// Does not check whether the namespace has subpages (see `is_subpage`);
+    /// a title without `/` is its own base page. The fragment is not kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mediawiki::title::Title;
+    /// assert_eq!(Title::new("Foo/Bar/Baz", 2).base_page(), Title::new("Foo/Bar", 2));
+    /// assert_eq!(Title::new("Foo", 2).base_page(), Title::new("Foo", 2));
+    /// ```
+    pub fn base_page(&self) -> Title {
+        let base = match self.title.rsplit_once('/') {
+            Some((base, _)) if !base.is_empty() => base,
+            _ => &self.title,
+        };
+        self.with_title_text(base)
+    }
+
+    /// Returns the top-level page of a subpage, e.g. "Foo" for "Foo/Bar/Baz".
+    /// Does not check whether the namespace has subpages (see `is_subpage`);
+    /// a title without `/` is its own root page. The fragment is not kept.
+    pub fn root_page(&self) -> Title {
+        let root = match self.title.split_once('/') {
+            Some((root, _)) if !root.is_empty() => root,
+            _ => &self.title,
+        };
+        self.with_title_text(root)
+    }
+
+    /// Returns the last part of a subpage title, e.g. "Baz" for "Foo/Bar/Baz",
+    /// or the whole title if it has no `/`.
+    /// Does not check whether the namespace has subpages (see `is_subpage`).
+    pub fn subpage_name(&self) -> &str {
+        match self.title.rsplit_once('/') {
+            Some((base, name)) if !base.is_empty() => name,
+            _ => &self.title,
+        }
+    }
+
+    /// Returns a title in the same namespace (and on the same wiki), with the given text
+    fn with_title_text(&self, title: &str) -> Title {
+        Title {
+            title: title.to_string(),
+            namespace_id: self.namespace_id,
+            interwiki: self.interwiki.clone(),
+            fragment: None,
+        }
+    }
+
     /// Changes this Title to refer to the other member of the corresponding
     /// article-talk page pair for this page. Won't change Special pages.
     ///
@@ -270,11 +623,124 @@ impl Title {
     /// assert_eq!(Title::new("Test", -1).into_toggle_talk(),
     ///     Title::new("Test", -1));
     /// ```
-    pub fn into_toggle_talk(self) -> Self {
-        Title::new(&self.title, toggle_namespace_id(self.namespace_id).unwrap_or(self.namespace_id))
+    pub fn into_toggle_talk(mut self) -> Self {
+        self.toggle_talk();
+        self
     }
 }
 
+/// `$wgLegalTitleChars` of MediaWiki, used if the site info has no `legaltitlechars`
+const DEFAULT_LEGAL_TITLE_CHARS: &str = " %!\"$&'()*,\\-.\\/0-9:;=?@A-Z\\\\^_`a-z~\\x80-\\xFF+";
+
+/// Maximum length of a title (without namespace prefix), in bytes
+const MAX_TITLE_LENGTH: usize = 255;
+
+/// Maximum length of a special page title (without namespace prefix), in bytes
+const MAX_SPECIAL_TITLE_LENGTH: usize = 512;
+
+/// Parses a PCRE character class body like `legaltitlechars` into inclusive ranges.
+/// `\x80-\xFF` matches UTF-8 bytes in MediaWiki, so it allows all non-ASCII characters.
+fn parse_char_class(class: &str) -> Vec<(char, char)> {
+    fn next_char(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<char> {
+        match chars.next()? {
+            '\\' => match chars.next()? {
+                'x' => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+                }
+                c => Some(c),
+            },
+            c => Some(c),
+        }
+    }
+
+    let mut ranges = vec![];
+    let mut chars = class.chars().peekable();
+    while let Some(from) = next_char(&mut chars) {
+        let mut to = from;
+        if chars.peek() == Some(&'-') {
+            chars.next();
+            match next_char(&mut chars) {
+                Some(c) => to = c,
+                None => ranges.push(('-', '-')),
+            }
+        }
+        if from <= '\u{80}' && to >= '\u{ff}' {
+            to = char::MAX;
+        }
+        ranges.push((from, to));
+    }
+    ranges
+}
+
+/// Checks for percent-encoded characters like `%20`, which MediaWiki does not allow in titles
+fn contains_percent_encoding(s: &str) -> bool {
+    s.as_bytes()
+        .windows(3)
+        .any(|w| w[0] == b'%' && w[1].is_ascii_hexdigit() && w[2].is_ascii_hexdigit())
+}
+
+/// Checks for named HTML entities like `&amp;`, which MediaWiki does not allow in titles
+fn contains_html_entity(s: &str) -> bool {
+    s.match_indices('&').any(|(pos, _)| {
+        let rest = &s[pos + 1..];
+        match rest.find(';') {
+            Some(end) => end > 0 && rest[..end].chars().all(|c| c.is_alphanumeric()),
+            None => false,
+        }
+    })
+}
+
+/// Reasons why a string is not a valid `Title`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TitleError {
+    /// The title is empty, or consists only of a namespace prefix
+    Empty,
+    /// The title contains a character that is not in the wiki's `legaltitlechars`
+    IllegalCharacter(char),
+    /// The title contains a percent-encoded character, like `%20`
+    PercentEncoded,
+    /// The title contains an HTML entity, like `&amp;`
+    HtmlEntity,
+    /// The title is, or contains, a relative path, like `..` or `a/./b`
+    RelativePath,
+    /// The title contains `~~~`, which would be replaced by a signature
+    MagicTildes,
+    /// The URL is not a page URL of the wiki
+    UnknownUrl,
+    /// The title is longer than the maximum length
+    TooLong {
+        /// Length of the title, in bytes
+        length: usize,
+        /// Maximum length, in bytes
+        max_length: usize,
+    },
+}
+
+impl fmt::Display for TitleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TitleError::Empty => write!(f, "empty title"),
+            TitleError::IllegalCharacter(c) => {
+                write!(f, "title contains illegal character {:?}", c)
+            }
+            TitleError::PercentEncoded => write!(f, "title contains percent-encoded characters"),
+            TitleError::HtmlEntity => write!(f, "title contains HTML entities"),
+            TitleError::RelativePath => write!(f, "title is a relative path"),
+            TitleError::MagicTildes => write!(f, "title contains ~~~"),
+            TitleError::UnknownUrl => write!(f, "not a page URL of this wiki"),
+            TitleError::TooLong { length, max_length } => write!(
+                f,
+                "title is {} bytes long, maximum is {}",
+                length, max_length
+            ),
+        }
+    }
+}
+
+impl Error for TitleError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -287,10 +753,286 @@ mod tests {
         &API
     }
 
+    #[test]
+    fn parse_localized_namespace() {
+        let api = test_api();
+        assert_eq!(
+            Title::parse(&api, "Diskussion:Foo/Bar"),
+            Ok(Title::new("Foo/Bar", 1))
+        );
+        assert_eq!(
+            Title::parse(&api, "benutzer_diskussion:example"),
+            Ok(Title::new("Example", 3))
+        );
+    }
+
+    #[test]
+    fn parse_canonical_namespace_and_alias() {
+        let api = test_api();
+        assert_eq!(Title::parse(&api, "Talk:Foo"), Ok(Title::new("Foo", 1)));
+        assert_eq!(
+            Title::parse(&api, "Bild:Foo.jpg"),
+            Ok(Title::new("Foo.jpg", 6))
+        );
+        assert_eq!(
+            Title::parse(&api, "WP : Hauptseite"),
+            Ok(Title::new("Hauptseite", 4))
+        );
+    }
+
+    #[test]
+    fn parse_main_namespace() {
+        let api = test_api();
+        assert_eq!(Title::parse(&api, "foo"), Ok(Title::new("Foo", 0)));
+        assert_eq!(
+            Title::parse(&api, "Keine Namensraum:Foo"),
+            Ok(Title::new("Keine Namensraum:Foo", 0))
+        );
+        assert_eq!(Title::parse(&api, ":Foo"), Ok(Title::new("Foo", 0)));
+        assert_eq!(
+            Title::parse(&api, ":Kategorie:Foo"),
+            Ok(Title::new("Foo", Namespace::CATEGORY))
+        );
+    }
+
+    #[test]
+    fn parse_case_sensitive_namespace() {
+        let api = test_api();
+        assert_eq!(
+            Title::parse(&api, "Gadget:foo"),
+            Ok(Title::new("foo", 2300))
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        let api = test_api();
+        assert_eq!(Title::parse(&api, ""), Err(TitleError::Empty));
+        assert_eq!(Title::parse(&api, "Diskussion:"), Err(TitleError::Empty));
+        assert_eq!(Title::parse(&api, ":"), Err(TitleError::Empty));
+    }
+
+    #[test]
+    fn parse_interwiki_and_fragment() {
+        let api = test_api();
+        let title = Title::parse(&api, "EN:user_talk:foo#Some_section").unwrap();
+        assert_eq!(title.interwiki(), Some("en"));
+        assert!(title.is_external());
+        assert_eq!(title.namespace_id(), 0);
+        assert_eq!(title.pretty(), "user talk:foo");
+        assert_eq!(title.fragment(), Some("Some section"));
+        assert_eq!(
+            title.full_pretty(&api),
+            Some("en:user talk:foo#Some section".to_string())
+        );
+        assert_eq!(
+            title.full_with_underscores(&api),
+            Some("en:user_talk:foo#Some_section".to_string())
+        );
+
+        let title = Title::parse(&api, "Diskussion:foo # bar ").unwrap();
+        assert_eq!(title.interwiki(), None);
+        assert_eq!(title.pretty(), "Foo");
+        assert_eq!(title.fragment(), Some("bar"));
+        assert_eq!(
+            title.full_pretty(&api),
+            Some("Diskussion:Foo#bar".to_string())
+        );
+
+        assert_eq!(Title::parse(&api, "Foo#").unwrap(), Title::new("Foo", 0));
+        assert_eq!(
+            Title::parse(&api, "commons:").unwrap().interwiki(),
+            Some("commons")
+        );
+        assert_eq!(Title::parse(&api, "#Foo"), Err(TitleError::Empty));
+        // Unknown prefixes are still part of the title
+        assert_eq!(
+            Title::parse(&api, "xx:Foo").unwrap(),
+            Title::new("Xx:Foo", 0)
+        );
+    }
+
+    #[test]
+    fn fragment_setter() {
+        let mut title = Title::new("Foo", 0);
+        title.set_fragment(Some("a_b"));
+        assert_eq!(title.fragment(), Some("a b"));
+        assert_ne!(title, Title::new("Foo", 0));
+        title.set_fragment(Some(""));
+        assert_eq!(title, Title::new("Foo", 0));
+        title.set_interwiki(Some("Wikt"));
+        assert_eq!(title.interwiki(), Some("wikt"));
+    }
+
+    #[test]
+    fn subpages() {
+        let api = test_api();
+        let title = Title::parse(&api, "Benutzer:Example/Archiv/2020#Oben").unwrap();
+        assert!(title.is_subpage(&api));
+        assert_eq!(title.base_page(), Title::new("Example/Archiv", 2));
+        assert_eq!(title.root_page(), Title::new("Example", 2));
+        assert_eq!(title.subpage_name(), "2020");
+        // The main namespace has no subpages on dewiki
+        let title = Title::new("AC/DC", 0);
+        assert!(!title.is_subpage(&api));
+        assert_eq!(title.subpage_name(), "DC");
+        assert!(!Title::new("Example", 2).is_subpage(&api));
+        assert_eq!(Title::new("/Foo", 2).subpage_name(), "/Foo");
+        assert_eq!(Title::new("/Foo", 2).root_page(), Title::new("/Foo", 2));
+    }
+
+    #[test]
+    fn urls() {
+        let api = test_api();
+        let title = Title::parse(&api, "Diskussion:Foo bar?#Ab & zu").unwrap();
+        assert_eq!(
+            title.full_url(&api).unwrap(),
+            "https://de.wikipedia.org/wiki/Diskussion:Foo_bar%3F#Ab_%26_zu"
+        );
+        assert_eq!(
+            title.edit_url(&api).unwrap(),
+            "https://de.wikipedia.org/w/index.php?title=Diskussion:Foo_bar%3F&action=edit"
+        );
+        assert_eq!(
+            Title::new("Straße/1+1", 0).full_url(&api).unwrap(),
+            "https://de.wikipedia.org/wiki/Stra%C3%9Fe/1%2B1"
+        );
+        let title = Title::parse(&api, "en:Rust (programming language)").unwrap();
+        assert_eq!(
+            title.full_url(&api).unwrap(),
+            "https://en.wikipedia.org/wiki/Rust_(programming_language)"
+        );
+        assert_eq!(title.edit_url(&api), None);
+        assert_eq!(Title::new("Foo", 4711).full_url(&api), None);
+    }
+
+    #[test]
+    fn from_url() {
+        let api = test_api();
+        let expected = Title::new("Foo bar", 1);
+        for url in &[
+            "https://de.wikipedia.org/wiki/Diskussion:Foo_bar",
+            "http://de.wikipedia.org/wiki/diskussion:foo%20bar",
+            "//de.m.wikipedia.org/wiki/Diskussion:Foo_bar",
+            "https://de.wikipedia.org/w/index.php?title=Diskussion:Foo_bar&action=history",
+            "https://de.wikipedia.org/w/index.php?action=edit&title=Diskussion%3AFoo+bar",
+        ] {
+            assert_eq!(Title::from_url(&api, url), Ok(expected.clone()), "{}", url);
+        }
+        let title = Title::from_url(
+            &api,
+            "https://de.wikipedia.org/wiki/Stra%C3%9Fe/1+1#Geschichte_(alt)",
+        )
+        .unwrap();
+        assert_eq!(title.pretty(), "Straße/1+1");
+        assert_eq!(title.fragment(), Some("Geschichte (alt)"));
+        for url in &[
+            "https://en.wikipedia.org/wiki/Foo",
+            "https://de.wikipedia.org/w/api.php?title=Foo",
+            "https://de.wikipedia.org/w/index.php?action=history",
+            "not a url",
+        ] {
+            assert_eq!(
+                Title::from_url(&api, url),
+                Err(TitleError::UnknownUrl),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let api = test_api();
+        assert_eq!(Title::new("Foo/Bar (baz)", 0).validate(&api), Ok(()));
+        assert_eq!(Title::new("Zürich – €", 0).validate(&api), Ok(()));
+        assert_eq!(Title::new("", 0).validate(&api), Err(TitleError::Empty));
+        assert_eq!(
+            Title::new("Foo[bar]", 0).validate(&api),
+            Err(TitleError::IllegalCharacter('['))
+        );
+        assert_eq!(
+            Title::new("Foo#bar", 0).validate(&api),
+            Err(TitleError::IllegalCharacter('#'))
+        );
+        assert_eq!(Title::new("100% sure", 0).validate(&api), Ok(()));
+        assert_eq!(
+            Title::new("Foo%2Fbar", 0).validate(&api),
+            Err(TitleError::PercentEncoded)
+        );
+        assert_eq!(Title::new("A & B; C", 0).validate(&api), Ok(()));
+        assert_eq!(
+            Title::new("A&amp;B", 0).validate(&api),
+            Err(TitleError::HtmlEntity)
+        );
+        for relative in &["..", "./Foo", "Foo/../Bar", "Foo/."] {
+            assert_eq!(
+                Title::new(relative, 0).validate(&api),
+                Err(TitleError::RelativePath)
+            );
+        }
+        assert_eq!(Title::new("Foo..Bar", 0).validate(&api), Ok(()));
+        assert_eq!(
+            Title::new("Foo ~~~~", 1).validate(&api),
+            Err(TitleError::MagicTildes)
+        );
+        assert_eq!(
+            Title::new(&"ä".repeat(128), 0).validate(&api),
+            Err(TitleError::TooLong {
+                length: 256,
+                max_length: 255
+            })
+        );
+        assert_eq!(Title::new(&"ä".repeat(128), -1).validate(&api), Ok(()));
+    }
+
+    #[test]
+    fn legal_title_chars() {
+        let ranges = parse_char_class(DEFAULT_LEGAL_TITLE_CHARS);
+        let legal = |c: char| ranges.iter().any(|(from, to)| (*from..=*to).contains(&c));
+        for c in " %-/\\+9Zz~ÿ\u{10000}".chars() {
+            assert!(legal(c), "{:?} should be legal", c);
+        }
+        for c in "#<>[]|{}\u{7f}".chars() {
+            assert!(!legal(c), "{:?} should be illegal", c);
+        }
+    }
+
+    #[test]
+    fn normalize_text() {
+        assert_eq!(Title::normalize_text("__Foo__bar__"), "Foo bar");
+        assert_eq!(Title::normalize_text("Foo\u{2003}\u{3000}bar"), "Foo bar");
+        assert_eq!(Title::normalize_text("\u{200e}Foo\u{200f}"), "Foo");
+        assert_eq!(
+            Title::normalize_text("A\u{30a}ngstr\u{f6}m"),
+            "\u{c5}ngstr\u{f6}m"
+        );
+        assert_eq!(Title::normalize_text(" \t"), "");
+    }
+
+    #[test]
+    fn normalize_with_namespace_case() {
+        let api = test_api();
+        assert_eq!(
+            Title::new_normalized(&api, "foo_ bar", 1),
+            Title::new("Foo bar", 1)
+        );
+        assert_eq!(
+            Title::new_normalized(&api, "foo_ bar", 2300),
+            Title::new("foo bar", 2300)
+        );
+        // Unknown namespaces use the wiki default
+        assert_eq!(
+            Title::new_normalized(&api, "über", 4711),
+            Title::new("Über", 4711)
+        );
+        assert_eq!(Title::new("Caf\u{e9}", 0), Title::new("Cafe\u{301}", 0));
+    }
+
     #[test]
     fn new_from_full_main_namespace() {
         assert_eq!(
-            Title::new_from_full(&"Main namespace", wd_api()),
+            Title::new_from_full("Main namespace", wd_api()),
             Title::new("Main namespace", 0)
         );
     }
@@ -298,7 +1040,7 @@ mod tests {
     #[test]
     fn new_from_full_canonical_namespace() {
         assert_eq!(
-            Title::new_from_full(&"File:Some file.jpg", wd_api()),
+            Title::new_from_full("File:Some file.jpg", wd_api()),
             Title::new("Some file.jpg", 6)
         );
     }
@@ -306,7 +1048,7 @@ mod tests {
     #[test]
     fn new_from_full_canonical_namespace_with_colon() {
         assert_eq!(
-            Title::new_from_full(&"Project talk:A project:yes, really", wd_api()),
+            Title::new_from_full("Project talk:A project:yes, really", wd_api()),
             Title::new("A project:yes, really", 5)
         );
     }
@@ -314,7 +1056,7 @@ mod tests {
     #[test]
     fn new_from_full_namespace_alias() {
         assert_eq!(
-            Title::new_from_full(&"Item:Q12345", wd_api()),
+            Title::new_from_full("Item:Q12345", wd_api()),
             Title::new("Q12345", 0)
         );
     }
@@ -322,7 +1064,7 @@ mod tests {
     #[test]
     fn new_from_full_special_namespace() {
         assert_eq!(
-            Title::new_from_full(&"Special:A title", wd_api()),
+            Title::new_from_full("Special:A title", wd_api()),
             Title::new("A title", -1)
         );
     }
@@ -330,7 +1072,7 @@ mod tests {
     #[test]
     fn new_from_full_invalid_namespace() {
         assert_eq!(
-            Title::new_from_full(&"This is not a namespace:A title", wd_api()),
+            Title::new_from_full("This is not a namespace:A title", wd_api()),
             Title::new("This is not a namespace:A title", 0)
         );
     }
@@ -338,7 +1080,7 @@ mod tests {
     #[test]
     fn spaces_to_underscores() {
         assert_eq!(
-            Title::spaces_to_underscores(&" A little  test "),
+            Title::spaces_to_underscores(" A little  test "),
             "A_little__test"
         );
     }
@@ -346,29 +1088,23 @@ mod tests {
     #[test]
     fn underscores_to_spaces() {
         assert_eq!(
-            Title::underscores_to_spaces(&"_A_little__test_"),
+            Title::underscores_to_spaces("_A_little__test_"),
             "A little  test"
         );
     }
 
     #[test]
     fn first_letter_uppercase() {
-        assert_eq!(Title::first_letter_uppercase(&""), "");
-        assert_eq!(
-            Title::first_letter_uppercase(&"FooBar"),
-            "FooBar"
-        );
-        assert_eq!(
-            Title::first_letter_uppercase(&"fooBar"),
-            "FooBar"
-        );
-        assert_eq!(Title::first_letter_uppercase(&"über"), "Über");
+        assert_eq!(Title::first_letter_uppercase(""), "");
+        assert_eq!(Title::first_letter_uppercase("FooBar"), "FooBar");
+        assert_eq!(Title::first_letter_uppercase("fooBar"), "FooBar");
+        assert_eq!(Title::first_letter_uppercase("über"), "Über");
     }
 
     #[test]
     fn full() {
         let api = wd_api();
-        let title = Title::new_from_full(&"User talk:Magnus_Manske", api);
+        let title = Title::new_from_full("User talk:Magnus_Manske", api);
         assert_eq!(
             title.full_pretty(api),
             Some("User talk:Magnus Manske".to_string())
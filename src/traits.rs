@@ -0,0 +1,103 @@
+/*!
+Traits for working with MediaWiki API results, e.g. to combine the results of several requests of a
+query that is continued via the `continue` parameter, or to count the results.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use serde_json::Value;
+
+/// A value that can take in another value of the same type, e.g. a further batch of results
+pub trait Mergeable {
+    /// Merges `other` into `self`
+    fn merge(&mut self, other: Self);
+}
+
+/// Merges JSON values that are MediaWiki API results, as done by `Api::get_query_api_json_limit`:
+/// - objects are merged key by key, recursively; keys that are only in `other` are added
+/// - arrays are extended with the elements of the array in `other`
+/// - in all other cases, including a scalar and an array, or an object and an array, `other`
+///   replaces `self`, so a `null` in `other` removes the value
+impl Mergeable for Value {
+    fn merge(&mut self, other: Self) {
+        match (self, other) {
+            (Value::Object(a), Value::Object(b)) => {
+                for (k, v) in b {
+                    a.entry(k).or_insert(Value::Null).merge(v);
+                }
+            }
+            (Value::Array(a), Value::Array(b)) => a.extend(b),
+            (a, b) => *a = b,
+        }
+    }
+}
+
+/// A query result that contains lists of items
+pub trait Countable {
+    /// Returns the number of items in the list `key` of a query result (e.g. "search", "allpages"
+    /// or "pages"); 0 if there is no such list
+    fn count_list(&self, key: &str) -> usize;
+}
+
+impl Countable for Value {
+    fn count_list(&self, key: &str) -> usize {
+        match &self["query"][key] {
+            Value::Array(items) => items.len(),
+            // `formatversion=1` pages are an object keyed by page ID
+            Value::Object(items) => items.len(),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_values() {
+        let mut a =
+            json!({"query": {"pages": [{"title": "A"}], "normalized": [{"from": "a", "to": "A"}]}});
+        a.merge(json!({"query": {"pages": [{"title": "B"}], "searchinfo": {"totalhits": 2}}}));
+        assert_eq!(
+            a,
+            json!({"query": {
+                "pages": [{"title": "A"}, {"title": "B"}],
+                "normalized": [{"from": "a", "to": "A"}],
+                "searchinfo": {"totalhits": 2}
+            }})
+        );
+
+        // Conflicting types: the later value wins
+        let mut a = json!({"x": [1, 2], "y": 1, "z": {"a": 1}});
+        a.merge(json!({"x": 3, "y": [4], "z": null}));
+        assert_eq!(a, json!({"x": 3, "y": [4], "z": null}));
+
+        let mut a = Value::Null;
+        a.merge(json!({"query": {"allpages": [1]}}));
+        a.merge(json!({"query": {"allpages": [2]}}));
+        assert_eq!(a, json!({"query": {"allpages": [1, 2]}}));
+    }
+
+    #[test]
+    fn count_lists() {
+        let result = json!({"query": {
+            "normalized": [{"from": "a", "to": "A"}],
+            "search": [{"title": "A"}, {"title": "B"}],
+            "pages": {"1": {"title": "A"}}
+        }});
+        assert_eq!(result.count_list("search"), 2);
+        assert_eq!(result.count_list("pages"), 1);
+        assert_eq!(result.count_list("allpages"), 0);
+    }
+}
@@ -0,0 +1,309 @@
+/*!
+Translations with the [Translate](https://www.mediawiki.org/wiki/Extension:Translate) extension:
+message groups (`meta=messagegroups`, e.g. "page-Main Page" for a translatable page) and their
+translation progress per language (`meta=messagegroupstats`), the messages of a group with their
+translations and review state (`list=messagecollection`), reviewing translations
+(`action=translationreview`), and marking translatable pages for translation
+(`action=markfortranslation`).
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A message group, from `meta=messagegroups`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MessageGroup {
+    /// ID of the group, e.g. "page-Main Page"
+    pub id: String,
+    /// Label of the group
+    #[serde(default)]
+    pub label: String,
+    /// Description of the group, as wikitext
+    #[serde(default)]
+    pub description: String,
+    /// PHP class of the group, e.g. "WikiPageMessageGroup" for translatable pages
+    #[serde(default)]
+    pub class: String,
+}
+
+impl MessageGroup {
+    /// Checks if the group is a translatable page
+    pub fn is_translatable_page(&self) -> bool {
+        self.id.starts_with("page-")
+    }
+}
+
+/// Translation progress of a message group in a language, from `meta=messagegroupstats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct MessageGroupStats {
+    /// Number of messages
+    #[serde(default)]
+    pub total: u64,
+    /// Number of up-to-date translations, including proofread ones
+    #[serde(default)]
+    pub translated: u64,
+    /// Number of translations that are outdated (not counted as translated)
+    #[serde(default)]
+    pub fuzzy: u64,
+    /// Number of reviewed translations
+    #[serde(default)]
+    pub proofread: u64,
+}
+
+impl MessageGroupStats {
+    /// Returns the share of up-to-date translations, between 0 and 1
+    pub fn completion(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.translated as f64 / self.total as f64
+    }
+}
+
+/// The state of a translation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationStatus {
+    /// Not translated
+    #[default]
+    Untranslated,
+    /// Translated, but not reviewed
+    Translated,
+    /// Translated, but the message has changed since
+    Fuzzy,
+    /// Translated and reviewed
+    Proofread,
+}
+
+/// Translation properties of a message
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TranslationProperties {
+    /// The state of the translation
+    #[serde(default)]
+    pub status: TranslationStatus,
+    /// Users who reviewed the translation
+    #[serde(default)]
+    pub reviewers: Vec<String>,
+    /// User who last changed the translation
+    #[serde(rename = "last-translator-text")]
+    pub last_translator: Option<String>,
+}
+
+/// A message of a message group, with its translation, from `list=messagecollection`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TranslationMessage {
+    /// Key of the message
+    pub key: String,
+    /// Title of the translation page, e.g. "Translations:Main Page/1/de"
+    pub title: String,
+    /// The message in the source language
+    #[serde(default)]
+    pub definition: String,
+    /// The translation, if any
+    pub translation: Option<String>,
+    /// ID of the latest revision of the translation, to review it
+    pub revision: Option<u64>,
+    /// Tags of the message, e.g. "fuzzy" or "optional"
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The state of the translation
+    #[serde(default)]
+    pub properties: TranslationProperties,
+}
+
+impl Api {
+    /// Loads all message groups
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the extension is not installed),
+    /// or any error from the request.
+    pub fn message_groups(&self) -> Result<Vec<MessageGroup>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "meta" => "messagegroups",
+            "mgprop" => "id|label|description|class",
+            "formatversion" => "2",
+        ];
+        self.translate_query(&params, "messagegroups")
+    }
+
+    /// Loads the translation progress of the message group `group` in all languages, by language
+    /// code
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. there is no such group), or any error
+    /// from the requests.
+    pub fn message_group_stats(
+        &self,
+        group: &str,
+    ) -> Result<HashMap<String, MessageGroupStats>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "meta" => "messagegroupstats",
+            "mgsgroup" => group,
+            "formatversion" => "2",
+        ];
+        #[derive(Deserialize)]
+        struct LanguageStats {
+            code: String,
+            #[serde(flatten)]
+            stats: MessageGroupStats,
+        }
+        let stats: Vec<LanguageStats> = self.translate_query(&params, "messagegroupstats")?;
+        Ok(stats
+            .into_iter()
+            .map(|language| (language.code, language.stats))
+            .collect())
+    }
+
+    /// Loads the messages of the message group `group`, with their translations into `language`
+    /// and the state of the translations; optional and ignored messages are left out
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. there is no such group), or any error
+    /// from the requests.
+    pub fn message_collection(
+        &self,
+        group: &str,
+        language: &str,
+    ) -> Result<Vec<TranslationMessage>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "list" => "messagecollection",
+            "mcgroup" => group,
+            "mclanguage" => language,
+            "mcprop" => "definition|translation|tags|revision|properties",
+            "mcfilter" => "!optional|!ignored",
+            "mclimit" => "max",
+            "formatversion" => "2",
+        ];
+        self.translate_query(&params, "messagecollection")
+    }
+
+    /// Marks the translation revision `revision` as reviewed (proofread), which needs the
+    /// `translate-messagereview` right. Translations by the reviewer themselves can't be reviewed.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the translation is fuzzy), or any error
+    /// from the request.
+    pub fn review_translation(&self, revision: u64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "translationreview",
+            "revision" => revision,
+            "token" => self.get_edit_token()?,
+            "formatversion" => "2",
+        ];
+        let result = self.post_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!(
+                "action=translationreview failed: {}",
+                error
+            )));
+        }
+        Ok(())
+    }
+
+    /// Marks the latest revision of the translatable page `title` for translation, after its source
+    /// text changed, which needs the `pagetranslation` right
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the page has no `<translate>` tags),
+    /// or any error from the request.
+    pub fn mark_for_translation(&self, title: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "markfortranslation",
+            "title" => title,
+            "token" => self.get_edit_token()?,
+            "formatversion" => "2",
+        ];
+        let result = self.post_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!(
+                "action=markfortranslation failed: {}",
+                error
+            )));
+        }
+        Ok(())
+    }
+
+    /// Runs a query and deserializes all entries of the query module `module`
+    fn translate_query<T: DeserializeOwned>(
+        &self,
+        params: &HashMap<String, String>,
+        module: &str,
+    ) -> Result<Vec<T>, Box<dyn Error + Send + Sync>> {
+        let mut ret = vec![];
+        for result in self.get_query_api_json_limit_iter(params, None) {
+            let result = result?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!("{} failed: {}", module, error)));
+            }
+            if let Some(entries) = result["query"].get(module) {
+                ret.extend(Vec::<T>::deserialize(entries)?);
+            }
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages() {
+        let messages: Vec<TranslationMessage> = serde_json::from_value(json!([
+            {"key": "1_tour", "title": "Translations:Main Page/1/de", "definition": "Welcome",
+             "translation": "Willkommen", "revision": 123, "tags": [],
+             "primaryGroup": "page-Main Page",
+             "properties": {"status": "proofread", "reviewers": ["Reviewer"],
+             "last-translator-text": "Translator"}},
+            {"key": "2_tour", "title": "Translations:Main Page/2/de", "definition": "Hello",
+             "translation": null, "tags": [], "properties": {"status": "untranslated",
+             "reviewers": []}}
+        ]))
+        .unwrap();
+        assert_eq!(messages[0].properties.status, TranslationStatus::Proofread);
+        assert_eq!(
+            messages[0].properties.last_translator.as_deref(),
+            Some("Translator")
+        );
+        assert_eq!(messages[1].translation, None);
+        assert_eq!(
+            messages[1].properties.status,
+            TranslationStatus::Untranslated
+        );
+    }
+
+    #[test]
+    fn groups_and_stats() {
+        let group: MessageGroup = serde_json::from_value(json!({
+            "id": "page-Main Page", "label": "Main Page", "description": "",
+            "class": "WikiPageMessageGroup"
+        }))
+        .unwrap();
+        assert!(group.is_translatable_page());
+        let stats: MessageGroupStats = serde_json::from_value(
+            json!({"total": 10, "translated": 5, "fuzzy": 1, "proofread": 2, "code": "de"}),
+        )
+        .unwrap();
+        assert!((stats.completion() - 0.5).abs() < f64::EPSILON);
+        assert_eq!(MessageGroupStats::default().completion(), 0.0);
+    }
+}
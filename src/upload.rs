@@ -0,0 +1,906 @@
+/*!
+The `Upload` class uploads files via `action=upload`, automatically using chunked uploads for large
+files, and publishes files from the upload stash (see `Api::my_stashed_files`). File data can come
+from any `Read`, and is streamed chunk by chunk.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use reqwest::blocking::multipart::Part;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Default chunk size for chunked uploads (5 MiB).
+/// Files up to this size are uploaded in a single request.
+pub const DEFAULT_CHUNK_SIZE: usize = 5 * 1024 * 1024;
+
+/// Progress of an upload, passed to the progress callback after every request that sent file data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadProgress {
+    /// Number of bytes uploaded so far
+    pub bytes_sent: u64,
+    /// Size of the file, in bytes
+    pub total_bytes: u64,
+}
+
+/// A warning returned by `action=upload`
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum UploadWarning {
+    /// A file with this name already exists
+    Exists(String),
+    /// A file with this name, but a differently capitalized extension, exists
+    ExistsNormalized(String),
+    /// Files with the same content exist
+    Duplicate(Vec<String>),
+    /// A deleted file with the same content exists
+    DuplicateArchive(String),
+    /// A file with this name was deleted
+    WasDeleted(String),
+    /// The file name is not valid; the suggested file name is given
+    BadFilename(String),
+    /// A non-file page with this name exists
+    PageExists(String),
+    /// The file is identical to the current version of the file
+    NoChange,
+    /// Any other warning, with its code and value
+    Other {
+        /// The warning code, e.g. "filetype-unwanted-type"
+        code: String,
+        /// The value of the warning
+        value: Value,
+    },
+}
+
+impl UploadWarning {
+    /// Parses the `warnings` object of an `action=upload` result
+    pub fn new_from_api_result(warnings: &Value) -> Vec<UploadWarning> {
+        let warnings = match warnings.as_object() {
+            Some(warnings) => warnings,
+            None => return vec![],
+        };
+        let string = |v: &Value| v.as_str().unwrap_or_default().to_string();
+        warnings
+            .iter()
+            .map(|(code, value)| match code.as_str() {
+                "exists" => UploadWarning::Exists(string(value)),
+                "exists-normalized" => UploadWarning::ExistsNormalized(string(value)),
+                "duplicate" => UploadWarning::Duplicate(
+                    value
+                        .as_array()
+                        .map(|files| files.iter().map(string).collect())
+                        .unwrap_or_default(),
+                ),
+                "duplicate-archive" => UploadWarning::DuplicateArchive(string(value)),
+                "was-deleted" => UploadWarning::WasDeleted(string(value)),
+                "badfilename" => UploadWarning::BadFilename(string(value)),
+                "page-exists" => UploadWarning::PageExists(string(value)),
+                "nochange" => UploadWarning::NoChange,
+                _ => UploadWarning::Other {
+                    code: code.to_string(),
+                    value: value.clone(),
+                },
+            })
+            .collect()
+    }
+
+    /// Returns the warning code, as used by the API
+    pub fn code(&self) -> &str {
+        match self {
+            UploadWarning::Exists(_) => "exists",
+            UploadWarning::ExistsNormalized(_) => "exists-normalized",
+            UploadWarning::Duplicate(_) => "duplicate",
+            UploadWarning::DuplicateArchive(_) => "duplicate-archive",
+            UploadWarning::WasDeleted(_) => "was-deleted",
+            UploadWarning::BadFilename(_) => "badfilename",
+            UploadWarning::PageExists(_) => "page-exists",
+            UploadWarning::NoChange => "nochange",
+            UploadWarning::Other { code, .. } => code,
+        }
+    }
+}
+
+/// Which upload warnings to ignore
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum IgnoreWarnings {
+    /// Fail on any warning
+    #[default]
+    None,
+    /// Ignore all warnings (`ignorewarnings=1`); this overwrites existing files
+    All,
+    /// Ignore only warnings with these codes (e.g. "was-deleted"), and fail on all others
+    Codes(Vec<String>),
+}
+
+impl IgnoreWarnings {
+    /// Checks if a warning is ignored by this policy
+    pub fn ignores(&self, warning: &UploadWarning) -> bool {
+        match self {
+            IgnoreWarnings::None => false,
+            IgnoreWarnings::All => true,
+            IgnoreWarnings::Codes(codes) => codes.iter().any(|code| code == warning.code()),
+        }
+    }
+}
+
+/// The result of a successful upload
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadResult {
+    /// The name of the uploaded file, without namespace prefix
+    pub filename: String,
+    /// Warnings that were ignored
+    pub warnings: Vec<UploadWarning>,
+    /// The `imageinfo` of the new file version, as returned by the API
+    pub imageinfo: Value,
+    /// The SHA-1 of the uploaded data, in hexadecimal, as computed while reading it.
+    /// It has been verified against the SHA-1 reported by the wiki.
+    pub sha1: String,
+}
+
+/// A reader that computes the SHA-1 of everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha1,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        HashingReader {
+            inner,
+            hasher: Sha1::new(),
+        }
+    }
+
+    /// Returns the SHA-1 of the data read so far, in hexadecimal
+    fn hex_digest(&self) -> String {
+        self.hasher
+            .clone()
+            .result()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.hasher.input(&buf[..len]);
+        Ok(len)
+    }
+}
+
+/// A file upload.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use mediawiki::api::Api;
+/// # use mediawiki::upload::{IgnoreWarnings, Upload};
+/// # let api = Api::new("https://commons.wikimedia.org/w/api.php").unwrap();
+/// let mut upload = Upload::new("Example.jpg");
+/// upload.set_comment("Upload example");
+/// upload.set_text(Some("== Summary ==\n...".to_string()));
+/// upload.set_ignore_warnings(IgnoreWarnings::Codes(vec!["was-deleted".to_string()]));
+/// upload.set_progress_callback(|p| println!("{}/{} bytes", p.bytes_sent, p.total_bytes));
+/// let result = upload.upload_file(&api, "example.jpg".as_ref()).unwrap();
+/// ```
+pub struct Upload {
+    filename: String,
+    comment: String,
+    text: Option<String>,
+    chunk_size: usize,
+    ignore_warnings: IgnoreWarnings,
+    progress_callback: Option<Box<dyn FnMut(UploadProgress)>>,
+}
+
+impl fmt::Debug for Upload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upload")
+            .field("filename", &self.filename)
+            .field("comment", &self.comment)
+            .field("text", &self.text)
+            .field("chunk_size", &self.chunk_size)
+            .field("ignore_warnings", &self.ignore_warnings)
+            .field("progress_callback", &self.progress_callback.is_some())
+            .finish()
+    }
+}
+
+impl Upload {
+    /// Creates a new upload to the given file name (without namespace prefix)
+    pub fn new(filename: &str) -> Upload {
+        Upload {
+            filename: filename.to_string(),
+            comment: String::new(),
+            text: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            ignore_warnings: IgnoreWarnings::default(),
+            progress_callback: None,
+        }
+    }
+
+    /// Returns the target file name
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Sets the upload comment
+    pub fn set_comment<S: Into<String>>(&mut self, comment: S) {
+        self.comment = comment.into();
+    }
+
+    /// Sets the initial page text for new files; if `None`, the comment is used
+    pub fn set_text(&mut self, text: Option<String>) {
+        self.text = text;
+    }
+
+    /// Sets the chunk size for chunked uploads.
+    /// The wiki's `minuploadchunksize` is used if it is larger.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size;
+    }
+
+    /// Sets which warnings to ignore
+    pub fn set_ignore_warnings(&mut self, ignore_warnings: IgnoreWarnings) {
+        self.ignore_warnings = ignore_warnings;
+    }
+
+    /// Sets a callback that is called with the progress after every request that sent file data
+    pub fn set_progress_callback<F: FnMut(UploadProgress) + 'static>(&mut self, callback: F) {
+        self.progress_callback = Some(Box::new(callback));
+    }
+
+    /// Uploads a file from disk. The file is streamed, so only one chunk is held in memory at a
+    /// time.
+    ///
+    /// # Errors
+    /// Returns `UploadError::Io` if the file can not be read; see also `upload_bytes`.
+    pub fn upload_file(&mut self, api: &Api, path: &Path) -> Result<UploadResult, UploadError> {
+        let file = File::open(path).map_err(UploadError::Io)?;
+        let size = file.metadata().map_err(UploadError::Io)?.len();
+        self.upload_reader(api, file, size)
+    }
+
+    /// Uploads file data. Data larger than the chunk size is uploaded in chunks to the
+    /// upload stash, and then published from there.
+    ///
+    /// # Errors
+    /// Returns `UploadError::Warnings` if the API returned warnings that are not ignored,
+    /// `UploadError::ApiError` if the API returned an error, or any error from the request.
+    pub fn upload_bytes(&mut self, api: &Api, data: &[u8]) -> Result<UploadResult, UploadError> {
+        self.upload_reader(api, data, data.len() as u64)
+    }
+
+    /// Uploads `size` bytes read from `reader`, e.g. a file or a network stream.
+    /// Only one chunk is held in memory at a time, so files of any size can be uploaded.
+    ///
+    /// # Errors
+    /// Returns `UploadError::DisallowedExtension`, `UploadError::UploadsDisabled` or
+    /// `UploadError::TooLarge` (before sending anything) if the site info of the wiki does not
+    /// permit the file, `UploadError::Io` if reading fails or `reader` ends before `size` bytes,
+    /// and `UploadError::Sha1Mismatch` if the file on the wiki differs from the data that was
+    /// read; see also `upload_bytes`.
+    pub fn upload_reader<R: Read>(
+        &mut self,
+        api: &Api,
+        reader: R,
+        size: u64,
+    ) -> Result<UploadResult, UploadError> {
+        self.check_site_limits(api, size)?;
+        let mut reader = HashingReader::new(reader);
+        let token = api.get_edit_token().map_err(UploadError::RequestError)?;
+        let chunk_size = self.effective_chunk_size(api);
+        let result = if size <= chunk_size as u64 {
+            let data = Upload::read_chunk(&mut reader, size as usize)?;
+            let part = Part::bytes(data).file_name(self.filename.clone());
+            let result = api
+                .post_multipart_api_json(&self.publish_params(&token, None), "file", part)
+                .map_err(UploadError::RequestError)?;
+            self.report_progress(size, size);
+            result
+        } else {
+            let filekey = self.upload_chunks(api, &token, &mut reader, size, chunk_size)?;
+            api.post_query_api_json(&self.publish_params(&token, Some(&filekey)))
+                .map_err(UploadError::RequestError)?
+        };
+
+        let mut ret = self.handle_warnings(api, &token, result)?;
+        ret.sha1 = reader.hex_digest();
+        if !api.simulate() {
+            self.verify_sha1(api, &ret)?;
+        }
+        Ok(ret)
+    }
+
+    /// Publishes a file from the upload stash of the current user, e.g. one left behind by an
+    /// interrupted or rejected upload (see `Api::my_stashed_files`). The SHA-1 of the result is the
+    /// one reported by the wiki.
+    ///
+    /// # Errors
+    /// Returns `UploadError::Warnings` if the API returned warnings that are not ignored,
+    /// `UploadError::ApiError` if the API returned an error (e.g. for an unknown file key), or any
+    /// error from the request.
+    pub fn publish_stashed(
+        &mut self,
+        api: &Api,
+        filekey: &str,
+    ) -> Result<UploadResult, UploadError> {
+        let token = api.get_edit_token().map_err(UploadError::RequestError)?;
+        let result = api
+            .post_query_api_json(&self.publish_params(&token, Some(filekey)))
+            .map_err(UploadError::RequestError)?;
+        let mut ret = self.handle_warnings(api, &token, result)?;
+        ret.sha1 = ret.imageinfo["sha1"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        Ok(ret)
+    }
+
+    /// Interprets the result of publishing a file; if all warnings are ignored, publishes the
+    /// stashed file again
+    fn handle_warnings(
+        &self,
+        api: &Api,
+        token: &str,
+        result: Value,
+    ) -> Result<UploadResult, UploadError> {
+        match Upload::parse_response(result) {
+            Err(UploadError::Warnings {
+                warnings,
+                filekey: Some(filekey),
+            }) if warnings.iter().all(|w| self.ignore_warnings.ignores(w)) => {
+                // All warnings are acceptable; publish the stashed file, ignoring them
+                let mut params = self.publish_params(token, Some(&filekey));
+                params.insert("ignorewarnings".to_string(), "1".to_string());
+                let result = api
+                    .post_query_api_json(&params)
+                    .map_err(UploadError::RequestError)?;
+                let mut ret = Upload::parse_response(result)?;
+                ret.warnings = warnings;
+                Ok(ret)
+            }
+            other => other,
+        }
+    }
+
+    /// Checks that the SHA-1 of the uploaded file, as reported by the wiki, matches the local one.
+    /// If the upload result does not include the SHA-1, it is queried.
+    fn verify_sha1(&self, api: &Api, result: &UploadResult) -> Result<(), UploadError> {
+        let remote = match result.imageinfo["sha1"].as_str() {
+            Some(sha1) => sha1.to_string(),
+            None => {
+                let params = api.params_into(&[
+                    ("action", "query"),
+                    ("prop", "imageinfo"),
+                    ("iiprop", "sha1"),
+                    ("titles", &format!("File:{}", result.filename)),
+                    ("formatversion", "2"),
+                ]);
+                let response = api
+                    .get_query_api_json(&params)
+                    .map_err(UploadError::RequestError)?;
+                match response["query"]["pages"][0]["imageinfo"][0]["sha1"].as_str() {
+                    Some(sha1) => sha1.to_string(),
+                    None => return Err(UploadError::BadResponse(response)),
+                }
+            }
+        };
+        if remote.eq_ignore_ascii_case(&result.sha1) {
+            Ok(())
+        } else {
+            Err(UploadError::Sha1Mismatch {
+                local: result.sha1.clone(),
+                remote,
+            })
+        }
+    }
+
+    /// Reads exactly `len` bytes
+    fn read_chunk(reader: &mut dyn Read, len: usize) -> Result<Vec<u8>, UploadError> {
+        let mut data = vec![0; len];
+        reader.read_exact(&mut data).map_err(UploadError::Io)?;
+        Ok(data)
+    }
+
+    /// Uploads the data to the stash in chunks, and returns the file key
+    fn upload_chunks(
+        &mut self,
+        api: &Api,
+        token: &str,
+        reader: &mut dyn Read,
+        total_bytes: u64,
+        chunk_size: usize,
+    ) -> Result<String, UploadError> {
+        let mut filekey: Option<String> = None;
+        let mut offset: u64 = 0;
+        while offset < total_bytes {
+            let chunk = Upload::read_chunk(
+                reader,
+                (total_bytes - offset).min(chunk_size as u64) as usize,
+            )?;
+            let chunk_len = chunk.len() as u64;
+            let mut params = api.params_into(&[
+                ("action", "upload"),
+                ("stash", "1"),
+                ("filename", &self.filename),
+                ("filesize", &total_bytes.to_string()),
+                ("offset", &offset.to_string()),
+                ("ignorewarnings", "1"),
+                ("token", token),
+                ("formatversion", "2"),
+            ]);
+            if let Some(filekey) = &filekey {
+                params.insert("filekey".to_string(), filekey.clone());
+            }
+            let part = Part::bytes(chunk).file_name(self.filename.clone());
+            let result = api
+                .post_multipart_api_json(&params, "chunk", part)
+                .map_err(UploadError::RequestError)?;
+            if result.get("error").is_some() {
+                return Err(UploadError::ApiError(result));
+            }
+            match result["upload"]["result"].as_str() {
+                Some("Continue") | Some("Success") => {}
+                _ => return Err(UploadError::BadResponse(result)),
+            }
+            match result["upload"]["filekey"].as_str() {
+                Some(key) => filekey = Some(key.to_string()),
+                None => return Err(UploadError::BadResponse(result)),
+            }
+            offset += chunk_len;
+            self.report_progress(offset, total_bytes);
+        }
+        filekey.ok_or(UploadError::BadResponse(Value::Null))
+    }
+
+    /// Returns the parameters for publishing the file, either directly or from the stash
+    fn publish_params(&self, token: &str, filekey: Option<&str>) -> HashMap<String, String> {
+        let mut params: HashMap<String, String> = [
+            ("action", "upload"),
+            ("filename", &self.filename),
+            ("comment", &self.comment),
+            ("token", token),
+            ("formatversion", "2"),
+        ]
+        .iter()
+        .map(|&(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        if let Some(text) = &self.text {
+            params.insert("text".to_string(), text.clone());
+        }
+        if let Some(filekey) = filekey {
+            params.insert("filekey".to_string(), filekey.to_string());
+        }
+        if self.ignore_warnings == IgnoreWarnings::All {
+            params.insert("ignorewarnings".to_string(), "1".to_string());
+        }
+        params
+    }
+
+    /// Checks the file name and size against the permitted file extensions and the upload size
+    /// limit of the wiki. The extension is not checked if the site info has no file extensions
+    /// (`siprop=fileextensions` was not loaded, see `Api::load_site_info_sections`).
+    fn check_site_limits(&self, api: &Api, size: u64) -> Result<(), UploadError> {
+        let site_info = api.get_site_info();
+        if !site_info.fileextensions.is_empty() && !site_info.allows_file_extension(&self.filename)
+        {
+            return Err(UploadError::DisallowedExtension(self.filename.clone()));
+        }
+        if !site_info.general.uploadsenabled {
+            return Err(UploadError::UploadsDisabled);
+        }
+        if let Some(max) = site_info.general.maxuploadsize.filter(|&max| size > max) {
+            return Err(UploadError::TooLarge { size, max });
+        }
+        Ok(())
+    }
+
+    /// Returns the chunk size, respecting the wiki's minimum chunk size
+    fn effective_chunk_size(&self, api: &Api) -> usize {
+        let min = api.get_site_info().general.minuploadchunksize.unwrap_or(0) as usize;
+        self.chunk_size.max(min).max(1)
+    }
+
+    /// Calls the progress callback, if set
+    fn report_progress(&mut self, bytes_sent: u64, total_bytes: u64) {
+        if let Some(callback) = &mut self.progress_callback {
+            callback(UploadProgress {
+                bytes_sent,
+                total_bytes,
+            });
+        }
+    }
+
+    /// Interprets the result of publishing a file
+    fn parse_response(result: Value) -> Result<UploadResult, UploadError> {
+        if result.get("error").is_some() {
+            return Err(UploadError::ApiError(result));
+        }
+        let upload = &result["upload"];
+        let warnings = UploadWarning::new_from_api_result(&upload["warnings"]);
+        match upload["result"].as_str() {
+            Some("Success") => Ok(UploadResult {
+                filename: upload["filename"].as_str().unwrap_or_default().to_string(),
+                warnings,
+                imageinfo: upload["imageinfo"].clone(),
+                sha1: String::new(),
+            }),
+            Some("Warning") => Err(UploadError::Warnings {
+                warnings,
+                filekey: upload["filekey"].as_str().map(|s| s.to_string()),
+            }),
+            _ => Err(UploadError::BadResponse(result)),
+        }
+    }
+}
+
+/// A file in the upload stash of the current user, from `list=mystashedfiles`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StashedFile {
+    /// Key of the file, for publishing it with `Upload::publish_stashed`
+    pub filekey: String,
+    /// Status: "finished", or e.g. "chunks" while a chunked upload is incomplete
+    pub status: String,
+    /// Size of the file, in bytes
+    pub size: Option<u64>,
+    /// MIME type of the file
+    pub mime: Option<String>,
+}
+
+impl StashedFile {
+    /// Parses an entry of a `list=mystashedfiles` result
+    pub fn new_from_api_result(file: &Value) -> Option<StashedFile> {
+        Some(StashedFile {
+            filekey: file["filekey"].as_str()?.to_string(),
+            status: file["status"].as_str().unwrap_or_default().to_string(),
+            size: file["size"].as_u64(),
+            mime: file["mimetype"].as_str().map(|s| s.to_string()),
+        })
+    }
+
+    /// Checks if the file is complete, and can be published
+    pub fn is_finished(&self) -> bool {
+        self.status == "finished"
+    }
+}
+
+impl Api {
+    /// Loads the files in the upload stash of the current user. Stashed files can be published with
+    /// `Upload::publish_stashed`; the API can't remove them, but the wiki deletes them after a
+    /// while (or on `Special:UploadStash`).
+    ///
+    /// # Errors
+    /// Returns `UploadError::ApiError` if the API returned an error (e.g. when not logged in), or
+    /// any error from the request.
+    pub fn my_stashed_files(&self) -> Result<Vec<StashedFile>, UploadError> {
+        let params = self.params_into(&[
+            ("action", "query"),
+            ("list", "mystashedfiles"),
+            ("msfprop", "size|type"),
+            ("msflimit", "max"),
+            ("formatversion", "2"),
+        ]);
+        let result = self
+            .get_query_api_json_all(&params)
+            .map_err(UploadError::RequestError)?;
+        if result.get("error").is_some() {
+            return Err(UploadError::ApiError(result));
+        }
+        Ok(result["query"]["mystashedfiles"]
+            .as_array()
+            .map(|files| {
+                files
+                    .iter()
+                    .filter_map(StashedFile::new_from_api_result)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+/// Errors that can go wrong while uploading a file
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum UploadError {
+    /// The API returned warnings that are not ignored
+    Warnings {
+        /// The warnings
+        warnings: Vec<UploadWarning>,
+        /// The key of the stashed file, which can be used to publish it later
+        filekey: Option<String>,
+    },
+
+    /// The API returned an error; the API response is provided.
+    ApiError(Value),
+
+    /// Couldn't understand the API response (provided).
+    BadResponse(Value),
+
+    /// The wiki does not permit files with the extension of the file name (provided).
+    DisallowedExtension(String),
+
+    /// Uploads are disabled on the wiki.
+    UploadsDisabled,
+
+    /// The file is larger than the wiki permits.
+    TooLarge {
+        /// Size of the file, in bytes
+        size: u64,
+        /// Maximum upload size of the wiki, in bytes
+        max: u64,
+    },
+
+    /// Error while reading the file data.
+    Io(std::io::Error),
+
+    /// The SHA-1 of the file on the wiki differs from the SHA-1 of the data that was read.
+    Sha1Mismatch {
+        /// SHA-1 of the data that was read, in hexadecimal
+        local: String,
+        /// SHA-1 reported by the wiki, in hexadecimal
+        remote: String,
+    },
+
+    /// Error while performing the API request.
+    RequestError(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::Warnings { warnings, .. } => {
+                let codes: Vec<&str> = warnings.iter().map(UploadWarning::code).collect();
+                write!(f, "upload warnings: {}", codes.join(", "))
+            }
+            UploadError::ApiError(response) => {
+                write!(f, "upload resulted in error: {:?}", response)
+            }
+            UploadError::BadResponse(response) => {
+                write!(f, "bad API response while uploading: {:?}", response)
+            }
+            UploadError::DisallowedExtension(filename) => {
+                write!(
+                    f,
+                    "the wiki does not permit the file extension of {}",
+                    filename
+                )
+            }
+            UploadError::UploadsDisabled => write!(f, "uploads are disabled on the wiki"),
+            UploadError::TooLarge { size, max } => {
+                write!(
+                    f,
+                    "file of {} bytes exceeds the upload size limit of {} bytes",
+                    size, max
+                )
+            }
+            UploadError::Io(error) => write!(f, "error reading file: {}", error),
+            UploadError::Sha1Mismatch { local, remote } => write!(
+                f,
+                "SHA-1 mismatch: uploaded data has {}, file on wiki has {}",
+                local, remote
+            ),
+            UploadError::RequestError(error) => write!(f, "request error: {}", error),
+        }
+    }
+}
+
+impl Error for UploadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    #[test]
+    fn warnings() {
+        let warnings = UploadWarning::new_from_api_result(&json!({
+            "duplicate": ["Other.jpg", "Third.jpg"],
+            "exists": "Example.jpg",
+            "nochange": {"timestamp": "2020-04-14T10:22:31Z"},
+            "was-deleted": "Example.jpg",
+            "filetype-unwanted-type": ["bmp", 1]
+        }));
+        assert_eq!(
+            warnings,
+            vec![
+                UploadWarning::Duplicate(vec!["Other.jpg".to_string(), "Third.jpg".to_string()]),
+                UploadWarning::Exists("Example.jpg".to_string()),
+                UploadWarning::Other {
+                    code: "filetype-unwanted-type".to_string(),
+                    value: json!(["bmp", 1])
+                },
+                UploadWarning::NoChange,
+                UploadWarning::WasDeleted("Example.jpg".to_string()),
+            ]
+        );
+        assert_eq!(warnings[4].code(), "was-deleted");
+        assert!(UploadWarning::new_from_api_result(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn ignore_warnings() {
+        let exists = UploadWarning::Exists("Example.jpg".to_string());
+        let deleted = UploadWarning::WasDeleted("Example.jpg".to_string());
+        let policy = IgnoreWarnings::Codes(vec!["was-deleted".to_string()]);
+        assert!(policy.ignores(&deleted));
+        assert!(!policy.ignores(&exists));
+        assert!(IgnoreWarnings::All.ignores(&exists));
+        assert!(!IgnoreWarnings::default().ignores(&deleted));
+    }
+
+    #[test]
+    fn responses() {
+        let result = Upload::parse_response(json!({"upload": {
+            "result": "Success", "filename": "Example.jpg",
+            "imageinfo": {"size": 1234, "sha1": "abc"}
+        }}))
+        .unwrap();
+        assert_eq!(result.filename, "Example.jpg");
+        assert_eq!(result.imageinfo["size"], json!(1234));
+
+        match Upload::parse_response(json!({"upload": {
+            "result": "Warning", "filekey": "abc.123.jpg",
+            "warnings": {"exists": "Example.jpg"}
+        }})) {
+            Err(UploadError::Warnings { warnings, filekey }) => {
+                assert_eq!(
+                    warnings,
+                    vec![UploadWarning::Exists("Example.jpg".to_string())]
+                );
+                assert_eq!(filekey.as_deref(), Some("abc.123.jpg"));
+            }
+            x => panic!("expected warnings, found {:?}", x),
+        }
+
+        assert!(matches!(
+            Upload::parse_response(json!({"error": {"code": "badtoken"}})),
+            Err(UploadError::ApiError(_))
+        ));
+        assert!(matches!(
+            Upload::parse_response(json!({"upload": {"result": "Poll"}})),
+            Err(UploadError::BadResponse(_))
+        ));
+    }
+
+    #[test]
+    fn stashed_file() {
+        let file = StashedFile::new_from_api_result(&json!({
+            "filekey": "1a2b3c.abc123.1.jpg", "status": "finished", "size": 12345,
+            "mimetype": "image/jpeg"
+        }))
+        .unwrap();
+        assert!(file.is_finished());
+        assert_eq!(file.size, Some(12345));
+        let file =
+            StashedFile::new_from_api_result(&json!({"filekey": "x.y.jpg", "status": "chunks"}))
+                .unwrap();
+        assert!(!file.is_finished());
+        assert_eq!(
+            StashedFile::new_from_api_result(&json!({"status": "finished"})),
+            None
+        );
+    }
+
+    #[test]
+    fn read_chunks() {
+        let mut reader: &[u8] = b"0123456789";
+        assert_eq!(Upload::read_chunk(&mut reader, 4).unwrap(), b"0123");
+        assert_eq!(Upload::read_chunk(&mut reader, 6).unwrap(), b"456789");
+        assert!(matches!(
+            Upload::read_chunk(&mut reader, 1),
+            Err(UploadError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn hashing_reader() {
+        let mut reader = HashingReader::new(&b"The quick brown fox jumps over the lazy dog"[..]);
+        Upload::read_chunk(&mut reader, 10).unwrap();
+        Upload::read_chunk(&mut reader, 33).unwrap();
+        assert_eq!(
+            reader.hex_digest(),
+            "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12"
+        );
+        assert_eq!(
+            HashingReader::new(&b""[..]).hex_digest(),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+    }
+
+    #[test]
+    fn sha1_verification() {
+        let api = test_api();
+        let upload = Upload::new("Example.jpg");
+        let mut result = UploadResult {
+            filename: "Example.jpg".to_string(),
+            warnings: vec![],
+            imageinfo: json!({"sha1": "2FD4E1C67A2D28FCED849EE1BB76E7391B93EB12"}),
+            sha1: "2fd4e1c67a2d28fced849ee1bb76e7391b93eb12".to_string(),
+        };
+        assert!(upload.verify_sha1(&api, &result).is_ok());
+        result.sha1 = "da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string();
+        match upload.verify_sha1(&api, &result) {
+            Err(UploadError::Sha1Mismatch { local, remote }) => {
+                assert_eq!(local, "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+                assert_eq!(remote, "2FD4E1C67A2D28FCED849EE1BB76E7391B93EB12");
+            }
+            x => panic!("expected SHA-1 mismatch, found {:?}", x),
+        }
+    }
+
+    #[test]
+    fn site_limits() {
+        let api = test_api();
+        let mut site_info = api.get_site_info().clone();
+        site_info.general.maxuploadsize = Some(1000);
+        let mut api = Api::new_with_site_info(api.api_url(), site_info.clone()).unwrap();
+        // Without siprop=fileextensions, only the size is checked
+        assert!(Upload::new("Example.xyz")
+            .check_site_limits(&api, 1000)
+            .is_ok());
+        assert!(matches!(
+            Upload::new("Example.jpg").check_site_limits(&api, 1001),
+            Err(UploadError::TooLarge {
+                size: 1001,
+                max: 1000
+            })
+        ));
+
+        site_info
+            .add_sections_from_api_result(
+                &json!({"query": {"fileextensions": [{"ext": "png"}, {"ext": "jpg"}]}}),
+            )
+            .unwrap();
+        api = Api::new_with_site_info(api.api_url(), site_info.clone()).unwrap();
+        assert!(Upload::new("Example.JPG")
+            .check_site_limits(&api, 10)
+            .is_ok());
+        match Upload::new("Example.exe").upload_bytes(&api, b"MZ") {
+            Err(UploadError::DisallowedExtension(filename)) => assert_eq!(filename, "Example.exe"),
+            x => panic!("expected disallowed extension, found {:?}", x),
+        }
+
+        site_info.general.uploadsenabled = false;
+        api = Api::new_with_site_info(api.api_url(), site_info).unwrap();
+        assert!(matches!(
+            Upload::new("Example.jpg").check_site_limits(&api, 10),
+            Err(UploadError::UploadsDisabled)
+        ));
+    }
+
+    #[test]
+    fn publish_params() {
+        let mut upload = Upload::new("Example.jpg");
+        upload.set_comment("Test");
+        let params = upload.publish_params("token", Some("key"));
+        assert_eq!(params["filekey"], "key");
+        assert_eq!(params["comment"], "Test");
+        assert!(!params.contains_key("text"));
+        assert!(!params.contains_key("ignorewarnings"));
+        upload.set_ignore_warnings(IgnoreWarnings::All);
+        upload.set_text(Some("Text".to_string()));
+        let params = upload.publish_params("token", None);
+        assert_eq!(params["ignorewarnings"], "1");
+        assert_eq!(params["text"], "Text");
+        assert!(!params.contains_key("filekey"));
+    }
+}
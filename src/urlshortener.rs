@@ -0,0 +1,165 @@
+/*!
+Short URLs (e.g. <https://w.wiki/3>) from the
+[UrlShortener](https://www.mediawiki.org/wiki/Extension:UrlShortener) extension, via
+`action=shortenurl`. Only URLs on domains that the wiki farm allows can be shortened.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::params_map;
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+
+/// A shortened URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortUrl {
+    /// The short URL, e.g. "https://w.wiki/3"
+    pub url: String,
+    /// An alternative short URL without ambiguous characters, e.g. "https://w.wiki/_3"
+    pub alt: Option<String>,
+}
+
+impl ShortUrl {
+    /// Parses the `shortenurl` object of an `action=shortenurl` result
+    pub fn new_from_api_result(result: &Value) -> Option<ShortUrl> {
+        Some(ShortUrl {
+            url: result["shorturl"].as_str()?.to_string(),
+            alt: result["shorturlalt"].as_str().map(|s| s.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for ShortUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+impl Api {
+    /// Returns a short URL for `url`; the same URL always gets the same short URL
+    ///
+    /// # Errors
+    /// Returns `ShortUrlError::Disallowed` if the domain of `url` may not be shortened,
+    /// `ShortUrlError::Malformed` if `url` is not a valid URL, `ShortUrlError::TooLong` if it is
+    /// too long, `ShortUrlError::RateLimited` if too many URLs were shortened recently,
+    /// `ShortUrlError::ApiError` for other API errors (e.g. the extension is not installed), or any
+    /// error from the request.
+    pub fn shorten_url(&self, url: &str) -> Result<ShortUrl, ShortUrlError> {
+        let params = params_map![
+            "action" => "shortenurl",
+            "url" => url,
+            "formatversion" => "2",
+        ];
+        let result = self
+            .post_query_api_json(&params)
+            .map_err(ShortUrlError::RequestError)?;
+        short_url_from_api_result(url, result)
+    }
+}
+
+/// Parses an `action=shortenurl` result for `url`
+fn short_url_from_api_result(url: &str, result: Value) -> Result<ShortUrl, ShortUrlError> {
+    if let Some(code) = result["error"]["code"].as_str() {
+        let info = result["error"]["info"].as_str().unwrap_or_default();
+        return Err(match code {
+            "urlshortener-error-disallowed-url"
+            | "urlshortener-error-badports"
+            | "urlshortener-error-nouserpass" => ShortUrlError::Disallowed(url.to_string()),
+            "urlshortener-error-malformed-url" => ShortUrlError::Malformed(url.to_string()),
+            "urlshortener-url-too-long" => ShortUrlError::TooLong(url.to_string()),
+            "urlshortener-ratelimit" | "ratelimited" => ShortUrlError::RateLimited,
+            _ => ShortUrlError::ApiError(code.to_string(), info.to_string()),
+        });
+    }
+    ShortUrl::new_from_api_result(&result["shortenurl"]).ok_or(ShortUrlError::BadResponse(result))
+}
+
+/// Errors that can go wrong while shortening a URL.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ShortUrlError {
+    /// The URL (provided) may not be shortened, e.g. because of its domain.
+    Disallowed(String),
+
+    /// The URL (provided) is malformed.
+    Malformed(String),
+
+    /// The URL (provided) is too long.
+    TooLong(String),
+
+    /// Too many URLs were shortened recently.
+    RateLimited,
+
+    /// Any other API error (code and info).
+    ApiError(String, String),
+
+    /// Couldn't understand the API response (provided).
+    BadResponse(Value),
+
+    /// Error while performing the request.
+    RequestError(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for ShortUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShortUrlError::Disallowed(url) => write!(f, "URL may not be shortened: {}", url),
+            ShortUrlError::Malformed(url) => write!(f, "malformed URL: {}", url),
+            ShortUrlError::TooLong(url) => write!(f, "URL too long to be shortened: {}", url),
+            ShortUrlError::RateLimited => write!(f, "rate limit for shortening URLs exceeded"),
+            ShortUrlError::ApiError(code, info) => write!(f, "API error {}: {}", code, info),
+            ShortUrlError::BadResponse(response) => {
+                write!(f, "bad API response while shortening URL: {:?}", response)
+            }
+            ShortUrlError::RequestError(error) => write!(f, "request error: {}", error),
+        }
+    }
+}
+
+impl Error for ShortUrlError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_url() {
+        let url = "https://en.wikipedia.org/wiki/Main_Page";
+        let short_url = short_url_from_api_result(
+            url,
+            json!({"shortenurl": {"shorturl": "https://w.wiki/3",
+                "shorturlalt": "https://w.wiki/_3"}}),
+        )
+        .unwrap();
+        assert_eq!(short_url.to_string(), "https://w.wiki/3");
+        assert_eq!(short_url.alt.as_deref(), Some("https://w.wiki/_3"));
+
+        let error = short_url_from_api_result(
+            "https://example.com/",
+            json!({"error": {"code": "urlshortener-error-disallowed-url",
+                "info": "URLs to domain example.com are not allowed to be shortened."}}),
+        );
+        assert!(
+            matches!(error, Err(ShortUrlError::Disallowed(url)) if url == "https://example.com/")
+        );
+        let error =
+            short_url_from_api_result(url, json!({"error": {"code": "badvalue", "info": "x"}}));
+        assert!(matches!(error, Err(ShortUrlError::ApiError(..))));
+        assert!(matches!(
+            short_url_from_api_result(url, json!({})),
+            Err(ShortUrlError::BadResponse(_))
+        ));
+    }
+}
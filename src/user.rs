@@ -1,5 +1,8 @@
 /*!
-The `User` class deals with the (current) Api user.
+The `User` class deals with the (current) Api user. `UserInfo` holds the groups, rights, edit count,
+registration date and block of a user. `Api::validate_password` checks a password against the
+password policy of the wiki, and `Api::check_user_name_spoofing` finds existing user names that look
+like a new one.
 */
 
 #![deny(
@@ -15,9 +18,150 @@ The `User` class deals with the (current) Api user.
 )]
 
 use crate::api::Api;
+use crate::params_map;
+use crate::site_info::CaseSensitivity;
+use crate::timestamp::{parse_timestamp, Expiry, Timestamp};
+use crate::title::Title;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::error::Error;
+use std::net::{IpAddr, Ipv6Addr};
+
+/// A block of a user or IP address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// Block ID
+    pub id: u64,
+    /// Name of the user who made the block
+    pub blocked_by: String,
+    /// ID of the user who made the block
+    pub blocked_by_id: u64,
+    /// Reason given for the block
+    pub reason: String,
+    /// Time of the block, if known
+    pub timestamp: Option<Timestamp>,
+    /// Expiry of the block
+    pub expiry: Expiry,
+    /// Whether the block only applies to some pages or namespaces, rather than sitewide
+    pub partial: bool,
+}
+
+impl BlockInfo {
+    /// Parses the block fields of a `meta=userinfo` or `list=users` result (`formatversion=2`);
+    /// returns `None` if the user is not blocked
+    pub fn new_from_api_result(user: &Value) -> Option<BlockInfo> {
+        Some(BlockInfo {
+            id: user["blockid"].as_u64()?,
+            blocked_by: user["blockedby"].as_str().unwrap_or_default().to_string(),
+            blocked_by_id: user["blockedbyid"].as_u64().unwrap_or_default(),
+            reason: user["blockreason"].as_str().unwrap_or_default().to_string(),
+            timestamp: user["blockedtimestamp"].as_str().and_then(parse_timestamp),
+            expiry: parse_expiry(&user["blockexpiry"]),
+            partial: user["blockpartial"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Parses an entry of a `list=blocks` result (`formatversion=2`)
+    pub fn new_from_blocks_result(block: &Value) -> Option<BlockInfo> {
+        Some(BlockInfo {
+            id: block["id"].as_u64()?,
+            blocked_by: block["by"].as_str().unwrap_or_default().to_string(),
+            blocked_by_id: block["byid"].as_u64().unwrap_or_default(),
+            reason: block["reason"].as_str().unwrap_or_default().to_string(),
+            timestamp: block["timestamp"].as_str().and_then(parse_timestamp),
+            expiry: parse_expiry(&block["expiry"]),
+            partial: block["partial"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Checks if the block applies to the whole wiki
+    pub fn is_sitewide(&self) -> bool {
+        !self.partial
+    }
+
+    /// Checks if the block does not expire
+    pub fn is_infinite(&self) -> bool {
+        self.expiry.is_infinite()
+    }
+}
+
+/// Parses the expiry of a block; a missing expiry means the block does not expire
+fn parse_expiry(expiry: &Value) -> Expiry {
+    expiry
+        .as_str()
+        .and_then(Expiry::parse)
+        .unwrap_or(Expiry::Infinite)
+}
+
+/// Information about a user, from `meta=userinfo` or `list=users`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserInfo {
+    /// User ID; 0 for anonymous users
+    pub id: u64,
+    /// User name, or IP address for anonymous users
+    pub name: String,
+    /// Groups the user was added to, e.g. "sysop"
+    pub groups: Vec<String>,
+    /// Groups the user is in automatically, e.g. "autoconfirmed"
+    pub implicit_groups: Vec<String>,
+    /// Rights of the user
+    pub rights: Vec<String>,
+    /// Number of edits
+    pub edit_count: u64,
+    /// Registration time; `None` if unknown (e.g. for very old accounts)
+    pub registration: Option<Timestamp>,
+    /// Gender set in the preferences: "male", "female" or "unknown"; `None` if not loaded
+    pub gender: Option<String>,
+    /// The block of the user, if any
+    pub block: Option<BlockInfo>,
+}
+
+impl UserInfo {
+    /// Parses a `meta=userinfo` entry or a `list=users` entry (`formatversion=2`)
+    pub fn new_from_api_result(user: &Value) -> UserInfo {
+        let strings = |key: &str| -> Vec<String> {
+            user[key]
+                .as_array()
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        UserInfo {
+            id: user["userid"]
+                .as_u64()
+                .or_else(|| user["id"].as_u64())
+                .unwrap_or_default(),
+            name: user["name"].as_str().unwrap_or_default().to_string(),
+            groups: strings("groups"),
+            implicit_groups: strings("implicitgroups"),
+            rights: strings("rights"),
+            edit_count: user["editcount"].as_u64().unwrap_or_default(),
+            registration: user["registrationdate"]
+                .as_str()
+                .or_else(|| user["registration"].as_str())
+                .and_then(parse_timestamp),
+            gender: user["gender"].as_str().map(|s| s.to_string()),
+            block: BlockInfo::new_from_api_result(user),
+        }
+    }
+
+    /// Checks if the user is in a group, either explicitly or implicitly
+    pub fn in_group(&self, group: &str) -> bool {
+        self.groups
+            .iter()
+            .chain(&self.implicit_groups)
+            .any(|g| g == group)
+    }
+
+    /// Checks if the user has a right
+    pub fn has_right(&self, right: &str) -> bool {
+        self.rights.iter().any(|r| r == right)
+    }
+}
 
 /// `User` contains the login data for the `Api`
 #[derive(Debug, Default, Clone)]
@@ -25,7 +169,7 @@ pub struct User {
     lgusername: String,
     lguserid: u64,
     is_logged_in: bool,
-    user_info: Option<Value>,
+    user_info: Option<UserInfo>,
 }
 
 impl User {
@@ -44,20 +188,55 @@ impl User {
         self.is_logged_in
     }
 
+    /// Returns the user info, if loaded with `load_user_info`
+    pub fn info(&self) -> Option<&UserInfo> {
+        self.user_info.as_ref()
+    }
+
     /// Checks is the user has a spefic right (e.g. "bot", "autocinfirmed")
     pub fn has_right(&self, right: &str) -> bool {
-        match &self.user_info {
-            Some(ui) => {
-                ui["query"]["userinfo"]["rights"]
-                    .as_array()
-                    .unwrap_or(&vec![])
-                    .iter()
-                    .filter(|x| x.as_str().unwrap_or("") == right)
-                    .count()
-                    > 0
-            }
-            None => false,
-        }
+        self.user_info
+            .as_ref()
+            .is_some_and(|info| info.has_right(right))
+    }
+
+    /// Checks if the user is in a group, explicitly (e.g. "sysop") or implicitly (e.g.
+    /// "autoconfirmed")
+    pub fn in_group(&self, group: &str) -> bool {
+        self.user_info
+            .as_ref()
+            .is_some_and(|info| info.in_group(group))
+    }
+
+    /// Checks if the user is an administrator
+    pub fn is_sysop(&self) -> bool {
+        self.in_group("sysop")
+    }
+
+    /// Returns the groups the user was added to (empty if the user info is not loaded)
+    pub fn groups(&self) -> &[String] {
+        self.user_info
+            .as_ref()
+            .map(|info| info.groups.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of edits of the user (0 if the user info is not loaded)
+    pub fn edit_count(&self) -> u64 {
+        self.user_info
+            .as_ref()
+            .map(|info| info.edit_count)
+            .unwrap_or_default()
+    }
+
+    /// Returns the registration time of the user, if known
+    pub fn registration(&self) -> Option<Timestamp> {
+        self.user_info.as_ref()?.registration.as_ref().cloned()
+    }
+
+    /// Returns the block of the user, if any, as of loading the user info
+    pub fn block(&self) -> Option<&BlockInfo> {
+        self.user_info.as_ref()?.block.as_ref()
     }
 
     /// Checks if the user has a bot flag
@@ -96,25 +275,51 @@ impl User {
     }
 
     /// Loads the user info, which is stored in the object; returns Ok(()) if successful
-    pub fn load_user_info(&mut self, api: &Api) -> Result<(), Box<dyn Error>> {
+    pub fn load_user_info(&mut self, api: &Api) -> Result<(), Box<dyn Error + Send + Sync>> {
         match self.user_info {
-            Some(_) => return Ok(()),
+            Some(_) => Ok(()),
             None => {
-                let params: HashMap<String, String> = vec![
+                let params: HashMap<String, String> = [
                     ("action", "query"),
                     ("meta", "userinfo"),
-                    ("uiprop", "blockinfo|groups|groupmemberships|implicitgroups|rights|options|ratelimits|realname|registrationdate|unreadcount|centralids|hasmsg"),
+                    (
+                        "uiprop",
+                        "blockinfo|groups|groupmemberships|implicitgroups|rights|editcount|registrationdate",
+                    ),
+                    ("formatversion", "2"),
                 ]
                 .iter()
                 .map(|x| (x.0.to_string(), x.1.to_string()))
                 .collect();
                 let res = api.query_api_json(&params, "GET")?;
-                self.user_info = Some(res);
+                if let Some(error) = res["error"]["info"].as_str() {
+                    return Err(From::from(format!("Could not load user info: {}", error)));
+                }
+                self.user_info = Some(UserInfo::new_from_api_result(&res["query"]["userinfo"]));
                 Ok(())
             }
         }
     }
 
+    /// Loads the current block of the user, if any; this includes blocks of the IP address the
+    /// `Api` uses. See `Api::block_status` for the blocks of other users.
+    pub fn own_block_status(
+        &self,
+        api: &Api,
+    ) -> Result<Option<BlockInfo>, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "query",
+            "meta" => "userinfo",
+            "uiprop" => "blockinfo",
+            "formatversion" => "2",
+        ];
+        let result = api.get_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("Could not load block info: {}", error)));
+        }
+        Ok(BlockInfo::new_from_api_result(&result["query"]["userinfo"]))
+    }
+
     /// Returns the user name ("" if not logged in)
     pub fn user_name(&self) -> &str {
         &self.lgusername
@@ -145,6 +350,295 @@ impl User {
     }
 }
 
+/// How a password fares against the password policy of a wiki
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordValidity {
+    /// The password is acceptable
+    Good,
+    /// The password may be used to log in, but should be changed
+    Change,
+    /// The password may not be used
+    Invalid,
+}
+
+/// The result of `action=validatepassword`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordValidation {
+    /// The validity of the password
+    pub validity: PasswordValidity,
+    /// Why the password is not good, if it isn't
+    pub message: Option<String>,
+}
+
+impl PasswordValidation {
+    /// Parses the `validatepassword` object of an `action=validatepassword` result
+    pub fn new_from_api_result(result: &Value) -> Option<PasswordValidation> {
+        let validity = match result["validity"].as_str()? {
+            "Good" => PasswordValidity::Good,
+            "Change" => PasswordValidity::Change,
+            "Invalid" => PasswordValidity::Invalid,
+            _ => return None,
+        };
+        Some(PasswordValidation {
+            validity,
+            message: result["validitymessage"].as_str().map(|s| s.to_string()),
+        })
+    }
+
+    /// Checks if the password is acceptable
+    pub fn is_good(&self) -> bool {
+        self.validity == PasswordValidity::Good
+    }
+}
+
+/// The result of checking a user name for confusable existing user names, via `action=antispoof`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpoofCheck {
+    /// No existing user name looks like the name
+    Pass,
+    /// Existing user names that look like the name (e.g. "Examp1e" for "Example")
+    Conflict(Vec<String>),
+    /// The name is not acceptable at all (e.g. it mixes scripts); the message says why
+    Invalid(String),
+}
+
+impl SpoofCheck {
+    /// Parses the `antispoof` object of an `action=antispoof` result
+    pub fn new_from_api_result(result: &Value) -> Option<SpoofCheck> {
+        match result["result"].as_str()? {
+            "pass" => Some(SpoofCheck::Pass),
+            "conflict" => Some(SpoofCheck::Conflict(
+                result["users"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|user| user.as_str().map(|s| s.to_string()))
+                    .collect(),
+            )),
+            "error" => Some(SpoofCheck::Invalid(
+                result["error"].as_str().unwrap_or_default().to_string(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Checks if the name can be used without conflicts
+    pub fn is_pass(&self) -> bool {
+        *self == SpoofCheck::Pass
+    }
+}
+
+/// Maximum number of users per `list=users` request
+const USERS_LIMIT: usize = 50;
+
+impl Api {
+    /// Loads information about a user, who need not be the logged-in user: groups, rights, edit
+    /// count, registration, gender and block. Returns `None` if the user does not exist. For IP
+    /// addresses, only the block is loaded.
+    pub fn user_info(
+        &self,
+        user_name: &str,
+    ) -> Result<Option<UserInfo>, Box<dyn Error + Send + Sync>> {
+        Ok(self.users_info(&[user_name])?.into_iter().next().flatten())
+    }
+
+    /// Loads the block of a user or an IP address, if any. For IP addresses (and ranges), blocks of
+    /// ranges that contain them are found as well; if there are several blocks, the first one is
+    /// returned.
+    pub fn block_status(
+        &self,
+        user_or_ip: &str,
+    ) -> Result<Option<BlockInfo>, Box<dyn Error + Send + Sync>> {
+        let is_ip = is_ip_or_range(user_or_ip);
+        let key = if is_ip { "bkip" } else { "bkusers" };
+        let params = params_map![
+            "action" => "query",
+            "list" => "blocks",
+            key => user_or_ip,
+            "bkprop" => "id|user|by|timestamp|expiry|reason|flags",
+            "bklimit" => Some("max").filter(|_| is_ip),
+            "formatversion" => "2",
+        ];
+        let result = self.get_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("list=blocks failed: {}", error)));
+        }
+        Ok(result["query"]["blocks"]
+            .as_array()
+            .and_then(|blocks| blocks.iter().find_map(BlockInfo::new_from_blocks_result)))
+    }
+
+    /// Checks if a user or an IP address is blocked, see `block_status`
+    pub fn is_blocked(&self, user_or_ip: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        Ok(self.block_status(user_or_ip)?.is_some())
+    }
+
+    /// Checks `password` against the password policy of the wiki, for the account `user_name`
+    /// (which need not exist yet), or for the logged-in user if `None`
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn validate_password(
+        &self,
+        password: &str,
+        user_name: Option<&str>,
+    ) -> Result<PasswordValidation, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "validatepassword",
+            "password" => password,
+            "user" => user_name,
+            "formatversion" => "2",
+        ];
+        let result = self.post_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!(
+                "action=validatepassword failed: {}",
+                error
+            )));
+        }
+        PasswordValidation::new_from_api_result(&result["validatepassword"]).ok_or_else(|| {
+            From::from(format!(
+                "Unexpected action=validatepassword result: {:?}",
+                result
+            ))
+        })
+    }
+
+    /// Checks if the user name `user_name` looks like existing user names, with the AntiSpoof
+    /// extension, e.g. before creating or renaming an account
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. the extension is not installed),
+    /// or any error from the request.
+    pub fn check_user_name_spoofing(
+        &self,
+        user_name: &str,
+    ) -> Result<SpoofCheck, Box<dyn Error + Send + Sync>> {
+        let params = params_map![
+            "action" => "antispoof",
+            "username" => user_name,
+            "formatversion" => "2",
+        ];
+        let result = self.get_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!("action=antispoof failed: {}", error)));
+        }
+        SpoofCheck::new_from_api_result(&result["antispoof"])
+            .ok_or_else(|| From::from(format!("Unexpected action=antispoof result: {:?}", result)))
+    }
+
+    /// Loads information about several users, like `user_info`, in batches. The results are in the
+    /// order of `user_names` (duplicates included); `None` for users that do not exist and for
+    /// invalid names.
+    pub fn users_info(
+        &self,
+        user_names: &[&str],
+    ) -> Result<Vec<Option<UserInfo>>, Box<dyn Error + Send + Sync>> {
+        let mut ret = Vec::with_capacity(user_names.len());
+        for chunk in user_names.chunks(USERS_LIMIT) {
+            let params = params_map![
+                "action" => "query",
+                "list" => "users",
+                "ususers" => chunk,
+                "usprop" => "blockinfo|groups|implicitgroups|rights|editcount|registration|gender",
+                "formatversion" => "2",
+            ];
+            let result = self.get_query_api_json(&params)?;
+            if let Some(error) = result["error"]["info"].as_str() {
+                return Err(From::from(format!("list=users failed: {}", error)));
+            }
+            ret.extend(users_from_api_result(&result, chunk));
+        }
+        Ok(ret)
+    }
+}
+
+/// Checks if a string is an IP address, or an IP range in CIDR notation
+fn is_ip_or_range(s: &str) -> bool {
+    let address = match s.split_once('/') {
+        Some((address, bits)) if bits.parse::<u8>().is_ok() => address,
+        Some(_) => return false,
+        None => s,
+    };
+    address.parse::<IpAddr>().is_ok()
+}
+
+/// Sanitizes an IP address or range like `IPUtils::sanitizeIP` of MediaWiki: IPv6 addresses are
+/// expanded (without `::`), in uppercase and without leading zeros in each group, and leading
+/// zeros are removed from IPv4 bytes. Returns `None` if `ip` is not an IP address or range.
+fn sanitize_ip(ip: &str) -> Option<String> {
+    let (address, bits) = match ip.split_once('/') {
+        Some((address, bits)) => (address, Some(bits)),
+        None => (ip, None),
+    };
+    if bits.is_some_and(|bits| bits.is_empty() || !bits.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let address = if address.contains(':') {
+        if address.contains('.') {
+            return None;
+        }
+        let segments = address.parse::<Ipv6Addr>().ok()?.segments();
+        segments
+            .iter()
+            .map(|segment| format!("{:X}", segment))
+            .collect::<Vec<_>>()
+            .join(":")
+    } else {
+        let bytes = address
+            .split('.')
+            .map(|byte| byte.parse::<u8>().ok().filter(|_| is_number(byte)))
+            .collect::<Option<Vec<u8>>>()?;
+        if bytes.len() != 4 {
+            return None;
+        }
+        bytes
+            .iter()
+            .map(|b| b.to_string())
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+    Some(match bits {
+        Some(bits) => format!("{}/{}", address, bits),
+        None => address,
+    })
+}
+
+/// Normalizes a user name the way `list=users` reports it: like a title, with the first letter
+/// always in uppercase, and IP addresses sanitized (see `sanitize_ip`)
+fn normalize_user_name(name: &str) -> String {
+    let name = Title::normalize_text_with_case(name, CaseSensitivity::FirstLetter);
+    sanitize_ip(&name).unwrap_or(name)
+}
+
+/// Parses the users of a `list=users` result, in the order of `user_names`; `None` for missing or
+/// invalid users. The API lists invalid names first, normalizes names and drops duplicates, so
+/// entries are matched by name.
+fn users_from_api_result(result: &Value, user_names: &[&str]) -> Vec<Option<UserInfo>> {
+    let users: HashMap<&str, &Value> = result["query"]["users"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|user| Some((user["name"].as_str()?, user)))
+        .collect();
+    user_names
+        .iter()
+        .map(|name| {
+            // Invalid names are reported as given
+            let user = users
+                .get(normalize_user_name(name).as_str())
+                .or_else(|| users.get(name))?;
+            let exists = user.get("missing").is_none() && user.get("invalid").is_none();
+            if exists {
+                Some(UserInfo::new_from_api_result(user))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,6 +669,168 @@ mod tests {
         assert_eq!(user.user_id(), user_id);
     }
 
+    #[test]
+    fn user_info() {
+        let info = UserInfo::new_from_api_result(&json!({
+            "id": 12, "name": "Example", "groups": ["sysop", "*", "user"], "implicitgroups": ["*",
+            "user", "autoconfirmed"],
+            "rights": ["edit", "block"], "editcount": 4321,
+            "registrationdate": "2010-01-01T00:00:00Z",
+            "blockid": 5, "blockedby": "Admin", "blockedbyid": 1, "blockreason": "Test",
+            "blockedtimestamp": "2020-01-01T00:00:00Z",
+            "blockexpiry": "infinite", "blockpartial": true
+        }));
+        assert_eq!(info.id, 12);
+        assert!(info.in_group("autoconfirmed"));
+        assert!(info.has_right("block"));
+        assert_eq!(info.registration, parse_timestamp("2010-01-01T00:00:00Z"));
+        let block = info.block.as_ref().unwrap();
+        assert!(!block.is_sitewide());
+        assert!(block.is_infinite());
+        let user = User {
+            user_info: Some(info),
+            ..User::new()
+        };
+        assert!(user.is_sysop());
+        assert_eq!(user.edit_count(), 4321);
+        assert_eq!(user.registration(), parse_timestamp("2010-01-01T00:00:00Z"));
+        assert!(UserInfo::new_from_api_result(
+            &json!({"id": 0, "name": "127.0.0.1", "anon": true})
+        )
+        .block
+        .is_none());
+    }
+
+    #[test]
+    fn users() {
+        let result = json!({"query": {"users": [
+            {"userid": 3, "name": "Foo", "editcount": 10, "registration": "2004-01-01T00:00:00Z",
+            "gender": "female",
+             "groups": ["*", "user"], "implicitgroups": ["*", "user"], "rights": ["edit"]},
+            {"name": "Nonexistent", "missing": true},
+            {"name": "127.0.0.1", "blockid": 1, "blockedby": "Admin",
+            "blockexpiry": "2030-01-01T00:00:00Z"}
+        ]}});
+        let users = users_from_api_result(&result, &["Foo", "Nonexistent", "127.0.0.1"]);
+        let foo = users[0].as_ref().unwrap();
+        assert_eq!(
+            (foo.id, foo.edit_count, foo.gender.as_deref()),
+            (3, 10, Some("female"))
+        );
+        assert_eq!(foo.registration, parse_timestamp("2004-01-01T00:00:00Z"));
+        assert!(users[1].is_none());
+        let block = users[2].as_ref().unwrap().block.as_ref().unwrap();
+        assert!(!block.is_infinite());
+        assert_eq!(
+            block.expiry,
+            Expiry::At(parse_timestamp("2030-01-01T00:00:00Z").unwrap())
+        );
+    }
+
+    #[test]
+    fn users_matched_by_name() {
+        // Invalid names come first, names are normalized, and duplicates are dropped
+        let result = json!({"query": {"users": [
+            {"name": "Foo|Bar", "invalid": true},
+            {"userid": 3, "name": "Foo bar", "editcount": 10},
+            {"name": "Nonexistent", "missing": true},
+            {"userid": 0, "name": "2001:DB8:0:0:0:0:0:1", "blockid": 1, "blockedby": "Admin",
+            "blockexpiry": "infinity"}
+        ]}});
+        let users = users_from_api_result(
+            &result,
+            &[
+                "nonexistent",
+                "foo_bar",
+                "Foo|Bar",
+                "Foo bar",
+                "2001:db8::1",
+            ],
+        );
+        assert_eq!(users.len(), 5);
+        assert!(users[0].is_none());
+        assert_eq!(users[1].as_ref().unwrap().id, 3);
+        assert!(users[2].is_none());
+        assert_eq!(users[3].as_ref().unwrap().id, 3);
+        assert!(users[4].as_ref().unwrap().block.is_some());
+        assert_eq!(
+            users_from_api_result(&json!({"batchcomplete": true}), &["Foo"]),
+            vec![None]
+        );
+    }
+
+    #[test]
+    fn ip_names() {
+        assert_eq!(
+            normalize_user_name("2001:db8::1").as_str(),
+            "2001:DB8:0:0:0:0:0:1"
+        );
+        assert_eq!(
+            normalize_user_name("2001:0db8::/32").as_str(),
+            "2001:DB8:0:0:0:0:0:0/32"
+        );
+        assert_eq!(normalize_user_name("::1").as_str(), "0:0:0:0:0:0:0:1");
+        assert_eq!(normalize_user_name("127.000.0.01").as_str(), "127.0.0.1");
+        assert_eq!(normalize_user_name("192.0.2.0/24").as_str(), "192.0.2.0/24");
+        assert_eq!(normalize_user_name("foo_bar").as_str(), "Foo bar");
+        assert_eq!(normalize_user_name("1.2.3").as_str(), "1.2.3");
+    }
+
+    #[test]
+    fn blocks() {
+        assert!(is_ip_or_range("127.0.0.1"));
+        assert!(is_ip_or_range("2001:db8::/32"));
+        assert!(!is_ip_or_range("Example"));
+        assert!(!is_ip_or_range("1.2.3.4/x"));
+        let block = BlockInfo::new_from_blocks_result(&json!({
+            "id": 7, "user": "Example", "by": "Admin", "byid": 1,
+            "timestamp": "2020-01-01T00:00:00Z",
+            "expiry": "infinity", "reason": "Spam", "partial": false, "nocreate": true
+        }))
+        .unwrap();
+        assert!(block.is_sitewide());
+        assert!(block.is_infinite());
+        assert_eq!(block.blocked_by, "Admin");
+        assert_eq!(block.timestamp, parse_timestamp("2020-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn password_validation() {
+        let good = PasswordValidation::new_from_api_result(&json!({"validity": "Good"})).unwrap();
+        assert!(good.is_good());
+        let invalid = PasswordValidation::new_from_api_result(&json!({
+            "validity": "Invalid", "validitymessage": "Passwords must be at least 8 characters."
+        }))
+        .unwrap();
+        assert_eq!(invalid.validity, PasswordValidity::Invalid);
+        assert!(invalid.message.is_some());
+        assert_eq!(PasswordValidation::new_from_api_result(&json!({})), None);
+    }
+
+    #[test]
+    fn spoof_check() {
+        assert!(SpoofCheck::new_from_api_result(
+            &json!({"result": "pass", "normalised": "v2:EXAMPLE"})
+        )
+        .unwrap()
+        .is_pass());
+        assert_eq!(
+            SpoofCheck::new_from_api_result(
+                &json!({"result": "conflict", "users": ["Examp1e", "EXAMPLE"]})
+            ),
+            Some(SpoofCheck::Conflict(vec![
+                "Examp1e".to_string(),
+                "EXAMPLE".to_string()
+            ]))
+        );
+        assert!(matches!(
+            SpoofCheck::new_from_api_result(
+                &json!({"result": "error", "error": "Contains incompatible mixed scripts"})
+            ),
+            Some(SpoofCheck::Invalid(_))
+        ));
+    }
+
     #[test]
     fn user_rights() {
         let mut user = User::new();
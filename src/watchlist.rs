@@ -0,0 +1,143 @@
+/*!
+Notification timestamps of watched pages, changed via `action=setnotificationtimestamp`: e.g. for
+watchlist digest tools, to mark pages as visited after processing their changes, so that they are no
+longer shown as changed.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::{Api, NamespaceID};
+use crate::params_map;
+use crate::timestamp::{format_timestamp, parse_timestamp, Timestamp};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// The notification timestamp of a page after `action=setnotificationtimestamp`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationTimestamp {
+    /// Namespace of the page
+    pub ns: NamespaceID,
+    /// Full title of the page
+    pub title: String,
+    /// Time of the first unseen change, or `None` if all changes have been seen
+    pub notification_timestamp: Option<Timestamp>,
+    /// Whether the page does not exist
+    pub missing: bool,
+    /// Whether the page is not on the watchlist
+    pub not_watched: bool,
+}
+
+impl NotificationTimestamp {
+    /// Parses an entry of an `action=setnotificationtimestamp` result (`formatversion=2`)
+    pub fn new_from_api_result(page: &Value) -> Option<NotificationTimestamp> {
+        Some(NotificationTimestamp {
+            ns: page["ns"].as_i64().unwrap_or_default(),
+            title: page["title"].as_str()?.to_string(),
+            notification_timestamp: page["notificationtimestamp"]
+                .as_str()
+                .and_then(parse_timestamp),
+            missing: page["missing"].as_bool().unwrap_or(false),
+            not_watched: page["notwatched"].as_bool().unwrap_or(false),
+        })
+    }
+}
+
+impl Api {
+    /// Marks the watched pages `titles` as visited, up to `timestamp` (now if `None`);
+    /// changes after `timestamp` are shown as unseen
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. when not logged in), or any error from
+    /// the request.
+    pub fn mark_pages_visited(
+        &self,
+        titles: &[&str],
+        timestamp: Option<&Timestamp>,
+    ) -> Result<Vec<NotificationTimestamp>, Box<dyn Error + Send + Sync>> {
+        let result = self.set_notification_timestamp(params_map![
+            "titles" => titles.join("|"),
+            "timestamp" => timestamp.map(format_timestamp),
+        ])?;
+        Ok(notification_timestamps_from_api_result(&result))
+    }
+
+    /// Marks all pages on the watchlist as visited, up to `timestamp` (now if `None`)
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error (e.g. when not logged in), or any error from
+    /// the request.
+    pub fn mark_watchlist_visited(
+        &self,
+        timestamp: Option<&Timestamp>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.set_notification_timestamp(params_map![
+            "entirewatchlist" => true,
+            "timestamp" => timestamp.map(format_timestamp),
+        ])?;
+        Ok(())
+    }
+
+    /// Posts an `action=setnotificationtimestamp` request with additional parameters
+    fn set_notification_timestamp(
+        &self,
+        mut params: HashMap<String, String>,
+    ) -> Result<Value, Box<dyn Error + Send + Sync>> {
+        params.extend(params_map![
+            "action" => "setnotificationtimestamp",
+            "token" => self.get_edit_token()?,
+            "formatversion" => "2",
+        ]);
+        let result = self.post_query_api_json(&params)?;
+        if let Some(error) = result["error"]["info"].as_str() {
+            return Err(From::from(format!(
+                "action=setnotificationtimestamp failed: {}",
+                error
+            )));
+        }
+        Ok(result)
+    }
+}
+
+/// Parses the pages of an `action=setnotificationtimestamp` result
+fn notification_timestamps_from_api_result(result: &Value) -> Vec<NotificationTimestamp> {
+    result["setnotificationtimestamp"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(NotificationTimestamp::new_from_api_result)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_from_api_result() {
+        let result = json!({"batchcomplete": true, "setnotificationtimestamp": [
+            {"ns": 0, "title": "Foo", "notificationtimestamp": ""},
+            {"ns": 0, "title": "Bar", "notificationtimestamp": "2020-04-14T10:22:31Z"},
+            {"ns": 0, "title": "Baz", "missing": true, "notwatched": true}
+        ]});
+        let pages = notification_timestamps_from_api_result(&result);
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0].notification_timestamp, None);
+        assert!(pages[1].notification_timestamp.is_some());
+        assert!(pages[2].missing && pages[2].not_watched);
+        assert!(
+            notification_timestamps_from_api_result(&json!({"setnotificationtimestamp": {}}))
+                .is_empty()
+        );
+    }
+}
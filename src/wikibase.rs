@@ -0,0 +1,1635 @@
+/*!
+Typed Wikibase entities (items, properties, ...), as used on Wikidata, and the API modules to read
+them.
+*/
+
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    unstable_features,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+use crate::api::Api;
+use crate::datavalue::DataValue;
+use crate::params_map;
+use crate::timestamp::Timestamp;
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::thread;
+
+/// A text in a language: a label, description, or alias of an entity
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Term {
+    /// Language code
+    pub language: String,
+    /// The text
+    pub value: String,
+}
+
+impl Term {
+    /// Creates a new term
+    pub fn new(language: &str, value: &str) -> Term {
+        Term {
+            language: language.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// The label of an entity in a language
+pub type Label = Term;
+
+/// The description of an entity in a language
+pub type Description = Term;
+
+/// An alias of an entity in a language
+pub type Alias = Term;
+
+/// A link from an item to a page on another wiki
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Sitelink {
+    /// Global site ID, e.g. "enwiki"
+    pub site: String,
+    /// Title of the page
+    pub title: String,
+    /// Item IDs of the badges, e.g. "Q17437796" (featured article)
+    #[serde(default)]
+    pub badges: Vec<String>,
+    /// URL of the page, if requested via `props=sitelinks/urls`
+    pub url: Option<String>,
+}
+
+/// The rank of a statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rank {
+    /// Preferred rank
+    Preferred,
+    /// Normal rank
+    #[default]
+    Normal,
+    /// Deprecated rank
+    Deprecated,
+}
+
+impl Rank {
+    /// Returns the name of the rank, as used by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rank::Preferred => "preferred",
+            Rank::Normal => "normal",
+            Rank::Deprecated => "deprecated",
+        }
+    }
+}
+
+/// The type of a snak
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnakType {
+    /// A specific value
+    Value,
+    /// Some unknown value
+    SomeValue,
+    /// No value
+    NoValue,
+}
+
+/// A property-value pair: the main part of a statement, a qualifier, or part of a reference
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Snak {
+    /// Whether the snak has a value, an unknown value, or no value
+    pub snaktype: SnakType,
+    /// Property ID
+    pub property: String,
+    /// Hash of the snak (not set for main snaks)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The value, for `SnakType::Value`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datavalue: Option<DataValue>,
+    /// Data type of the property, e.g. "wikibase-item"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datatype: Option<String>,
+}
+
+impl Snak {
+    /// Creates a snak with a value for a property
+    pub fn new_value(property: &str, datavalue: DataValue) -> Snak {
+        Snak {
+            snaktype: SnakType::Value,
+            property: property.to_string(),
+            hash: None,
+            datavalue: Some(datavalue),
+            datatype: None,
+        }
+    }
+
+    /// Creates a snak without a value (`SnakType::SomeValue` or `SnakType::NoValue`) for a property
+    pub fn new_without_value(property: &str, snaktype: SnakType) -> Snak {
+        Snak {
+            snaktype,
+            property: property.to_string(),
+            hash: None,
+            datavalue: None,
+            datatype: None,
+        }
+    }
+}
+
+/// A reference of a statement
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Reference {
+    /// Hash of the reference
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Snaks, keyed by property ID
+    pub snaks: HashMap<String, Vec<Snak>>,
+    /// Order of the properties in `snaks`
+    #[serde(rename = "snaks-order", default)]
+    pub snaks_order: Vec<String>,
+}
+
+/// A statement (claim) about an entity
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Statement {
+    /// Statement GUID, e.g. "Q42$F078E5B3-F9A8-480E-B7AC-D97778CBBEF9"; not set for new statements
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// The main snak
+    pub mainsnak: Snak,
+    /// The rank
+    #[serde(default)]
+    pub rank: Rank,
+    /// Qualifiers, keyed by property ID
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub qualifiers: HashMap<String, Vec<Snak>>,
+    /// Order of the properties in `qualifiers`
+    #[serde(rename = "qualifiers-order", default)]
+    pub qualifiers_order: Vec<String>,
+    /// References
+    #[serde(default)]
+    pub references: Vec<Reference>,
+    /// Always "statement"
+    #[serde(rename = "type", default = "statement_type")]
+    pub statement_type: String,
+}
+
+fn statement_type() -> String {
+    "statement".to_string()
+}
+
+/// A Wikibase entity, from `wbgetentities`. Which fields are set depends on the entity type,
+/// and on the `props` that were requested.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Entity {
+    /// Entity ID, e.g. "Q42"
+    pub id: String,
+    /// Entity type, e.g. "item", "property", "lexeme", or "mediainfo"
+    #[serde(rename = "type")]
+    pub entity_type: String,
+    /// Last revision ID of the entity
+    pub lastrevid: Option<u64>,
+    /// Time of the last change
+    pub modified: Option<Timestamp>,
+    /// Data type, for properties (e.g. "wikibase-item")
+    pub datatype: Option<String>,
+    /// Labels, keyed by language
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub labels: HashMap<String, Label>,
+    /// Descriptions, keyed by language
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub descriptions: HashMap<String, Description>,
+    /// Aliases, keyed by language
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub aliases: HashMap<String, Vec<Alias>>,
+    /// Sitelinks, keyed by site ID
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub sitelinks: HashMap<String, Sitelink>,
+    /// Statements, keyed by property ID (called `statements` for MediaInfo entities)
+    #[serde(default, alias = "statements", deserialize_with = "map_or_empty_array")]
+    pub claims: HashMap<String, Vec<Statement>>,
+    /// All other fields, e.g. those of lexemes
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl Entity {
+    /// Returns the label in a language
+    pub fn label(&self, language: &str) -> Option<&str> {
+        self.labels.get(language).map(|t| t.value.as_str())
+    }
+
+    /// Returns the description in a language
+    pub fn description(&self, language: &str) -> Option<&str> {
+        self.descriptions.get(language).map(|t| t.value.as_str())
+    }
+
+    /// Returns the aliases in a language
+    pub fn aliases(&self, language: &str) -> Vec<&str> {
+        self.aliases
+            .get(language)
+            .map(|aliases| aliases.iter().map(|t| t.value.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the sitelink to a site, e.g. "enwiki"
+    pub fn sitelink(&self, site: &str) -> Option<&Sitelink> {
+        self.sitelinks.get(site)
+    }
+
+    /// Returns the statements for a property
+    pub fn claims_for(&self, property: &str) -> &[Statement] {
+        self.claims.get(property).map_or(&[], Vec::as_slice)
+    }
+
+    /// Parses the entities of a `wbgetentities` result, keyed by ID.
+    /// Missing entities are left out.
+    pub fn new_from_api_result(
+        result: &Value,
+    ) -> Result<HashMap<String, Entity>, serde_json::Error> {
+        let entities = match result["entities"].as_object() {
+            Some(entities) => entities,
+            None => return Ok(HashMap::new()),
+        };
+        entities
+            .iter()
+            .filter(|(_, entity)| entity.get("missing").is_none())
+            .map(|(id, entity)| Ok((id.to_string(), Entity::deserialize(entity)?)))
+            .collect()
+    }
+}
+
+/// The type of an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityType {
+    /// An item ("Q...")
+    Item,
+    /// A property ("P...")
+    Property,
+    /// A lexeme ("L...")
+    Lexeme,
+    /// A form of a lexeme ("L...-F...")
+    Form,
+    /// A sense of a lexeme ("L...-S...")
+    Sense,
+    /// The structured data of a file ("M...")
+    MediaInfo,
+}
+
+impl EntityType {
+    /// Returns the name of the type, as used by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntityType::Item => "item",
+            EntityType::Property => "property",
+            EntityType::Lexeme => "lexeme",
+            EntityType::Form => "form",
+            EntityType::Sense => "sense",
+            EntityType::MediaInfo => "mediainfo",
+        }
+    }
+}
+
+/// A typed entity ID, e.g. "Q42", "P31", "L7", "L7-F1", "L7-S1", or "M123"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EntityId {
+    /// An item, "Q..."
+    Item(u64),
+    /// A property, "P..."
+    Property(u64),
+    /// A lexeme, "L..."
+    Lexeme(u64),
+    /// A form of a lexeme, "L...-F..."
+    Form(u64, u64),
+    /// A sense of a lexeme, "L...-S..."
+    Sense(u64, u64),
+    /// A MediaInfo entity, "M..."
+    MediaInfo(u64),
+}
+
+impl EntityId {
+    /// Returns the type of the entity
+    pub fn entity_type(&self) -> EntityType {
+        match self {
+            EntityId::Item(_) => EntityType::Item,
+            EntityId::Property(_) => EntityType::Property,
+            EntityId::Lexeme(_) => EntityType::Lexeme,
+            EntityId::Form(..) => EntityType::Form,
+            EntityId::Sense(..) => EntityType::Sense,
+            EntityId::MediaInfo(_) => EntityType::MediaInfo,
+        }
+    }
+}
+
+impl std::str::FromStr for EntityId {
+    type Err = ParseEntityIdError;
+
+    fn from_str(s: &str) -> Result<EntityId, ParseEntityIdError> {
+        let error = || ParseEntityIdError(s.to_string());
+        let number = |n: &str| -> Result<u64, ParseEntityIdError> {
+            if n.starts_with(['0', '+']) {
+                return Err(error());
+            }
+            n.parse().map_err(|_| error())
+        };
+        let mut chars = s.chars();
+        let prefix = chars.next().ok_or_else(error)?;
+        let rest = chars.as_str();
+        Ok(match prefix {
+            'Q' => EntityId::Item(number(rest)?),
+            'P' => EntityId::Property(number(rest)?),
+            'M' => EntityId::MediaInfo(number(rest)?),
+            'L' => match rest.split_once('-') {
+                None => EntityId::Lexeme(number(rest)?),
+                Some((lexeme, sub)) if sub.starts_with('F') => {
+                    EntityId::Form(number(lexeme)?, number(&sub[1..])?)
+                }
+                Some((lexeme, sub)) if sub.starts_with('S') => {
+                    EntityId::Sense(number(lexeme)?, number(&sub[1..])?)
+                }
+                Some(_) => return Err(error()),
+            },
+            _ => return Err(error()),
+        })
+    }
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityId::Item(n) => write!(f, "Q{}", n),
+            EntityId::Property(n) => write!(f, "P{}", n),
+            EntityId::Lexeme(n) => write!(f, "L{}", n),
+            EntityId::Form(l, n) => write!(f, "L{}-F{}", l, n),
+            EntityId::Sense(l, n) => write!(f, "L{}-S{}", l, n),
+            EntityId::MediaInfo(n) => write!(f, "M{}", n),
+        }
+    }
+}
+
+/// The error for a string that is not a valid entity ID (provided)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEntityIdError(pub String);
+
+impl fmt::Display for ParseEntityIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid entity ID: {}", self.0)
+    }
+}
+
+impl Error for ParseEntityIdError {}
+
+/// Which part of an entity matched a search
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SearchMatch {
+    /// What matched, e.g. "label", "alias", or "entityId"
+    #[serde(rename = "type")]
+    pub match_type: String,
+    /// Language of the matching term, if any
+    pub language: Option<String>,
+    /// The matching text
+    pub text: String,
+}
+
+/// An entity found by `wbsearchentities`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EntitySearchResult {
+    /// Entity ID
+    pub id: String,
+    /// Title of the entity page, e.g. "Property:P31"
+    pub title: Option<String>,
+    /// Page ID of the entity page
+    pub pageid: Option<u64>,
+    /// Concept URI of the entity, e.g. "http://www.wikidata.org/entity/Q42"
+    pub concepturi: Option<String>,
+    /// URL of the entity page, possibly protocol-relative
+    pub url: Option<String>,
+    /// Label in the search language (or a fallback language)
+    pub label: Option<String>,
+    /// Description in the search language (or a fallback language)
+    pub description: Option<String>,
+    /// Matching aliases
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// What matched the search
+    #[serde(rename = "match")]
+    pub match_info: SearchMatch,
+}
+
+/// A format of `Special:EntityData`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityDataFormat {
+    /// JSON, as in `wbgetentities`
+    Json,
+    /// RDF in Turtle
+    Turtle,
+    /// RDF in N-Triples
+    NTriples,
+    /// RDF in RDF/XML
+    RdfXml,
+    /// RDF in JSON-LD
+    JsonLd,
+}
+
+impl EntityDataFormat {
+    /// Returns the file extension of the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EntityDataFormat::Json => "json",
+            EntityDataFormat::Turtle => "ttl",
+            EntityDataFormat::NTriples => "nt",
+            EntityDataFormat::RdfXml => "rdf",
+            EntityDataFormat::JsonLd => "jsonld",
+        }
+    }
+}
+
+/// How much RDF `Special:EntityData` returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityDataFlavor {
+    /// Everything, as in the RDF dumps, but without data about other entities
+    Dump,
+    /// Only the truthy statements (simple values), sitelinks, and terms
+    Simple,
+    /// Everything, including data about referenced entities (the default)
+    Full,
+    /// Like `Full`, with more data about referenced entities
+    Long,
+}
+
+impl EntityDataFlavor {
+    /// Returns the name of the flavor, as used in the URL
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntityDataFlavor::Dump => "dump",
+            EntityDataFlavor::Simple => "simple",
+            EntityDataFlavor::Full => "full",
+            EntityDataFlavor::Long => "long",
+        }
+    }
+}
+
+/// The entity to edit with `Api::wbeditentity`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityTarget {
+    /// An existing entity, by ID
+    Id(String),
+    /// A new item
+    NewItem,
+    /// A new property, with the given data type (e.g. "wikibase-item")
+    NewProperty(String),
+}
+
+/// Changes to an entity, for `Api::wbeditentity`.
+/// Only the given parts are changed, unless `set_clear` is used.
+///
+/// # Examples
+///
+/// ```
+/// use mediawiki::wikibase::EntityEdit;
+/// let mut edit = EntityEdit::new();
+/// edit.set_label("en", "Douglas Adams");
+/// edit.add_alias("en", "DNA");
+/// edit.remove_description("de");
+/// assert_eq!(edit.to_data()["labels"][0]["value"], "Douglas Adams");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntityEdit {
+    labels: Vec<Value>,
+    descriptions: Vec<Value>,
+    aliases: Vec<Value>,
+    sitelinks: Vec<Value>,
+    claims: Vec<Value>,
+    clear: bool,
+}
+
+impl EntityEdit {
+    /// Creates an empty edit
+    pub fn new() -> EntityEdit {
+        EntityEdit::default()
+    }
+
+    /// Checks if the edit changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+            && self.descriptions.is_empty()
+            && self.aliases.is_empty()
+            && self.sitelinks.is_empty()
+            && self.claims.is_empty()
+            && !self.clear
+    }
+
+    /// Sets the label in a language
+    pub fn set_label(&mut self, language: &str, value: &str) {
+        self.labels
+            .push(json!({"language": language, "value": value}));
+    }
+
+    /// Removes the label in a language
+    pub fn remove_label(&mut self, language: &str) {
+        self.labels
+            .push(json!({"language": language, "remove": ""}));
+    }
+
+    /// Sets the description in a language
+    pub fn set_description(&mut self, language: &str, value: &str) {
+        self.descriptions
+            .push(json!({"language": language, "value": value}));
+    }
+
+    /// Removes the description in a language
+    pub fn remove_description(&mut self, language: &str) {
+        self.descriptions
+            .push(json!({"language": language, "remove": ""}));
+    }
+
+    /// Adds an alias in a language, keeping the existing ones
+    pub fn add_alias(&mut self, language: &str, value: &str) {
+        self.aliases
+            .push(json!({"language": language, "value": value, "add": ""}));
+    }
+
+    /// Removes an alias in a language
+    pub fn remove_alias(&mut self, language: &str, value: &str) {
+        self.aliases
+            .push(json!({"language": language, "value": value, "remove": ""}));
+    }
+
+    /// Sets the sitelink to a site, with badges (item IDs)
+    pub fn set_sitelink(&mut self, site: &str, title: &str, badges: &[&str]) {
+        self.sitelinks
+            .push(json!({"site": site, "title": title, "badges": badges}));
+    }
+
+    /// Removes the sitelink to a site
+    pub fn remove_sitelink(&mut self, site: &str) {
+        self.sitelinks
+            .push(json!({"site": site, "title": "", "remove": ""}));
+    }
+
+    /// Adds a statement, or changes it if it has the ID of an existing statement
+    pub fn set_claim(&mut self, statement: &Statement) {
+        self.claims.push(json!(statement));
+    }
+
+    /// Removes a statement by GUID
+    pub fn remove_claim(&mut self, guid: &str) {
+        self.claims.push(json!({"id": guid, "remove": ""}));
+    }
+
+    /// If set, everything not in this edit is removed from the entity (`clear=1`)
+    pub fn set_clear(&mut self, clear: bool) {
+        self.clear = clear;
+    }
+
+    /// Returns the `data` parameter for `wbeditentity`
+    pub fn to_data(&self) -> Value {
+        let mut data = serde_json::Map::new();
+        for (key, values) in [
+            ("labels", &self.labels),
+            ("descriptions", &self.descriptions),
+            ("aliases", &self.aliases),
+            ("sitelinks", &self.sitelinks),
+            ("claims", &self.claims),
+        ] {
+            if !values.is_empty() {
+                data.insert(key.to_string(), Value::Array(values.clone()));
+            }
+        }
+        Value::Object(data)
+    }
+}
+
+/// Errors from editing Wikibase entities
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WikibaseError {
+    /// The edit was rejected because the data is not valid, e.g. a label that conflicts with
+    /// another entity, or a malformed value. `messages` are the names of the validation messages.
+    Validation {
+        /// Error code, e.g. "modification-failed"
+        code: String,
+        /// Error description
+        info: String,
+        /// Names of the validation messages, e.g.
+        /// "wikibase-validator-label-with-description-conflict"
+        messages: Vec<String>,
+    },
+
+    /// The entity was changed since the base revision.
+    EditConflict(String),
+
+    /// The language code is not known to the wiki.
+    UnknownLanguage(String),
+
+    /// The entity (ID provided) does not exist.
+    Missing(String),
+
+    /// Any other error returned by the API.
+    ApiError {
+        /// Error code
+        code: String,
+        /// Error description
+        info: String,
+    },
+
+    /// Couldn't understand the API response (provided).
+    BadResponse(Value),
+
+    /// Error while performing the API request.
+    RequestError(Box<dyn Error + Send + Sync>),
+}
+
+impl WikibaseError {
+    /// Error codes of validation failures
+    const VALIDATION_CODES: &'static [&'static str] = &[
+        "modification-failed",
+        "failed-save",
+        "invalid-claim",
+        "invalid-snak",
+        "invalid-guid",
+        "invalid-json",
+        "not-recognized",
+        "not-recognized-language",
+        "not-recognized-site",
+        "no-such-entity",
+        "no-such-property",
+        "bad-data",
+    ];
+
+    /// Returns the error in an API result, if any
+    pub fn new_from_api_result(result: &Value) -> Option<WikibaseError> {
+        let error = result.get("error")?;
+        let code = error["code"].as_str().unwrap_or_default().to_string();
+        let info = error["info"].as_str().unwrap_or_default().to_string();
+        Some(if code == "editconflict" {
+            WikibaseError::EditConflict(info)
+        } else if WikibaseError::VALIDATION_CODES.contains(&code.as_str()) {
+            let messages = error["messages"]
+                .as_array()
+                .map(|messages| {
+                    messages
+                        .iter()
+                        .filter_map(|m| m["name"].as_str().map(|s| s.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            WikibaseError::Validation {
+                code,
+                info,
+                messages,
+            }
+        } else {
+            WikibaseError::ApiError { code, info }
+        })
+    }
+
+    /// Checks the result of a Wikibase edit
+    pub(crate) fn check_edit(result: Value) -> Result<Value, WikibaseError> {
+        if let Some(error) = WikibaseError::new_from_api_result(&result) {
+            return Err(error);
+        }
+        match result["success"].as_i64() {
+            Some(1) => Ok(result),
+            _ => Err(WikibaseError::BadResponse(result)),
+        }
+    }
+}
+
+impl fmt::Display for WikibaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WikibaseError::Validation {
+                code,
+                info,
+                messages,
+            } => {
+                write!(
+                    f,
+                    "validation failed ({}): {} [{}]",
+                    code,
+                    info,
+                    messages.join(", ")
+                )
+            }
+            WikibaseError::EditConflict(info) => write!(f, "edit conflict: {}", info),
+            WikibaseError::UnknownLanguage(language) => {
+                write!(f, "unknown language code: {}", language)
+            }
+            WikibaseError::Missing(id) => write!(f, "entity missing: {}", id),
+            WikibaseError::ApiError { code, info } => write!(f, "API error ({}): {}", code, info),
+            WikibaseError::BadResponse(response) => {
+                write!(f, "bad API response from Wikibase: {:?}", response)
+            }
+            WikibaseError::RequestError(error) => write!(f, "request error: {}", error),
+        }
+    }
+}
+
+impl Error for WikibaseError {}
+
+/// Deserializes a JSON object into a map; Wikibase returns an empty array instead of an empty
+/// object.
+pub(crate) fn map_or_empty_array<'de, D, T>(deserializer: D) -> Result<HashMap<String, T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Array(a) if a.is_empty() => Ok(HashMap::new()),
+        Value::Null => Ok(HashMap::new()),
+        v => serde_json::from_value(v).map_err(serde::de::Error::custom),
+    }
+}
+
+impl Api {
+    /// Loads entities with `wbgetentities`. `props` (e.g. "labels", "claims") and `languages`
+    /// restrict what is loaded; if empty, everything is loaded. More IDs than the API allows per
+    /// request (50, or 500 with `apihighlimits`) are loaded in several requests. Returns the
+    /// entities keyed by ID; missing entities are left out.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn wbgetentities(
+        &self,
+        ids: &[&str],
+        props: &[&str],
+        languages: &[&str],
+    ) -> Result<HashMap<String, Entity>, Box<dyn Error + Send + Sync>> {
+        let mut entities = HashMap::new();
+        for chunk in ids.chunks(self.wbgetentities_chunk_size()) {
+            entities.extend(self.wbgetentities_chunk(chunk, props, languages)?);
+        }
+        Ok(entities)
+    }
+
+    /// Like `wbgetentities`, but runs the requests for up to `threads` chunks of IDs at the same
+    /// time
+    ///
+    /// # Errors
+    /// Returns the first error from any of the requests.
+    pub fn wbgetentities_parallel(
+        &self,
+        ids: &[&str],
+        props: &[&str],
+        languages: &[&str],
+        threads: usize,
+    ) -> Result<HashMap<String, Entity>, Box<dyn Error + Send + Sync>> {
+        let chunks: Vec<&[&str]> = ids.chunks(self.wbgetentities_chunk_size()).collect();
+        let mut entities = HashMap::new();
+        for group in chunks.chunks(threads.max(1)) {
+            let results: Vec<Result<HashMap<String, Entity>, String>> = thread::scope(|scope| {
+                let handles: Vec<_> = group
+                    .iter()
+                    .map(|chunk| {
+                        scope.spawn(move || {
+                            self.wbgetentities_chunk(chunk, props, languages)
+                                .map_err(|e| e.to_string())
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|_| Err("wbgetentities thread panicked".to_string()))
+                    })
+                    .collect()
+            });
+            for result in results {
+                entities.extend(result?);
+            }
+        }
+        Ok(entities)
+    }
+
+    /// Returns the maximum number of IDs per `wbgetentities` request for the current user
+    fn wbgetentities_chunk_size(&self) -> usize {
+        if self.user().has_right("apihighlimits") {
+            500
+        } else {
+            50
+        }
+    }
+
+    /// Loads entities with a single `wbgetentities` request
+    fn wbgetentities_chunk(
+        &self,
+        ids: &[&str],
+        props: &[&str],
+        languages: &[&str],
+    ) -> Result<HashMap<String, Entity>, Box<dyn Error + Send + Sync>> {
+        let mut params = self.params_into(&[("action", "wbgetentities"), ("ids", &ids.join("|"))]);
+        if !props.is_empty() {
+            params.insert("props".to_string(), props.join("|"));
+        }
+        if !languages.is_empty() {
+            params.insert("languages".to_string(), languages.join("|"));
+        }
+        let result = self.get_query_api_json(&params)?;
+        if let Some(error) = result.get("error") {
+            return Err(From::from(format!("wbgetentities failed: {}", error)));
+        }
+        Ok(Entity::new_from_api_result(&result)?)
+    }
+
+    /// Exports an entity via `Special:EntityData`, in a format and flavor, optionally of an old
+    /// revision. This returns the canonical JSON or RDF of the entity, without a SPARQL query.
+    ///
+    /// # Errors
+    /// Returns an error if there is no such entity or revision, or any error from the request.
+    pub fn entity_data(
+        &self,
+        id: &str,
+        format: EntityDataFormat,
+        flavor: Option<EntityDataFlavor>,
+        revision: Option<u64>,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let (url, params) = self.entity_data_request(id, format, flavor, revision);
+        let response = self
+            .query_raw_response(&url, &params, "GET")?
+            .error_for_status()?;
+        Ok(response.text()?)
+    }
+
+    /// Returns the URL and parameters for `Special:EntityData`
+    fn entity_data_request(
+        &self,
+        id: &str,
+        format: EntityDataFormat,
+        flavor: Option<EntityDataFlavor>,
+        revision: Option<u64>,
+    ) -> (String, HashMap<String, String>) {
+        let general = &self.get_site_info().general;
+        let url = general.server.to_absolute() + &general.script;
+        let title = format!("Special:EntityData/{}.{}", id, format.extension());
+        let mut params = self.params_into(&[("title", &title)]);
+        if let Some(flavor) = flavor {
+            params.insert("flavor".to_string(), flavor.as_str().to_string());
+        }
+        if let Some(revision) = revision {
+            params.insert("revision".to_string(), revision.to_string());
+        }
+        (url, params)
+    }
+
+    /// Loads the statements of an entity with `wbgetclaims`, optionally only those for a `property`
+    /// and/or with a `rank`. Returns the statements keyed by property ID.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn wbgetclaims(
+        &self,
+        entity: &str,
+        property: Option<&str>,
+        rank: Option<Rank>,
+    ) -> Result<HashMap<String, Vec<Statement>>, Box<dyn Error + Send + Sync>> {
+        let mut params = self.params_into(&[("action", "wbgetclaims"), ("entity", entity)]);
+        if let Some(property) = property {
+            params.insert("property".to_string(), property.to_string());
+        }
+        if let Some(rank) = rank {
+            params.insert("rank".to_string(), rank.as_str().to_string());
+        }
+        let result = self.get_query_api_json(&params)?;
+        if let Some(error) = result.get("error") {
+            return Err(From::from(format!("wbgetclaims failed: {}", error)));
+        }
+        Ok(claims_from_api_result(&result)?)
+    }
+
+    /// Searches entities of a type by label and alias, with `wbsearchentities`, following
+    /// continuation until `limit` results are found (or there are no more). Labels and descriptions
+    /// are returned in `language`.
+    ///
+    /// # Errors
+    /// Returns an error if the API returned an error, or any error from the request.
+    pub fn wbsearchentities(
+        &self,
+        search: &str,
+        entity_type: EntityType,
+        language: &str,
+        limit: usize,
+    ) -> Result<Vec<EntitySearchResult>, Box<dyn Error + Send + Sync>> {
+        let mut params = self.params_into(&[
+            ("action", "wbsearchentities"),
+            ("search", search),
+            ("type", entity_type.as_str()),
+            ("language", language),
+            ("uselang", language),
+            ("limit", "max"),
+        ]);
+        let mut results = vec![];
+        while results.len() < limit {
+            let result = self.get_query_api_json(&params)?;
+            if let Some(error) = result.get("error") {
+                return Err(From::from(format!("wbsearchentities failed: {}", error)));
+            }
+            let (found, next) = search_results_from_api_result(&result)?;
+            results.extend(found);
+            match next {
+                Some(offset) => params.insert("continue".to_string(), offset.to_string()),
+                None => break,
+            };
+        }
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    /// Edits an entity, or creates a new one, with `wbeditentity`, and returns the entity after the
+    /// edit. With `baserevid`, the edit fails with `WikibaseError::EditConflict` if the entity was
+    /// changed since.
+    ///
+    /// # Errors
+    /// Returns `WikibaseError::Validation` if the edit was rejected, or another `WikibaseError`.
+    pub fn wbeditentity(
+        &self,
+        target: &EntityTarget,
+        edit: &EntityEdit,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Entity, WikibaseError> {
+        let mut data = edit.to_data();
+        let mut params = HashMap::new();
+        match target {
+            EntityTarget::Id(id) => {
+                params.insert("id".to_string(), id.to_string());
+            }
+            EntityTarget::NewItem => {
+                params.insert("new".to_string(), "item".to_string());
+            }
+            EntityTarget::NewProperty(datatype) => {
+                params.insert("new".to_string(), "property".to_string());
+                data["datatype"] = json!(datatype);
+            }
+        }
+        if edit.clear {
+            params.insert("clear".to_string(), "1".to_string());
+        }
+        params.insert("data".to_string(), data.to_string());
+        let result = self.wikibase_edit("wbeditentity", params, summary, baserevid)?;
+        result_field(&result, "entity")
+    }
+
+    /// Adds a statement with the given main snak to an entity, with `wbcreateclaim`.
+    /// Returns the new statement, including its GUID.
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbcreateclaim(
+        &self,
+        entity: &str,
+        snak: &Snak,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Statement, WikibaseError> {
+        let mut params = snak_params(snak);
+        params.insert("entity".to_string(), entity.to_string());
+        let result = self.wikibase_edit("wbcreateclaim", params, summary, baserevid)?;
+        result_field(&result, "claim")
+    }
+
+    /// Changes the main snak of a statement, by GUID, with `wbsetclaimvalue`.
+    /// The property of the snak must be the same as before.
+    /// Returns the changed statement.
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbsetclaimvalue(
+        &self,
+        guid: &str,
+        snak: &Snak,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Statement, WikibaseError> {
+        let mut params = snak_params(snak);
+        params.remove("property");
+        params.insert("claim".to_string(), guid.to_string());
+        let result = self.wikibase_edit("wbsetclaimvalue", params, summary, baserevid)?;
+        result_field(&result, "claim")
+    }
+
+    /// Creates or replaces a whole statement, with `wbsetclaim`. The statement must have a GUID;
+    /// for a new statement, it is made up of the entity ID, "$", and a new UUID. `index` is the new
+    /// position of the statement among those of its property. Returns the statement as saved.
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbsetclaim(
+        &self,
+        statement: &Statement,
+        index: Option<usize>,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Statement, WikibaseError> {
+        let mut params = HashMap::new();
+        params.insert("claim".to_string(), json!(statement).to_string());
+        if let Some(index) = index {
+            params.insert("index".to_string(), index.to_string());
+        }
+        let result = self.wikibase_edit("wbsetclaim", params, summary, baserevid)?;
+        result_field(&result, "claim")
+    }
+
+    /// Removes statements, by GUID, with `wbremoveclaims`. All must belong to the same entity.
+    /// Returns the GUIDs of the removed statements.
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbremoveclaims(
+        &self,
+        guids: &[&str],
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Vec<String>, WikibaseError> {
+        let mut params = HashMap::new();
+        params.insert("claim".to_string(), guids.join("|"));
+        let result = self.wikibase_edit("wbremoveclaims", params, summary, baserevid)?;
+        result_field(&result, "claims")
+    }
+
+    /// Adds a qualifier to a statement, by GUID, with `wbsetqualifier`.
+    /// With `snakhash`, the existing qualifier with that hash is replaced instead.
+    /// Returns the changed statement.
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbsetqualifier(
+        &self,
+        guid: &str,
+        snak: &Snak,
+        snakhash: Option<&str>,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Statement, WikibaseError> {
+        let mut params = snak_params(snak);
+        params.insert("claim".to_string(), guid.to_string());
+        if let Some(snakhash) = snakhash {
+            params.insert("snakhash".to_string(), snakhash.to_string());
+        }
+        let result = self.wikibase_edit("wbsetqualifier", params, summary, baserevid)?;
+        result_field(&result, "claim")
+    }
+
+    /// Adds a reference to a statement, by GUID, with `wbsetreference`.
+    /// If the reference has a hash, the existing reference with that hash is replaced instead.
+    /// `index` is the new position of the reference.
+    /// Returns the reference as saved, with its new hash.
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbsetreference(
+        &self,
+        guid: &str,
+        reference: &Reference,
+        index: Option<usize>,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Reference, WikibaseError> {
+        let mut params = HashMap::new();
+        params.insert("statement".to_string(), guid.to_string());
+        params.insert("snaks".to_string(), json!(reference.snaks).to_string());
+        if !reference.snaks_order.is_empty() {
+            params.insert(
+                "snaks-order".to_string(),
+                json!(reference.snaks_order).to_string(),
+            );
+        }
+        if let Some(hash) = &reference.hash {
+            params.insert("reference".to_string(), hash.to_string());
+        }
+        if let Some(index) = index {
+            params.insert("index".to_string(), index.to_string());
+        }
+        let result = self.wikibase_edit("wbsetreference", params, summary, baserevid)?;
+        result_field(&result, "reference")
+    }
+
+    /// Sets the label of an entity in a language, with `wbsetlabel`; an empty `value` removes the
+    /// label. Returns the new label, if any.
+    ///
+    /// # Errors
+    /// Returns `WikibaseError::UnknownLanguage` if the language is not in `meta=languageinfo`, or
+    /// another `WikibaseError`.
+    pub fn wbsetlabel(
+        &self,
+        id: &str,
+        language: &str,
+        value: &str,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Option<Label>, WikibaseError> {
+        let term = Term::new(language, value);
+        self.set_term("wbsetlabel", "labels", id, &term, summary, baserevid)
+    }
+
+    /// Sets the description of an entity in a language, with `wbsetdescription`; an empty `value`
+    /// removes the description. Returns the new description, if any.
+    ///
+    /// # Errors
+    /// Returns `WikibaseError::UnknownLanguage` if the language is not in `meta=languageinfo`, or
+    /// another `WikibaseError`.
+    pub fn wbsetdescription(
+        &self,
+        id: &str,
+        language: &str,
+        value: &str,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Option<Description>, WikibaseError> {
+        let term = Term::new(language, value);
+        self.set_term(
+            "wbsetdescription",
+            "descriptions",
+            id,
+            &term,
+            summary,
+            baserevid,
+        )
+    }
+
+    /// Adds and removes aliases of an entity in a language, with `wbsetaliases`.
+    /// Returns the aliases in that language after the edit.
+    ///
+    /// # Errors
+    /// Returns `WikibaseError::UnknownLanguage` if the language is not in `meta=languageinfo`, or
+    /// another `WikibaseError`.
+    pub fn wbsetaliases(
+        &self,
+        id: &str,
+        language: &str,
+        add: &[&str],
+        remove: &[&str],
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Vec<Alias>, WikibaseError> {
+        self.check_language(language)?;
+        // Aliases may contain "|", so they are joined with U+001F if needed
+        let params =
+            params_map!["id" => id, "language" => language, "add" => add, "remove" => remove];
+        let result = self.wikibase_edit("wbsetaliases", params, summary, baserevid)?;
+        let aliases: Vec<Value> = match result["entity"]["aliases"].get(language) {
+            Some(aliases) => {
+                Vec::deserialize(aliases).map_err(|_| WikibaseError::BadResponse(result.clone()))?
+            }
+            None => vec![],
+        };
+        Ok(aliases
+            .iter()
+            .filter(|alias| alias.get("removed").is_none())
+            .filter_map(|alias| Alias::deserialize(alias).ok())
+            .collect())
+    }
+
+    /// Sets the sitelink of an item to a site (a global site ID like "enwiki"; see
+    /// `SiteMatrix::normalize_site_id`), with `wbsetsitelink`. If `title` is `None`, the sitelink
+    /// is removed. `badges` are item IDs, e.g. "Q17437796" (featured article). Returns the new
+    /// sitelink, if any.
+    ///
+    /// # Errors
+    /// Returns a `WikibaseError`.
+    pub fn wbsetsitelink(
+        &self,
+        id: &str,
+        site: &str,
+        title: Option<&str>,
+        badges: &[&str],
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Option<Sitelink>, WikibaseError> {
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), id.to_string());
+        params.insert("linksite".to_string(), site.to_string());
+        if let Some(title) = title {
+            params.insert("linktitle".to_string(), title.to_string());
+            params.insert("badges".to_string(), badges.join("|"));
+        }
+        let result = self.wikibase_edit("wbsetsitelink", params, summary, baserevid)?;
+        Ok(sitelink_from_result(&result, site))
+    }
+
+    /// Sets a label or description, and returns it from the `key` part of the result
+    fn set_term(
+        &self,
+        action: &str,
+        key: &str,
+        id: &str,
+        term: &Term,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Option<Term>, WikibaseError> {
+        self.check_language(&term.language)?;
+        let mut params = HashMap::new();
+        params.insert("id".to_string(), id.to_string());
+        params.insert("language".to_string(), term.language.clone());
+        params.insert("value".to_string(), term.value.clone());
+        let result = self.wikibase_edit(action, params, summary, baserevid)?;
+        Ok(term_from_result(&result, key, &term.language))
+    }
+
+    /// Checks that a language code is known to the wiki
+    fn check_language(&self, language: &str) -> Result<(), WikibaseError> {
+        if self
+            .language_codes()
+            .map_err(WikibaseError::RequestError)?
+            .contains(language)
+        {
+            Ok(())
+        } else {
+            Err(WikibaseError::UnknownLanguage(language.to_string()))
+        }
+    }
+
+    /// Performs a Wikibase edit action with the given parameters, and checks the result
+    pub(crate) fn wikibase_edit(
+        &self,
+        action: &str,
+        mut params: HashMap<String, String>,
+        summary: &str,
+        baserevid: Option<u64>,
+    ) -> Result<Value, WikibaseError> {
+        let token = self.get_edit_token().map_err(WikibaseError::RequestError)?;
+        params.extend(self.params_into(&[
+            ("action", action),
+            ("summary", summary),
+            ("token", &token),
+        ]));
+        if let Some(baserevid) = baserevid {
+            params.insert("baserevid".to_string(), baserevid.to_string());
+        }
+        if self.user().is_bot() {
+            params.insert("bot".to_string(), "1".to_string());
+        }
+        let result = self
+            .post_query_api_json(&params)
+            .map_err(WikibaseError::RequestError)?;
+        WikibaseError::check_edit(result)
+    }
+}
+
+/// Returns the `snaktype`, `property` and `value` parameters for a snak
+fn snak_params(snak: &Snak) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    params.insert("property".to_string(), snak.property.to_string());
+    params.insert(
+        "snaktype".to_string(),
+        json!(snak.snaktype)
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    );
+    if let Some(datavalue) = &snak.datavalue {
+        params.insert("value".to_string(), datavalue.value_json().to_string());
+    }
+    params
+}
+
+/// Returns the statements of a `wbgetclaims` result, keyed by property ID
+fn claims_from_api_result(
+    result: &Value,
+) -> Result<HashMap<String, Vec<Statement>>, serde_json::Error> {
+    map_or_empty_array(&result["claims"])
+}
+
+/// Returns the results of a `wbsearchentities` result, and the offset to continue from, if any
+fn search_results_from_api_result(
+    result: &Value,
+) -> Result<(Vec<EntitySearchResult>, Option<u64>), serde_json::Error> {
+    let found = match result.get("search") {
+        Some(search) => Vec::deserialize(search)?,
+        None => vec![],
+    };
+    Ok((found, result["search-continue"].as_u64()))
+}
+
+/// Returns the label or description (`key`) in a language from the result of a term edit,
+/// or `None` if it was removed
+fn term_from_result(result: &Value, key: &str, language: &str) -> Option<Term> {
+    let term = &result["entity"][key][language];
+    if term.get("removed").is_some() {
+        return None;
+    }
+    Term::deserialize(term).ok()
+}
+
+/// Returns the sitelink to a site from the result of a sitelink edit, or `None` if it was removed
+fn sitelink_from_result(result: &Value, site: &str) -> Option<Sitelink> {
+    let sitelink = &result["entity"]["sitelinks"][site];
+    if sitelink.get("removed").is_some() {
+        return None;
+    }
+    Sitelink::deserialize(sitelink).ok()
+}
+
+/// Deserializes a field of an API result
+fn result_field<T: DeserializeOwned>(result: &Value, key: &str) -> Result<T, WikibaseError> {
+    T::deserialize(&result[key]).map_err(|_| WikibaseError::BadResponse(result.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::test_api;
+
+    fn q42() -> Value {
+        json!({"entities": {
+            "Q42": {
+                "type": "item", "id": "Q42", "lastrevid": 1234, "modified": "2020-04-14T10:22:31Z",
+                "labels": {"en": {"language": "en", "value": "Douglas Adams"}},
+                "descriptions": {"en": {"language": "en", "value": "English writer and humorist"}},
+                "aliases": {"en": [{"language": "en", "value": "Douglas Noël Adams"},
+                {"language": "en", "value": "DNA"}]},
+                "sitelinks": {"enwiki": {"site": "enwiki", "title": "Douglas Adams",
+                "badges": ["Q17437798"]}},
+                "claims": {"P31": [{
+                    "mainsnak": {"snaktype": "value", "property": "P31",
+                    "hash": "ad7d38a03cdd40cdc373de0dc4e7b7fcbccb31d9",
+                        "datavalue": {"value": {"entity-type": "item", "numeric-id": 5, "id": "Q5"},
+                        "type": "wikibase-entityid"},
+                        "datatype": "wikibase-item"},
+                    "type": "statement", "id": "Q42$F078E5B3-F9A8-480E-B7AC-D97778CBBEF9",
+                    "rank": "normal",
+                    "qualifiers": {"P580": [{"snaktype": "novalue", "property": "P580",
+                    "hash": "abc"}]},
+                    "qualifiers-order": ["P580"],
+                    "references": [{"hash": "def", "snaks": {"P143": [{"snaktype": "value",
+                    "property": "P143",
+                        "datavalue": {"value": {"entity-type": "item", "numeric-id": 328,
+                        "id": "Q328"}, "type": "wikibase-entityid"},
+                        "datatype": "wikibase-item"}]}, "snaks-order": ["P143"]}]
+                }]}
+            },
+            "Q0": {"id": "Q0", "missing": ""},
+            "P31": {"type": "property", "datatype": "wikibase-item", "id": "P31", "labels": [],
+            "descriptions": [],
+                "aliases": [], "claims": []}
+        }, "success": 1})
+    }
+
+    #[test]
+    fn parse_entities() {
+        let entities = Entity::new_from_api_result(&q42()).unwrap();
+        assert_eq!(entities.len(), 2);
+        let q42 = &entities["Q42"];
+        assert_eq!(q42.entity_type, "item");
+        assert_eq!(q42.label("en"), Some("Douglas Adams"));
+        assert_eq!(q42.description("en"), Some("English writer and humorist"));
+        assert_eq!(q42.aliases("en"), vec!["Douglas Noël Adams", "DNA"]);
+        assert_eq!(q42.aliases("de"), Vec::<&str>::new());
+        assert_eq!(q42.sitelink("enwiki").unwrap().badges, vec!["Q17437798"]);
+        let p31 = q42.claims_for("P31");
+        assert_eq!(p31.len(), 1);
+        assert_eq!(p31[0].rank, Rank::Normal);
+        assert_eq!(p31[0].mainsnak.snaktype, SnakType::Value);
+        assert_eq!(p31[0].qualifiers["P580"][0].snaktype, SnakType::NoValue);
+        assert_eq!(p31[0].references[0].snaks_order, vec!["P143"]);
+        assert!(q42.claims_for("P999").is_empty());
+
+        let p31 = &entities["P31"];
+        assert_eq!(p31.datatype.as_deref(), Some("wikibase-item"));
+        assert!(p31.labels.is_empty());
+    }
+
+    #[test]
+    fn statement_roundtrip() {
+        let entities = Entity::new_from_api_result(&q42()).unwrap();
+        let statement = &entities["Q42"].claims["P31"][0];
+        let json = serde_json::to_value(statement).unwrap();
+        assert_eq!(json["qualifiers-order"], json!(["P580"]));
+        assert_eq!(json["type"], json!("statement"));
+        let parsed: Statement = serde_json::from_value(json).unwrap();
+        assert_eq!(&parsed, statement);
+    }
+
+    #[test]
+    fn entity_edit_data() {
+        let mut edit = EntityEdit::new();
+        assert!(edit.is_empty());
+        edit.set_label("en", "Foo");
+        edit.remove_label("de");
+        edit.add_alias("en", "Bar");
+        edit.set_sitelink("enwiki", "Foo", &["Q17437796"]);
+        edit.remove_claim("Q1$abc");
+        let data = edit.to_data();
+        assert_eq!(
+            data,
+            json!({
+                "labels": [{"language": "en", "value": "Foo"}, {"language": "de", "remove": ""}],
+                "aliases": [{"language": "en", "value": "Bar", "add": ""}],
+                "sitelinks": [{"site": "enwiki", "title": "Foo", "badges": ["Q17437796"]}],
+                "claims": [{"id": "Q1$abc", "remove": ""}]
+            })
+        );
+        assert!(!edit.is_empty());
+    }
+
+    #[test]
+    fn edit_errors() {
+        let result = json!({"error": {"code": "modification-failed",
+            "info": "Item Q1 already has label \"Foo\" associated with language code en, \
+                using the same description text.",
+            "messages": [{"name": "wikibase-validator-label-with-description-conflict",
+            "parameters": ["Foo", "en", "Q1"]}]}});
+        match WikibaseError::check_edit(result) {
+            Err(WikibaseError::Validation { code, messages, .. }) => {
+                assert_eq!(code, "modification-failed");
+                assert_eq!(
+                    messages,
+                    vec!["wikibase-validator-label-with-description-conflict"]
+                );
+            }
+            x => panic!("expected validation error, found {:?}", x),
+        }
+        assert!(matches!(
+            WikibaseError::check_edit(
+                json!({"error": {"code": "editconflict", "info": "Edit conflict."}})
+            ),
+            Err(WikibaseError::EditConflict(_))
+        ));
+        assert!(matches!(
+            WikibaseError::check_edit(
+                json!({"error": {"code": "badtoken", "info": "Invalid CSRF token."}})
+            ),
+            Err(WikibaseError::ApiError { .. })
+        ));
+        assert!(WikibaseError::check_edit(json!({"success": 1, "entity": {}})).is_ok());
+    }
+
+    #[test]
+    fn snak_params() {
+        let snak: Snak = serde_json::from_value(json!({"snaktype": "value", "property": "P31",
+            "datavalue": {"value": {"entity-type": "item", "numeric-id": 5, "id": "Q5"},
+            "type": "wikibase-entityid"}}))
+        .unwrap();
+        let params = super::snak_params(&snak);
+        assert_eq!(params["snaktype"], "value");
+        assert_eq!(params["property"], "P31");
+        assert_eq!(
+            serde_json::from_str::<Value>(&params["value"]).unwrap(),
+            json!({"entity-type": "item", "numeric-id": 5, "id": "Q5"})
+        );
+        let snak: Snak =
+            serde_json::from_value(json!({"snaktype": "somevalue", "property": "P570"})).unwrap();
+        let params = super::snak_params(&snak);
+        assert_eq!(params["snaktype"], "somevalue");
+        assert!(!params.contains_key("value"));
+    }
+
+    #[test]
+    fn claim_results() {
+        let result = json!({"pageinfo": {"lastrevid": 5}, "success": 1,
+            "reference": {"hash": "abc", "snaks": {"P854": [{"snaktype": "value",
+            "property": "P854",
+                "datavalue": {"value": "https://example.org", "type": "string"}}]},
+                "snaks-order": ["P854"]}});
+        let reference: Reference = result_field(&result, "reference").unwrap();
+        assert_eq!(reference.hash.as_deref(), Some("abc"));
+        assert_eq!(reference.snaks_order, vec!["P854"]);
+        let result = json!({"success": 1, "claims": ["Q1$a", "Q1$b"]});
+        let removed: Vec<String> = result_field(&result, "claims").unwrap();
+        assert_eq!(removed, vec!["Q1$a", "Q1$b"]);
+        assert!(matches!(
+            result_field::<Statement>(&result, "claim"),
+            Err(WikibaseError::BadResponse(_))
+        ));
+    }
+
+    #[test]
+    fn term_results() {
+        let result = json!({"entity": {"labels": {"en": {"language": "en", "value": "Foo"}},
+            "id": "Q1", "type": "item", "lastrevid": 7}, "success": 1});
+        assert_eq!(
+            term_from_result(&result, "labels", "en"),
+            Some(Term::new("en", "Foo"))
+        );
+        let result = json!({"entity": {"descriptions": {"en": {"language": "en", "removed": ""}},
+            "id": "Q1", "type": "item", "lastrevid": 8}, "success": 1});
+        assert_eq!(term_from_result(&result, "descriptions", "en"), None);
+    }
+
+    #[test]
+    fn search_results() {
+        let result = json!({"searchinfo": {"search": "douglas adams"}, "search": [{
+            "id": "Q42", "title": "Q42", "pageid": 138, "repository": "wikidata",
+            "url": "//www.wikidata.org/wiki/Q42",
+            "concepturi": "http://www.wikidata.org/entity/Q42",
+            "label": "Douglas Adams", "description": "English writer and humorist",
+            "display": {"label": {"value": "Douglas Adams", "language": "en"}},
+            "match": {"type": "label", "language": "en", "text": "Douglas Adams"}
+        }, {
+            "id": "Q28421831", "title": "Q28421831", "pageid": 30264017,
+            "label": "Douglas Adams", "aliases": ["Douglas Noel Adams"],
+            "match": {"type": "alias", "language": "en", "text": "Douglas Noel Adams"}
+        }], "search-continue": 2, "success": 1});
+        let (found, next) = search_results_from_api_result(&result).unwrap();
+        assert_eq!(next, Some(2));
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].id, "Q42");
+        assert_eq!(
+            found[0].description.as_deref(),
+            Some("English writer and humorist")
+        );
+        assert_eq!(found[0].match_info.match_type, "label");
+        assert_eq!(found[1].aliases, vec!["Douglas Noel Adams"]);
+        assert_eq!(found[1].description, None);
+        let (found, next) =
+            search_results_from_api_result(&json!({"search": [], "success": 1})).unwrap();
+        assert!(found.is_empty());
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn claims_result() {
+        let result = json!({"claims": {"P31": [{
+            "mainsnak": {"snaktype": "value", "property": "P31",
+            "hash": "ad7d38a03cdd40cdc373de0dc4e7b7fcbccb31d9",
+                "datavalue": {"value": {"entity-type": "item", "numeric-id": 5, "id": "Q5"},
+                "type": "wikibase-entityid"},
+                "datatype": "wikibase-item"},
+            "type": "statement", "id": "Q42$F078E5B3-F9A8-480E-B7AC-D97778CBBEF9",
+            "rank": "preferred"}]}});
+        let claims = claims_from_api_result(&result).unwrap();
+        assert_eq!(claims["P31"].len(), 1);
+        assert_eq!(claims["P31"][0].rank, Rank::Preferred);
+        assert!(claims_from_api_result(&json!({"claims": []}))
+            .unwrap()
+            .is_empty());
+        assert_eq!(Rank::Deprecated.as_str(), "deprecated");
+    }
+
+    #[test]
+    fn sitelink_results() {
+        let result = json!({"entity": {"sitelinks": {"dewiki": {"site": "dewiki", "title": "Berlin",
+            "badges": ["Q17437796"], "url": "https://de.wikipedia.org/wiki/Berlin"}}, "id": "Q64",
+            "type": "item"},
+            "success": 1});
+        let sitelink = sitelink_from_result(&result, "dewiki").unwrap();
+        assert_eq!(sitelink.title, "Berlin");
+        assert_eq!(sitelink.badges, vec!["Q17437796"]);
+        let result = json!({"entity": {"sitelinks": {"dewiki": {"site": "dewiki", "title": "Berlin",
+            "removed": ""}},
+            "id": "Q64", "type": "item"}, "success": 1});
+        assert_eq!(sitelink_from_result(&result, "dewiki"), None);
+    }
+
+    #[test]
+    fn entity_data_request() {
+        let api = test_api();
+        let (url, params) = api.entity_data_request(
+            "Q42",
+            EntityDataFormat::Turtle,
+            Some(EntityDataFlavor::Dump),
+            Some(123),
+        );
+        assert_eq!(url, "https://de.wikipedia.org/w/index.php");
+        assert_eq!(params["title"], "Special:EntityData/Q42.ttl");
+        assert_eq!(params["flavor"], "dump");
+        assert_eq!(params["revision"], "123");
+        let (_, params) = api.entity_data_request("L7-F1", EntityDataFormat::Json, None, None);
+        assert_eq!(params["title"], "Special:EntityData/L7-F1.json");
+        assert!(!params.contains_key("flavor"));
+        assert!(!params.contains_key("revision"));
+    }
+
+    #[test]
+    fn wbgetentities_chunk_size() {
+        let api = test_api();
+        assert_eq!(api.wbgetentities_chunk_size(), 50);
+        assert!(api
+            .wbgetentities_parallel(&[], &[], &[], 4)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn entity_ids() {
+        for id in ["Q42", "P31", "L7", "L7-F1", "L7-S12", "M123"] {
+            assert_eq!(id.parse::<EntityId>().unwrap().to_string(), id);
+        }
+        assert_eq!("L7-F1".parse(), Ok(EntityId::Form(7, 1)));
+        assert_eq!(EntityId::Sense(7, 1).entity_type(), EntityType::Sense);
+        for id in [
+            "", "Q", "Q0", "Q042", "Q+1", "X1", "L7-X1", "L7-F", "q42", "Q42 ",
+        ] {
+            assert_eq!(
+                id.parse::<EntityId>(),
+                Err(ParseEntityIdError(id.to_string())),
+                "{}",
+                id
+            );
+        }
+    }
+}